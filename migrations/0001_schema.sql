@@ -0,0 +1,61 @@
+-- Users, organisations, members, services and verifications.
+-- Ids are ObjectId hex strings, flags are 0 or 1, lists are JSON arrays and times are RFC 3339 strings in UTC,
+-- so the same schema runs on SQLite and PostgreSQL.
+
+CREATE TABLE users (
+    id TEXT NOT NULL PRIMARY KEY,
+    username TEXT NOT NULL,
+    first_name TEXT NOT NULL,
+    last_name TEXT NOT NULL,
+    password TEXT NOT NULL,
+    phone TEXT,
+    phone_verified BIGINT NOT NULL DEFAULT 0,
+    email TEXT,
+    email_verified BIGINT NOT NULL DEFAULT 0,
+    CONSTRAINT users_phone_key UNIQUE (phone),
+    CONSTRAINT users_email_key UNIQUE (email)
+);
+
+CREATE TABLE organisations (
+    id TEXT NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL,
+    domain TEXT,
+    home TEXT,
+    contacts TEXT NOT NULL,
+    CONSTRAINT organisations_name_key UNIQUE (name)
+);
+
+CREATE TABLE members (
+    org_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    owner BIGINT NOT NULL DEFAULT 0,
+    roles TEXT NOT NULL,
+    CONSTRAINT members_pkey PRIMARY KEY (org_id, user_id)
+);
+
+CREATE INDEX members_user_id_idx ON members (user_id);
+
+CREATE TABLE services (
+    id TEXT NOT NULL PRIMARY KEY,
+    owner_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    client_secret TEXT NOT NULL,
+    redirect_uris TEXT NOT NULL,
+    scopes TEXT NOT NULL,
+    grant_types TEXT NOT NULL,
+    token_expiry BIGINT,
+    CONSTRAINT services_owner_id_name_key UNIQUE (owner_id, name)
+);
+
+CREATE INDEX services_name_idx ON services (name);
+
+-- A contact has at most one pending verification, a new one replaces it.
+CREATE TABLE verifications (
+    contact TEXT NOT NULL PRIMARY KEY,
+    id TEXT NOT NULL,
+    owner_contact TEXT NOT NULL,
+    code BIGINT NOT NULL,
+    expires TEXT NOT NULL,
+    CONSTRAINT verifications_id_key UNIQUE (id)
+);
@@ -0,0 +1,3 @@
+-- The number of wrong codes tried against a verification.
+
+ALTER TABLE verifications ADD COLUMN attempts BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,6 @@
+-- Codes are kept exactly as they are sent, with their leading zeros and letters.
+
+ALTER TABLE verifications ADD COLUMN sent_code TEXT NOT NULL DEFAULT '';
+UPDATE verifications SET sent_code = CAST(code AS TEXT);
+ALTER TABLE verifications DROP COLUMN code;
+ALTER TABLE verifications RENAME COLUMN sent_code TO code;
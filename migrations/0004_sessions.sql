@@ -0,0 +1,57 @@
+-- Invitations, authorization codes, refresh tokens, revocations and failed logins,
+-- so the API can be served from the SQL database.
+
+-- An organisation invites a contact once, whether the contact is verified or not.
+CREATE TABLE invitations (
+    id TEXT NOT NULL PRIMARY KEY,
+    org_id TEXT NOT NULL,
+    contact TEXT NOT NULL,
+    owner_contact TEXT NOT NULL,
+    title TEXT NOT NULL,
+    roles TEXT NOT NULL,
+    invited_by TEXT NOT NULL,
+    expires TEXT NOT NULL,
+    CONSTRAINT invitations_org_id_contact_key UNIQUE (org_id, contact)
+);
+
+CREATE TABLE authorization_codes (
+    code TEXT NOT NULL PRIMARY KEY,
+    client_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    redirect_uri TEXT NOT NULL,
+    code_challenge TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    nonce TEXT,
+    expires TEXT NOT NULL
+);
+
+-- Tokens are keyed by their digest, the token itself is never stored.
+CREATE TABLE refresh_tokens (
+    id TEXT NOT NULL PRIMARY KEY,
+    family TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    client_id TEXT,
+    scope TEXT NOT NULL,
+    used BIGINT NOT NULL DEFAULT 0,
+    expires TEXT NOT NULL
+);
+
+CREATE INDEX refresh_tokens_family_idx ON refresh_tokens (family);
+CREATE INDEX refresh_tokens_user_id_idx ON refresh_tokens (user_id);
+
+CREATE TABLE revocations (
+    id TEXT NOT NULL PRIMARY KEY,
+    subject TEXT NOT NULL,
+    revoked_at TEXT NOT NULL,
+    expires TEXT NOT NULL
+);
+
+CREATE INDEX revocations_subject_idx ON revocations (subject);
+
+-- `previous` keeps the time of the failure before `last`, so counting one returns the earlier count in one statement.
+CREATE TABLE login_attempts (
+    id TEXT NOT NULL PRIMARY KEY,
+    count BIGINT NOT NULL,
+    last TEXT NOT NULL,
+    previous TEXT NOT NULL
+);
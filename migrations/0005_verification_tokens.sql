@@ -0,0 +1,3 @@
+-- The secret carried by magic links, verifications stored before have none and match no link.
+
+ALTER TABLE verifications ADD COLUMN token TEXT NOT NULL DEFAULT '';
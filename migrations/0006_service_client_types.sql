@@ -0,0 +1,3 @@
+-- Whether a client can keep a secret, clients registered before are confidential.
+
+ALTER TABLE services ADD COLUMN client_type TEXT NOT NULL DEFAULT 'confidential';
@@ -0,0 +1,42 @@
+use actix_web::{web::{self, Json, Data}, Responder, HttpResponse};
+use crate::domain::types::{Config, Contact, EmailAddress, Phone, Throttle, Error as DomainError};
+use crate::domain::services::Lockout;
+use super::{auth::Authenticated, Response, Store, Messenger};
+use std::net::IpAddr;
+use serde::Deserialize;
+use std::sync::Arc;
+
+
+#[derive(Deserialize)]
+struct UnlockRequest {
+    pub email: Option<EmailAddress>,
+    pub phone: Option<Phone>,
+    pub ip: Option<IpAddr>
+}
+
+
+/// Lifting lockouts.
+pub(super) fn routes<DB: Store, V: Messenger>(cfg: &mut web::ServiceConfig) {
+    cfg.route("/admin/unlock", web::post().to(unlock::<DB, V>));
+}
+
+
+/// Lifts the lockout of an account, a client address or both. Only for the configured admins.
+async fn unlock<DB: Store, V: Messenger>(Authenticated(token): Authenticated, json: Json<UnlockRequest>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    if !config.http().admins.contains(&token.subject) || !token.has_scope("admin") {
+        Err(DomainError::Forbidden)?
+    }
+    let UnlockRequest {email, phone, ip} = json.into_inner();
+    let contact = match (phone, email) {
+        (Some(phone), Some(email)) => Some(Contact::Both(phone, email)),
+        (Some(phone), None) => Some(Contact::Phone(phone)),
+        (None, Some(email)) => Some(Contact::Email(email)),
+        (None, None) => None
+    };
+    if contact.is_none() && ip.is_none() {
+        Err(DomainError::ValidationError { field: String::from("email"), message: String::from("an email, phone or ip to unlock is required") })?
+    }
+    let db = config.db();
+    Throttle::unlock(contact.as_ref(), ip, db).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
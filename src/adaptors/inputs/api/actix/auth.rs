@@ -0,0 +1,274 @@
+//! Authentication shared by every endpoint.
+//!
+//! [`Authenticated`] extracts the token from the `token` cookie or the `Authorization` header
+//! and checks its signature, expiry, `nbf`, revocation and audience, once per request.
+//! It only accepts first party tokens, [`Authorized`] also accepts tokens issued to clients.
+//! [`Require`] guards a route or scope on the scopes or permissions the token grants.
+//! The tokens are verified by the [`Tokens`] of the app, on whichever [`Store`] it serves from.
+//! Failures are 401 or 403 responses with a `WWW-Authenticate` challenge.
+
+use actix_web::{dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform}, body::EitherBody, FromRequest, HttpMessage, HttpRequest, Error};
+use crate::domain::types::{Config, Error as DomainError, Id, Permission, Token, User};
+use crate::domain::services::Authentication;
+use super::{oidc::issuer, token, Store, Messenger};
+use crate::ports::Error as PortError;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+
+type LocalBoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+
+/// A verified first party token, issued to the user themselves.
+///
+/// Tokens issued to clients are rejected, they must not act with the user's full authority.
+#[derive(Debug, Clone)]
+pub struct Authenticated<T = Token>(pub T);
+
+
+/// A verified token, first party or issued to a client and limited to the scopes it was granted.
+#[derive(Debug, Clone)]
+pub struct Authorized<T = Token>(pub T);
+
+
+/// Verifies tokens on the database of the app, so the extractors need not know its type.
+#[derive(Clone)]
+pub(super) struct Tokens {
+    verify: Rc<dyn Fn(String) -> LocalBoxFuture<Result<Token, PortError>>>,
+    /// The audience first party tokens may carry besides none
+    issuer: String,
+}
+
+
+impl Tokens {
+    pub(super) fn new<DB: Store, V: Messenger>(config: Arc<Config<DB, V>>) -> Self {
+        let issuer = issuer(&config);
+        let verify = move |signature: String| -> LocalBoxFuture<Result<Token, PortError>> {
+            let config = config.clone();
+            Box::pin(async move { User::verify(&signature, config.paseto(), config.db()).await })
+        };
+        Self {verify: Rc::new(verify), issuer}
+    }
+}
+
+
+/// Verifies the token of the request once, the token is shared by the handler and the guards of the request.
+///
+/// `accepts` tells whether the token may be used where it is extracted, given the audiences of this server.
+fn verified(req: &HttpRequest, accepts: fn(&Token, &[&str]) -> bool) -> LocalBoxFuture<Result<Token, Error>> {
+    let req = req.clone();
+    Box::pin(async move {
+        let tokens = req.app_data::<Tokens>()
+            .ok_or_else(|| DomainError::Internal {message: String::from("the token verifier is missing from the app data"), source: None})
+            .map_err(PortError::from)?;
+        let cached = req.extensions().get::<Token>().cloned();
+        let token = match cached {
+            Some(token) => token,
+            None => {
+                let signature = token(&req)?;
+                let token = (tokens.verify)(signature).await?;
+                req.extensions_mut().insert(token.clone());
+                token
+            }
+        };
+        if !accepts(&token, &[&tokens.issuer]) {
+            Err(PortError::from(DomainError::InvalidToken))?
+        }
+        Ok(token)
+    })
+}
+
+
+impl FromRequest for Authenticated {
+    type Error = Error;
+    type Future = LocalBoxFuture<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = verified(req, Token::first_party);
+        Box::pin(async move { Ok(Self(token.await?)) })
+    }
+}
+
+
+impl FromRequest for Authorized {
+    type Error = Error;
+    type Future = LocalBoxFuture<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = verified(req, Token::intended_for);
+        Box::pin(async move { Ok(Self(token.await?)) })
+    }
+}
+
+
+#[derive(Debug, Clone)]
+enum Requirement {
+    Scope(String),
+    Permission(Id, String, Permission),
+}
+
+
+impl Requirement {
+    fn check(&self, token: &Token) -> Result<(), DomainError> {
+        let (granted, scope) = match self {
+            Self::Scope(scope) => (token.has_scope(scope), scope.clone()),
+            Self::Permission(service, resource, permission) => (token.has_permission(service, resource, *permission), format!("{}:{}:{}", service.to_hex(), resource, permission)),
+        };
+        match granted {
+            true => Ok(()),
+            false => Err(DomainError::InsufficientScope {scope})
+        }
+    }
+}
+
+
+/// Middleware rejecting requests whose token does not grant a scope or permission.
+///
+/// First party tokens and tokens issued to clients are both let through when they grant it.
+///
+/// `App::new().service(web::scope("/files").wrap(Require::permission(service, "files", Permission::Write)))`
+#[derive(Debug, Clone)]
+pub struct Require {
+    requirement: Rc<Requirement>,
+}
+
+
+impl Require {
+    /// Requires a scope such as `openid`.
+    pub fn scope(scope: &str) -> Self {
+        Self {requirement: Rc::new(Requirement::Scope(scope.to_string()))}
+    }
+
+    /// Requires a `service:resource:permission` scope on the resource of the service.
+    pub fn permission(service: Id, resource: &str, permission: Permission) -> Self {
+        Self {requirement: Rc::new(Requirement::Permission(service, resource.to_string(), permission))}
+    }
+}
+
+
+impl<S, B> Transform<S, ServiceRequest> for Require
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequireMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireMiddleware {service: Rc::new(service), requirement: self.requirement.clone()}))
+    }
+}
+
+
+pub struct RequireMiddleware<S> {
+    service: Rc<S>,
+    requirement: Rc<Requirement>,
+}
+
+
+impl<S, B> Service<ServiceRequest> for RequireMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let requirement = self.requirement.clone();
+        Box::pin(async move {
+            // Rejections are responses rather than errors, so they get the same body and headers everywhere
+            let checked = match req.extract::<Authorized>().await {
+                Ok(Authorized(token)) => requirement.check(&token).map_err(|err| PortError::from(err).into()),
+                Err(err) => Err(err),
+            };
+            match checked {
+                Ok(()) => Ok(service.call(req).await?.map_into_left_body()),
+                Err(err) => Ok(req.error_response(err).map_into_right_body()),
+            }
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::{Audience, Id, Value};
+    use crate::domain::services::Paseto;
+    use crate::adaptors::outputs::{database::memory::Memory, verify::Verifyer};
+    use super::super::state;
+    use actix_web::{http::header, test, web, App, HttpResponse};
+
+    fn config(path: &std::path::Path) -> Arc<Config<Memory, Verifyer>> {
+        let config = serde_json::json!({"paseto": {"path": path, "ttl": 60, "refresh_ttl": 600}});
+        Arc::new(serde_json::from_str(&config.to_string()).unwrap())
+    }
+
+    fn client_token(config: &Config<Memory, Verifyer>, scope: &str) -> String {
+        let client = Id::default().to_hex();
+        let mut token = Token::new(String::from("Beekeeper"), Id::default(), Audience::One(client.clone()), 60);
+        token.claims.insert("client_id".to_string(), Value::String(client));
+        token.claims.insert("scope".to_string(), Value::String(scope.to_string()));
+        token.try_sign(&config.paseto().keys()).unwrap().signature.unwrap()
+    }
+
+    async fn subject(Authenticated(token): Authenticated) -> HttpResponse {
+        HttpResponse::Ok().body(token.subject.to_hex())
+    }
+
+    async fn authorized(Authorized(token): Authorized) -> HttpResponse {
+        HttpResponse::Ok().body(token.subject.to_hex())
+    }
+
+    #[actix_web::test]
+    async fn test_authenticated_and_required_scope() {
+        let path = std::env::temp_dir().join(format!("beekeeper_auth_{}.json", std::process::id()));
+        let config = config(&path);
+        let app = test::init_service(App::new()
+            .configure(state(config.clone()))
+            .route("/me", web::get().to(subject))
+            .route("/client", web::get().to(authorized))
+            .service(web::resource("/email").wrap(Require::scope("email")).route(web::get().to(authorized)))
+        ).await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/me").to_request()).await;
+        assert_eq!(res.status(), 401);
+        assert_eq!(res.headers().get(header::WWW_AUTHENTICATE).unwrap(), "Bearer");
+
+        let req = test::TestRequest::get().uri("/me").insert_header((header::AUTHORIZATION, "Bearer v4.public.garbage")).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 401);
+        assert!(res.headers().get(header::WWW_AUTHENTICATE).unwrap().to_str().unwrap().starts_with("Bearer error=\"invalid_token\""));
+
+        let user = Token::new(String::from("Beekeeper"), Id::default(), Audience::None, 60).try_sign(&config.paseto().keys()).unwrap().signature.unwrap();
+        let req = test::TestRequest::get().uri("/me").insert_header((header::AUTHORIZATION, format!("Bearer {}", user))).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        // Tokens issued to clients never pass for the user themselves
+        let token = client_token(&config, "openid");
+        let req = test::TestRequest::get().uri("/me").insert_header((header::AUTHORIZATION, format!("Bearer {}", token))).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+        let req = test::TestRequest::get().uri("/client").insert_header((header::AUTHORIZATION, format!("Bearer {}", token))).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        let req = test::TestRequest::get().uri("/email").insert_header((header::AUTHORIZATION, format!("Bearer {}", token))).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 403);
+        assert_eq!(res.headers().get(header::WWW_AUTHENTICATE).unwrap(), "Bearer error=\"insufficient_scope\", scope=\"email\"");
+
+        let token = client_token(&config, "openid email");
+        let req = test::TestRequest::get().uri("/email").insert_header((header::AUTHORIZATION, format!("Bearer {}", token))).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+//! Cross-origin resource sharing for the SPAs calling the API from other origins.
+//!
+//! Allowed origins are echoed back with credentials allowed, so the session
+//! cookies and the `Authorization` header can be used across origins.
+//! Requests from other origins get no CORS headers and are blocked by the browser.
+
+use actix_web::{dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, body::EitherBody, http::{Method, header::{self, HeaderMap, HeaderValue}}, HttpResponse, Error};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+
+/// How long browsers may cache a preflight response, in seconds.
+const MAX_AGE: &str = "3600";
+const ALLOWED_METHODS: &str = "GET, POST, PATCH, DELETE, OPTIONS";
+const ALLOWED_HEADERS: &str = "Authorization, Content-Type";
+
+
+/// Middleware answering preflight requests and adding CORS headers for the allowed origins.
+#[derive(Clone)]
+pub struct Cors {
+    origins: Rc<[String]>,
+}
+
+
+impl Cors {
+    /// `*` allows any origin.
+    pub fn new(origins: &[String]) -> Self {
+        Self {origins: origins.into()}
+    }
+}
+
+
+fn allowed(origins: &[String], origin: &HeaderValue) -> bool {
+    match origin.to_str() {
+        Ok(origin) => origins.iter().any(|allowed| allowed == "*" || allowed.trim_end_matches('/') == origin),
+        Err(_) => false
+    }
+}
+
+
+fn allow(headers: &mut HeaderMap, origin: HeaderValue) {
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+    headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_static("Authorization"));
+    headers.append(header::VARY, HeaderValue::from_static("Origin"));
+}
+
+
+impl<S, B> Transform<S, ServiceRequest> for Cors
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = CorsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CorsMiddleware {service, origins: self.origins.clone()}))
+    }
+}
+
+
+pub struct CorsMiddleware<S> {
+    service: S,
+    origins: Rc<[String]>,
+}
+
+
+impl<S, B> Service<ServiceRequest> for CorsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let origin = req.headers().get(header::ORIGIN).filter(|origin| allowed(&self.origins, origin)).cloned();
+        let origin = match origin {
+            Some(origin) => origin,
+            None => {
+                let response = self.service.call(req);
+                return Box::pin(async move { Ok(response.await?.map_into_left_body()) })
+            }
+        };
+        if req.method() == Method::OPTIONS && req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD) {
+            let headers = req.headers().get(header::ACCESS_CONTROL_REQUEST_HEADERS).cloned()
+                .unwrap_or(HeaderValue::from_static(ALLOWED_HEADERS));
+            let mut response = HttpResponse::NoContent()
+                .insert_header((header::ACCESS_CONTROL_ALLOW_METHODS, ALLOWED_METHODS))
+                .insert_header((header::ACCESS_CONTROL_ALLOW_HEADERS, headers))
+                .insert_header((header::ACCESS_CONTROL_MAX_AGE, MAX_AGE))
+                .finish();
+            allow(response.headers_mut(), origin);
+            return Box::pin(ready(Ok(req.into_response(response).map_into_right_body())))
+        }
+        let response = self.service.call(req);
+        Box::pin(async move {
+            let mut response = response.await?;
+            allow(response.headers_mut(), origin);
+            Ok(response.map_into_left_body())
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App};
+
+    #[actix_web::test]
+    async fn test_cors() {
+        let cors = Cors::new(&["https://app.example.com".to_string()]);
+        let app = test::init_service(App::new().wrap(cors).route("/", web::get().to(HttpResponse::Ok))).await;
+
+        let req = test::TestRequest::default()
+            .method(Method::OPTIONS)
+            .insert_header((header::ORIGIN, "https://app.example.com"))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 204);
+        assert_eq!(res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "https://app.example.com");
+        assert_eq!(res.headers().get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).unwrap(), "true");
+
+        let req = test::TestRequest::get().insert_header((header::ORIGIN, "https://app.example.com")).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "https://app.example.com");
+
+        let req = test::TestRequest::get().insert_header((header::ORIGIN, "https://evil.example.com")).to_request();
+        let res = test::call_service(&app, req).await;
+        assert!(res.status().is_success());
+        assert!(res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+}
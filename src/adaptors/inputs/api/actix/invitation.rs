@@ -0,0 +1,84 @@
+use actix_web::{web::{self, Json, Data, Path}, Responder, HttpResponse, HttpRequest};
+use crate::domain::services::{Authentication, Membership, Invitations, Get, List};
+use crate::domain::types::{Config, Id, Invitation, Organisation, User};
+use super::{Response, Store, Messenger, auth::Authenticated};
+use std::sync::Arc;
+
+
+/// The base URL the invitation links point back to.
+fn base_url<DB, V>(config: &Config<DB, V>) -> String {
+    format!("{}/invitations", config.domain().trim_end_matches('/'))
+}
+
+
+/// Sending, listing and answering invitations.
+pub(super) fn routes<DB: Store, V: Messenger>(cfg: &mut web::ServiceConfig) {
+    cfg.route("/organisations/{id}/invitations", web::post().to(invite::<DB, V>));
+    cfg.route("/organisations/{id}/invitations", web::get().to(list_invitations::<DB, V>));
+    cfg.route("/organisations/{id}/invitations/{invitation}", web::delete().to(revoke::<DB, V>));
+    cfg.route("/invitations/{id}", web::get().to(open::<DB, V>));
+    cfg.route("/invitations/{id}/accept", web::post().to(accept::<DB, V>));
+    cfg.route("/invitations/{id}/decline", web::post().to(decline::<DB, V>));
+}
+
+
+async fn invite<DB: Store, V: Messenger>(Authenticated(token): Authenticated, id: Path<String>, json: Json<Invitation>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let id: &Id = &id.parse()?;
+    let db = config.db();
+    let inviter = config.verifyer();
+    let base_url = &base_url(&config);
+    let user_id = &token.subject;
+    Organisation::owner(id, user_id, db).await?;
+    let organisation = &Organisation::get(id, db).await?;
+    let invitation = json.0.send(organisation, user_id, base_url, db, inviter).await?;
+    Ok(invitation)
+}
+
+
+async fn list_invitations<DB: Store, V: Messenger>(Authenticated(token): Authenticated, id: Path<String>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let id: &Id = &id.parse()?;
+    let db = config.db();
+    let user_id = &token.subject;
+    Organisation::owner(id, user_id, db).await?;
+    let invitations = <Organisation as List<Invitation>>::list(id, (), db).await?;
+    Ok(Json(invitations))
+}
+
+
+async fn revoke<DB: Store, V: Messenger>(Authenticated(token): Authenticated, path: Path<(String, String)>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let (id, invitation) = path.into_inner();
+    let (id, invitation): (&Id, &Id) = (&id.parse()?, &invitation.parse()?);
+    let db = config.db();
+    let user_id = &token.subject;
+    Organisation::owner(id, user_id, db).await?;
+    Invitation::revoke(invitation, id, db).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+
+/// Where the emailed invitation links lead, the invitation is answered with `accept` or `decline`.
+async fn open<DB: Store, V: Messenger>(Authenticated(token): Authenticated, id: Path<String>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let id: &Id = &id.parse()?;
+    let db = config.db();
+    let user_id = &token.subject;
+    let invitation = Invitation::open(id, user_id, db).await?;
+    Ok(invitation)
+}
+
+
+async fn accept<DB: Store, V: Messenger>(Authenticated(token): Authenticated, id: Path<String>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let id: &Id = &id.parse()?;
+    let db = config.db();
+    let user_id = &token.subject;
+    let member = Invitation::accept(id, user_id, db).await?;
+    Ok(member)
+}
+
+
+async fn decline<DB: Store, V: Messenger>(Authenticated(token): Authenticated, id: Path<String>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let id: &Id = &id.parse()?;
+    let db = config.db();
+    let user_id = &token.subject;
+    Invitation::decline(id, user_id, db).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,44 +1,356 @@
-use crate::adaptors::outputs::database::memory::Memory;
+use crate::adaptors::outputs::database::{memory::Memory, Database};
+#[cfg(feature = "mongodb")]
+use crate::adaptors::outputs::database::mongo::Mongo;
+#[cfg(feature = "sql")]
+use crate::adaptors::outputs::database::sql::Sql;
 use crate::ports::inputs::config::Config as Conf;
-use actix_web::{web::Data, App, HttpServer};
+use crate::ports::outputs::verify::{Invite, Verifyer};
+use crate::ports::outputs::{attempts::LoginAttempts, consume::ConsumeRefreshToken, expiry::TokenExpiry, wrong_code::CountWrongCode};
+use crate::ports::outputs::database::{CreateItem, DeleteItem, GetItem, GetItems, Map, UpdateItem};
+use actix_web::{web::{Data, ServiceConfig}, App, HttpServer, HttpRequest};
 use crate::domain::services::Authentication;
 use std::error::Error as StdError;
-use crate::domain::types::Config;
+use crate::domain::types::{Args, AuthorizationCode, Config, Id, Invitation, Member, Organisation, Paseto, RefreshToken, Revocation, Service, Tls, User, Vault, Verification};
+use serde::{de::DeserializeOwned, Serialize};
+use log::LevelFilter;
 use crate::ports::Error;
+use std::time::Duration;
+use std::io::BufReader;
+use std::fs::File;
 use std::sync::Arc;
+use cors::Cors;
 
 
+mod admin;
+mod auth;
+mod cors;
 mod error;
+mod invitation;
+mod oauth;
+mod oidc;
+mod organisation;
+mod service;
 mod user;
+mod verify;
+
+
+pub use verify::{VerifyEmail, VerifyPhone};
 
 
 type Response<T> = std::result::Result<T, Error>;
-#[cfg(feature = "memory")]
-type DB = Memory;
 pub type Result<T> = std::result::Result<T, Box<dyn StdError + 'static>>;
-type Verifyer = crate::adaptors::outputs::verify::Verifyer;
+/// How often the running server checks whether its keys need rotating.
+const KEY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// How often expired verification codes and refresh tokens are purged from the memory database.
+const VERIFICATION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
 
 #[derive(Default)]
 pub struct Actix;
 
 
+/// A database the API can be served from, it implements every database port the endpoints use.
+///
+/// The memory database and, with the `mongodb` and `sql` features, MongoDB and SQL are stores.
+pub trait Store: Send + Sync + 'static
+    + CreateItem<User> + GetItem<User> + UpdateItem<User, Update = Map>
+    + CreateItem<Organisation> + GetItem<Organisation> + UpdateItem<Organisation, Update = Map> + DeleteItem<Organisation>
+    + CreateItem<Member> + GetItem<(Organisation, User), Member> + UpdateItem<(Organisation, User), Member, Update = Map> + DeleteItem<Member>
+    + GetItems<User, Organisation, Filter = bool> + GetItems<Organisation, (Member, User), Filter = bool>
+    + CreateItem<Service> + GetItem<Service> + TokenExpiry
+    + CreateItem<Verification<Id>> + GetItem<Verification<Id>> + DeleteItem<Verification<Id>> + CountWrongCode<Verification<Id>>
+    + CreateItem<Invitation> + GetItem<Invitation> + DeleteItem<Invitation> + GetItems<Organisation, Invitation, Filter = ()>
+    + CreateItem<AuthorizationCode> + GetItem<AuthorizationCode> + DeleteItem<AuthorizationCode>
+    + CreateItem<RefreshToken> + GetItem<RefreshToken> + GetItems<RefreshToken, Filter = ()> + DeleteItem<RefreshToken>
+    + GetItems<User, RefreshToken, Filter = ()> + ConsumeRefreshToken
+    + CreateItem<Revocation> + GetItems<Revocation, Filter = ()>
+    + LoginAttempts
+{
+    /// Gets the database ready before the server starts and starts its upkeep in the background.
+    async fn open<V: Messenger>(_config: &Arc<Config<Self, V>>) -> Result<()> {
+        Ok(())
+    }
+}
+
+
+/// A verifyer the API can be served with, it sends invitations and verifies the contacts the
+/// `email` and `phone` features enable.
+///
+/// The verifyer picked by the enabled features,
+/// [`Verifyer`](crate::adaptors::outputs::verify::Verifyer), is one when there is any.
+pub trait Messenger: Send + Sync + 'static + Verifyer + Invite + VerifyEmail + VerifyPhone {}
+impl<V: Send + Sync + 'static + Verifyer + Invite + VerifyEmail + VerifyPhone> Messenger for V {}
+
+
+/// Restores a persisted memory database, then compacts it and purges what expired in the background.
+impl Store for Memory {
+    async fn open<V: Messenger>(config: &Arc<Config<Self, V>>) -> Result<()> {
+        config.db().restore().await?;
+        tokio::spawn(sweeper(config.clone()));
+        if let Some(persistence) = config.db().persistence().filter(|persistence| persistence.interval > 0) {
+            tokio::spawn(snapshots(config.clone(), Duration::from_secs(persistence.interval)));
+        }
+        Ok(())
+    }
+}
+
+
+/// Expired verifications are removed by a TTL index and stale failed logins are pruned as failures are counted.
+#[cfg(feature = "mongodb")]
+impl Store for Mongo {}
+
+
+/// Stale failed logins and expired revocations are pruned as new ones are recorded.
+#[cfg(feature = "sql")]
+impl Store for Sql {}
+
+
 impl Actix {
-    pub async fn start() -> Result<()> {
-        std::env::set_var("RUST_LOG", "debug");
-        env_logger::init();
-        let state = Arc::new(<Config<Memory, Verifyer> as Conf>::load(None, ()).await?);
-        let data = Data::new(state);
-        HttpServer::new(move|| {
+    /// Loads the configuration and serves the API with the verifyer `V`, from the database the
+    /// `database` section picks, see [`Database`].
+    pub async fn start<V>(args: &[String]) -> Result<()>
+    where
+        V: Messenger + Default + Serialize + DeserializeOwned,
+    {
+        // `RUST_LOG` wins over the configured level, which is only known once the config is loaded
+        let rust_log = std::env::var_os("RUST_LOG").is_some();
+        let logger = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("trace")).build();
+        let filter = logger.filter();
+        log::set_boxed_logger(Box::new(logger))?;
+        log::set_max_level(if rust_log {filter} else {LevelFilter::Info});
+        let args = Args::parse(args.iter().cloned())?;
+        let config = <Config<Database, V> as Conf>::load(None, args).await?;
+        let http = config.http();
+        if !rust_log {
+            let level = http.log_level.parse::<LevelFilter>().map_err(|_| format!("invalid log level {}", http.log_level))?;
+            log::set_max_level(level);
+        }
+        for (key, layer) in config.sources().iter() {
+            log::info!("config {} set by {}", key, layer);
+        }
+        let (url, name) = (config.db().url.clone(), config.db().name.clone());
+        match url.as_deref() {
+            None => Self::serve(config.map_db(|database| database.memory)).await,
+            #[cfg(feature = "mongodb")]
+            Some(url) if url.starts_with("mongodb://") || url.starts_with("mongodb+srv://") => {
+                let mongo = Mongo::connect(url, name.as_deref().unwrap_or("beekeeper")).await?;
+                Self::serve(config.map_db(|_| mongo)).await
+            },
+            #[cfg(feature = "sql")]
+            Some(url) if url.starts_with("postgres://") || url.starts_with("postgresql://") || url.starts_with("sqlite:") => {
+                let sql = Sql::connect(url).await?;
+                Self::serve(config.map_db(|_| sql)).await
+            },
+            // Only the scheme, the url may hold credentials
+            Some(url) => Err(format!("no database for {} urls, is its feature enabled?", url.split(':').next().unwrap_or_default()))?
+        }
+    }
+
+    /// Serves the API with a configuration assembled in code, such as by [`Config::builder`],
+    /// on any [`Store`].
+    ///
+    /// The keys are kept fresh in the background, see [`Actix::rotate_keys`].
+    /// The database is opened first, see [`Store::open`]. A persisted memory database is restored
+    /// and compacted in the background, and its expired verification codes are purged every minute.
+    ///
+    /// A domain must be configured, it is the issuer of OpenID Connect tokens.
+    pub async fn serve<DB: Store, V: Messenger>(config: Config<DB, V>) -> Result<()> {
+        if config.domain().is_empty() {
+            Err("a domain is required, it is the issuer of OpenID Connect tokens")?
+        }
+        let state = Arc::new(config);
+        DB::open(&state).await?;
+        let http = state.http().clone();
+        let tls = match &http.tls {
+            Some(tls) => Some(server_config(tls)?),
+            None => None
+        };
+        tokio::spawn(rotation(state.paseto().clone()));
+        let cookies = http.cookie();
+        let origins = http.cors_origins.clone();
+        let mut server = HttpServer::new(move|| {
             App::new()
-            .app_data(data.clone())
-            .service(user::signup)
-            .service(user::login)
-            .service(user::user_info)
-            .service(user::patch_user)
-        })
-        .bind(("127.0.0.1", 8080))?
-        .run()
-        .await?;
+            .wrap(Cors::new(&origins))
+            .configure(self::state(state.clone()))
+            .app_data(cookies.clone())
+            .configure(user::routes::<DB, V>)
+            .configure(verify::routes::<DB, V>)
+            .configure(organisation::routes::<DB, V>)
+            .configure(invitation::routes::<DB, V>)
+            .configure(service::routes::<DB, V>)
+            .configure(oauth::routes::<DB, V>)
+            .configure(oidc::routes::<DB, V>)
+            .configure(admin::routes::<DB, V>)
+        });
+        if let Some(workers) = http.workers {
+            server = server.workers(workers);
+        }
+        for addr in &http.bind {
+            server = match &tls {
+                Some(config) => server.bind_rustls_0_23(addr, config.clone())?,
+                None => server.bind(addr)?
+            };
+        }
+        server.run().await?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Rotates the PASETO keys right away, for when the signing key may have leaked.
+    ///
+    /// Running servers pick up the new keys on their next check.
+    pub async fn rotate_keys<V>(args: &[String]) -> Result<()>
+    where
+        V: Messenger + Default + Serialize + DeserializeOwned,
+    {
+        let args = Args::parse(args.iter().cloned())?;
+        let config = <Config<Database, V> as Conf>::load(None, args).await?;
+        let keys = config.paseto().rotate()?;
+        println!("rotated PASETO keys, now signing with {}", keys.kid());
+        Ok(())
+    }
+
+    /// Prints the layer each configuration value came from, without the values.
+    pub async fn sources<V>(args: &[String]) -> Result<()>
+    where
+        V: Messenger + Default + Serialize + DeserializeOwned,
+    {
+        let args = Args::parse(args.iter().cloned())?;
+        let config = <Config<Database, V> as Conf>::load(None, args).await?;
+        print!("{}", config.sources());
+        Ok(())
+    }
+
+    /// Manages the secrets vault read by `$$$name` references.
+    ///
+    /// `vault set <name>` reads the secret from stdin, `vault remove <name>` deletes it
+    /// and `vault list` prints the names of the stored secrets.
+    pub async fn vault(args: &[String]) -> Result<()> {
+        let path = Vault::path();
+        let kek = Vault::master_key()?;
+        let mut vault = match Vault::open(&path, &kek) {
+            Ok(vault) => vault,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vault::default(),
+            Err(err) => Err(err)?
+        };
+        match (args.first().map(String::as_str), args.get(1)) {
+            (Some("set"), Some(name)) => {
+                let mut secret = String::new();
+                std::io::stdin().read_line(&mut secret)?;
+                vault.set(name.clone(), secret.trim_end_matches(['\r', '\n']).to_string());
+            },
+            (Some("remove"), Some(name)) => {
+                if !vault.remove(name) {
+                    Err(format!("secret {} is not in the vault", name))?
+                }
+            },
+            (Some("list"), None) => {
+                vault.names().for_each(|name| println!("{}", name));
+                return Ok(())
+            },
+            _ => Err("usage: vault set <name> | vault remove <name> | vault list")?
+        }
+        vault.save(&path, &kek)?;
+        Ok(())
+    }
+}
+
+
+/// Loads the certificate chain and private key to serve HTTPS with.
+fn server_config(tls: &Tls) -> Result<rustls::ServerConfig> {
+    let open = |path: &str| File::open(path).map(BufReader::new).map_err(|err| format!("could not read {}: {}", path, err));
+    let certs = rustls_pemfile::certs(&mut open(&tls.cert)?).collect::<std::result::Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut open(&tls.key)?)?.ok_or_else(|| format!("no private key in {}", tls.key))?;
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    Ok(config)
+}
+
+
+/// Keeps the keys of the running server fresh.
+///
+/// Keys rotated by the `rotate-keys` command are swapped in, and keys about to expire are rotated.
+async fn rotation(paseto: Paseto) {
+    let mut interval = tokio::time::interval(KEY_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        match paseto.reload() {
+            Ok(true) => log::info!("reloaded rotated PASETO keys, now verifying with {}", paseto.keys().kid()),
+            Ok(false) => (),
+            Err(err) => log::error!("failed to reload the PASETO keys: {}", err),
+        }
+        if paseto.rotation_due() {
+            match paseto.rotate() {
+                Ok(keys) => log::info!("rotated PASETO keys, now signing with {}", keys.kid()),
+                Err(err) => log::error!("failed to rotate the PASETO keys: {}", err),
+            }
+        }
+    }
+}
+
+
+/// Compacts the persisted memory database every `period`.
+async fn snapshots<V>(state: Arc<Config<Memory, V>>, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    // The first tick completes right away, just after the database was restored
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match state.db().snapshot().await {
+            Ok(()) => log::info!("wrote a snapshot of the memory database"),
+            Err(err) => log::error!("failed to write a snapshot of the memory database: {}", err),
+        }
+    }
+}
+
+
+/// Purges expired verification codes, expired refresh tokens and failed logins older than the lockout from the memory database.
+async fn sweeper<V>(state: Arc<Config<Memory, V>>) {
+    let mut interval = tokio::time::interval(VERIFICATION_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        match state.db().purge_expired_verifications().await {
+            Ok(0) => (),
+            Ok(purged) => log::debug!("purged {} expired verification codes", purged),
+            Err(err) => log::error!("failed to purge expired verification codes: {}", err),
+        }
+        match state.db().purge_expired_refresh_tokens().await {
+            Ok(0) => (),
+            Ok(purged) => log::debug!("purged {} expired refresh tokens", purged),
+            Err(err) => log::error!("failed to purge expired refresh tokens: {}", err),
+        }
+        let before = chrono::Utc::now() - state.http().throttle.lockout();
+        match state.db().forget_failures(before) {
+            Ok(0) => (),
+            Ok(forgotten) => log::debug!("forgot the failed logins of {} accounts and addresses", forgotten),
+            Err(err) => log::error!("failed to forget failed logins: {}", err),
+        }
+    }
+}
+
+
+/// Shares the configuration with the endpoints and the token extractors of an app serving from `DB`.
+fn state<DB: Store, V: Messenger>(config: Arc<Config<DB, V>>) -> impl FnOnce(&mut ServiceConfig) {
+    move |cfg| {
+        cfg.app_data(auth::Tokens::new(config.clone())).app_data(Data::new(config));
+    }
+}
+
+
+/// Extracts the raw token from the `token` cookie or the `Authorization` header.
+fn token(req: &HttpRequest) -> Response<String> {
+    let token = match req.cookie("token") {
+        Some(cookie) => cookie.value().to_string(),
+        None => {
+            match req.headers().get(actix_web::http::header::AUTHORIZATION) {
+                Some(value) => match value.to_str(){
+                    Ok(value) => value.to_string(),
+                    _ => Err(error::Error::UnAuthorized)?
+                },
+                None => Err(error::Error::UnAuthorized)?
+            }
+        }
+    };
+    Ok(token.replace("Bearer ", ""))
+}
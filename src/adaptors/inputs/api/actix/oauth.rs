@@ -0,0 +1,395 @@
+use actix_web::{web::{self, Json, Data, Query, Form, Either}, Responder, HttpResponse, HttpRequest, http::header::{LOCATION, CACHE_CONTROL, AUTHORIZATION}};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crate::domain::types::{AuthorizationCode, AuthorizationRequest, Config, Error, GrantType, Id, RefreshToken, Service, Session, TokenResponse, User};
+use crate::domain::services::{Authentication, OAuth, Refresh};
+use super::{Response, Store, Messenger, auth::Authenticated};
+use crate::ports::Error as PortError;
+use serde::Deserialize;
+use std::sync::Arc;
+
+
+#[derive(Deserialize)]
+struct Decision {
+    /// The consent ticket handed out by `GET /oauth/authorize`.
+    pub ticket: String,
+    pub approve: bool
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub client_id: Option<Id>,
+    pub client_secret: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    pub scope: Option<String>
+}
+
+#[derive(Deserialize)]
+struct TokenHint {
+    pub token: String,
+    /// Accepted as RFC 7009 asks, the kind of token is told apart by its format
+    #[serde(rename = "token_type_hint")]
+    pub _token_type_hint: Option<String>,
+    pub client_id: Option<Id>,
+    pub client_secret: Option<String>
+}
+
+/// Pulls a parameter the grant type requires out of the token request.
+fn required<T>(param: Option<T>, name: &str) -> Response<T> {
+    match param {
+        Some(param) => Ok(param),
+        None => Err(Error::validation(name, "missing parameter"))?
+    }
+}
+
+
+/// Reads the client credentials from the `Authorization: Basic` header, falling back to the request body.
+///
+/// The secret is left out for clients that do not send one.
+fn credentials(req: &HttpRequest, client_id: Option<Id>, client_secret: Option<String>) -> Response<(Id, Option<String>)> {
+    let basic = req.headers().get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|value| STANDARD.decode(value).ok())
+        .and_then(|value| String::from_utf8(value).ok());
+    if let Some(credentials) = basic {
+        let (id, secret) = match credentials.split_once(':') {
+            Some(credentials) => credentials,
+            None => Err(Error::InvalidClient)?
+        };
+        return Ok((id.parse()?, Some(secret.to_string())))
+    }
+    Ok((required(client_id, "client_id")?, client_secret))
+}
+
+
+/// Reads the credentials of a client that has to authenticate.
+fn client(req: &HttpRequest, client_id: Option<Id>, client_secret: Option<String>) -> Response<(Id, String)> {
+    let (id, secret) = credentials(req, client_id, client_secret)?;
+    Ok((id, required(secret, "client_secret")?))
+}
+
+
+/// The RFC 6749 section 4.1.2.1 error code for an authorization request that failed.
+fn error_code(err: &PortError) -> &'static str {
+    match err.downcast_ref::<Error>() {
+        Some(Error::UnauthorizedClient) => "unauthorized_client",
+        Some(Error::UnsupportedResponseType) => "unsupported_response_type",
+        Some(Error::InvalidScope { .. }) => "invalid_scope",
+        Some(Error::AccessDenied) => "access_denied",
+        Some(Error::Internal { .. }) | None => "server_error",
+        Some(_) => "invalid_request"
+    }
+}
+
+
+/// Sends the user back to the client with the outcome of the authorization request.
+///
+/// Only called once the redirect URI is known to be registered on the client.
+fn redirect(request: &AuthorizationRequest, outcome: Result<AuthorizationCode, PortError>) -> Response<HttpResponse> {
+    let mut params = match outcome {
+        Ok(code) => vec![("code", code.code)],
+        Err(err) => {
+            let code = error_code(&err);
+            if code == "server_error" {
+                log::error!("{}", err.get_source().log_message());
+            }
+            vec![("error", code.to_string()), ("error_description", err.to_string())]
+        }
+    };
+    if let Some(state) = &request.state {
+        params.push(("state", state.clone()));
+    }
+    let params = serde_urlencoded::to_string(params).map_err(Error::internal)?;
+    let separator = if request.redirect_uri.contains('?') {'&'} else {'?'};
+    let location = format!("{}{}{}", request.redirect_uri, separator, params);
+    Ok(HttpResponse::Found()
+        .insert_header((LOCATION, location))
+        .insert_header((CACHE_CONTROL, "no-store"))
+        .finish())
+}
+
+
+/// The OAuth 2.0 authorization, token, introspection and revocation endpoints.
+pub(super) fn routes<DB: Store, V: Messenger>(cfg: &mut web::ServiceConfig) {
+    cfg.route("/oauth/authorize", web::get().to(authorize::<DB, V>));
+    cfg.route("/oauth/authorize", web::post().to(approve::<DB, V>));
+    cfg.route("/oauth/token", web::post().to(token_exchange::<DB, V>));
+    cfg.route("/oauth/introspect", web::post().to(introspect::<DB, V>));
+    cfg.route("/oauth/revoke", web::post().to(revoke::<DB, V>));
+}
+
+
+/// Validates an authorization request and asks the user to approve the client.
+///
+/// Errors are redirected to the client once it and the redirect URI check out.
+async fn authorize<DB: Store, V: Messenger>(Authenticated(token): Authenticated, query: Query<AuthorizationRequest>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let request = query.into_inner();
+    let paseto = config.paseto();
+    let db = config.db();
+    let user_id = &token.subject;
+    let client = Service::redirect_client(&request.client_id, &request.redirect_uri, db).await?;
+    match client.consent(&request, user_id, paseto, config.name.clone()) {
+        Ok(consent) => Ok(HttpResponse::Ok().insert_header((CACHE_CONTROL, "no-store")).json(consent)),
+        Err(err) => redirect(&request, Err(err))
+    }
+}
+
+
+/// Takes the user's decision on a consent ticket, redirecting them to the client with a code or `access_denied`.
+async fn approve<DB: Store, V: Messenger>(Authenticated(token): Authenticated, form: Either<Form<Decision>, Json<Decision>>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let decision = form.into_inner();
+    let paseto = config.paseto();
+    let db = config.db();
+    let user_id = &token.subject;
+    let request = Service::consented(&decision.ticket, user_id, paseto)?;
+    let client = Service::redirect_client(&request.client_id, &request.redirect_uri, db).await?;
+    let code = match decision.approve {
+        true => client.issue_code(&request, user_id, db).await,
+        false => Err(Error::AccessDenied.into())
+    };
+    redirect(&request, code)
+}
+
+
+async fn token_exchange<DB: Store, V: Messenger>(req: HttpRequest, form: Either<Form<TokenRequest>, Json<TokenRequest>>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let request = form.into_inner();
+    let issuer = config.name.clone();
+    let paseto = config.paseto();
+    let db = config.db();
+    let session = match request.grant_type.parse::<GrantType>() {
+        Ok(GrantType::AuthorizationCode) => {
+            let code = required(request.code, "code")?;
+            let (client_id, client_secret) = credentials(&req, request.client_id, request.client_secret)?;
+            let client = &Service::identify_client(&client_id, client_secret.as_deref(), db, config.argon()).await?;
+            let redirect_uri = &required(request.redirect_uri, "redirect_uri")?;
+            let code_verifier = required(request.code_verifier, "code_verifier")?;
+            let domain = &super::oidc::issuer(&config);
+            let code = (code.as_str(), code_verifier.as_str());
+            Service::exchange_code(code, client, redirect_uri, db, paseto, issuer, domain).await?
+        },
+        Ok(GrantType::ClientCredentials) => {
+            let (client_id, client_secret) = &client(&req, request.client_id, request.client_secret)?;
+            let verifier = config.argon();
+            let scope = request.scope.as_deref();
+            Session::from(Service::client_credentials(client_id, client_secret, scope, db, verifier, paseto, issuer).await?)
+        },
+        Ok(GrantType::RefreshToken) => {
+            let refresh_token = &required(request.refresh_token, "refresh_token")?;
+            let (client_id, client_secret) = credentials(&req, request.client_id, request.client_secret)?;
+            let client = Service::identify_client(&client_id, client_secret.as_deref(), db, config.argon()).await?;
+            RefreshToken::refresh(refresh_token, Some(&client.id), db, paseto, issuer).await?
+        },
+        _ => Err(Error::validation("grant_type", "unsupported grant type"))?
+    };
+    Ok(TokenResponse::try_from(session)?)
+}
+
+
+async fn introspect<DB: Store, V: Messenger>(req: HttpRequest, form: Either<Form<TokenHint>, Json<TokenHint>>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let request = form.into_inner();
+    let (client_id, client_secret) = &client(&req, request.client_id, request.client_secret)?;
+    let verifier = config.argon();
+    let paseto = config.paseto();
+    let db = config.db();
+    let introspection = Service::introspect(client_id, client_secret, &request.token, db, verifier, paseto).await?;
+    Ok(introspection)
+}
+
+
+async fn revoke<DB: Store, V: Messenger>(req: HttpRequest, form: Either<Form<TokenHint>, Json<TokenHint>>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let request = form.into_inner();
+    let (client_id, client_secret) = &client(&req, request.client_id, request.client_secret)?;
+    let verifier = config.argon();
+    let paseto = config.paseto();
+    let db = config.db();
+    Service::revoke(client_id, client_secret, &request.token, db, verifier, paseto).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::{Audience, ClientType, Permission, Scope, Token};
+    use crate::domain::services::Paseto;
+    use crate::adaptors::outputs::{database::memory::Memory, verify::Verifyer};
+    use super::super::state;
+    use actix_web::{http::header, test, App};
+
+    const REDIRECT_URI: &str = "https://client.example.com/callback";
+    // The example from RFC 7636 appendix B
+    const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    const CODE_CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+    fn config(path: &std::path::Path) -> Arc<Config<Memory, Verifyer>> {
+        let config = serde_json::json!({"domain": "https://auth.example.com", "paseto": {"path": path, "ttl": 60, "refresh_ttl": 600}});
+        Arc::new(serde_json::from_str(&config.to_string()).unwrap())
+    }
+
+    fn user_token(config: &Config<Memory, Verifyer>, user_id: Id) -> String {
+        let token = Token::new(config.name.clone(), user_id, Audience::None, 60);
+        format!("Bearer {}", token.try_sign(&config.paseto().keys()).unwrap().signature.unwrap())
+    }
+
+    fn query(location: &str) -> std::collections::HashMap<String, String> {
+        let (_, query) = location.split_once('?').unwrap();
+        serde_urlencoded::from_str(query).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_consent_and_code_exchange() {
+        let path = std::env::temp_dir().join(format!("beekeeper_oauth_{}.json", std::process::id()));
+        let config = config(&path);
+        let app = test::init_service(App::new().configure(state(config.clone())).configure(routes::<Memory, Verifyer>)).await;
+        let client = Service {
+            name: String::from("Client"),
+            redirect_uris: vec![REDIRECT_URI.to_string()],
+            grant_types: vec![GrantType::AuthorizationCode, GrantType::RefreshToken],
+            ..Default::default()
+        };
+        let client = client.register_client(&Id::default(), config.db(), config.argon()).await.unwrap();
+        let user = user_token(&config, Id::default());
+        let request = |redirect_uri: &str, scope: &str| {
+            let params = serde_urlencoded::to_string([
+                ("response_type", "code"), ("client_id", client.id.to_hex().as_str()), ("redirect_uri", redirect_uri),
+                ("scope", scope), ("state", "xyz"), ("code_challenge", CODE_CHALLENGE), ("code_challenge_method", "S256")
+            ]).unwrap();
+            test::TestRequest::get().uri(&format!("/oauth/authorize?{}", params)).insert_header((header::AUTHORIZATION, user.clone())).to_request()
+        };
+
+        // An unregistered redirect uri is never redirected to
+        let res = test::call_service(&app, request("https://evil.example.com", "")).await;
+        assert_eq!(res.status(), 400);
+
+        let res = test::call_service(&app, request(REDIRECT_URI, "unknown")).await;
+        assert_eq!(res.status(), 302);
+        let params = query(res.headers().get(header::LOCATION).unwrap().to_str().unwrap());
+        assert_eq!(params["error"], "invalid_scope");
+        assert_eq!(params["state"], "xyz");
+
+        // No code is issued before the user approves
+        let consent: serde_json::Value = test::call_and_read_body_json(&app, request(REDIRECT_URI, "")).await;
+        let ticket = consent["ticket"].as_str().unwrap();
+        assert_eq!(consent["client_name"], "Client");
+
+        let decide = |user: &str, approved: bool| test::TestRequest::post().uri("/oauth/authorize")
+            .insert_header((header::AUTHORIZATION, user.to_string()))
+            .set_json(serde_json::json!({"ticket": ticket, "approve": approved}))
+            .to_request();
+        let res = test::call_service(&app, decide(&user_token(&config, Id::default()), true)).await;
+        assert_eq!(res.status(), 400);
+
+        let res = test::call_service(&app, decide(&user, false)).await;
+        assert_eq!(res.status(), 302);
+        assert_eq!(query(res.headers().get(header::LOCATION).unwrap().to_str().unwrap())["error"], "access_denied");
+
+        let res = test::call_service(&app, decide(&user, true)).await;
+        assert_eq!(res.status(), 302);
+        let params = query(res.headers().get(header::LOCATION).unwrap().to_str().unwrap());
+        assert_eq!(params["state"], "xyz");
+        let code = &params["code"];
+
+        // The client holds a secret, so it has to authenticate to exchange the code
+        let exchange = |secret: Option<&str>| {
+            let mut form = vec![("grant_type", "authorization_code"), ("code", code), ("redirect_uri", REDIRECT_URI), ("code_verifier", CODE_VERIFIER)];
+            let client_id = client.id.to_hex();
+            form.push(("client_id", &client_id));
+            if let Some(secret) = secret {
+                form.push(("client_secret", secret));
+            }
+            test::TestRequest::post().uri("/oauth/token").set_form(form).to_request()
+        };
+        assert_eq!(test::call_service(&app, exchange(None)).await.status(), 401);
+        assert_eq!(test::call_service(&app, exchange(Some("wrong"))).await.status(), 401);
+        let session: serde_json::Value = test::call_and_read_body_json(&app, exchange(Some(&client.client_secret))).await;
+        let refresh_token = session["refresh_token"].as_str().unwrap();
+
+        // So does it to refresh, and a refresh token is only good once
+        let refresh = |secret: &str| {
+            let client_id = client.id.to_hex();
+            let form = [("grant_type", "refresh_token"), ("refresh_token", refresh_token), ("client_id", &client_id), ("client_secret", secret)];
+            test::TestRequest::post().uri("/oauth/token").set_form(form).to_request()
+        };
+        assert_eq!(test::call_service(&app, refresh("wrong")).await.status(), 401);
+        assert_eq!(test::call_service(&app, refresh(&client.client_secret)).await.status(), 200);
+        assert_eq!(test::call_service(&app, refresh(&client.client_secret)).await.status(), 400);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_client_credentials() {
+        let path = std::env::temp_dir().join(format!("beekeeper_credentials_{}.json", std::process::id()));
+        let config = config(&path);
+        let app = test::init_service(App::new().configure(state(config.clone())).configure(routes::<Memory, Verifyer>)).await;
+        let (db, hasher) = (config.db(), config.argon());
+        let owner = Id::default();
+        let api = Service { name: String::from("Api"), grant_types: vec![GrantType::ClientCredentials], ..Default::default() };
+        let api = api.register_client(&owner, db, hasher).await.unwrap();
+        let read = Scope { id: api.id, name: String::from("profile"), permission: Permission::Read };
+        let write = Scope { permission: Permission::Write, ..read.clone() };
+
+        // Only the owner of a service can hand out its scopes
+        let client = Service { name: String::from("Client"), scopes: vec![read.clone(), write.clone()], grant_types: vec![GrantType::ClientCredentials], ..Default::default() };
+        assert!(client.clone().register_client(&Id::default(), db, hasher).await.is_err());
+        let unknown = Scope { id: Id::default(), ..read.clone() };
+        let stranger = Service { scopes: vec![unknown], ..client.clone() };
+        assert!(stranger.register_client(&owner, db, hasher).await.is_err());
+        let client = client.register_client(&owner, db, hasher).await.unwrap();
+
+        let credentials = |client: &Service, secret: &str, scope: Option<&str>| {
+            let mut form = vec![("grant_type", String::from("client_credentials")), ("client_id", client.id.to_hex()), ("client_secret", secret.to_string())];
+            if let Some(scope) = scope {
+                form.push(("scope", scope.to_string()));
+            }
+            test::TestRequest::post().uri("/oauth/token").set_form(form).to_request()
+        };
+        let scope = |token: &serde_json::Value| {
+            let token = Token::try_verify(token["access_token"].as_str().unwrap(), &config.paseto().keys()).unwrap();
+            token.scopes().join(" ")
+        };
+        let (read, write) = (String::from(read), String::from(write));
+
+        assert_eq!(test::call_service(&app, credentials(&client, "wrong", None)).await.status(), 401);
+
+        let token = test::call_and_read_body_json(&app, credentials(&client, &client.client_secret, None)).await;
+        assert_eq!(scope(&token), format!("{} {}", read, write));
+        let token = test::call_and_read_body_json(&app, credentials(&client, &client.client_secret, Some(&read))).await;
+        assert_eq!(scope(&token), read);
+        let unregistered = format!("{}:profile:delete", api.id.to_hex());
+        assert_eq!(test::call_service(&app, credentials(&client, &client.client_secret, Some(&unregistered))).await.status(), 400);
+
+        // The grant types a client registered with are the only ones it can use
+        let web = Service { name: String::from("Web"), redirect_uris: vec![REDIRECT_URI.to_string()], grant_types: vec![GrantType::AuthorizationCode], ..Default::default() };
+        let web = web.register_client(&owner, db, hasher).await.unwrap();
+        assert_eq!(test::call_service(&app, credentials(&web, &web.client_secret, None)).await.status(), 400);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_public_client() {
+        let path = std::env::temp_dir().join(format!("beekeeper_public_{}.json", std::process::id()));
+        let config = config(&path);
+        let (db, hasher) = (config.db(), config.argon());
+        let app = Service { name: String::from("App"), redirect_uris: vec![REDIRECT_URI.to_string()], grant_types: vec![GrantType::AuthorizationCode], client_type: ClientType::Public, ..Default::default() };
+
+        // A public client cannot keep a secret, so it gets none and cannot act on its own behalf
+        let credentials = Service { grant_types: vec![GrantType::ClientCredentials], ..app.clone() };
+        assert!(credentials.register_client(&Id::default(), db, hasher).await.is_err());
+        let app = app.register_client(&Id::default(), db, hasher).await.unwrap();
+        assert!(app.client_secret.is_empty());
+        assert!(Service::identify_client(&app.id, None, db, hasher).await.is_ok());
+        assert!(Service::authenticate_client(&app.id, "", db, hasher).await.is_err());
+
+        // A confidential client always has to authenticate
+        let web = Service { name: String::from("Web"), client_type: ClientType::Confidential, ..app.clone() };
+        let web = web.register_client(&Id::default(), db, hasher).await.unwrap();
+        assert!(!web.client_secret.is_empty());
+        assert!(Service::identify_client(&web.id, None, db, hasher).await.is_err());
+        assert!(Service::identify_client(&web.id, Some(&web.client_secret), db, hasher).await.is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use actix_web::{guard, web::{self, Json, Data}, Responder};
+use crate::domain::types::{Config, Jwks, PaserkSet, User, OIDC_SCOPES};
+use crate::domain::services::OpenId;
+use super::{Response, Store, Messenger, auth::Authorized};
+use serde::Serialize;
+use std::sync::Arc;
+
+
+/// The OpenID Provider metadata (OpenID Connect Discovery section 3).
+#[derive(Serialize)]
+struct Discovery {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub introspection_endpoint: String,
+    pub revocation_endpoint: String,
+    pub scopes_supported: Vec<&'static str>,
+    pub response_types_supported: Vec<&'static str>,
+    pub grant_types_supported: Vec<&'static str>,
+    pub subject_types_supported: Vec<&'static str>,
+    pub id_token_signing_alg_values_supported: Vec<&'static str>,
+    pub token_endpoint_auth_methods_supported: Vec<&'static str>,
+    pub code_challenge_methods_supported: Vec<&'static str>,
+    pub claims_supported: Vec<&'static str>,
+}
+
+
+/// The public URL of this server, the issuer of ID tokens.
+///
+/// The configured domain is required by [`super::Actix::serve`], the request headers are never trusted for it.
+pub(super) fn issuer<DB, V>(config: &Config<DB, V>) -> String {
+    config.domain().trim_end_matches('/').to_string()
+}
+
+
+/// Discovery, the published keys and the OpenID Connect userinfo endpoint.
+pub(super) fn routes<DB: Store, V: Messenger>(cfg: &mut web::ServiceConfig) {
+    cfg.route("/.well-known/openid-configuration", web::get().to(discovery::<DB, V>));
+    cfg.route("/userinfo", web::route().guard(guard::Any(guard::Get()).or(guard::Post())).to(user_info::<DB, V>));
+    cfg.route("/.well-known/jwks.json", web::get().to(jwks::<DB, V>));
+    cfg.route("/.well-known/paserk.json", web::get().to(paserk::<DB, V>));
+}
+
+
+async fn discovery<DB: Store, V: Messenger>(config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let issuer = issuer(&config);
+    let discovery = Discovery {
+        authorization_endpoint: format!("{}/oauth/authorize", issuer),
+        token_endpoint: format!("{}/oauth/token", issuer),
+        userinfo_endpoint: format!("{}/userinfo", issuer),
+        jwks_uri: format!("{}/.well-known/jwks.json", issuer),
+        introspection_endpoint: format!("{}/oauth/introspect", issuer),
+        revocation_endpoint: format!("{}/oauth/revoke", issuer),
+        issuer,
+        scopes_supported: OIDC_SCOPES.to_vec(),
+        response_types_supported: vec!["code"],
+        grant_types_supported: vec!["authorization_code", "client_credentials", "refresh_token"],
+        subject_types_supported: vec!["public"],
+        id_token_signing_alg_values_supported: vec!["EdDSA"],
+        token_endpoint_auth_methods_supported: vec!["client_secret_basic", "client_secret_post"],
+        code_challenge_methods_supported: vec!["S256"],
+        claims_supported: vec!["sub", "iss", "aud", "exp", "iat", "nonce", "preferred_username", "given_name", "family_name", "email", "email_verified", "phone_number", "phone_number_verified"],
+    };
+    Ok(Json(discovery))
+}
+
+
+async fn user_info<DB: Store, V: Messenger>(Authorized(token): Authorized, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let db = config.db();
+    let info = User::user_info(&token, db).await?;
+    Ok(info)
+}
+
+
+async fn jwks<DB: Store, V: Messenger>(config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let keys = &config.paseto().keys();
+    Ok(Jwks::from(keys))
+}
+
+
+async fn paserk<DB: Store, V: Messenger>(config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let keys = &config.paseto().keys();
+    Ok(PaserkSet::from(keys))
+}
@@ -0,0 +1,96 @@
+use actix_web::{web::{self, Json, Data, Path, Query}, Responder, HttpResponse, HttpRequest};
+use crate::domain::services::{Authentication, Membership, Get, Update, Delete, List};
+use crate::domain::types::{Config, Id, Organisation, User, Value};
+use super::{Response, Store, Messenger, auth::Authenticated};
+use std::collections::HashMap;
+use serde::Deserialize;
+use std::sync::Arc;
+
+
+#[derive(Deserialize)]
+struct Filter {
+    #[serde(default)]
+    pub owner: bool
+}
+
+
+/// Organisations and their members.
+pub(super) fn routes<DB: Store, V: Messenger>(cfg: &mut web::ServiceConfig) {
+    cfg.route("/organisations", web::post().to(create_organisation::<DB, V>));
+    cfg.route("/organisations", web::get().to(list_organisations::<DB, V>));
+    cfg.route("/organisations/{id}", web::get().to(get_organisation::<DB, V>));
+    cfg.route("/organisations/{id}", web::patch().to(patch_organisation::<DB, V>));
+    cfg.route("/organisations/{id}", web::delete().to(delete_organisation::<DB, V>));
+    cfg.route("/organisations/{id}/members/{user_id}", web::patch().to(patch_member::<DB, V>));
+    cfg.route("/organisations/{id}/members/{user_id}", web::delete().to(delete_member::<DB, V>));
+}
+
+
+async fn create_organisation<DB: Store, V: Messenger>(Authenticated(token): Authenticated, json: Json<Organisation>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let db = config.db();
+    let user_id = &token.subject;
+    let organisation = json.0.found(user_id, db).await?;
+    Ok(organisation)
+}
+
+
+async fn list_organisations<DB: Store, V: Messenger>(Authenticated(token): Authenticated, filter: Query<Filter>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let db = config.db();
+    let user_id = &token.subject;
+    let organisations = <User as List<Organisation>>::list(user_id, filter.owner, db).await?;
+    Ok(Json(organisations))
+}
+
+
+async fn get_organisation<DB: Store, V: Messenger>(Authenticated(token): Authenticated, id: Path<String>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let id: &Id = &id.parse()?;
+    let db = config.db();
+    let user_id = &token.subject;
+    Organisation::member(id, user_id, db).await?;
+    let organisation = Organisation::get(id, db).await?;
+    Ok(organisation)
+}
+
+
+async fn patch_organisation<DB: Store, V: Messenger>(Authenticated(token): Authenticated, id: Path<String>, item: Json<HashMap<String, Value>>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let id: &Id = &id.parse()?;
+    let db = config.db();
+    let user_id = &token.subject;
+    Organisation::owner(id, user_id, db).await?;
+    let item = item.0;
+    let organisation = Organisation::update(id, db, item).await?;
+    Ok(organisation)
+}
+
+
+async fn delete_organisation<DB: Store, V: Messenger>(Authenticated(token): Authenticated, id: Path<String>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let id: &Id = &id.parse()?;
+    let db = config.db();
+    let user_id = &token.subject;
+    Organisation::owner(id, user_id, db).await?;
+    Organisation::delete(id, db).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+
+async fn patch_member<DB: Store, V: Messenger>(Authenticated(token): Authenticated, path: Path<(String, String)>, item: Json<HashMap<String, Value>>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let (id, user_id) = path.into_inner();
+    let (id, member_id): (&Id, &Id) = (&id.parse()?, &user_id.parse()?);
+    let db = config.db();
+    let user_id = &token.subject;
+    Organisation::owner(id, user_id, db).await?;
+    let item = item.0;
+    let member = Organisation::update_member(id, member_id, db, item).await?;
+    Ok(member)
+}
+
+
+async fn delete_member<DB: Store, V: Messenger>(Authenticated(token): Authenticated, path: Path<(String, String)>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let (id, user_id) = path.into_inner();
+    let (id, member_id): (&Id, &Id) = (&id.parse()?, &user_id.parse()?);
+    let db = config.db();
+    let user_id = &token.subject;
+    Organisation::owner(id, user_id, db).await?;
+    Organisation::remove_member(id, member_id, db).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,20 @@
+use actix_web::{web::{self, Json, Data}, Responder, HttpRequest};
+use crate::domain::services::{Authentication, OAuth};
+use crate::domain::types::{Config, Service, User};
+use super::{Response, Store, Messenger, auth::Authenticated};
+use std::sync::Arc;
+
+
+/// Registering services as OAuth clients.
+pub(super) fn routes<DB: Store, V: Messenger>(cfg: &mut web::ServiceConfig) {
+    cfg.route("/services", web::post().to(register_service::<DB, V>));
+}
+
+
+async fn register_service<DB: Store, V: Messenger>(Authenticated(token): Authenticated, json: Json<Service>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let db = config.db();
+    let hasher = config.argon();
+    let owner_id = &token.subject;
+    let service = json.0.register_client(owner_id, db, hasher).await?;
+    Ok(service)
+}
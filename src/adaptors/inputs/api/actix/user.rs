@@ -1,7 +1,7 @@
-use actix_web::{post, get, patch, web::{Json, Data, Either, Form}, Responder, HttpResponse, HttpRequest};
-use crate::domain::{services::{Get, Update}, types::{Audience, Config, Contact, User, Value}};
-use crate::domain::services::Authentication;
-use super::{Response, DB, Verifyer};
+use actix_web::{web::{self, Json, Data, Either, Form}, Responder, HttpResponse, HttpRequest};
+use crate::domain::{services::{Get, Update}, types::{Audience, Config, Contact, CookieConfig, RefreshToken, User, Value}};
+use crate::domain::services::{Authentication, Lockout, Logout, Refresh};
+use super::{auth::Authenticated, Response, Store, Messenger};
 use std::collections::HashMap;
 use super::error::Error;
 use serde::Deserialize;
@@ -15,8 +15,42 @@ struct Credentials {
     pub password: String
 }
 
-#[post("/signup")]
-async fn signup(json: Json<User>, config: Data<Arc<Config<DB, Verifyer>>>) -> Response<impl Responder> {
+#[derive(Deserialize)]
+struct RefreshRequest {
+    pub refresh_token: String
+}
+
+#[derive(Deserialize)]
+struct LogoutRequest {
+    pub refresh_token: Option<String>
+}
+
+
+/// An empty response telling the browser to drop the session cookies.
+fn logged_out(req: &HttpRequest) -> Response<HttpResponse> {
+    let mut response = HttpResponse::NoContent().finish();
+    let cookies = req.app_data::<CookieConfig>().cloned().unwrap_or_default();
+    let mut refresh_token = cookies.build("refresh_token", String::new());
+    refresh_token.set_path("/token/refresh");
+    for cookie in [cookies.build("token", String::new()), refresh_token] {
+        response.add_removal_cookie(&cookie).map_err(crate::domain::types::Error::internal)?;
+    }
+    Ok(response)
+}
+
+/// Signing up, logging in and out, refreshing sessions and the user's own account.
+pub(super) fn routes<DB: Store, V: Messenger>(cfg: &mut web::ServiceConfig) {
+    cfg.route("/signup", web::post().to(signup::<DB, V>));
+    cfg.route("/login", web::post().to(login::<DB, V>));
+    cfg.route("/token/refresh", web::post().to(refresh::<DB, V>));
+    cfg.route("/users/", web::get().to(user_info::<DB, V>));
+    cfg.route("/users/", web::patch().to(patch_user::<DB, V>));
+    cfg.route("/logout", web::post().to(logout::<DB, V>));
+    cfg.route("/logout/everywhere", web::post().to(logout_everywhere::<DB, V>));
+}
+
+
+async fn signup<DB: Store, V: Messenger>(json: Json<User>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
     let db = config.db();
     let hasher = config.argon();
     let issuer = config.name.clone();
@@ -24,12 +58,14 @@ async fn signup(json: Json<User>, config: Data<Arc<Config<DB, Verifyer>>>) -> Re
     let audience = Audience::None;
     let user = json.0;
     let token = user.register(db, hasher, paseto, issuer, audience).await?;
-    Ok(token)
+    let session = RefreshToken::issue(token, None, Vec::new(), db, paseto).await?;
+    Ok(session)
 }
 
 
-#[post("/login")]
-async fn login(creds: Either<Json<Credentials>, Form<Credentials>>, config: Data<Arc<Config<DB, Verifyer>>>) -> Response<impl Responder> {
+/// Logs in with a password, failed logins are slowed down and then locked out per account and per
+/// client address.
+async fn login<DB: Store, V: Messenger>(req: HttpRequest, creds: Either<Json<Credentials>, Form<Credentials>>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
     let credentials = creds.into_inner();
     let issuer = config.name.clone();
     let paseto = config.paseto();
@@ -38,53 +74,149 @@ async fn login(creds: Either<Json<Credentials>, Form<Credentials>>, config: Data
     let db = config.db();
     let contact = &credentials.contact;
     let password = credentials.password.as_str();
-    let token = User::authenticate(contact, password, db, verifier, paseto, issuer, audience).await?;
-    Ok(token)
+    // The peer itself, forwarded headers can be made up by the client
+    let address = req.peer_addr().map(|addr| addr.ip());
+    let login = User::authenticate(contact, password, db, verifier, paseto, issuer, audience);
+    let token = config.http().throttle.guard(contact, address, db, login).await?;
+    let session = RefreshToken::issue(token, None, Vec::new(), db, paseto).await?;
+    Ok(session)
 }
 
 
-#[get("/users/")]
-async fn user_info(req: HttpRequest, config: Data<Arc<Config<DB, Verifyer>>>) -> Response<impl Responder> {
-    let token = match req.cookie("token") {
-        Some(cookie) => cookie.value().to_string(),
-        None => {
-            match req.headers().get(actix_web::http::header::AUTHORIZATION) {
-                Some(value) => match value.to_str(){
-                    Ok(value) => value.to_string(),
-                    _ => Err(Error::UnAuthorized)?
-                },
-                None => Err(Error::UnAuthorized)?
-            }
-        }
+async fn refresh<DB: Store, V: Messenger>(req: HttpRequest, body: Option<Either<Json<RefreshRequest>, Form<RefreshRequest>>>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let refresh_token = match (body, req.cookie("refresh_token")) {
+        (Some(body), _) => body.into_inner().refresh_token,
+        (None, Some(cookie)) => cookie.value().to_string(),
+        (None, None) => Err(Error::UnAuthorized)?
     };
-    let token = &token.replace("Bearer ", "");
+    let issuer = config.name.clone();
     let paseto = config.paseto();
     let db = config.db();
-    let id = &User::authorize(token, paseto).await?;
+    let session = RefreshToken::refresh(&refresh_token, None, db, paseto, issuer).await?;
+    Ok(session)
+}
+
+
+async fn user_info<DB: Store, V: Messenger>(Authenticated(token): Authenticated, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let db = config.db();
+    let id = &token.subject;
     let user = User::get(id, db).await?;
     Ok(user)
 }
 
 
-#[patch("/users/")]
-async fn patch_user(req: HttpRequest, item: Json<HashMap<String, Value>>, config: Data<Arc<Config<DB, Verifyer>>>) -> Response<impl Responder> {
-    let token = match req.cookie("token") {
-        Some(cookie) => cookie.value().to_string(),
-        None => {
-            match req.headers().get(actix_web::http::header::AUTHORIZATION) {
-                Some(value) => match value.to_str(){
-                    Ok(value) => value.to_string(),
-                    _ => Err(Error::UnAuthorized)?
-                },
-                None => Err(Error::UnAuthorized)?
-            }
-        }
-    };
-    let token = &token.replace("Bearer ", "");
-    let paseto = config.paseto();
+async fn patch_user<DB: Store, V: Messenger>(Authenticated(token): Authenticated, item: Json<HashMap<String, Value>>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
     let db = config.db();
-    let id = &User::authorize(token, paseto).await?;
+    let id = &token.subject;
     let item = item.0;
     let updated_user = User::update(id, db, item).await?;
     Ok(updated_user)
-}
\ No newline at end of file
+}
+
+async fn logout<DB: Store, V: Messenger>(req: HttpRequest, Authenticated(token): Authenticated, body: Option<Either<Json<LogoutRequest>, Form<LogoutRequest>>>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let refresh_token = body.and_then(|body| body.into_inner().refresh_token);
+    let db = config.db();
+    User::logout(&token, refresh_token.as_deref(), db).await?;
+    logged_out(&req)
+}
+
+
+async fn logout_everywhere<DB: Store, V: Messenger>(req: HttpRequest, Authenticated(token): Authenticated, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let paseto = config.paseto();
+    let db = config.db();
+    User::logout_everywhere(&token, paseto, db).await?;
+    logged_out(&req)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::{EmailAddress, Phone, Throttle};
+    use crate::ports::outputs::attempts::LoginAttempts;
+    use crate::adaptors::outputs::{database::memory::Memory, verify::Verifyer};
+    use super::super::state;
+    use actix_web::{test, App};
+
+    const PHONE: &str = "+14155552671";
+    const EMAIL: &str = "jane@example.com";
+
+    #[actix_web::test]
+    async fn test_login_lockout() {
+        let path = std::env::temp_dir().join(format!("beekeeper_login_{}.json", std::process::id()));
+        let config = serde_json::json!({
+            "domain": "https://auth.example.com",
+            "paseto": {"path": path, "ttl": 60, "refresh_ttl": 600},
+            "http": {"throttle": {"account": {"free": 10, "max": 2}, "address": {"free": 100, "max": 200}, "lockout": 60}}
+        });
+        let config: Arc<Config<Memory, Verifyer>> = Arc::new(serde_json::from_str(&config.to_string()).unwrap());
+        let app = test::init_service(App::new().configure(state(config.clone())).configure(routes::<Memory, Verifyer>)).await;
+        let user = User {
+            id: Default::default(),
+            username: String::from("jane"),
+            first_name: String::from("Jane"),
+            last_name: String::from("Doe"),
+            password: String::from("correct horse"),
+            contact: Contact::Both(Phone::New(PHONE.to_string()), EmailAddress::New(EMAIL.parse().unwrap())),
+        };
+        user.register(config.db(), config.argon(), config.paseto(), config.name.clone(), Audience::None).await.unwrap();
+        let attempt = |contact: (&str, &str), password: &str| test::TestRequest::post().uri("/login")
+            .peer_addr("127.0.0.1:4000".parse().unwrap())
+            .set_json(serde_json::json!({contact.0: contact.1, "password": password}))
+            .to_request();
+
+        // Failures by phone and by email count against the same account
+        assert_eq!(test::call_service(&app, attempt(("email", EMAIL), "wrong")).await.status(), 401);
+        assert_eq!(test::call_service(&app, attempt(("phone", PHONE), "wrong")).await.status(), 401);
+        assert_eq!(test::call_service(&app, attempt(("email", EMAIL), "correct horse")).await.status(), 429);
+        assert_eq!(test::call_service(&app, attempt(("phone", PHONE), "correct horse")).await.status(), 429);
+
+        // Unlocking the account under one contact unlocks it under the other
+        let phone = Contact::Phone(Phone::New(PHONE.to_string()));
+        Throttle::unlock(Some(&phone), None, config.db()).await.unwrap();
+        assert_eq!(test::call_service(&app, attempt(("email", EMAIL), "correct horse")).await.status(), 200);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_retry_during_lockout() {
+        let path = std::env::temp_dir().join(format!("beekeeper_retry_{}.json", std::process::id()));
+        let config = serde_json::json!({
+            "domain": "https://auth.example.com",
+            "paseto": {"path": path, "ttl": 60, "refresh_ttl": 600},
+            "http": {"throttle": {"account": {"free": 10, "max": 2}, "address": {"free": 100, "max": 200}, "lockout": 60}}
+        });
+        let config: Arc<Config<Memory, Verifyer>> = Arc::new(serde_json::from_str(&config.to_string()).unwrap());
+        let app = test::init_service(App::new().configure(state(config.clone())).configure(routes::<Memory, Verifyer>)).await;
+        let user = User {
+            id: Default::default(),
+            username: String::from("jane"),
+            first_name: String::from("Jane"),
+            last_name: String::from("Doe"),
+            password: String::from("correct horse"),
+            contact: Contact::Email(EmailAddress::New(EMAIL.parse().unwrap())),
+        };
+        let token = user.register(config.db(), config.argon(), config.paseto(), config.name.clone(), Audience::None).await.unwrap();
+        let account = format!("user:{}", token.subject.0);
+        let attempt = |address: &str| test::TestRequest::post().uri("/login")
+            .peer_addr(address.parse().unwrap())
+            .set_json(serde_json::json!({"email": EMAIL, "password": "wrong"}))
+            .to_request();
+        assert_eq!(test::call_service(&app, attempt("127.0.0.1:4000")).await.status(), 401);
+        assert_eq!(test::call_service(&app, attempt("127.0.0.2:4000")).await.status(), 401);
+        let retry_after = |res: &actix_web::dev::ServiceResponse| res.headers().get(actix_web::http::header::RETRY_AFTER).unwrap().to_str().unwrap().parse::<i64>().unwrap();
+        let res = test::call_service(&app, attempt("127.0.0.3:4000")).await;
+        assert_eq!(res.status(), 429);
+        let ends = chrono::Utc::now() + chrono::Duration::seconds(retry_after(&res));
+        let failures = config.db().failures(&account).await.unwrap();
+
+        // Retrying from other addresses during the lockout neither counts nor pushes its end back
+        for address in ["127.0.0.4:4000", "127.0.0.5:4000", "127.0.0.6:4000"] {
+            let res = test::call_service(&app, attempt(address)).await;
+            assert_eq!(res.status(), 429);
+            assert!(chrono::Utc::now() + chrono::Duration::seconds(retry_after(&res)) <= ends + chrono::Duration::seconds(1));
+        }
+        assert_eq!(config.db().failures(&account).await.unwrap(), failures);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+use actix_web::{web::{self, Json, Data, Path, Query}, Responder, HttpResponse, HttpRequest};
+use crate::domain::types::{Config, Contact, EmailAddress, Either, Id, User, Verification};
+use crate::domain::services::{Authentication, ContactVerification};
+#[cfg(feature = "phone")]
+use crate::domain::types::{Phone, VerificationMedia};
+use super::{Response, Store, Messenger, auth::Authenticated};
+use crate::ports::outputs::verify::Verify;
+use crate::domain::types::Error;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::sync::Arc;
+
+
+/// Verifies email addresses when the `email` feature is on, the codes are stored as [`Verification`]s.
+#[cfg(feature = "email")]
+pub trait VerifyEmail: Verify<EmailAddress, Verification = Verification<Id>, Channel: Default + DeserializeOwned> {}
+#[cfg(feature = "email")]
+impl<V: Verify<EmailAddress, Verification = Verification<Id>, Channel: Default + DeserializeOwned>> VerifyEmail for V {}
+#[cfg(not(feature = "email"))]
+pub trait VerifyEmail {}
+#[cfg(not(feature = "email"))]
+impl<V> VerifyEmail for V {}
+
+/// Verifies phone numbers when the `phone` feature is on, by SMS or another [`VerificationMedia`].
+#[cfg(feature = "phone")]
+pub trait VerifyPhone: Verify<Phone, Verification = Verification<Id>, Channel = VerificationMedia> {}
+#[cfg(feature = "phone")]
+impl<V: Verify<Phone, Verification = Verification<Id>, Channel = VerificationMedia>> VerifyPhone for V {}
+#[cfg(not(feature = "phone"))]
+pub trait VerifyPhone {}
+#[cfg(not(feature = "phone"))]
+impl<V> VerifyPhone for V {}
+
+
+#[derive(Deserialize)]
+struct Confirmation {
+    #[serde(flatten)]
+    pub contact: Contact,
+    pub code: String
+}
+
+#[derive(Deserialize)]
+struct MagicLink {
+    pub contact: String
+}
+
+#[derive(Deserialize)]
+struct Channel<C> {
+    #[serde(default = "Option::default")]
+    pub channel: Option<C>
+}
+
+
+/// The base URL the magic links sent out point back to.
+fn base_url<DB, V>(config: &Config<DB, V>) -> String {
+    format!("{}/verify", config.domain().trim_end_matches('/'))
+}
+
+
+/// Verifying contacts, by code or by magic link.
+pub(super) fn routes<DB: Store, V: Messenger>(cfg: &mut web::ServiceConfig) {
+    #[cfg(feature = "email")]
+    cfg.route("/verify/email", web::post().to(initiate_email::<DB, V>));
+    #[cfg(feature = "phone")]
+    cfg.route("/verify/phone", web::post().to(initiate_phone::<DB, V>));
+    cfg.route("/verify/confirm", web::post().to(confirm::<DB, V>));
+    #[cfg(feature = "email")]
+    cfg.route("/verify/{token}", web::get().to(magic_link::<DB, V>));
+}
+
+
+#[cfg(feature = "email")]
+async fn initiate_email<DB: Store, V: Messenger>(Authenticated(token): Authenticated, body: Option<Json<Channel<<V as Verify<EmailAddress>>::Channel>>>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let db = config.db();
+    let verifyer = config.verifyer();
+    let base_url = &base_url(&config);
+    let id = &token.subject;
+    // The channel type depends on the configured verifyer and is `()` for SMTP
+    #[allow(clippy::let_unit_value)]
+    let channel = body.and_then(|body| body.0.channel).unwrap_or_default();
+    <User as ContactVerification<EmailAddress>>::initiate(id, channel, base_url, db, verifyer).await?;
+    Ok(HttpResponse::Accepted().finish())
+}
+
+
+#[cfg(feature = "phone")]
+async fn initiate_phone<DB: Store, V: Messenger>(Authenticated(token): Authenticated, body: Option<Json<Channel<VerificationMedia>>>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let db = config.db();
+    let verifyer = config.verifyer();
+    let base_url = &base_url(&config);
+    let id = &token.subject;
+    let channel = body.and_then(|body| body.0.channel).unwrap_or(VerificationMedia::SMS);
+    <User as ContactVerification<Phone>>::initiate(id, channel, base_url, db, verifyer).await?;
+    Ok(HttpResponse::Accepted().finish())
+}
+
+
+async fn confirm<DB: Store, V: Messenger>(json: Json<Confirmation>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let confirmation = json.0;
+    let db = config.db();
+    let verifyer = config.verifyer();
+    let code: Either<&str, &str> = Either::Left(confirmation.code.as_str());
+    let user: User = match &confirmation.contact {
+        #[cfg(feature = "email")]
+        Contact::Email(email) => User::confirm(email, code, db, verifyer).await?,
+        #[cfg(feature = "phone")]
+        Contact::Phone(phone) => User::confirm(phone, code, db, verifyer).await?,
+        Contact::Both(..) => Err(Error::validation("contact", "only one contact can be verified at a time"))?,
+        #[allow(unreachable_patterns)]
+        _ => Err(Error::validation("contact", "this kind of contact cannot be verified"))?
+    };
+    Ok(Json(user))
+}
+
+
+#[cfg(feature = "email")]
+async fn magic_link<DB: Store, V: Messenger>(token: Path<String>, query: Query<MagicLink>, config: Data<Arc<Config<DB, V>>>) -> Response<impl Responder> {
+    let email = EmailAddress::new(&query.contact)?;
+    let db = config.db();
+    let verifyer = config.verifyer();
+    let code = Either::Right(token.as_str());
+    let user = User::confirm(&email, code, db, verifyer).await?;
+    Ok(Json(user))
+}
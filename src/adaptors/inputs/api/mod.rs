@@ -1 +1,3 @@
-pub mod actix;
\ No newline at end of file
+/// The HTTP API, served from the in-memory database or any other [`actix::Store`].
+#[cfg(all(feature = "http", feature = "memory"))]
+pub mod actix;
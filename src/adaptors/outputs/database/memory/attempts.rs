@@ -0,0 +1,103 @@
+//! Login attempts collection implementation for the memory database
+//!
+//! This module counts failed logins in memory with thread-safe access.
+//! The counts are never persisted, a restart forgets them.
+
+use crate::ports::outputs::attempts::LoginAttempts;
+use crate::domain::types::Failures;
+use std::collections::HashMap;
+use std::sync::RwLock as Lock;
+use chrono::{DateTime, Utc};
+use super::error::Error;
+
+/// Thread-safe storage for failed login counts
+///
+/// # Indexes
+/// - Primary index: Account or client address key -> Failures
+///
+/// # Concurrency
+/// Uses RwLock to ensure safe concurrent read and write operations
+#[derive(Debug, Default)]
+pub struct Attempts {
+    /// Primary storage of the failures, keyed by what they are held against
+    pub attempts: Lock<HashMap<String, Failures>>,
+}
+
+impl Attempts {
+    /// Forgets every count whose last failure happened before `before`, returning how many
+    pub fn forget(&self, before: DateTime<Utc>) -> Result<usize, Error> {
+        let mut attempts = self.attempts.write()?;
+        let len = attempts.len();
+        attempts.retain(|_, failures| failures.last >= before);
+        Ok(len - attempts.len())
+    }
+}
+
+impl LoginAttempts for Attempts {
+    type Error = Error;
+
+    async fn failures(&self, key: &str) -> Result<Option<Failures>, Self::Error> {
+        Ok(self.attempts.read()?.get(key).copied())
+    }
+
+    async fn fail(&self, key: &str, at: DateTime<Utc>, forget_before: DateTime<Utc>) -> Result<Option<Failures>, Self::Error> {
+        let mut attempts = self.attempts.write()?;
+        let failures = attempts.entry(key.to_string()).or_insert(Failures {count: 0, last: at});
+        if failures.last < forget_before {
+            failures.count = 0;
+        }
+        let before = Some(*failures).filter(|failures| failures.count > 0);
+        failures.count += 1;
+        failures.last = at;
+        Ok(before)
+    }
+
+    async fn forgive(&self, key: &str) -> Result<(), Self::Error> {
+        let mut attempts = self.attempts.write()?;
+        if let Some(failures) = attempts.get_mut(key) {
+            failures.count = failures.count.saturating_sub(1);
+            if failures.count == 0 {
+                attempts.remove(key);
+            }
+        }
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), Self::Error> {
+        self.attempts.write()?.remove(key);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn test_fail_and_forget() {
+        let attempts = Attempts::default();
+        let now = Utc::now();
+        let forget_before = now - Duration::minutes(15);
+        assert!(attempts.failures("email:jane@example.com").await.unwrap().is_none());
+
+        attempts.fail("email:jane@example.com", now - Duration::hours(1), forget_before - Duration::hours(1)).await.unwrap();
+        attempts.fail("email:jane@example.com", now - Duration::hours(1), forget_before - Duration::hours(1)).await.unwrap();
+        // The earlier failures are older than the lockout, so the count starts over
+        assert!(attempts.fail("email:jane@example.com", now, forget_before).await.unwrap().is_none());
+        assert_eq!(attempts.failures("email:jane@example.com").await.unwrap(), Some(Failures {count: 1, last: now}));
+        assert_eq!(attempts.fail("email:jane@example.com", now, forget_before).await.unwrap(), Some(Failures {count: 1, last: now}));
+        assert_eq!(attempts.fail("email:jane@example.com", now, forget_before).await.unwrap().unwrap().count, 2);
+
+        attempts.forgive("email:jane@example.com").await.unwrap();
+        assert_eq!(attempts.failures("email:jane@example.com").await.unwrap().unwrap().count, 2);
+
+        attempts.fail("ip:127.0.0.1", now - Duration::hours(1), forget_before).await.unwrap();
+        assert_eq!(attempts.forget(forget_before).unwrap(), 1);
+        assert!(attempts.failures("ip:127.0.0.1").await.unwrap().is_none());
+
+        attempts.clear("email:jane@example.com").await.unwrap();
+        assert!(attempts.failures("email:jane@example.com").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,94 @@
+//! Authorization codes collection implementation for the memory database
+//! 
+//! This module provides the implementation for storing OAuth2 authorization codes
+//! in memory with thread-safe access.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, DeleteItem};
+use crate::domain::types::{AuthorizationCode, Key};
+use std::collections::HashMap;
+use std::sync::RwLock as Lock;
+use super::error::Error;
+
+/// Thread-safe storage for authorization codes
+/// 
+/// # Indexes
+/// - Primary index: Code -> Authorization code
+/// 
+/// Codes are only ever looked up by the code itself, so no secondary index is kept.
+#[derive(Debug, Default)]
+pub struct AuthorizationCodes {
+    /// Primary storage of authorization codes
+    pub codes: Lock<HashMap<<AuthorizationCode as Item>::PK, AuthorizationCode>>,
+}
+
+impl CreateItem<AuthorizationCode> for AuthorizationCodes {
+    type Error = Error;
+    
+    async fn create_item(&self, code: AuthorizationCode) -> Result<AuthorizationCode, Self::Error> {
+        self.codes.write()?.insert(code.code.clone(), code.clone());
+        Ok(code)
+    }
+}
+
+impl GetItem<AuthorizationCode> for AuthorizationCodes {
+    type Error = Error;
+    
+    async fn get_item(&self, key: Key<&<AuthorizationCode as Item>::PK, &<AuthorizationCode as Item>::SK>) -> Result<AuthorizationCode, Self::Error> {
+        let option = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => self.codes.read()?.get(pk).cloned(),
+            Key::Sk(_) => None
+        };
+        
+        option.ok_or(Error::AuthorizationCodeNotFound)
+    }
+}
+
+impl DeleteItem<AuthorizationCode> for AuthorizationCodes {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<AuthorizationCode as Item>::PK, &<AuthorizationCode as Item>::SK>) -> Result<(), Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::AuthorizationCodeNotFound)
+        };
+
+        self.codes.write()?
+            .remove(pk)
+            .ok_or(Error::AuthorizationCodeNotFound)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::Id;
+
+    /// Helper function to create a test authorization code
+    fn create_test_code() -> AuthorizationCode {
+        AuthorizationCode::new(Id::default(), Id::default(), "https://example.com/callback".to_string(), "challenge".to_string(), vec![])
+    }
+
+    #[tokio::test]
+    async fn test_get_authorization_code() {
+        let codes = AuthorizationCodes::default();
+        let code = create_test_code();
+        let _ = codes.create_item(code.clone()).await;
+
+        let result = codes.get_item(Key::Pk(&code.code)).await;
+        assert_eq!(result.unwrap(), code);
+    }
+
+    #[tokio::test]
+    async fn test_delete_authorization_code() {
+        let codes = AuthorizationCodes::default();
+        let code = create_test_code();
+        let _ = codes.create_item(code.clone()).await;
+
+        assert!(codes.delete_item(Key::Pk(&code.code)).await.is_ok());
+
+        // A code can only be consumed once
+        let result = codes.delete_item(Key::Pk(&code.code)).await;
+        assert!(matches!(result, Err(Error::AuthorizationCodeNotFound)));
+    }
+}
@@ -29,6 +29,11 @@ pub enum Error {
     ServiceNotFound,
     ServiceAlreadyExists,
     VerificationNotFound,
+    InvitationNotFound,
+    InvitationAlreadyExists,
+    AuthorizationCodeNotFound,
+    RefreshTokenNotFound,
+    RevocationNotFound,
     CannotDeleteFields(HashSet<String>),
     CannotDeleteContact,
     UnsupportedOperation,
@@ -50,6 +55,11 @@ impl Display for Error {
             Self::ServiceNotFound => write!(f, "service not found"),
             Self::ServiceAlreadyExists => write!(f, "Service with this name already exists"),
             Self::VerificationNotFound => write!(f, "Verification code not found"),
+            Self::InvitationNotFound => write!(f, "Invitation not found"),
+            Self::InvitationAlreadyExists => write!(f, "This contact has already been invited to the organisation"),
+            Self::AuthorizationCodeNotFound => write!(f, "Authorization code not found"),
+            Self::RefreshTokenNotFound => write!(f, "Refresh token not found"),
+            Self::RevocationNotFound => write!(f, "Revocation not found"),
             Self::CannotDeleteFields(fields) => {
                 if fields.len() == 1 {
                     // unwrap is used here because the above condition makes sure that there is at least one item
@@ -91,11 +101,15 @@ impl ErrorTrait for Error {
         match self {
             Self::UserWithEmailExists | 
             Self::UserWithPhoneExists | Self::MemberAlreadyExists |
-            Self::OrganisationWithNameExists | Self::ServiceAlreadyExists | Self::ServiceAlreadyExists => StatusCode::CONFLICT,
+            Self::OrganisationWithNameExists | Self::ServiceAlreadyExists | Self::ServiceAlreadyExists |
+            Self::InvitationAlreadyExists => StatusCode::CONFLICT,
             Self::UserNotFound |
             Self::OrganisationNotFound | Self::ServiceNotFound |
             Self::MemberNotFound | Self::ServiceNotFound | 
-            Self::VerificationNotFound => StatusCode::NOT_FOUND,
+            Self::VerificationNotFound | Self::InvitationNotFound |
+            Self::AuthorizationCodeNotFound => StatusCode::NOT_FOUND,
+            Self::RefreshTokenNotFound => StatusCode::NOT_FOUND,
+            Self::RevocationNotFound => StatusCode::NOT_FOUND,
             Self::CannotDeleteFields(_) | Self::CannotDeleteContact | Self::UnsupportedOperation => StatusCode::BAD_REQUEST,
             Self::PoisonedLock(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::DomainError(err) => err.status()
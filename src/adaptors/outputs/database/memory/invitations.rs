@@ -0,0 +1,174 @@
+//! Invitations collection implementation for the memory database
+//! 
+//! This module provides the implementation for storing and managing organisation invitations
+//! in memory with thread-safe access and index management.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, DeleteItem};
+use crate::domain::types::{Invitation, Contact, Key, Id};
+use std::collections::HashMap;
+use std::sync::RwLock as Lock;
+use super::error::Error;
+
+/// Thread-safe storage for invitations
+/// 
+/// # Indexes
+/// - Primary index: Invitation ID -> Invitation
+/// - Secondary index: (Organisation ID, raw contact) -> Invitation ID
+/// 
+/// Contacts are indexed by their raw address or number so that an invitation is found
+/// whether the contact it is looked up with is verified or not.
+#[derive(Debug, Default)]
+pub struct Invitations {
+    /// Primary storage of invitations
+    pub invitations: Lock<HashMap<<Invitation as Item>::PK, Invitation>>,
+    
+    /// Secondary index mapping an organisation and a contact to the invitation ID
+    pub contacts_index: Lock<HashMap<(Id, String), <Invitation as Item>::PK>>,
+}
+
+impl Invitations {
+    /// The raw email address or phone number an invitation is indexed by
+    fn contact(contact: &Contact) -> String {
+        match contact {
+            Contact::Phone(phone) => phone.to_string(),
+            Contact::Email(email) | Contact::Both(_, email) => email.to_string()
+        }
+    }
+
+    /// Finds the primary key for a given secondary key (organisation ID and contact)
+    pub fn pk(&self, sk: &<Invitation as Item>::SK) -> Result<Option<<Invitation as Item>::PK>, Error> {
+        let (org_id, contact) = sk;
+        let key = (*org_id, Self::contact(contact));
+        Ok(self.contacts_index.read()?.get(&key).cloned())
+    }
+
+    /// Returns every invitation of the given organisation
+    pub fn of(&self, org_id: &Id) -> Result<Vec<Invitation>, Error> {
+        Ok(self.invitations.read()?
+            .values()
+            .filter(|invitation| &invitation.org_id == org_id)
+            .cloned()
+            .collect())
+    }
+}
+
+impl CreateItem<Invitation> for Invitations {
+    type Error = Error;
+    
+    async fn create_item(&self, invitation: Invitation) -> Result<Invitation, Self::Error> {
+        let key = (invitation.org_id, Self::contact(&invitation.contact));
+        {
+            let mut index = self.contacts_index.write()?;
+            if index.contains_key(&key) {
+                return Err(Error::InvitationAlreadyExists);
+            }
+            index.insert(key, invitation.id);
+        }
+        
+        self.invitations.write()?.insert(invitation.id, invitation.clone());
+        Ok(invitation)
+    }
+}
+
+impl GetItem<Invitation> for Invitations {
+    type Error = Error;
+    
+    async fn get_item(&self, key: Key<&<Invitation as Item>::PK, &<Invitation as Item>::SK>) -> Result<Invitation, Self::Error> {
+        let option = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => self.invitations.read()?.get(pk).cloned(),
+            Key::Sk(sk) => match self.pk(sk)? {
+                Some(pk) => self.invitations.read()?.get(&pk).cloned(),
+                None => None
+            }
+        };
+        
+        option.ok_or(Error::InvitationNotFound)
+    }
+}
+
+impl DeleteItem<Invitation> for Invitations {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<Invitation as Item>::PK, &<Invitation as Item>::SK>) -> Result<(), Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => *pk,
+            Key::Sk(sk) => self.pk(sk)?.ok_or(Error::InvitationNotFound)?
+        };
+
+        let invitation = self.invitations.write()?
+            .remove(&pk)
+            .ok_or(Error::InvitationNotFound)?;
+
+        let key = (invitation.org_id, Self::contact(&invitation.contact));
+        self.contacts_index.write()?.remove(&key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::EmailAddress;
+    use bson::oid::ObjectId;
+
+    /// Helper function to create a test invitation
+    fn create_test_invitation() -> Invitation {
+        let mut invitation = Invitation {
+            id: Id(ObjectId::new()),
+            org_id: Id(ObjectId::new()),
+            contact: Contact::Email(EmailAddress::New("invitee@example.com".parse().unwrap())),
+            title: "Engineer".to_string(),
+            roles: vec![],
+            invited_by: Id(ObjectId::new()),
+            expires: Default::default(),
+        };
+        invitation.renew();
+        invitation
+    }
+
+    #[tokio::test]
+    async fn test_create_invitation() {
+        let invitations = Invitations::default();
+        let invitation = create_test_invitation();
+        let result = invitations.create_item(invitation.clone()).await;
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), invitation);
+    }
+
+    #[tokio::test]
+    async fn test_create_duplicate_invitation() {
+        let invitations = Invitations::default();
+        let invitation = create_test_invitation();
+        let _ = invitations.create_item(invitation.clone()).await;
+
+        let mut duplicate = invitation.clone();
+        duplicate.id = Id(ObjectId::new());
+        let result = invitations.create_item(duplicate).await;
+        assert!(matches!(result, Err(Error::InvitationAlreadyExists)));
+    }
+
+    #[tokio::test]
+    async fn test_get_invitation_by_verified_contact() {
+        let invitations = Invitations::default();
+        let invitation = create_test_invitation();
+        let _ = invitations.create_item(invitation.clone()).await;
+
+        let contact = Contact::Email(EmailAddress::Verified("invitee@example.com".parse().unwrap()));
+        let result = invitations.get_item(Key::Sk(&(invitation.org_id, contact))).await;
+        assert_eq!(result.unwrap(), invitation);
+    }
+
+    #[tokio::test]
+    async fn test_delete_invitation() {
+        let invitations = Invitations::default();
+        let invitation = create_test_invitation();
+        let _ = invitations.create_item(invitation.clone()).await;
+
+        let result = invitations.delete_item(Key::Pk(&invitation.id)).await;
+        assert!(result.is_ok());
+
+        let sk = (invitation.org_id, invitation.contact.clone());
+        let result = invitations.get_item(Key::Sk(&sk)).await;
+        assert!(matches!(result, Err(Error::InvitationNotFound)));
+    }
+}
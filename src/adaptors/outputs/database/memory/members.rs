@@ -84,6 +84,22 @@ impl Members {
     }
 }
 
+/// Applies a partial update to a member, allowing title, owner status and roles
+pub(crate) fn patch(member: &mut Member, map: Map) -> Result<(), Error> {
+    // Update basic fields
+    if let Some(value) = map.get("title") {
+        member.title = value.clone().try_into()?;
+    }
+    if let Some(value) = map.get("owner") {
+        member.owner = value.clone().try_into()?;
+    }
+    if let Some(value) = map.get("roles") {
+        member.roles = value.clone().try_into()?;
+    }
+    Ok(())
+}
+
+
 impl CreateItem<Member> for Members {
     type Error = Error;
     
@@ -115,7 +131,7 @@ impl GetItem<(Organisation, User), Member> for Members {
         if let Some(member) = option {
             return Ok(member)
         }
-        Err(Error::ServiceNotFound)
+        Err(Error::MemberNotFound)
     }
 }
 
@@ -156,17 +172,7 @@ impl UpdateItem<(Organisation, User), Member> for Members {
     async fn patch_item(&self, key: Key<&<(Organisation, User) as Item>::PK, &<(Organisation, User) as Item>::SK>, map: Map) -> Result<Member, Self::Error> {
         // First, retrieve the existing member
         let mut member = self.get_item(key.clone()).await?;
-        
-        // Update basic fields
-        if let Some(value) = map.get("title") {
-            member.title = value.clone().try_into()?;
-        }
-        if let Some(value) = map.get("owner") {
-            member.owner = value.clone().try_into()?;
-        }
-        if let Some(value) = map.get("roles") {
-            member.roles = value.clone().try_into()?;
-        }
+        patch(&mut member, map)?;
 
         // Use update_item to handle indexes and storage
         self.update_item(key, member.clone()).await
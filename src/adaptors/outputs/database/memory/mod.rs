@@ -10,25 +10,49 @@
 //! 
 //! # Thread Safety
 //! All operations are protected by read-write locks, ensuring safe concurrent access.
+//!
+//! # Persistence
+//! With a [`Persistence`] configured, users, organisations, members, services and verifications
+//! survive restarts, see the [`persistence`] module.
 
-mod organisations;
+pub(crate) mod organisations;
 mod error;
-mod users;
-mod members;
-mod services;
+pub(crate) mod users;
+pub(crate) mod members;
+pub(crate) mod services;
 mod verifications;
-
+mod invitations;
+mod authorization_codes;
+mod refresh_tokens;
+mod revocations;
+mod attempts;
+pub mod persistence;
+
+use crate::ports::outputs::attempts::LoginAttempts;
+use crate::ports::outputs::consume::ConsumeRefreshToken;
+use crate::ports::outputs::expiry::TokenExpiry;
+use crate::ports::outputs::wrong_code::CountWrongCode;
 use crate::ports::outputs::database::{Item, CreateItem, GetItem, GetItems, UpdateItem, DeleteItem, Map};
-use crate::domain::types::{User, Key, Value, Organisation, Member, Service, Verification};
+use crate::domain::types::{User, Key, Value, Organisation, Member, Service, Verification, Invitation, AuthorizationCode, RefreshToken, Revocation, Failures};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Serialize, Deserialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::RwLock as Lock;
+use persistence::{Log, Record};
 use organisations::*;
 use services::*;
 use members::*;
 use error::*;
 use users::*;
 use verifications::*;
+use invitations::*;
+use authorization_codes::*;
+use refresh_tokens::*;
+use revocations::*;
+use attempts::*;
+
+pub use error::Error;
+pub use persistence::Persistence;
 
 /// An in-memory database implementation for User entities.
 /// 
@@ -62,24 +86,103 @@ pub struct Memory {
     
     /// Internal verifications collection, not serialized
     #[serde(skip)]
-    verifications: Verifications
+    verifications: Verifications,
+
+    /// Internal invitations collection, not serialized
+    #[serde(skip)]
+    invitations: Invitations,
+
+    /// Internal authorization codes collection, not serialized
+    #[serde(skip)]
+    authorization_codes: AuthorizationCodes,
+
+    /// Internal refresh tokens collection, not serialized
+    #[serde(skip)]
+    refresh_tokens: RefreshTokens,
+
+    /// Internal revocations collection, not serialized
+    #[serde(skip)]
+    revocations: Revocations,
+
+    /// Internal failed login counts, not serialized nor persisted
+    #[serde(skip)]
+    attempts: Attempts,
+
+    /// Where the data is persisted, it only lives in memory when none is configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    persistence: Option<Persistence>,
+
+    /// The log of persisted changes, held for the whole of a change so records land in order
+    #[serde(skip)]
+    log: tokio::sync::Mutex<Log>
 }
 
 
-/// Database Operations for Different Entity Types
-/// 
-/// This section provides a centralized implementation of CRUD operations 
-/// for various domain entities using the in-memory database strategy.
-/// 
-/// ## Supported Entity Types
-/// - Users
-/// - Organisations
-/// - Services
-/// - Members
-/// - Roles
-/// - Verifications
-/// - Resources
-/// - Scopes
+impl Memory {
+    /// Deletes an organisation with every membership of and invitation to it
+    async fn delete_organisation(&self, org_id: &<Organisation as Item>::PK) -> Result<(), Error> {
+        self.organisations.delete_item(Key::Pk(org_id)).await?;
+
+        let user_ids = self.members.org_index.read()?
+            .get(org_id)
+            .cloned()
+            .unwrap_or_default();
+        for user_id in user_ids {
+            self.members.delete_item(Key::Pk(&(*org_id, user_id))).await?;
+        }
+
+        for invitation in self.invitations.of(org_id)? {
+            self.invitations.delete_item(Key::Pk(&invitation.id)).await?;
+        }
+        Ok(())
+    }
+
+    /// Removes every expired verification, returning how many were removed
+    pub async fn purge_expired_verifications(&self) -> Result<usize, Error> {
+        let mut log = self.log.lock().await;
+        let expired = self.verifications.expired(chrono::Utc::now())?;
+        for contact in &expired {
+            log.append(Record::VerificationDeleted(contact.clone()))?;
+            self.verifications.delete_item(Key::Pk(contact)).await?;
+        }
+        Ok(expired.len())
+    }
+
+    /// Removes every expired refresh token, used or not, returning how many were removed
+    ///
+    /// A used token is kept until it expires so presenting it again still revokes its family.
+    pub async fn purge_expired_refresh_tokens(&self) -> Result<usize, Error> {
+        let mut log = self.log.lock().await;
+        let expired = self.refresh_tokens.expired(Utc::now())?;
+        for digest in &expired {
+            log.append(Record::RefreshTokenDeleted(digest.clone()))?;
+            self.refresh_tokens.delete_item(Key::Pk(digest)).await?;
+        }
+        Ok(expired.len())
+    }
+
+    /// Forgets the failed logins counted against anything that has not failed since `before`,
+    /// returning how many counts were forgotten
+    pub fn forget_failures(&self, before: DateTime<Utc>) -> Result<usize, Error> {
+        self.attempts.forget(before)
+    }
+}
+
+
+// Database Operations for Different Entity Types
+//
+// This section provides a centralized implementation of CRUD operations
+// for various domain entities using the in-memory database strategy.
+//
+// ## Supported Entity Types
+// - Users
+// - Organisations
+// - Services
+// - Members
+// - Roles
+// - Verifications
+// - Resources
+// - Scopes
 
 /// # User-related Database Operations
 impl CreateItem<User> for Memory {
@@ -89,7 +192,10 @@ impl CreateItem<User> for Memory {
     /// # Errors
     /// - Returns an error if a user with the same contact info already exists
     async fn create_item(&self, user: User) -> Result<User, Self::Error> {
-        self.users.create_item(user).await
+        let mut log = self.log.lock().await;
+        let user = self.users.create_item(user).await?;
+        self.commit(&mut log, Record::User((&user).into()), Record::UserDeleted(user.id)).await?;
+        Ok(user)
     }
 }
 
@@ -114,7 +220,11 @@ impl UpdateItem<User> for Memory {
     /// - Allows full replacement of user data
     /// - Maintains index consistency
     async fn update_item(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, user: User) -> Result<User, Self::Error> {
-        self.users.update_item(key, user).await
+        let mut log = self.log.lock().await;
+        let before = self.users.get_item(key.clone()).await?;
+        let user = self.users.update_item(key, user).await?;
+        self.commit(&mut log, Record::User((&user).into()), Record::User((&before).into())).await?;
+        Ok(user)
     }
 
     /// Partially updates a user's information
@@ -126,11 +236,19 @@ impl UpdateItem<User> for Memory {
     /// - Password
     /// - Contact information
     async fn patch_item(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, update: Map) -> Result<User, Self::Error> {
-        self.users.patch_item(key, update).await
+        let mut log = self.log.lock().await;
+        let before = self.users.get_item(key.clone()).await?;
+        let user = self.users.patch_item(key, update).await?;
+        self.commit(&mut log, Record::User((&user).into()), Record::User((&before).into())).await?;
+        Ok(user)
     }
 
     async fn delete_fields(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, fields: HashSet<String>) -> Result<User, Self::Error> {
-        self.users.delete_fields(key, fields).await
+        let mut log = self.log.lock().await;
+        let before = self.users.get_item(key.clone()).await?;
+        let user = self.users.delete_fields(key, fields).await?;
+        self.commit(&mut log, Record::User((&user).into()), Record::User((&before).into())).await?;
+        Ok(user)
     }
 }
 
@@ -141,7 +259,10 @@ impl DeleteItem<User> for Memory {
     /// # Behavior
     /// - Removes user from primary and secondary indexes
     async fn delete_item(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>) -> Result<(), Self::Error> {
-        self.users.delete_item(key).await
+        let mut log = self.log.lock().await;
+        let id = self.users.get_item(key).await?.id;
+        log.append(Record::UserDeleted(id))?;
+        self.users.delete_item(Key::Pk(&id)).await
     }
 }
 
@@ -154,7 +275,10 @@ impl CreateItem<Organisation> for Memory {
     /// # Errors
     /// - Returns an error if an organisation with the same name already exists
     async fn create_item(&self, organisation: Organisation) -> Result<Organisation, Self::Error> {
-        self.organisations.create_item(organisation).await
+        let mut log = self.log.lock().await;
+        let organisation = self.organisations.create_item(organisation).await?;
+        self.commit(&mut log, Record::Organisation(organisation.clone()), Record::OrganisationDeleted(organisation.id)).await?;
+        Ok(organisation)
     }
 }
 
@@ -179,7 +303,11 @@ impl UpdateItem<Organisation> for Memory {
     /// - Allows full replacement of organisation data
     /// - Maintains index consistency
     async fn update_item(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, organisation: Organisation) -> Result<Organisation, Self::Error> {
-        self.organisations.update_item(key, organisation).await
+        let mut log = self.log.lock().await;
+        let before = self.organisations.get_item(key.clone()).await?;
+        let organisation = self.organisations.update_item(key, organisation).await?;
+        self.commit(&mut log, Record::Organisation(organisation.clone()), Record::Organisation(before)).await?;
+        Ok(organisation)
     }
 
     /// Partially updates an organisation's information
@@ -187,11 +315,19 @@ impl UpdateItem<Organisation> for Memory {
     /// # Supported Partial Updates
     /// - Name
     async fn patch_item(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, update: Map) -> Result<Organisation, Self::Error> {
-        self.organisations.patch_item(key, update).await
+        let mut log = self.log.lock().await;
+        let before = self.organisations.get_item(key.clone()).await?;
+        let organisation = self.organisations.patch_item(key, update).await?;
+        self.commit(&mut log, Record::Organisation(organisation.clone()), Record::Organisation(before)).await?;
+        Ok(organisation)
     }
 
     async fn delete_fields(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, fields: HashSet<String>) -> Result<Organisation, Self::Error> {
-        self.organisations.delete_fields(key, fields).await
+        let mut log = self.log.lock().await;
+        let before = self.organisations.get_item(key.clone()).await?;
+        let organisation = self.organisations.delete_fields(key, fields).await?;
+        self.commit(&mut log, Record::Organisation(organisation.clone()), Record::Organisation(before)).await?;
+        Ok(organisation)
     }
 }
 
@@ -201,8 +337,16 @@ impl DeleteItem<Organisation> for Memory {
     /// 
     /// # Behavior
     /// - Removes organisation from primary and secondary indexes
+    /// - Removes every membership and pending invitation of the organisation
     async fn delete_item(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>) -> Result<(), Self::Error> {
-        self.organisations.delete_item(key).await
+        let mut log = self.log.lock().await;
+        let org_id = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => *pk,
+            Key::Sk(sk) => self.organisations.pk(sk)?.ok_or(Error::OrganisationNotFound)?
+        };
+        self.organisations.get_item(Key::Pk(&org_id)).await?;
+        log.append(Record::OrganisationDeleted(org_id))?;
+        self.delete_organisation(&org_id).await
     }
 }
 
@@ -214,7 +358,10 @@ impl CreateItem<Member> for Memory {
     /// # Errors
     /// - Returns an error if the member already exists in the organisation
     async fn create_item(&self, member: Member) -> Result<Member, Self::Error> {
-        self.members.create_item(member).await
+        let mut log = self.log.lock().await;
+        let member = self.members.create_item(member).await?;
+        self.commit(&mut log, Record::Member(member.clone()), Record::MemberDeleted(member.org_id, member.user_id)).await?;
+        Ok(member)
     }
 }
 
@@ -239,7 +386,11 @@ impl UpdateItem<(Organisation, User), Member> for Memory {
     /// - Allows full replacement of member data
     /// - Maintains index consistency
     async fn update_item(&self, key: Key<&<(Organisation, User) as Item>::PK, &<(Organisation, User) as Item>::SK>, member: Member) -> Result<Member, Self::Error> {
-        self.members.update_item(key, member).await
+        let mut log = self.log.lock().await;
+        let before = self.members.get_item(key.clone()).await?;
+        let member = self.members.update_item(key, member).await?;
+        self.commit(&mut log, Record::Member(member.clone()), Record::Member(before)).await?;
+        Ok(member)
     }
 
     /// Partially updates a member's information
@@ -249,11 +400,19 @@ impl UpdateItem<(Organisation, User), Member> for Memory {
     /// - Owner status
     /// - Roles
     async fn patch_item(&self, key: Key<&<(Organisation, User) as Item>::PK, &<(Organisation, User) as Item>::SK>, update: Map) -> Result<Member, Self::Error> {
-        self.members.patch_item(key, update).await
+        let mut log = self.log.lock().await;
+        let before = self.members.get_item(key.clone()).await?;
+        let member = self.members.patch_item(key, update).await?;
+        self.commit(&mut log, Record::Member(member.clone()), Record::Member(before)).await?;
+        Ok(member)
     }
 
     async fn delete_fields(&self, key: Key<&<(Organisation, User) as Item>::PK, &<(Organisation, User) as Item>::SK>, fields: HashSet<String>) -> Result<Member, Self::Error> {
-        self.members.delete_fields(key, fields).await
+        let mut log = self.log.lock().await;
+        let before = self.members.get_item(key.clone()).await?;
+        let member = self.members.delete_fields(key, fields).await?;
+        self.commit(&mut log, Record::Member(member.clone()), Record::Member(before)).await?;
+        Ok(member)
     }
 }
 
@@ -264,6 +423,13 @@ impl DeleteItem<Member> for Memory {
     /// # Behavior
     /// - Removes member from primary and secondary indexes
     async fn delete_item(&self, key: Key<&<Member as Item>::PK, &<Member as Item>::SK>) -> Result<(), Self::Error> {
+        let mut log = self.log.lock().await;
+        let (org_id, user_id) = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => *pk,
+            _ => return Err(Error::MemberNotFound)
+        };
+        self.members.get_item(Key::Pk(&(org_id, user_id))).await?;
+        log.append(Record::MemberDeleted(org_id, user_id))?;
         self.members.delete_item(key).await
     }
 }
@@ -276,7 +442,10 @@ impl CreateItem<Service> for Memory {
     /// # Errors
     /// - Returns an error if a service with the same name already exists
     async fn create_item(&self, service: Service) -> Result<Service, Self::Error> {
-        self.services.create_item(service).await
+        let mut log = self.log.lock().await;
+        let service = self.services.create_item(service).await?;
+        self.commit(&mut log, Record::Service(service.clone()), Record::ServiceDeleted(service.id)).await?;
+        Ok(service)
     }
 }
 
@@ -292,6 +461,14 @@ impl GetItem<Service> for Memory {
     }
 }
 
+impl TokenExpiry for Memory {
+    type Error = Error;
+    /// The longest `token_expiry` set on any service
+    async fn longest_token_expiry(&self) -> Result<Option<Duration>, Self::Error> {
+        self.services.longest_token_expiry()
+    }
+}
+
 impl UpdateItem<Service> for Memory {
     type Error = Error;
     type Update = Map;
@@ -301,7 +478,11 @@ impl UpdateItem<Service> for Memory {
     /// - Allows full replacement of service data
     /// - Maintains index consistency
     async fn update_item(&self, key: Key<&<Service as Item>::PK, &<Service as Item>::SK>, service: Service) -> Result<Service, Self::Error> {
-        self.services.update_item(key, service).await
+        let mut log = self.log.lock().await;
+        let before = self.services.get_item(key.clone()).await?;
+        let service = self.services.update_item(key, service).await?;
+        self.commit(&mut log, Record::Service(service.clone()), Record::Service(before)).await?;
+        Ok(service)
     }
 
     /// Partially updates a service's information
@@ -315,11 +496,19 @@ impl UpdateItem<Service> for Memory {
     /// - Token expiry
     /// - Owner ID
     async fn patch_item(&self, key: Key<&<Service as Item>::PK, &<Service as Item>::SK>, update: Map) -> Result<Service, Self::Error> {
-        self.services.patch_item(key, update).await
+        let mut log = self.log.lock().await;
+        let before = self.services.get_item(key.clone()).await?;
+        let service = self.services.patch_item(key, update).await?;
+        self.commit(&mut log, Record::Service(service.clone()), Record::Service(before)).await?;
+        Ok(service)
     }
 
     async fn delete_fields(&self, key: Key<&<Service as Item>::PK, &<Service as Item>::SK>, fields: HashSet<String>) -> Result<Service, Self::Error> {
-        self.services.delete_fields(key, fields).await
+        let mut log = self.log.lock().await;
+        let before = self.services.get_item(key.clone()).await?;
+        let service = self.services.delete_fields(key, fields).await?;
+        self.commit(&mut log, Record::Service(service.clone()), Record::Service(before)).await?;
+        Ok(service)
     }
 }
 
@@ -330,7 +519,13 @@ impl DeleteItem<Service> for Memory {
     /// # Behavior
     /// - Removes service from primary and secondary indexes
     async fn delete_item(&self, key: Key<&<Service as Item>::PK, &<Service as Item>::SK>) -> Result<(), Self::Error> {
-        self.services.delete_item(key).await
+        let mut log = self.log.lock().await;
+        let id = match key {
+            Key::Both((owner_id, name)) => self.services.pk(owner_id, name)?.ok_or(Error::ServiceNotFound)?,
+            key => self.services.get_item(key).await?.id
+        };
+        log.append(Record::ServiceDeleted(id))?;
+        self.services.delete_item(Key::Pk(&id)).await
     }
 }
 
@@ -499,7 +694,15 @@ impl CreateItem<Verification> for Memory {
     type Error = Error;
     /// Creates a new verification in the in-memory database
     async fn create_item(&self, verification: Verification) -> Result<Verification, Self::Error> {
-        self.verifications.create_item(verification).await
+        let mut log = self.log.lock().await;
+        let contact = verification.owner_contact.clone();
+        let before = match self.verifications.verifications.read()?.get(&contact).cloned() {
+            Some(before) => Record::Verification(before),
+            None => Record::VerificationDeleted(contact)
+        };
+        let verification = self.verifications.create_item(verification).await?;
+        self.commit(&mut log, Record::Verification(verification.clone()), before).await?;
+        Ok(verification)
     }
 }
 
@@ -515,6 +718,289 @@ impl GetItem<Verification> for Memory {
     }
 }
 
+impl DeleteItem<Verification> for Memory {
+    type Error = Error;
+    /// Deletes a verification once it has been used
+    /// 
+    /// # Behavior
+    /// - Removes the verification from the primary and ID indexes
+    async fn delete_item(&self, key: Key<&<Verification as Item>::PK, &<Verification as Item>::SK>) -> Result<(), Self::Error> {
+        let mut log = self.log.lock().await;
+        let contact = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk.clone(),
+            Key::Sk(sk) => self.verifications.contact(sk)?.ok_or(Error::VerificationNotFound)?
+        };
+        if !self.verifications.verifications.read()?.contains_key(&contact) {
+            return Err(Error::VerificationNotFound)
+        }
+        log.append(Record::VerificationDeleted(contact.clone()))?;
+        self.verifications.delete_item(Key::Pk(&contact)).await
+    }
+}
+
+impl CountWrongCode<Verification> for Memory {
+    type Error = Error;
+    /// Counts a wrong code against a verification, recording it as deleted once it ran out of attempts
+    async fn count_wrong_code(&self, id: &<Verification as Item>::SK, max_attempts: u32) -> Result<u32, Self::Error> {
+        let mut log = self.log.lock().await;
+        let verification = self.verifications.wrong_code(id, max_attempts)?;
+        let attempts = verification.attempts;
+        let before = Record::Verification(Verification {attempts: attempts - 1, ..verification.clone()});
+        let record = match attempts >= max_attempts {
+            true => Record::VerificationDeleted(verification.owner_contact),
+            false => Record::Verification(verification),
+        };
+        self.commit(&mut log, record, before).await?;
+        Ok(attempts)
+    }
+}
+
+/// # Invitation-related Database Operations
+impl CreateItem<Invitation> for Memory {
+    type Error = Error;
+    /// Creates a new invitation in the in-memory database
+    /// 
+    /// # Errors
+    /// - Returns an error if the contact already has an invitation to the organisation
+    async fn create_item(&self, invitation: Invitation) -> Result<Invitation, Self::Error> {
+        self.invitations.create_item(invitation).await
+    }
+}
+
+impl GetItem<Invitation> for Memory {
+    type Error = Error;
+    /// Retrieves an invitation by primary key (ID) or secondary key (organisation ID and contact)
+    async fn get_item(&self, key: Key<&<Invitation as Item>::PK, &<Invitation as Item>::SK>) -> Result<Invitation, Self::Error> {
+        self.invitations.get_item(key).await
+    }
+}
+
+impl DeleteItem<Invitation> for Memory {
+    type Error = Error;
+    /// Deletes an invitation once it has been answered, revoked or has expired
+    async fn delete_item(&self, key: Key<&<Invitation as Item>::PK, &<Invitation as Item>::SK>) -> Result<(), Self::Error> {
+        self.invitations.delete_item(key).await
+    }
+}
+
+impl GetItems<Organisation, Invitation> for Memory {
+    type Error = Error;
+    type Filter = ();
+
+    /// Retrieves the pending invitations of an organisation
+    async fn get_items(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, _: Self::Filter) -> Result<Vec<Invitation>, Self::Error> {
+        let org_id = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => *pk,
+            Key::Sk(sk) => self.organisations.pk(sk)?.ok_or(Error::OrganisationNotFound)?
+        };
+        self.invitations.of(&org_id)
+    }
+}
+
+/// # Authorization code-related Database Operations
+impl CreateItem<AuthorizationCode> for Memory {
+    type Error = Error;
+    /// Stores a freshly issued authorization code
+    async fn create_item(&self, code: AuthorizationCode) -> Result<AuthorizationCode, Self::Error> {
+        self.authorization_codes.create_item(code).await
+    }
+}
+
+impl GetItem<AuthorizationCode> for Memory {
+    type Error = Error;
+    /// Retrieves an authorization code by the code itself
+    async fn get_item(&self, key: Key<&<AuthorizationCode as Item>::PK, &<AuthorizationCode as Item>::SK>) -> Result<AuthorizationCode, Self::Error> {
+        self.authorization_codes.get_item(key).await
+    }
+}
+
+impl DeleteItem<AuthorizationCode> for Memory {
+    type Error = Error;
+    /// Consumes an authorization code
+    /// 
+    /// # Errors
+    /// - Returns an error if the code does not exist or was already consumed
+    async fn delete_item(&self, key: Key<&<AuthorizationCode as Item>::PK, &<AuthorizationCode as Item>::SK>) -> Result<(), Self::Error> {
+        self.authorization_codes.delete_item(key).await
+    }
+}
+
+/// # Refresh token-related Database Operations
+impl CreateItem<RefreshToken> for Memory {
+    type Error = Error;
+    /// Stores a freshly issued refresh token
+    async fn create_item(&self, token: RefreshToken) -> Result<RefreshToken, Self::Error> {
+        let mut log = self.log.lock().await;
+        let token = self.refresh_tokens.create_item(token).await?;
+        self.commit(&mut log, Record::RefreshToken(token.clone()), Record::RefreshTokenDeleted(token.id.clone())).await?;
+        Ok(token)
+    }
+}
+
+impl GetItem<RefreshToken> for Memory {
+    type Error = Error;
+    /// Retrieves a refresh token by the digest of the token
+    async fn get_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>) -> Result<RefreshToken, Self::Error> {
+        self.refresh_tokens.get_item(key).await
+    }
+}
+
+impl GetItems<RefreshToken> for Memory {
+    type Error = Error;
+    type Filter = ();
+
+    /// Retrieves every token of a refresh token family
+    async fn get_items(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>, _: Self::Filter) -> Result<Vec<RefreshToken>, Self::Error> {
+        let family = match key {
+            Key::Sk(sk) | Key::Both((_, sk)) => *sk,
+            Key::Pk(pk) => self.refresh_tokens.get_item(Key::Pk(pk)).await?.family
+        };
+        self.refresh_tokens.family(&family)
+    }
+}
+
+impl UpdateItem<RefreshToken> for Memory {
+    type Error = Error;
+    type Update = Map;
+
+    /// Replaces a refresh token
+    async fn update_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>, token: RefreshToken) -> Result<RefreshToken, Self::Error> {
+        let mut log = self.log.lock().await;
+        let before = self.refresh_tokens.get_item(key.clone()).await?;
+        let token = self.refresh_tokens.update_item(key, token).await?;
+        self.commit(&mut log, Record::RefreshToken(token.clone()), Record::RefreshToken(before)).await?;
+        Ok(token)
+    }
+
+    /// Partially updates a refresh token, only `used` can be changed
+    async fn patch_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>, map: Self::Update) -> Result<RefreshToken, Self::Error> {
+        let mut log = self.log.lock().await;
+        let before = self.refresh_tokens.get_item(key.clone()).await?;
+        let token = self.refresh_tokens.patch_item(key, map).await?;
+        self.commit(&mut log, Record::RefreshToken(token.clone()), Record::RefreshToken(before)).await?;
+        Ok(token)
+    }
+
+    /// Refresh tokens do not support deleting fields
+    async fn delete_fields(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>, fields: HashSet<String>) -> Result<RefreshToken, Self::Error> {
+        let mut log = self.log.lock().await;
+        let before = self.refresh_tokens.get_item(key.clone()).await?;
+        let token = self.refresh_tokens.delete_fields(key, fields).await?;
+        self.commit(&mut log, Record::RefreshToken(token.clone()), Record::RefreshToken(before)).await?;
+        Ok(token)
+    }
+}
+
+impl DeleteItem<RefreshToken> for Memory {
+    type Error = Error;
+    /// Removes a refresh token
+    async fn delete_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>) -> Result<(), Self::Error> {
+        let mut log = self.log.lock().await;
+        let digest = self.refresh_tokens.get_item(key).await?.id;
+        log.append(Record::RefreshTokenDeleted(digest.clone()))?;
+        self.refresh_tokens.delete_item(Key::Pk(&digest)).await
+    }
+}
+
+impl ConsumeRefreshToken for Memory {
+    type Error = Error;
+    /// Marks a refresh token as used in a single step
+    async fn consume(&self, digest: &str) -> Result<RefreshToken, Self::Error> {
+        let mut log = self.log.lock().await;
+        let before = self.refresh_tokens.consume(digest)?;
+        let used = RefreshToken {used: true, ..before.clone()};
+        self.commit(&mut log, Record::RefreshToken(used), Record::RefreshToken(before.clone())).await?;
+        Ok(before)
+    }
+}
+
+impl GetItems<User, RefreshToken> for Memory {
+    type Error = Error;
+    type Filter = ();
+
+    /// Retrieves every refresh token issued to a user
+    async fn get_items(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, _: Self::Filter) -> Result<Vec<RefreshToken>, Self::Error> {
+        let user_id = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => *pk,
+            Key::Sk(sk) => self.users.get_item(Key::Sk(sk)).await?.id
+        };
+        self.refresh_tokens.of(&user_id)
+    }
+}
+
+/// # Revocation-related Database Operations
+impl CreateItem<Revocation> for Memory {
+    type Error = Error;
+    /// Records a revocation, pruning the ones that are no longer needed
+    async fn create_item(&self, revocation: Revocation) -> Result<Revocation, Self::Error> {
+        let mut log = self.log.lock().await;
+        let before = match self.revocations.revocations.read()?.get(&revocation.id).cloned() {
+            Some(before) => Record::Revocation(before),
+            None => Record::RevocationDeleted(revocation.id)
+        };
+        let revocation = self.revocations.create_item(revocation).await?;
+        self.commit(&mut log, Record::Revocation(revocation.clone()), before).await?;
+        Ok(revocation)
+    }
+}
+
+impl GetItem<Revocation> for Memory {
+    type Error = Error;
+    /// Retrieves a live revocation by its id
+    async fn get_item(&self, key: Key<&<Revocation as Item>::PK, &<Revocation as Item>::SK>) -> Result<Revocation, Self::Error> {
+        self.revocations.get_item(key).await
+    }
+}
+
+impl GetItems<Revocation> for Memory {
+    type Error = Error;
+    type Filter = ();
+
+    /// Retrieves the live revocations of a subject
+    async fn get_items(&self, key: Key<&<Revocation as Item>::PK, &<Revocation as Item>::SK>, _: Self::Filter) -> Result<Vec<Revocation>, Self::Error> {
+        match key {
+            Key::Sk(sk) | Key::Both((_, sk)) => self.revocations.of(sk),
+            Key::Pk(pk) => Ok(vec![self.revocations.get_item(Key::Pk(pk)).await?])
+        }
+    }
+}
+
+impl DeleteItem<Revocation> for Memory {
+    type Error = Error;
+    /// Removes a revocation
+    async fn delete_item(&self, key: Key<&<Revocation as Item>::PK, &<Revocation as Item>::SK>) -> Result<(), Self::Error> {
+        let mut log = self.log.lock().await;
+        let id = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) if self.revocations.revocations.read()?.contains_key(pk) => *pk,
+            _ => return Err(Error::RevocationNotFound)
+        };
+        log.append(Record::RevocationDeleted(id))?;
+        self.revocations.delete_item(Key::Pk(&id)).await
+    }
+}
+
+// LoginAttempts implementation
+
+impl LoginAttempts for Memory {
+    type Error = Error;
+
+    async fn failures(&self, key: &str) -> Result<Option<Failures>, Self::Error> {
+        self.attempts.failures(key).await
+    }
+
+    async fn fail(&self, key: &str, at: DateTime<Utc>, forget_before: DateTime<Utc>) -> Result<Option<Failures>, Self::Error> {
+        self.attempts.fail(key, at, forget_before).await
+    }
+
+    async fn forgive(&self, key: &str) -> Result<(), Self::Error> {
+        self.attempts.forgive(key).await
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), Self::Error> {
+        self.attempts.clear(key).await
+    }
+}
+
 // Similar placeholder implementations for other types would follow:
 // - Role
 // - Resource
@@ -522,7 +1008,7 @@ impl GetItem<Verification> for Memory {
 
 #[cfg(test)]
 mod tests {
-    use crate::domain::types::{Id, User, Organisation, Member, Contact, EmailAddress, Phone};
+    use crate::domain::types::{Id, User, Organisation, Member, Contact, EmailAddress, Phone, Either};
     use bson::oid::ObjectId;
     use super::*;
 
@@ -582,6 +1068,52 @@ mod tests {
         assert!(owned_orgs.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_delete_organisation_removes_members() {
+        let db = Memory::default();
+        let user = create_test_user();
+        let org = create_test_organisation();
+        let member = create_test_member(&org, &user);
+
+        db.create_item(user.clone()).await.unwrap();
+        db.create_item(org.clone()).await.unwrap();
+        db.create_item(member).await.unwrap();
+
+        DeleteItem::<Organisation>::delete_item(&db, Key::Pk(&org.id)).await.unwrap();
+
+        let result = GetItem::<(Organisation, User), Member>::get_item(&db, Key::Pk(&(org.id, user.id))).await;
+        assert!(matches!(result, Err(Error::MemberNotFound)));
+        let orgs: Vec<Organisation> = GetItems::<User, Organisation>::get_items(&db, Key::Pk(&user.id), false).await.unwrap();
+        assert!(orgs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_organisation_removes_invitations() {
+        let db = Memory::default();
+        let user = create_test_user();
+        let org = create_test_organisation();
+        let mut invitation = Invitation {
+            id: Id(ObjectId::new()),
+            org_id: org.id,
+            contact: Contact::Email(EmailAddress::New("invitee@example.com".parse().unwrap())),
+            title: "Engineer".to_string(),
+            roles: vec![],
+            invited_by: user.id,
+            expires: Default::default(),
+        };
+        invitation.renew();
+
+        db.create_item(org.clone()).await.unwrap();
+        db.create_item(invitation.clone()).await.unwrap();
+        let invitations: Vec<Invitation> = GetItems::<Organisation, Invitation>::get_items(&db, Key::Pk(&org.id), ()).await.unwrap();
+        assert_eq!(invitations, vec![invitation.clone()]);
+
+        DeleteItem::<Organisation>::delete_item(&db, Key::Pk(&org.id)).await.unwrap();
+
+        let result = GetItem::<Invitation>::get_item(&db, Key::Pk(&invitation.id)).await;
+        assert!(matches!(result, Err(Error::InvitationNotFound)));
+    }
+
     #[tokio::test]
     async fn test_get_organisation_users() {
         let db = Memory::default();
@@ -651,4 +1183,47 @@ mod tests {
         let owners: Vec<(Member, Organisation)> = GetItems::<User, (Member, Organisation)>::get_items(&db, Key::Pk(&user.id), true).await.unwrap();
         assert!(owners.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_purge_expired_verifications() {
+        let db = Memory::default();
+        let expired = Verification {
+            owner_contact: Either::Right(EmailAddress::New("expired@example.com".parse().unwrap())),
+            id: Id(ObjectId::new()),
+            code: String::from("123456"),
+            expires: chrono::Utc::now() - chrono::Duration::seconds(1),
+            attempts: 0,
+            token: String::new(),
+        };
+        let current = Verification {
+            owner_contact: Either::Left(Phone::New("1234567890".to_string())),
+            id: Id(ObjectId::new()),
+            expires: chrono::Utc::now() + chrono::Duration::minutes(5),
+            ..expired.clone()
+        };
+        db.create_item(expired.clone()).await.unwrap();
+        db.create_item(current.clone()).await.unwrap();
+
+        assert_eq!(db.purge_expired_verifications().await.unwrap(), 1);
+        assert!(db.verifications.verifications.read().unwrap().get(&expired.owner_contact).is_none());
+        assert_eq!(GetItem::<Verification>::get_item(&db, Key::Sk(&current.id)).await.unwrap(), current);
+        assert_eq!(db.purge_expired_verifications().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired_refresh_tokens() {
+        let db = Memory::default();
+        let user_id = Id(ObjectId::new());
+        let (used, _) = RefreshToken::new(user_id, None, vec![], -1);
+        let used = db.create_item(used).await.unwrap();
+        db.consume(&used.id).await.unwrap();
+        let expired = db.create_item(used.rotate(-1).0).await.unwrap();
+        let current = db.create_item(expired.rotate(60).0).await.unwrap();
+
+        assert_eq!(db.purge_expired_refresh_tokens().await.unwrap(), 2);
+        assert!(GetItem::<RefreshToken>::get_item(&db, Key::Pk(&used.id)).await.is_err());
+        assert!(GetItem::<RefreshToken>::get_item(&db, Key::Pk(&expired.id)).await.is_err());
+        assert_eq!(GetItems::<RefreshToken>::get_items(&db, Key::Sk(&current.family), ()).await.unwrap(), vec![current]);
+        assert_eq!(db.purge_expired_refresh_tokens().await.unwrap(), 0);
+    }
 }
@@ -61,8 +61,39 @@ impl Organisations {
         }
         Ok(())
     }
+
+    /// Checks that the name is not taken by an organisation other than the given one
+    pub fn name_available(&self, pk: &<Organisation as Item>::PK, sk: &<Organisation as Item>::SK) -> Result<(), Error> {
+        match self.pk(sk)? {
+            Some(id) if &id != pk => Err(Error::OrganisationWithNameExists),
+            _ => Ok(())
+        }
+    }
 }
 
+/// Applies a partial update to an organisation, allowing name, domain, home and contacts
+pub(crate) fn patch(organisation: &mut Organisation, map: Map) -> Result<(), Error> {
+    // Update fields
+    if let Some(value) = map.get("name") {
+        let new_name: String = value.clone().try_into()?;
+        organisation.name = new_name;
+    }
+
+    if let Some(value) = map.get("domain") {
+        organisation.domain = Some(value.clone().try_into()?);
+    }
+
+    if let Some(value) = map.get("home") {
+        organisation.home = Some(value.clone().try_into()?);
+    }
+
+    if let Some(value) = map.get("contacts") {
+        organisation.contacts = value.clone().try_into()?;
+    }
+    Ok(())
+}
+
+
 impl CreateItem<Organisation> for Organisations {
     type Error = Error;
     
@@ -90,7 +121,7 @@ impl GetItem<Organisation> for Organisations {
             },
         };
 
-        option.ok_or(Error::ServiceNotFound)
+        option.ok_or(Error::OrganisationNotFound)
     }
 }
 
@@ -99,6 +130,9 @@ impl UpdateItem<Organisation> for Organisations {
     type Update = Map;
 
     async fn update_item(&self, _: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, organisation: Organisation) -> Result<Organisation, Self::Error> {
+        // Renaming must not take over another organisation's name
+        self.name_available(&organisation.id, &organisation.name)?;
+
         // Update indexes for new organisation
         self.update_indexes(organisation.id, organisation.name.clone())?;
         
@@ -122,24 +156,7 @@ impl UpdateItem<Organisation> for Organisations {
     async fn patch_item(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, map: Map) -> Result<Organisation, Self::Error> {
         // First, retrieve the existing organisation
         let mut organisation = self.get_item(key.clone()).await?;
-        
-        // Update fields
-        if let Some(value) = map.get("name") {
-            let new_name: String = value.clone().try_into()?;
-            organisation.name = new_name;
-        }
-
-        if let Some(value) = map.get("domain") {
-            organisation.domain = Some(value.clone().try_into()?);
-        }
-
-        if let Some(value) = map.get("home") {
-            organisation.home = Some(value.clone().try_into()?);
-        }
-
-        if let Some(value) = map.get("contacts") {
-            organisation.contacts = value.clone().try_into()?;
-        }
+        patch(&mut organisation, map)?;
 
         // Create a new organisation with updated values
         self.update_item(key, organisation).await
@@ -258,6 +275,24 @@ mod tests {
         assert_eq!(result.name, "Updated Organisation", "Organisation name should be updated");
     }
 
+    #[tokio::test]
+    async fn test_patch_organisation_name_taken() {
+        let organisations = Organisations::default();
+        let organisation = create_test_organisation();
+        let mut other = create_test_organisation();
+        other.id = Id(ObjectId::new());
+        other.name = "Other Organisation".to_string();
+        let _ = organisations.create_item(organisation.clone()).await;
+        let _ = organisations.create_item(other.clone()).await;
+
+        let patch_map = HashMap::from([
+            ("name".to_string(), Value::String(organisation.name.clone()))
+        ]);
+
+        let result = organisations.patch_item(Key::Pk(&other.id), patch_map).await;
+        assert!(matches!(result, Err(Error::OrganisationWithNameExists)));
+    }
+
     #[tokio::test]
     async fn test_patch_organisation_domain() {
         let organisations = Organisations::default();
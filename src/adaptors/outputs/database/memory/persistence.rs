@@ -0,0 +1,552 @@
+//! Durable persistence for the memory database
+//!
+//! Every change to users, organisations, members, services, verifications, refresh tokens and
+//! revocations is appended to a log, one JSON record per line, and synced to disk before the change
+//! is acknowledged. A deletion is logged before it is made, any other change is undone when its
+//! record cannot be written, so memory never holds a change the log lost. A snapshot of those
+//! collections is written from time to time, after which the log starts over.
+//!
+//! Snapshots are numbered, and a log opens with the number of the snapshot its records go on
+//! top of. A crash after a snapshot was written but before the log started over leaves a log
+//! the snapshot already holds, which is then skipped rather than replayed.
+//!
+//! On startup the snapshot is loaded and the log replayed on top of it. A record cut short by a
+//! crash while it was being written is dropped, any other unreadable record fails the startup.
+
+use crate::ports::outputs::database::{CreateItem, DeleteItem};
+use crate::domain::types::{User, Contact, Organisation, Member, Service, Verification, RefreshToken, Revocation, Either, Phone, EmailAddress, Id, Key, Error as DomainError};
+use std::io::{self, ErrorKind, Write};
+use serde::{Serialize, Deserialize};
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use super::{Error, Memory};
+
+
+/// Default number of seconds between snapshots
+const DEFAULT_INTERVAL: u64 = 60 * 60;
+
+
+/// Where the memory database keeps its data and how often it is compacted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Persistence {
+    /// The snapshot file, the log is kept next to it with a `.log` suffix
+    pub path: String,
+    /// Seconds between snapshots, none are taken while serving when zero
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+
+fn default_interval() -> u64 {
+    DEFAULT_INTERVAL
+}
+
+
+impl Persistence {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {path: path.into(), interval: DEFAULT_INTERVAL}
+    }
+
+    fn snapshot(&self) -> PathBuf {
+        PathBuf::from(&self.path)
+    }
+
+    fn log(&self) -> PathBuf {
+        PathBuf::from(format!("{}.log", self.path))
+    }
+}
+
+
+/// A user with its password, which `User` leaves out when it is empty
+#[derive(Serialize, Deserialize)]
+pub(super) struct StoredUser {
+    id: Id,
+    username: String,
+    first_name: String,
+    last_name: String,
+    #[serde(flatten)]
+    contact: Contact,
+    password: String,
+}
+
+
+impl From<&User> for StoredUser {
+    fn from(user: &User) -> Self {
+        let User {id, username, first_name, last_name, contact, password} = user.clone();
+        Self {id, username, first_name, last_name, contact, password}
+    }
+}
+
+
+impl From<StoredUser> for User {
+    fn from(user: StoredUser) -> Self {
+        let StoredUser {id, username, first_name, last_name, contact, password} = user;
+        Self {id, username, first_name, last_name, contact, password}
+    }
+}
+
+
+/// The state a change left an item in.
+#[derive(Serialize, Deserialize)]
+pub(super) enum Record {
+    /// Opens a log, its records go on top of the snapshot of this generation
+    Generation(u64),
+    User(StoredUser),
+    UserDeleted(Id),
+    Organisation(Organisation),
+    OrganisationDeleted(Id),
+    Member(Member),
+    MemberDeleted(Id, Id),
+    Service(Service),
+    ServiceDeleted(Id),
+    Verification(Verification),
+    VerificationDeleted(Either<Phone, EmailAddress>),
+    RefreshToken(RefreshToken),
+    RefreshTokenDeleted(String),
+    Revocation(Revocation),
+    RevocationDeleted(Id),
+}
+
+
+/// The persisted collections at one point in time.
+#[derive(Default, Serialize, Deserialize)]
+struct Snapshot {
+    // Snapshots written before they were numbered are the first generation
+    #[serde(default)]
+    generation: u64,
+    users: Vec<StoredUser>,
+    organisations: Vec<Organisation>,
+    members: Vec<Member>,
+    services: Vec<Service>,
+    verifications: Vec<Verification>,
+    // Snapshots written before these were persisted have none
+    #[serde(default)]
+    refresh_tokens: Vec<RefreshToken>,
+    #[serde(default)]
+    revocations: Vec<Revocation>,
+}
+
+
+/// The open log, writes are only logged once the database was restored with persistence configured.
+#[derive(Debug, Default)]
+pub struct Log {
+    file: Option<File>,
+    /// The generation of the snapshot the log goes on top of
+    generation: u64,
+}
+
+
+impl Log {
+    /// Appends a record and syncs it to disk
+    pub(super) fn append(&mut self, record: Record) -> Result<(), Error> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => return Ok(())
+        };
+        let mut line = serde_json::to_vec(&record).map_err(DomainError::from)?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Empties the log and opens it on top of the snapshot of `generation`
+    fn start_over(&mut self, generation: u64) -> Result<(), Error> {
+        if let Some(file) = &self.file {
+            file.set_len(0)?;
+            file.sync_all()?;
+        }
+        self.generation = generation;
+        self.append(Record::Generation(generation))
+    }
+}
+
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::DomainError(DomainError::internal(err))
+    }
+}
+
+
+/// Writes `bytes` to a temporary file next to `path` and moves it over `path`, so a crash leaves either version whole
+fn replace(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let temporary = PathBuf::from(format!("{}.tmp", path.display()));
+    let mut file = File::create(&temporary)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)?;
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+
+/// Reads the records of a log, dropping and cutting off a torn final record
+fn records(path: &Path) -> Result<Vec<Record>, Error> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into())
+    };
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let end = bytes[offset..].iter().position(|byte| *byte == b'\n').map(|end| offset + end);
+        let line = &bytes[offset..end.unwrap_or(bytes.len())];
+        let last = end.is_none_or(|end| end + 1 == bytes.len());
+        match (serde_json::from_slice(line), end) {
+            (Ok(record), Some(end)) => {
+                records.push(record);
+                offset = end + 1;
+            },
+            (Err(err), _) if !last => return Err(DomainError::Internal {
+                message: format!("{} has an unreadable record at byte {}", path.display(), offset),
+                source: Some(Box::new(err))
+            }.into()),
+            // The last record was not written out completely
+            _ => {
+                log::warn!("dropping a torn record at the end of {}", path.display());
+                OpenOptions::new().write(true).open(path)?.set_len(offset as u64)?;
+                break
+            }
+        }
+    }
+    Ok(records)
+}
+
+
+/// Turns the errors of an item that is already gone into success, replaying a deletion twice is harmless
+fn gone(result: Result<(), Error>) -> Result<(), Error> {
+    match result {
+        Err(Error::UserNotFound | Error::OrganisationNotFound | Error::MemberNotFound | Error::ServiceNotFound | Error::VerificationNotFound | Error::RefreshTokenNotFound | Error::RevocationNotFound) => Ok(()),
+        result => result
+    }
+}
+
+
+impl Memory {
+    /// Starts a memory database that persists its data as configured.
+    ///
+    /// Nothing is read until [`Memory::restore`] is called.
+    pub fn persistent(persistence: Persistence) -> Self {
+        Self {persistence: Some(persistence), ..Default::default()}
+    }
+
+    /// The persistence settings, none when the data only lives in memory
+    pub fn persistence(&self) -> Option<&Persistence> {
+        self.persistence.as_ref()
+    }
+
+    /// Loads the snapshot, replays the log and opens it for the changes to come.
+    ///
+    /// Does nothing when persistence is not configured.
+    pub async fn restore(&self) -> Result<(), Error> {
+        let persistence = match &self.persistence {
+            Some(persistence) => persistence,
+            None => return Ok(())
+        };
+        let mut log = self.log.lock().await;
+        let snapshot = match fs::read(persistence.snapshot()) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(DomainError::from)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Snapshot::default(),
+            Err(err) => return Err(err.into())
+        };
+        let generation = snapshot.generation;
+        let mut logged = records(&persistence.log())?.into_iter().peekable();
+        // Logs written before snapshots were numbered go on top of the first generation
+        let logged_generation = match logged.peek() {
+            Some(Record::Generation(generation)) => *generation,
+            _ => 0
+        };
+        if logged_generation > generation {
+            Err(DomainError::Internal {message: format!("{} goes on top of a newer snapshot than {}", persistence.log().display(), persistence.path), source: None})?
+        }
+        let replayed = match logged_generation == generation {
+            true => Some(logged),
+            false => {
+                log::warn!("skipping {}, the snapshot already holds its records", persistence.log().display());
+                None
+            }
+        };
+        let fresh = replayed.as_ref().is_none_or(|logged| logged.len() == 0);
+        let records = snapshot.users.into_iter().map(Record::User)
+            .chain(snapshot.organisations.into_iter().map(Record::Organisation))
+            .chain(snapshot.members.into_iter().map(Record::Member))
+            .chain(snapshot.services.into_iter().map(Record::Service))
+            .chain(snapshot.verifications.into_iter().map(Record::Verification))
+            .chain(snapshot.refresh_tokens.into_iter().map(Record::RefreshToken))
+            .chain(snapshot.revocations.into_iter().map(Record::Revocation))
+            .chain(replayed.into_iter().flatten());
+        for record in records {
+            self.apply(record).await?;
+        }
+        log.file = Some(OpenOptions::new().create(true).append(true).open(persistence.log())?);
+        log.generation = generation;
+        if fresh {
+            log.start_over(generation)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a snapshot of the persisted collections and starts the log over.
+    ///
+    /// Changes wait for the snapshot to be written. Does nothing before the database is restored.
+    pub async fn snapshot(&self) -> Result<(), Error> {
+        let (persistence, mut log) = match &self.persistence {
+            Some(persistence) => (persistence, self.log.lock().await),
+            None => return Ok(())
+        };
+        if log.file.is_none() {
+            return Ok(())
+        }
+        let generation = log.generation + 1;
+        let snapshot = Snapshot {
+            generation,
+            users: self.users.users.read()?.values().map(StoredUser::from).collect(),
+            organisations: self.organisations.organisations.read()?.values().cloned().collect(),
+            members: self.members.members.read()?.values().cloned().collect(),
+            services: self.services.services.read()?.values().cloned().collect(),
+            verifications: self.verifications.verifications.read()?.values().cloned().collect(),
+            refresh_tokens: self.refresh_tokens.tokens.read()?.values().filter(|token| !token.expired()).cloned().collect(),
+            revocations: self.revocations.revocations.read()?.values().filter(|revocation| !revocation.expired()).cloned().collect(),
+        };
+        let bytes = serde_json::to_vec(&snapshot).map_err(DomainError::from)?;
+        replace(&persistence.snapshot(), &bytes)?;
+        log.start_over(generation)
+    }
+
+    /// Brings an item to the state a record left it in, replacing rather than updating a stored item
+    async fn apply(&self, record: Record) -> Result<(), Error> {
+        match record {
+            Record::Generation(_) => Ok(()),
+            Record::User(user) => {
+                let user = User::from(user);
+                gone(self.users.delete_item(Key::Pk(&user.id)).await)?;
+                self.users.create_item(user).await.map(|_| ())
+            },
+            Record::UserDeleted(id) => gone(self.users.delete_item(Key::Pk(&id)).await),
+            Record::Organisation(organisation) => {
+                gone(self.organisations.delete_item(Key::Pk(&organisation.id)).await)?;
+                self.organisations.create_item(organisation).await.map(|_| ())
+            },
+            Record::OrganisationDeleted(id) => gone(self.delete_organisation(&id).await),
+            Record::Member(member) => {
+                gone(self.members.delete_item(Key::Pk(&(member.org_id, member.user_id))).await)?;
+                self.members.create_item(member).await.map(|_| ())
+            },
+            Record::MemberDeleted(org_id, user_id) => gone(self.members.delete_item(Key::Pk(&(org_id, user_id))).await),
+            Record::Service(service) => {
+                gone(self.services.delete_item(Key::Pk(&service.id)).await)?;
+                self.services.create_item(service).await.map(|_| ())
+            },
+            Record::ServiceDeleted(id) => gone(self.services.delete_item(Key::Pk(&id)).await),
+            Record::Verification(verification) => self.verifications.create_item(verification).await.map(|_| ()),
+            Record::VerificationDeleted(contact) => gone(self.verifications.delete_item(Key::Pk(&contact)).await),
+            Record::RefreshToken(token) => {
+                gone(self.refresh_tokens.delete_item(Key::Pk(&token.id)).await)?;
+                self.refresh_tokens.create_item(token).await.map(|_| ())
+            },
+            Record::RefreshTokenDeleted(digest) => gone(self.refresh_tokens.delete_item(Key::Pk(&digest)).await),
+            Record::Revocation(revocation) => self.revocations.create_item(revocation).await.map(|_| ()),
+            Record::RevocationDeleted(id) => gone(self.revocations.delete_item(Key::Pk(&id)).await),
+        }
+    }
+
+    /// Logs the state a change left an item in, bringing the item back to the state `before`
+    /// describes when the record cannot be written
+    pub(super) async fn commit(&self, log: &mut Log, record: Record, before: Record) -> Result<(), Error> {
+        let err = match log.append(record) {
+            Ok(()) => return Ok(()),
+            Err(err) => err
+        };
+        if let Err(undo) = self.apply(before).await {
+            log::error!("failed to undo a change that could not be logged: {}", undo);
+        }
+        Err(err)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::Value;
+    use crate::ports::outputs::{database::{GetItem, GetItems, UpdateItem, Map}, consume::ConsumeRefreshToken};
+    use chrono::{Duration, Utc};
+    use bson::oid::ObjectId;
+
+    fn persistence() -> Persistence {
+        let path = std::env::temp_dir().join(format!("beekeeper-{}.snapshot", ObjectId::new()));
+        Persistence::new(path.to_string_lossy())
+    }
+
+    async fn restored(persistence: &Persistence) -> Memory {
+        let memory = Memory::persistent(persistence.clone());
+        memory.restore().await.unwrap();
+        memory
+    }
+
+    fn user() -> User {
+        User {
+            id: Id::default(),
+            username: "testuser".to_string(),
+            first_name: "Test".to_string(),
+            last_name: "User".to_string(),
+            password: String::new(),
+            contact: Contact::Email(EmailAddress::New("test@example.com".parse().unwrap()))
+        }
+    }
+
+    fn remove(persistence: &Persistence) {
+        let _ = fs::remove_file(persistence.snapshot());
+        let _ = fs::remove_file(persistence.log());
+    }
+
+    #[tokio::test]
+    async fn test_replay_log() {
+        let persistence = persistence();
+        let memory = restored(&persistence).await;
+        let user = memory.create_item(user()).await.unwrap();
+        let mut map = Map::new();
+        map.insert("first_name".to_string(), Value::String("Updated".to_string()));
+        let user = UpdateItem::<User>::patch_item(&memory, Key::Pk(&user.id), map).await.unwrap();
+        let organisation = Organisation {id: Id::default(), name: "Beekeeper".to_string(), domain: None, home: None, contacts: Vec::new()};
+        let organisation = memory.create_item(organisation).await.unwrap();
+        memory.create_item(Member {org_id: organisation.id, user_id: user.id, owner: true, ..Default::default()}).await.unwrap();
+        let verification = Verification {owner_contact: Either::Right(EmailAddress::New("test@example.com".parse().unwrap())), id: Id::default(), code: String::from("123456"), expires: Utc::now() + Duration::minutes(10), attempts: 0, token: String::new()};
+        memory.create_item(verification.clone()).await.unwrap();
+        DeleteItem::<Verification>::delete_item(&memory, Key::Sk(&verification.id)).await.unwrap();
+
+        let memory = restored(&persistence).await;
+        assert_eq!(GetItem::<User>::get_item(&memory, Key::Pk(&user.id)).await.unwrap(), user);
+        assert!(matches!(GetItem::<Verification>::get_item(&memory, Key::Sk(&verification.id)).await, Err(Error::VerificationNotFound)));
+        let owners = GetItems::<Organisation, User>::get_items(&memory, Key::Sk(&organisation.name), true).await.unwrap();
+        assert_eq!(owners, vec![user]);
+        remove(&persistence);
+    }
+
+    #[tokio::test]
+    async fn test_replay_sessions() {
+        let persistence = persistence();
+        let memory = restored(&persistence).await;
+        let (token, secret) = RefreshToken::new(Id::default(), None, Vec::new(), 600);
+        memory.create_item(token.clone()).await.unwrap();
+        let (dropped, _) = token.rotate(600);
+        memory.create_item(dropped.clone()).await.unwrap();
+        DeleteItem::<RefreshToken>::delete_item(&memory, Key::Pk(&dropped.id)).await.unwrap();
+        let revocation = Revocation::subject(token.user_id, 600);
+        memory.create_item(revocation.clone()).await.unwrap();
+        memory.snapshot().await.unwrap();
+        // Used after the snapshot, so only the log knows
+        memory.consume(&RefreshToken::digest(&secret)).await.unwrap();
+
+        let memory = restored(&persistence).await;
+        assert!(GetItem::<RefreshToken>::get_item(&memory, Key::Pk(&token.id)).await.unwrap().used);
+        assert!(matches!(GetItem::<RefreshToken>::get_item(&memory, Key::Pk(&dropped.id)).await, Err(Error::RefreshTokenNotFound)));
+        assert_eq!(GetItem::<Revocation>::get_item(&memory, Key::Pk(&revocation.id)).await.unwrap(), revocation);
+        remove(&persistence);
+    }
+
+    #[tokio::test]
+    async fn test_unlogged_change_is_undone() {
+        let persistence = persistence();
+        let memory = restored(&persistence).await;
+        let user = memory.create_item(user()).await.unwrap();
+        let (token, secret) = RefreshToken::new(user.id, None, Vec::new(), 600);
+        memory.create_item(token.clone()).await.unwrap();
+        // A log that cannot be written to
+        memory.log.lock().await.file = Some(File::open(persistence.log()).unwrap());
+
+        let mut map = Map::new();
+        map.insert("first_name".to_string(), Value::String("Updated".to_string()));
+        assert!(UpdateItem::<User>::patch_item(&memory, Key::Pk(&user.id), map).await.is_err());
+        assert_eq!(GetItem::<User>::get_item(&memory, Key::Pk(&user.id)).await.unwrap(), user);
+        assert!(memory.consume(&RefreshToken::digest(&secret)).await.is_err());
+        assert!(!GetItem::<RefreshToken>::get_item(&memory, Key::Pk(&token.id)).await.unwrap().used);
+        assert!(memory.create_item(Revocation::subject(user.id, 600)).await.is_err());
+        assert!(GetItem::<Revocation>::get_item(&memory, Key::Pk(&user.id)).await.is_err());
+        // Deletions are logged first, so they are refused too
+        assert!(DeleteItem::<User>::delete_item(&memory, Key::Pk(&user.id)).await.is_err());
+        assert_eq!(GetItem::<User>::get_item(&memory, Key::Pk(&user.id)).await.unwrap(), user);
+        remove(&persistence);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let persistence = persistence();
+        let memory = restored(&persistence).await;
+        let first = memory.create_item(user()).await.unwrap();
+        memory.snapshot().await.unwrap();
+        assert!(matches!(records(&persistence.log()).unwrap()[..], [Record::Generation(1)]));
+
+        // Changes after the snapshot are replayed on top of it
+        DeleteItem::<User>::delete_item(&memory, Key::Pk(&first.id)).await.unwrap();
+        let second = memory.create_item(user()).await.unwrap();
+
+        let memory = restored(&persistence).await;
+        assert!(matches!(GetItem::<User>::get_item(&memory, Key::Pk(&first.id)).await, Err(Error::UserNotFound)));
+        assert_eq!(GetItem::<User>::get_item(&memory, Key::Pk(&second.id)).await.unwrap(), second);
+        remove(&persistence);
+    }
+
+    #[tokio::test]
+    async fn test_crash_before_log_starts_over() {
+        let persistence = persistence();
+        let memory = restored(&persistence).await;
+        let first = memory.create_item(user()).await.unwrap();
+        DeleteItem::<User>::delete_item(&memory, Key::Pk(&first.id)).await.unwrap();
+        // The address is taken again, so replaying the first user would clash with the second
+        let second = memory.create_item(user()).await.unwrap();
+        let log = fs::read(persistence.log()).unwrap();
+        memory.snapshot().await.unwrap();
+        fs::write(persistence.log(), log).unwrap();
+
+        let memory = restored(&persistence).await;
+        assert!(matches!(GetItem::<User>::get_item(&memory, Key::Pk(&first.id)).await, Err(Error::UserNotFound)));
+        assert_eq!(GetItem::<User>::get_item(&memory, Key::Pk(&second.id)).await.unwrap(), second);
+        // The skipped log starts over on top of the snapshot, so later changes are replayed
+        DeleteItem::<User>::delete_item(&memory, Key::Pk(&second.id)).await.unwrap();
+        let memory = restored(&persistence).await;
+        assert!(matches!(GetItem::<User>::get_item(&memory, Key::Pk(&second.id)).await, Err(Error::UserNotFound)));
+        remove(&persistence);
+    }
+
+    #[tokio::test]
+    async fn test_unnumbered_log() {
+        let persistence = persistence();
+        let user = StoredUser::from(&user());
+        let id = user.id;
+        fs::write(persistence.log(), [serde_json::to_vec(&Record::User(user)).unwrap(), b"\n".to_vec()].concat()).unwrap();
+        let memory = restored(&persistence).await;
+        assert!(GetItem::<User>::get_item(&memory, Key::Pk(&id)).await.is_ok());
+
+        // A log must not go on top of a snapshot that is missing
+        fs::write(persistence.log(), b"{\"Generation\":2}\n").unwrap();
+        assert!(Memory::persistent(persistence.clone()).restore().await.is_err());
+        remove(&persistence);
+    }
+
+    #[tokio::test]
+    async fn test_torn_record() {
+        let persistence = persistence();
+        let memory = restored(&persistence).await;
+        let user = memory.create_item(user()).await.unwrap();
+        let length = fs::metadata(persistence.log()).unwrap().len();
+        OpenOptions::new().append(true).open(persistence.log()).unwrap().write_all(b"{\"User\":{\"id\":").unwrap();
+
+        let memory = restored(&persistence).await;
+        assert_eq!(GetItem::<User>::get_item(&memory, Key::Pk(&user.id)).await.unwrap(), user);
+        assert_eq!(fs::metadata(persistence.log()).unwrap().len(), length);
+        remove(&persistence);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_record() {
+        let persistence = persistence();
+        fs::write(persistence.log(), b"not a record\n{\"UserDeleted\":\"000000000000000000000000\"}\n").unwrap();
+        assert!(Memory::persistent(persistence.clone()).restore().await.is_err());
+        remove(&persistence);
+    }
+}
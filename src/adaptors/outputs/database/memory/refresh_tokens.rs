@@ -0,0 +1,235 @@
+//! Refresh tokens collection implementation for the memory database
+//!
+//! This module provides the implementation for storing refresh tokens
+//! in memory with thread-safe access and index management.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, UpdateItem, DeleteItem, Map};
+use crate::domain::types::{RefreshToken, User, Key};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock as Lock;
+use super::error::Error;
+
+/// Thread-safe, indexed storage for refresh tokens
+///
+/// # Indexes
+/// - Primary index: Token digest -> Refresh token
+/// - Secondary indexes:
+///   * Family -> Token digests
+///   * User ID -> Token digests
+///
+/// # Concurrency
+/// Uses RwLock to ensure safe concurrent read and write operations
+#[derive(Debug, Default)]
+pub struct RefreshTokens {
+    /// Primary storage of refresh tokens, keyed by the digest of the token
+    pub tokens: Lock<HashMap<<RefreshToken as Item>::PK, RefreshToken>>,
+
+    /// Secondary index mapping families to the digests of their tokens
+    pub families_index: Lock<HashMap<<RefreshToken as Item>::SK, Vec<<RefreshToken as Item>::PK>>>,
+
+    /// Secondary index mapping user IDs to the digests of their tokens
+    pub users_index: Lock<HashMap<<User as Item>::PK, Vec<<RefreshToken as Item>::PK>>>,
+}
+
+impl RefreshTokens {
+    /// Returns every token of a family, used or not
+    pub fn family(&self, family: &<RefreshToken as Item>::SK) -> Result<Vec<RefreshToken>, Error> {
+        let digests = self.families_index.read()?.get(family).cloned().unwrap_or_default();
+        let tokens = self.tokens.read()?;
+        Ok(digests.iter().filter_map(|digest| tokens.get(digest).cloned()).collect())
+    }
+
+    /// Marks a token as used, returning it as it was before
+    pub fn consume(&self, digest: &str) -> Result<RefreshToken, Error> {
+        let mut tokens = self.tokens.write()?;
+        let token = tokens.get_mut(digest).ok_or(Error::RefreshTokenNotFound)?;
+        let before = token.clone();
+        token.used = true;
+        Ok(before)
+    }
+
+    /// Find the digests of the tokens that expired by `now`, used or not
+    pub fn expired(&self, now: DateTime<Utc>) -> Result<Vec<<RefreshToken as Item>::PK>, Error> {
+        Ok(self.tokens.read()?
+            .values()
+            .filter(|token| token.expires <= now)
+            .map(|token| token.id.clone())
+            .collect())
+    }
+
+    /// Returns every token issued to a user, across all families
+    pub fn of(&self, user_id: &<User as Item>::PK) -> Result<Vec<RefreshToken>, Error> {
+        let digests = self.users_index.read()?.get(user_id).cloned().unwrap_or_default();
+        let tokens = self.tokens.read()?;
+        Ok(digests.iter().filter_map(|digest| tokens.get(digest).cloned()).collect())
+    }
+}
+
+impl CreateItem<RefreshToken> for RefreshTokens {
+    type Error = Error;
+
+    async fn create_item(&self, token: RefreshToken) -> Result<RefreshToken, Self::Error> {
+        self.families_index.write()?
+            .entry(token.family)
+            .or_default()
+            .push(token.id.clone());
+        self.users_index.write()?
+            .entry(token.user_id)
+            .or_default()
+            .push(token.id.clone());
+        self.tokens.write()?.insert(token.id.clone(), token.clone());
+        Ok(token)
+    }
+}
+
+impl GetItem<RefreshToken> for RefreshTokens {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>) -> Result<RefreshToken, Self::Error> {
+        let option = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => self.tokens.read()?.get(pk).cloned(),
+            // A family holds many tokens
+            Key::Sk(_) => None
+        };
+
+        option.ok_or(Error::RefreshTokenNotFound)
+    }
+}
+
+impl UpdateItem<RefreshToken> for RefreshTokens {
+    type Error = Error;
+    type Update = Map;
+
+    async fn update_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>, token: RefreshToken) -> Result<RefreshToken, Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::RefreshTokenNotFound)
+        };
+
+        let mut tokens = self.tokens.write()?;
+        match tokens.get_mut(pk) {
+            // Neither the digest nor the family of a token can change
+            Some(stored) if stored.id == token.id && stored.family == token.family => *stored = token.clone(),
+            Some(_) => return Err(Error::UnsupportedOperation),
+            None => return Err(Error::RefreshTokenNotFound)
+        }
+        Ok(token)
+    }
+
+    /// Partially update a refresh token's fields
+    ///
+    /// # Arguments
+    /// * `key`: The key to identify the refresh token to update
+    /// * `map`: A map of fields to update
+    ///
+    /// # Returns
+    /// The updated refresh token or an error if the update fails
+    ///
+    /// # Behavior
+    /// - Only allows marking the token as used
+    async fn patch_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>, map: Map) -> Result<RefreshToken, Self::Error> {
+        let mut token = self.get_item(key.clone()).await?;
+
+        if let Some(value) = map.get("used") {
+            token.used = value.clone().try_into()?;
+        }
+
+        self.update_item(key, token).await
+    }
+
+    /// Delete specific fields from a refresh token
+    ///
+    /// # Behavior
+    /// - Refresh tokens do not support deleting individual fields
+    async fn delete_fields(&self, _key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>, _fields: HashSet<String>) -> Result<RefreshToken, Self::Error> {
+        Err(Error::UnsupportedOperation)
+    }
+}
+
+impl DeleteItem<RefreshToken> for RefreshTokens {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>) -> Result<(), Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::RefreshTokenNotFound)
+        };
+
+        let token = self.tokens.write()?
+            .remove(pk)
+            .ok_or(Error::RefreshTokenNotFound)?;
+
+        let mut families = self.families_index.write()?;
+        if let Some(digests) = families.get_mut(&token.family) {
+            digests.retain(|digest| digest != pk);
+            if digests.is_empty() {
+                families.remove(&token.family);
+            }
+        }
+
+        let mut users = self.users_index.write()?;
+        if let Some(digests) = users.get_mut(&token.user_id) {
+            digests.retain(|digest| digest != pk);
+            if digests.is_empty() {
+                users.remove(&token.user_id);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::{Id, Value};
+
+    #[tokio::test]
+    async fn test_get_refresh_token() {
+        let tokens = RefreshTokens::default();
+        let (token, _) = RefreshToken::new(Id::default(), None, vec![], 60);
+        let _ = tokens.create_item(token.clone()).await;
+
+        let result = tokens.get_item(Key::Pk(&token.id)).await;
+        assert_eq!(result.unwrap(), token);
+    }
+
+    #[tokio::test]
+    async fn test_patch_refresh_token_used() {
+        let tokens = RefreshTokens::default();
+        let (token, _) = RefreshToken::new(Id::default(), None, vec![], 60);
+        let _ = tokens.create_item(token.clone()).await;
+
+        let map = HashMap::from([("used".to_string(), Value::Bool(true))]);
+        let result = tokens.patch_item(Key::Pk(&token.id), map).await;
+        assert!(result.unwrap().used);
+    }
+
+    #[tokio::test]
+    async fn test_consume() {
+        let tokens = RefreshTokens::default();
+        let (token, _) = RefreshToken::new(Id::default(), None, vec![], 60);
+        let _ = tokens.create_item(token.clone()).await;
+
+        assert!(!tokens.consume(&token.id).unwrap().used);
+        assert!(tokens.consume(&token.id).unwrap().used);
+        assert!(matches!(tokens.consume("unknown"), Err(Error::RefreshTokenNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_family() {
+        let tokens = RefreshTokens::default();
+        let (first, _) = RefreshToken::new(Id::default(), None, vec![], 60);
+        let (second, _) = first.rotate(60);
+        let (other, _) = RefreshToken::new(Id::default(), None, vec![], 60);
+        for token in [first.clone(), second.clone(), other] {
+            let _ = tokens.create_item(token).await;
+        }
+
+        assert_eq!(tokens.family(&first.family).unwrap().len(), 2);
+        assert_eq!(tokens.of(&first.user_id).unwrap().len(), 2);
+
+        let _ = tokens.delete_item(Key::Pk(&first.id)).await;
+        assert_eq!(tokens.family(&first.family).unwrap(), vec![second]);
+    }
+}
@@ -0,0 +1,142 @@
+//! Revocations collection implementation for the memory database
+//!
+//! This module provides the implementation for storing revoked tokens
+//! in memory with thread-safe access and index management.
+//! Entries are pruned once the tokens they cover have expired.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, DeleteItem};
+use crate::domain::types::{Revocation, Key};
+use std::collections::HashMap;
+use std::sync::RwLock as Lock;
+use super::error::Error;
+
+/// Thread-safe, indexed storage for revocations
+///
+/// # Indexes
+/// - Primary index: Token ID (or subject) -> Revocation
+/// - Secondary indexes:
+///   * Subject -> Revocation IDs
+///
+/// # Concurrency
+/// Uses RwLock to ensure safe concurrent read and write operations
+#[derive(Debug, Default)]
+pub struct Revocations {
+    /// Primary storage of revocations
+    pub revocations: Lock<HashMap<<Revocation as Item>::PK, Revocation>>,
+
+    /// Secondary index mapping subjects to their revocations
+    pub subjects_index: Lock<HashMap<<Revocation as Item>::SK, Vec<<Revocation as Item>::PK>>>,
+}
+
+impl Revocations {
+    /// Returns the live revocations of a subject
+    pub fn of(&self, subject: &<Revocation as Item>::SK) -> Result<Vec<Revocation>, Error> {
+        let ids = self.subjects_index.read()?.get(subject).cloned().unwrap_or_default();
+        let revocations = self.revocations.read()?;
+        Ok(ids.iter()
+            .filter_map(|id| revocations.get(id))
+            .filter(|revocation| !revocation.expired())
+            .cloned()
+            .collect())
+    }
+
+    /// Drops every revocation whose tokens have expired
+    pub fn prune(&self) -> Result<(), Error> {
+        let mut revocations = self.revocations.write()?;
+        revocations.retain(|_, revocation| !revocation.expired());
+
+        let mut subjects = self.subjects_index.write()?;
+        subjects.retain(|_, ids| {
+            ids.retain(|id| revocations.contains_key(id));
+            !ids.is_empty()
+        });
+        Ok(())
+    }
+}
+
+impl CreateItem<Revocation> for Revocations {
+    type Error = Error;
+
+    /// Stores a revocation, replacing an older one with the same id
+    async fn create_item(&self, revocation: Revocation) -> Result<Revocation, Self::Error> {
+        self.prune()?;
+        let previous = self.revocations.write()?.insert(revocation.id, revocation.clone());
+        if previous.is_none() {
+            self.subjects_index.write()?
+                .entry(revocation.subject)
+                .or_default()
+                .push(revocation.id);
+        }
+        Ok(revocation)
+    }
+}
+
+impl GetItem<Revocation> for Revocations {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<Revocation as Item>::PK, &<Revocation as Item>::SK>) -> Result<Revocation, Self::Error> {
+        let option = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => self.revocations.read()?.get(pk).cloned(),
+            // A subject can have many revocations
+            Key::Sk(_) => None
+        };
+
+        option.filter(|revocation| !revocation.expired()).ok_or(Error::RevocationNotFound)
+    }
+}
+
+impl DeleteItem<Revocation> for Revocations {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<Revocation as Item>::PK, &<Revocation as Item>::SK>) -> Result<(), Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::RevocationNotFound)
+        };
+
+        let revocation = self.revocations.write()?
+            .remove(pk)
+            .ok_or(Error::RevocationNotFound)?;
+
+        if let Some(ids) = self.subjects_index.write()?.get_mut(&revocation.subject) {
+            ids.retain(|id| id != pk);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::{Id, Token};
+    use chrono::{Duration, Utc};
+
+    #[tokio::test]
+    async fn test_revocations_of_subject() {
+        let revocations = Revocations::default();
+        let subject = Id::default();
+        let token = Token::new(String::new(), subject, Default::default(), 60);
+        let _ = revocations.create_item(Revocation::token(&token)).await;
+        let _ = revocations.create_item(Revocation::subject(subject, 60)).await;
+        let _ = revocations.create_item(Revocation::subject(Id::default(), 60)).await;
+
+        assert_eq!(revocations.of(&subject).unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_expired_revocations_are_pruned() {
+        let revocations = Revocations::default();
+        let mut expired = Revocation::subject(Id::default(), 60);
+        expired.expires = Utc::now() - Duration::seconds(1);
+        let _ = revocations.create_item(expired.clone()).await;
+
+        let result = revocations.get_item(Key::Pk(&expired.id)).await;
+        assert!(matches!(result, Err(Error::RevocationNotFound)));
+        assert!(revocations.of(&expired.subject).unwrap().is_empty());
+
+        // Storing any other revocation clears it out
+        let _ = revocations.create_item(Revocation::subject(Id::default(), 60)).await;
+        assert!(!revocations.revocations.read().unwrap().contains_key(&expired.id));
+        assert!(!revocations.subjects_index.read().unwrap().contains_key(&expired.subject));
+    }
+}
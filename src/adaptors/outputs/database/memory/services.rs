@@ -41,6 +41,11 @@ pub struct Services {
 }
 
 impl Services {
+    /// The longest `token_expiry` set on any service
+    pub fn longest_token_expiry(&self) -> Result<Option<Duration>, Error> {
+        Ok(self.services.read()?.values().filter_map(|service| service.token_expiry).max())
+    }
+
     /// Checks if a service with the given name already exists for this owner
     ///
     /// # Arguments
@@ -77,6 +82,46 @@ impl Services {
     }
 }
 
+/// Applies a partial update to a service
+///
+/// A rename only applies when `old_name`, if given, is the current name.
+pub(crate) fn patch(service: &mut Service, map: Map) -> Result<(), Error> {
+    // Update basic fields
+    if let Some(value) = map.get("new_name") {
+        let new_name: String = value.clone().try_into()?;
+
+        // Validate old_name matches the current service name
+        if let Some(old_name_value) = map.get("old_name") {
+            let old_name: String = old_name_value.clone().try_into()?;
+            if old_name != service.name {
+                return Err(Error::ServiceNotFound);
+            }
+        }
+
+        service.name = new_name;
+    }
+
+    // Update other fields
+    if let Some(value) = map.get("client_secret") {
+        service.client_secret = value.clone().try_into()?;
+    }
+    if let Some(value) = map.get("redirect_uris") {
+        service.redirect_uris = value.clone().try_into()?;
+    }
+    if let Some(value) = map.get("scopes") {
+        service.scopes = value.clone().try_into()?;
+    }
+    if let Some(value) = map.get("grant_types") {
+        service.grant_types = value.clone().try_into()?;
+    }
+    if let Some(value) = map.get("token_expiry") {
+        let (seconds,): (i64,) = value.clone().try_into()?;
+        service.token_expiry = Some(Duration::seconds(seconds));
+    }
+    Ok(())
+}
+
+
 impl CreateItem<Service> for Services {
     type Error = Error;
 
@@ -158,39 +203,7 @@ impl UpdateItem<Service> for Services {
     ) -> Result<Service, Self::Error> {
         // First, retrieve the existing service
         let mut service = self.get_item(key.clone()).await?;
-        
-        // Update basic fields
-        if let Some(value) = map.get("new_name") {
-            let new_name: String = value.clone().try_into()?;
-
-            // Validate old_name matches the current service name
-            if let Some(old_name_value) = map.get("old_name") {
-                let old_name: String = old_name_value.clone().try_into()?;
-                if old_name != service.name {
-                    return Err(Error::ServiceNotFound);
-                }
-            }
-
-            service.name = new_name;
-        }
-
-        // Update other fields
-        if let Some(value) = map.get("client_secret") {
-            service.client_secret = value.clone().try_into()?;
-        }
-        if let Some(value) = map.get("redirect_uris") {
-            service.redirect_uris = value.clone().try_into()?;
-        }
-        if let Some(value) = map.get("scopes") {
-            service.scopes = value.clone().try_into()?;
-        }
-        if let Some(value) = map.get("grant_types") {
-            service.grant_types = value.clone().try_into()?;
-        }
-        if let Some(value) = map.get("token_expiry") {
-            let (seconds,): (i64,) = value.clone().try_into()?;
-            service.token_expiry = Some(Duration::seconds(seconds));
-        }
+        patch(&mut service, map)?;
 
         // Use update_item to handle indexes and storage
         self.update_item(key, service).await
@@ -274,6 +287,7 @@ mod tests {
             }],
             grant_types: vec![GrantType::AuthorizationCode],
             token_expiry: Some(Duration::hours(1)),
+            client_type: Default::default(),
         }
     }
 
@@ -286,6 +300,17 @@ mod tests {
         assert_eq!(result.unwrap(), service, "Created service should match input");
     }
 
+    #[tokio::test]
+    async fn test_longest_token_expiry() {
+        let services = Services::default();
+        assert_eq!(services.longest_token_expiry().unwrap(), None);
+
+        let _ = services.create_item(Service {token_expiry: None, ..create_test_service()}).await;
+        let _ = services.create_item(create_test_service()).await;
+        let _ = services.create_item(Service {token_expiry: Some(Duration::minutes(5)), ..create_test_service()}).await;
+        assert_eq!(services.longest_token_expiry().unwrap(), Some(Duration::hours(1)));
+    }
+
     #[tokio::test]
     async fn test_create_duplicate_service_name_same_owner() {
         let services = Services::default();
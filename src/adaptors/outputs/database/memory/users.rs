@@ -17,6 +17,9 @@ use super::error::Error;
 ///   * Email Address -> User ID
 ///   * Phone Number -> User ID
 /// 
+/// The secondary indexes are keyed by the raw address/number so that a contact
+/// can still be found after it moves from the `New` to the `Verified` state.
+/// 
 /// # Concurrency
 /// Uses RwLock to ensure safe concurrent read and write operations
 #[derive(Debug, Default)]
@@ -26,11 +29,11 @@ pub struct Users {
     
     /// Secondary index mapping email addresses to user IDs
     /// Enables fast lookups of users by their email
-    pub emails_index: Lock<HashMap<EmailAddress, <User as Item>::PK>>,
+    pub emails_index: Lock<HashMap<String, <User as Item>::PK>>,
     
     /// Secondary index mapping phone numbers to user IDs
     /// Enables fast lookups of users by their phone number
-    pub phones_index: Lock<HashMap<Phone, <User as Item>::PK>>,
+    pub phones_index: Lock<HashMap<String, <User as Item>::PK>>,
 }
 
 
@@ -64,26 +67,26 @@ impl Users {
         // Update phone number index
         if let Some(phone) = new_phone {
             if let Some(old_phone) = &old_phone {
-                self.phones_index.write()?.remove(old_phone);
+                self.phones_index.write()?.remove(&old_phone[..]);
             }
-            self.phones_index.write()?.insert(phone, pk);
+            self.phones_index.write()?.insert(phone.to_string(), pk);
         }
 
         // Update email address index
         if let Some(email) = new_email {
             if let Some(old_email) = &old_email {
-                self.emails_index.write()?.remove(old_email);
+                self.emails_index.write()?.remove(&old_email[..]);
             }
-            self.emails_index.write()?.insert(email, pk);
+            self.emails_index.write()?.insert(email.to_string(), pk);
         }
         Ok(())
     }
 
     pub fn pk(&self, sk: &<User as Item>::SK) -> Result<Option<<User as Item>::PK>, Error> {
         match sk {
-            Contact::Phone(phone) => Ok(self.phones_index.read().map(|index| index.get(phone).cloned())?),
-            Contact::Email(email) => Ok(self.emails_index.read().map(|index| index.get(email).cloned())?),
-            Contact::Both(phone, _) => Ok(self.phones_index.read().map(|index| index.get(phone).cloned())?)
+            Contact::Phone(phone) => Ok(self.phones_index.read().map(|index| index.get(&phone[..]).cloned())?),
+            Contact::Email(email) => Ok(self.emails_index.read().map(|index| index.get(&email[..]).cloned())?),
+            Contact::Both(phone, _) => Ok(self.phones_index.read().map(|index| index.get(&phone[..]).cloned())?)
         }
     }
 
@@ -91,14 +94,14 @@ impl Users {
         match sk {
             Contact::Phone(phone) => {
                 let phones_index = self.phones_index.read()?;
-                if phones_index.contains_key(phone) {
+                if phones_index.contains_key(&phone[..]) {
                     return Err(Error::UserWithPhoneExists)
                 }
                 Ok(())
             },
             Contact::Email(email) => {
                 let emails_index = self.emails_index.read()?;
-                if emails_index.contains_key(email) {
+                if emails_index.contains_key(&email[..]) {
                     return Err(Error::UserWithEmailExists)
                 }
                 Ok(())
@@ -107,10 +110,10 @@ impl Users {
                 let phones_index = self.phones_index.read()?;
                 let emails_index = self.emails_index.read()?;
                 
-                if phones_index.contains_key(phone) {
+                if phones_index.contains_key(&phone[..]) {
                     return Err(Error::UserWithPhoneExists)
                 }
-                if emails_index.contains_key(email) {
+                if emails_index.contains_key(&email[..]) {
                     return Err(Error::UserWithEmailExists)
                 }
                 Ok(())
@@ -120,6 +123,40 @@ impl Users {
 }
 
 
+/// Applies a partial update to a user
+/// 
+/// # Supported Partial Updates
+/// - Username
+/// - First name
+/// - Last name
+/// - Password
+/// - Contact information
+/// 
+/// Shared with the other databases so every one of them accepts the same fields.
+pub(crate) fn patch(user: &mut User, map: Map) -> Result<(), Error> {
+    // Update basic fields
+    if let Some(value) = map.get("username").or_else(|| map.get("user_name")) {
+        user.username = value.clone().try_into()?;
+    }
+    if let Some(value) = map.get("first_name") {
+        user.first_name = value.clone().try_into()?;
+    }
+    if let Some(value) = map.get("last_name") {
+        user.last_name = value.clone().try_into()?;
+    }
+    if let Some(value) = map.get("password") {
+        user.password = value.clone().try_into()?;
+    }
+
+    // Update contact info if provided
+    if map.contains_key("email") || map.contains_key("phone") || 
+       map.contains_key("email_verified") || map.contains_key("phone_verified") {
+        user.contact = map.try_into()?;
+    }
+    Ok(())
+}
+
+
 impl CreateItem<User> for Users {
     type Error = Error;
     
@@ -176,26 +213,7 @@ impl UpdateItem<User> for Users {
     async fn patch_item(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, map: Map) -> Result<User, Self::Error> {
         // First, retrieve the existing user
         let mut user = self.get_item(key.clone()).await?;
-        
-        // Update basic fields
-        if let Some(value) = map.get("username").or_else(|| map.get("user_name")) {
-            user.username = value.clone().try_into()?;
-        }
-        if let Some(value) = map.get("first_name") {
-            user.first_name = value.clone().try_into()?;
-        }
-        if let Some(value) = map.get("last_name") {
-            user.last_name = value.clone().try_into()?;
-        }
-        if let Some(value) = map.get("password") {
-            user.password = value.clone().try_into()?;
-        }
-
-        // Update contact info if provided
-        if map.contains_key("email") || map.contains_key("phone") || 
-           map.contains_key("email_verified") || map.contains_key("phone_verified") {
-            user.contact = map.try_into()?;
-        }
+        patch(&mut user, map)?;
 
         // Use update_item to handle indexes and storage
         self.update_item(key, user).await
@@ -269,7 +287,13 @@ impl DeleteItem<User> for Users {
             .map(|user| user.contact.clone())
             .ok_or(Error::UserNotFound)?;
 
-        self.update_indexes(pk, contact)?;
+        // Free the contact info for other users
+        if let Contact::Phone(phone) | Contact::Both(phone, _) = &contact {
+            self.phones_index.write()?.remove(&phone[..]);
+        }
+        if let Contact::Email(email) | Contact::Both(_, email) = &contact {
+            self.emails_index.write()?.remove(&email[..]);
+        }
         self.users.write()?.remove(&pk);
         Ok(())
     }
@@ -354,6 +378,23 @@ mod tests {
         assert_eq!(result.unwrap(), user);
     }
 
+    #[tokio::test]
+    async fn test_get_user_after_verifying_email() {
+        let users = Users::default();
+        let mut user = create_test_user();
+        let _ = users.create_item(user.clone()).await;
+
+        let phone = Phone::New("1234567890".to_string());
+        let email = EmailAddress::Verified("test@example.com".parse().unwrap());
+        user.contact = Contact::Both(phone, email);
+        let _ = users.update_item(Key::Pk(&user.id), user.clone()).await;
+
+        // The unverified form used at login must still find the user
+        let key = Contact::Email(EmailAddress::New("test@example.com".parse().unwrap()));
+        let result = users.get_item(Key::Sk(&key)).await;
+        assert_eq!(result.unwrap(), user);
+    }
+
     #[tokio::test]
     async fn test_delete_user() {
         let users = Users::default();
@@ -2,12 +2,15 @@
 //! 
 //! This module provides the implementation for storing and managing verification records
 //! in memory with thread-safe access and index management.
+//! Expired verifications are still returned, so a verifier can tell an expired code from a wrong
+//! one, until they are removed by [`Verifications::expired`] sweeps.
 
-use crate::ports::outputs::database::{Item, CreateItem, GetItem};
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, DeleteItem};
 use crate::domain::types::{Verification, Key, Either, Phone, EmailAddress, Id};
 use std::collections::HashMap;
 use std::sync::RwLock as Lock;
 use super::error::Error;
+use chrono::{DateTime, Utc};
 
 /// Thread-safe storage for verification records
 /// 
@@ -39,17 +42,47 @@ impl Verifications {
     pub fn contact(&self, id: &Id) -> Result<Option<Either<Phone, EmailAddress>>, Error> {
         Ok(self.ids_index.read()?.get(id).cloned())
     }
+
+    /// Counts a wrong code against the verification with `id` under a single write lock
+    ///
+    /// Returns the verification with its new count, it is removed once it reaches `max_attempts`.
+    pub fn wrong_code(&self, id: &Id, max_attempts: u32) -> Result<Verification, Error> {
+        let mut verifications = self.verifications.write()?;
+        let contact = self.contact(id)?.ok_or(Error::VerificationNotFound)?;
+        let verification = verifications.get_mut(&contact).ok_or(Error::VerificationNotFound)?;
+        verification.attempts += 1;
+        let verification = verification.clone();
+        if verification.attempts >= max_attempts {
+            verifications.remove(&contact);
+            self.ids_index.write()?.remove(id);
+        }
+        Ok(verification)
+    }
+
+    /// Find the contacts whose verification expired by `now`
+    pub fn expired(&self, now: DateTime<Utc>) -> Result<Vec<Either<Phone, EmailAddress>>, Error> {
+        Ok(self.verifications.read()?
+            .values()
+            .filter(|verification| verification.expires <= now)
+            .map(|verification| verification.owner_contact.clone())
+            .collect())
+    }
 }
 
 impl CreateItem<Verification> for Verifications {
     type Error = Error;
     
     async fn create_item(&self, verification: Verification) -> Result<Verification, Self::Error> {
-        // Store the verification
-        self.verifications.write()?.insert(
+        // Store the verification, replacing any earlier one for the same contact
+        let previous = self.verifications.write()?.insert(
             verification.owner_contact.clone(), 
             verification.clone()
         );
+
+        // Drop the ID of the replaced verification from the index
+        if let Some(previous) = previous {
+            self.ids_index.write()?.remove(&previous.id);
+        }
         
         // Update the ID index
         self.ids_index.write()?.insert(
@@ -76,17 +109,35 @@ impl GetItem<Verification> for Verifications {
             },
             Key::Both((pk, _)) => self.verifications.read()?.get(pk).cloned(),
         };
-        
+
         option.ok_or(Error::VerificationNotFound)
     }
 }
 
+impl DeleteItem<Verification> for Verifications {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<Verification as Item>::PK, &<Verification as Item>::SK>) -> Result<(), Self::Error> {
+        let contact = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk.clone(),
+            Key::Sk(sk) => self.contact(sk)?.ok_or(Error::VerificationNotFound)?
+        };
+
+        let verification = self.verifications.write()?
+            .remove(&contact)
+            .ok_or(Error::VerificationNotFound)?;
+
+        self.ids_index.write()?.remove(&verification.id);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::domain::types::{EmailAddress, Phone};
     use bson::oid::ObjectId;
-    use chrono::{Utc, Duration};
+    use chrono::Duration;
 
     /// Helper function to create a test verification for email
     fn create_email_verification() -> Verification {
@@ -95,8 +146,10 @@ mod tests {
         Verification {
             owner_contact: contact,
             id: Id(ObjectId::new()),
-            code: 123456,
+            code: String::from("123456"),
             expires: Utc::now() + Duration::minutes(5),
+            attempts: 0,
+            token: String::new(),
         }
     }
 
@@ -107,8 +160,10 @@ mod tests {
         Verification {
             owner_contact: contact,
             id: Id(ObjectId::new()),
-            code: 654321,
+            code: String::from("654321"),
             expires: Utc::now() + Duration::minutes(5),
+            attempts: 0,
+            token: String::new(),
         }
     }
 
@@ -143,6 +198,50 @@ mod tests {
         assert_eq!(result.unwrap(), verification);
     }
 
+    #[tokio::test]
+    async fn test_delete_verification() {
+        let verifications = Verifications::default();
+        let verification = create_email_verification();
+        let _ = verifications.create_item(verification.clone()).await;
+
+        let result = verifications.delete_item(Key::Pk(&verification.owner_contact)).await;
+        assert!(result.is_ok());
+
+        let result = verifications.get_item(Key::Sk(&verification.id)).await;
+        assert!(matches!(result, Err(Error::VerificationNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_expired_verification() {
+        let verifications = Verifications::default();
+        let expired = Verification {expires: Utc::now() - Duration::seconds(1), ..create_email_verification()};
+        let current = create_phone_verification();
+        let _ = verifications.create_item(expired.clone()).await;
+        let _ = verifications.create_item(current.clone()).await;
+
+        // Left for the verifier to turn down as expired
+        let result = verifications.get_item(Key::Pk(&expired.owner_contact)).await;
+        assert_eq!(result.unwrap(), expired);
+        assert_eq!(verifications.expired(Utc::now()).unwrap(), vec![expired.owner_contact]);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_code() {
+        let verifications = Verifications::default();
+        let first = create_email_verification();
+        let _ = verifications.create_item(first.clone()).await;
+        assert_eq!(verifications.wrong_code(&first.id, 3).unwrap().attempts, 1);
+
+        // A newer verification for the contact is not counted under the replaced one's id
+        let second = create_email_verification();
+        let _ = verifications.create_item(second.clone()).await;
+        assert!(matches!(verifications.wrong_code(&first.id, 3), Err(Error::VerificationNotFound)));
+        assert_eq!(verifications.wrong_code(&second.id, 2).unwrap().attempts, 1);
+        assert_eq!(verifications.wrong_code(&second.id, 2).unwrap().attempts, 2);
+        assert!(matches!(verifications.get_item(Key::Sk(&second.id)).await, Err(Error::VerificationNotFound)));
+        assert!(matches!(verifications.wrong_code(&second.id, 2), Err(Error::VerificationNotFound)));
+    }
+
     #[tokio::test]
     async fn test_get_nonexistent_verification() {
         let verifications = Verifications::default();
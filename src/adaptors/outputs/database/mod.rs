@@ -1,3 +1,52 @@
 /// Module for in-memory database implementation.
-/// Module for in-memory database implementation.
-pub mod memory;
\ No newline at end of file
+#[cfg(feature = "memory")]
+pub mod memory;
+/// Module for the SQLite and PostgreSQL database implementation.
+#[cfg(feature = "sql")]
+pub mod sql;
+/// Module for the MongoDB database implementation.
+#[cfg(feature = "mongodb")]
+pub mod mongo;
+
+#[cfg(feature = "memory")]
+use serde::{Deserialize, Serialize};
+
+
+/// The `database` section of the configuration, it picks the database the server is served from.
+///
+/// Without a `url` the memory database is used, configured by the same section. A `mongodb://` or
+/// `mongodb+srv://` url picks MongoDB and a `postgres://`, `postgresql://` or `sqlite:` url picks the
+/// SQL database, when the `mongodb` or `sql` feature is enabled.
+#[cfg(feature = "memory")]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Database {
+    /// Where MongoDB or the SQL database is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The name of the MongoDB database, `beekeeper` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The memory database, served from when there is no `url`
+    #[serde(flatten)]
+    pub memory: memory::Memory,
+}
+
+
+#[cfg(all(test, feature = "memory"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database_section() {
+        // Configurations of the memory database keep working
+        let database: Database = serde_json::from_str(r#"{"persistence": {"path": "beekeeper.snapshot"}}"#).unwrap();
+        assert!(database.url.is_none());
+        assert_eq!(database.memory.persistence().unwrap().path, "beekeeper.snapshot");
+
+        let database: Database = serde_json::from_str(r#"{"url": "mongodb://localhost:27017", "name": "hive"}"#).unwrap();
+        assert_eq!(database.url.as_deref(), Some("mongodb://localhost:27017"));
+        assert_eq!(database.name.as_deref(), Some("hive"));
+        assert!(database.memory.persistence().is_none());
+        assert_eq!(serde_json::to_string(&Database::default()).unwrap(), "{}");
+    }
+}
@@ -0,0 +1,100 @@
+//! Login attempts collection implementation for the MongoDB database
+//!
+//! Failures are keyed by the account or client address they are held against. Counts whose last
+//! failure is older than the lockout are pruned whenever a failure is counted.
+
+use crate::ports::outputs::attempts::LoginAttempts;
+use crate::domain::types::{Error as DomainError, Failures};
+use mongodb::{bson::{doc, Document}, options::ReturnDocument};
+use super::super::memory::Error;
+use chrono::{DateTime, Utc};
+use super::{date, time, Mongo};
+
+
+fn from_document(document: &Document) -> Result<Failures, Error> {
+    let count = u32::try_from(document.get_i64("count")?).map_err(DomainError::internal)?;
+    Ok(Failures {count, last: time(document, "last")?})
+}
+
+
+impl LoginAttempts for Mongo {
+    type Error = Error;
+
+    async fn failures(&self, key: &str) -> Result<Option<Failures>, Self::Error> {
+        match self.collection("login_attempts").find_one(doc! {"_id": key}).await? {
+            Some(document) => Ok(Some(from_document(&document)?)),
+            None => Ok(None)
+        }
+    }
+
+    /// Counts the failure in one update, which starts the count over when the last failure is too old
+    async fn fail(&self, key: &str, at: DateTime<Utc>, forget_before: DateTime<Utc>) -> Result<Option<Failures>, Self::Error> {
+        let attempts = self.collection("login_attempts");
+        let stale = date(&forget_before);
+        attempts.delete_many(doc! {"_id": {"$ne": key}, "last": {"$lt": stale}}).await?;
+        let update = vec![doc! {"$set": {
+            "count": {"$add": [{"$cond": [{"$gte": ["$last", stale]}, "$count", 0i64]}, 1i64]},
+            "last": date(&at),
+        }}];
+        let before = attempts
+            .find_one_and_update(doc! {"_id": key}, update)
+            .upsert(true)
+            .return_document(ReturnDocument::Before)
+            .await?;
+        match before {
+            Some(document) => Ok(Some(from_document(&document)?).filter(|failures| failures.last >= forget_before)),
+            None => Ok(None)
+        }
+    }
+
+    async fn forgive(&self, key: &str) -> Result<(), Self::Error> {
+        let attempts = self.collection("login_attempts");
+        let forgiven = attempts.update_one(doc! {"_id": key, "count": {"$gt": 1i64}}, doc! {"$inc": {"count": -1i64}}).await?;
+        if forgiven.matched_count == 0 {
+            attempts.delete_one(doc! {"_id": key, "count": {"$lte": 1i64}}).await?;
+        }
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), Self::Error> {
+        self.collection("login_attempts").delete_one(doc! {"_id": key}).await?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::connect;
+    use chrono::{Duration, DurationRound};
+
+    #[tokio::test]
+    #[ignore = "needs a running mongod"]
+    async fn test_fail_and_forget() {
+        let db = connect().await;
+        let now = Utc::now().duration_trunc(Duration::milliseconds(1)).unwrap();
+        let forget_before = now - Duration::minutes(15);
+        assert!(db.failures("email:jane@example.com").await.unwrap().is_none());
+
+        db.fail("email:jane@example.com", now - Duration::hours(1), forget_before - Duration::hours(1)).await.unwrap();
+        db.fail("email:jane@example.com", now - Duration::hours(1), forget_before - Duration::hours(1)).await.unwrap();
+        // The earlier failures are older than the lockout, so the count starts over
+        assert!(db.fail("email:jane@example.com", now, forget_before).await.unwrap().is_none());
+        assert_eq!(db.failures("email:jane@example.com").await.unwrap(), Some(Failures {count: 1, last: now}));
+        assert_eq!(db.fail("email:jane@example.com", now, forget_before).await.unwrap(), Some(Failures {count: 1, last: now}));
+        assert_eq!(db.fail("email:jane@example.com", now, forget_before).await.unwrap().unwrap().count, 2);
+
+        db.forgive("email:jane@example.com").await.unwrap();
+        assert_eq!(db.failures("email:jane@example.com").await.unwrap().unwrap().count, 2);
+
+        // Stale counts of other keys are pruned
+        db.fail("ip:127.0.0.1", now - Duration::hours(1), forget_before).await.unwrap();
+        db.fail("email:jane@example.com", now, forget_before).await.unwrap();
+        assert!(db.failures("ip:127.0.0.1").await.unwrap().is_none());
+
+        db.clear("email:jane@example.com").await.unwrap();
+        assert!(db.failures("email:jane@example.com").await.unwrap().is_none());
+        db.database.drop().await.unwrap();
+    }
+}
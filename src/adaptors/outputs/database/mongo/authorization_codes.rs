@@ -0,0 +1,103 @@
+//! Authorization codes collection implementation for the MongoDB database
+//!
+//! Codes are keyed by the code itself, deleting one consumes it so it is only exchanged once.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, DeleteItem};
+use crate::domain::types::{AuthorizationCode, Key};
+use super::{date, from_nested, id, nested, time, Mongo};
+use super::super::memory::Error;
+use mongodb::bson::{doc, Document};
+
+
+fn to_document(code: &AuthorizationCode) -> Result<Document, Error> {
+    Ok(doc! {
+        "_id": &code.code,
+        "client_id": code.client_id.0,
+        "user_id": code.user_id.0,
+        "redirect_uri": &code.redirect_uri,
+        "code_challenge": &code.code_challenge,
+        "scope": nested(&code.scope)?,
+        "nonce": code.nonce.clone(),
+        "expires": date(&code.expires),
+    })
+}
+
+
+fn from_document(document: &Document) -> Result<AuthorizationCode, Error> {
+    Ok(AuthorizationCode {
+        code: document.get_str("_id")?.to_string(),
+        client_id: id(document, "client_id")?,
+        user_id: id(document, "user_id")?,
+        redirect_uri: document.get_str("redirect_uri")?.to_string(),
+        code_challenge: document.get_str("code_challenge")?.to_string(),
+        scope: from_nested(document, "scope")?,
+        nonce: from_nested(document, "nonce")?,
+        expires: time(document, "expires")?,
+    })
+}
+
+
+impl CreateItem<AuthorizationCode> for Mongo {
+    type Error = Error;
+
+    async fn create_item(&self, code: AuthorizationCode) -> Result<AuthorizationCode, Self::Error> {
+        self.collection("authorization_codes")
+            .replace_one(doc! {"_id": &code.code}, to_document(&code)?)
+            .upsert(true).await?;
+        Ok(code)
+    }
+}
+
+
+impl GetItem<AuthorizationCode> for Mongo {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<AuthorizationCode as Item>::PK, &<AuthorizationCode as Item>::SK>) -> Result<AuthorizationCode, Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::AuthorizationCodeNotFound)
+        };
+        let document = self.collection("authorization_codes").find_one(doc! {"_id": pk}).await?.ok_or(Error::AuthorizationCodeNotFound)?;
+        from_document(&document)
+    }
+}
+
+
+impl DeleteItem<AuthorizationCode> for Mongo {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<AuthorizationCode as Item>::PK, &<AuthorizationCode as Item>::SK>) -> Result<(), Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::AuthorizationCodeNotFound)
+        };
+        match self.collection("authorization_codes").delete_one(doc! {"_id": pk}).await?.deleted_count {
+            0 => Err(Error::AuthorizationCodeNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::connect;
+    use crate::domain::types::Id;
+    use chrono::{Duration, DurationRound};
+
+    #[tokio::test]
+    #[ignore = "needs a running mongod"]
+    async fn test_authorization_codes() {
+        let db = connect().await;
+        let mut code = AuthorizationCode::new(Id::default(), Id::default(), "https://example.com/callback".to_string(), "challenge".to_string(), vec!["openid".to_string()]);
+        code.expires = code.expires.duration_trunc(Duration::milliseconds(1)).unwrap();
+        let code = db.create_item(code).await.unwrap();
+        assert_eq!(GetItem::<AuthorizationCode>::get_item(&db, Key::Pk(&code.code)).await.unwrap(), code);
+
+        // A code is consumed once
+        DeleteItem::<AuthorizationCode>::delete_item(&db, Key::Pk(&code.code)).await.unwrap();
+        assert!(matches!(DeleteItem::<AuthorizationCode>::delete_item(&db, Key::Pk(&code.code)).await, Err(Error::AuthorizationCodeNotFound)));
+        db.database.drop().await.unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+//! Invitations collection implementation for the MongoDB database
+//!
+//! The raw number or address an invitation was sent to is kept in `contact`, an organisation
+//! invites a contact once, whether the contact is verified or not.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, GetItems, DeleteItem};
+use crate::domain::types::{Invitation, Organisation, Contact, Key};
+use super::{all, date, from_nested, id, nested, time, unique, Mongo};
+use super::super::memory::Error;
+use mongodb::bson::{doc, Document};
+
+
+/// The contact an invitation is unique by, the email address when there are both
+fn contact(contact: &Contact) -> &str {
+    match contact {
+        Contact::Phone(phone) => phone,
+        Contact::Email(email) | Contact::Both(_, email) => email
+    }
+}
+
+
+fn to_document(invitation: &Invitation) -> Result<Document, Error> {
+    Ok(doc! {
+        "_id": invitation.id.0,
+        "org_id": invitation.org_id.0,
+        "contact": contact(&invitation.contact),
+        "owner_contact": nested(&invitation.contact)?,
+        "title": &invitation.title,
+        "roles": nested(&invitation.roles)?,
+        "invited_by": invitation.invited_by.0,
+        "expires": date(&invitation.expires),
+    })
+}
+
+
+fn from_document(document: &Document) -> Result<Invitation, Error> {
+    Ok(Invitation {
+        id: id(document, "_id")?,
+        org_id: id(document, "org_id")?,
+        contact: from_nested(document, "owner_contact")?,
+        title: document.get_str("title")?.to_string(),
+        roles: from_nested(document, "roles")?,
+        invited_by: id(document, "invited_by")?,
+        expires: time(document, "expires")?,
+    })
+}
+
+
+fn filter(key: Key<&<Invitation as Item>::PK, &<Invitation as Item>::SK>) -> Document {
+    match key {
+        Key::Pk(pk) | Key::Both((pk, _)) => doc! {"_id": pk.0},
+        Key::Sk((org_id, sk)) => doc! {"org_id": org_id.0, "contact": contact(sk)}
+    }
+}
+
+
+impl CreateItem<Invitation> for Mongo {
+    type Error = Error;
+
+    async fn create_item(&self, invitation: Invitation) -> Result<Invitation, Self::Error> {
+        self.collection("invitations").insert_one(to_document(&invitation)?).await
+            .map_err(|err| unique(err, |_| Error::InvitationAlreadyExists))?;
+        Ok(invitation)
+    }
+}
+
+
+impl GetItem<Invitation> for Mongo {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<Invitation as Item>::PK, &<Invitation as Item>::SK>) -> Result<Invitation, Self::Error> {
+        let document = self.collection("invitations").find_one(filter(key)).await?.ok_or(Error::InvitationNotFound)?;
+        from_document(&document)
+    }
+}
+
+
+impl DeleteItem<Invitation> for Mongo {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<Invitation as Item>::PK, &<Invitation as Item>::SK>) -> Result<(), Self::Error> {
+        match self.collection("invitations").delete_one(filter(key)).await?.deleted_count {
+            0 => Err(Error::InvitationNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+impl GetItems<Organisation, Invitation> for Mongo {
+    type Error = Error;
+    type Filter = ();
+
+    /// Retrieves the pending invitations of an organisation
+    async fn get_items(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, _: Self::Filter) -> Result<Vec<Invitation>, Self::Error> {
+        let org_id = self.organisation_id(key).await?;
+        all(self.collection("invitations").find(doc! {"org_id": org_id.0}).await?).await?
+            .iter()
+            .map(from_document)
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::connect;
+    use crate::domain::types::{EmailAddress, Id};
+    use chrono::{Duration, DurationRound};
+
+    fn invitation(org_id: Id) -> Invitation {
+        let mut invitation = Invitation {
+            id: Id::default(),
+            org_id,
+            contact: Contact::Email(EmailAddress::New("invitee@example.com".parse().unwrap())),
+            title: "Engineer".to_string(),
+            roles: vec![],
+            invited_by: Id::default(),
+            expires: Default::default(),
+        };
+        invitation.renew();
+        invitation.expires = invitation.expires.duration_trunc(Duration::milliseconds(1)).unwrap();
+        invitation
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a running mongod"]
+    async fn test_invitations() {
+        let db = connect().await;
+        let organisation = Organisation {id: Id::default(), name: "Beekeeper".to_string(), domain: None, home: None, contacts: Vec::new()};
+        let organisation = db.create_item(organisation).await.unwrap();
+        let created = db.create_item(invitation(organisation.id)).await.unwrap();
+        assert!(matches!(db.create_item(invitation(organisation.id)).await, Err(Error::InvitationAlreadyExists)));
+
+        let verified = Contact::Email(EmailAddress::Verified("invitee@example.com".parse().unwrap()));
+        assert_eq!(GetItem::<Invitation>::get_item(&db, Key::Sk(&(organisation.id, verified))).await.unwrap(), created);
+        let invitations = GetItems::<Organisation, Invitation>::get_items(&db, Key::Sk(&organisation.name), ()).await.unwrap();
+        assert_eq!(invitations, vec![created.clone()]);
+
+        // Invitations go with their organisation
+        DeleteItem::<Organisation>::delete_item(&db, Key::Pk(&organisation.id)).await.unwrap();
+        assert!(matches!(DeleteItem::<Invitation>::delete_item(&db, Key::Pk(&created.id)).await, Err(Error::InvitationNotFound)));
+        db.database.drop().await.unwrap();
+    }
+}
@@ -0,0 +1,248 @@
+//! Members collection implementation for the MongoDB database
+//!
+//! Members are keyed by the organisation and user IDs in a unique index, and are looked up
+//! with their organisations or users for the `GetItems` operations.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, GetItems, UpdateItem, DeleteItem, Map};
+use crate::domain::types::{Member, Organisation, User, Key, Id};
+use super::super::memory::{members::patch, Error};
+use super::{all, from_nested, id, ids, nested, unique, Mongo};
+use mongodb::bson::{doc, Document};
+use std::collections::{HashMap, HashSet};
+
+
+fn to_document(member: &Member) -> Result<Document, Error> {
+    Ok(doc! {
+        "org_id": member.org_id.0,
+        "user_id": member.user_id.0,
+        "title": &member.title,
+        "owner": member.owner,
+        "roles": nested(&member.roles)?,
+    })
+}
+
+
+fn from_document(document: &Document) -> Result<Member, Error> {
+    Ok(Member {
+        org_id: id(document, "org_id")?,
+        user_id: id(document, "user_id")?,
+        title: document.get_str("title")?.to_string(),
+        owner: document.get_bool("owner")?,
+        roles: from_nested(document, "roles")?,
+    })
+}
+
+
+/// The filter for one membership, which is only found by the organisation and user IDs
+fn filter<SK>(key: Key<&(Id, Id), SK>) -> Result<Document, Error> {
+    match key {
+        Key::Pk((org_id, user_id)) | Key::Both(((org_id, user_id), _)) => Ok(doc! {"org_id": org_id.0, "user_id": user_id.0}),
+        Key::Sk(_) => Err(Error::MemberNotFound)
+    }
+}
+
+
+impl CreateItem<Member> for Mongo {
+    type Error = Error;
+
+    async fn create_item(&self, member: Member) -> Result<Member, Self::Error> {
+        self.collection("members").insert_one(to_document(&member)?).await
+            .map_err(|err| unique(err, |_| Error::MemberAlreadyExists))?;
+        Ok(member)
+    }
+}
+
+
+impl GetItem<(Organisation, User), Member> for Mongo {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<(Organisation, User) as Item>::PK, &<(Organisation, User) as Item>::SK>) -> Result<Member, Self::Error> {
+        let document = self.collection("members").find_one(filter(key)?).await?.ok_or(Error::MemberNotFound)?;
+        from_document(&document)
+    }
+}
+
+
+impl UpdateItem<(Organisation, User), Member> for Mongo {
+    type Error = Error;
+    type Update = Map;
+
+    async fn update_item(&self, key: Key<&<(Organisation, User) as Item>::PK, &<(Organisation, User) as Item>::SK>, member: Member) -> Result<Member, Self::Error> {
+        let update = doc! {"$set": {"title": &member.title, "owner": member.owner, "roles": nested(&member.roles)?}};
+        match self.collection("members").update_one(filter(key)?, update).await?.matched_count {
+            0 => Err(Error::MemberNotFound),
+            _ => Ok(member)
+        }
+    }
+
+    async fn patch_item(&self, key: Key<&<(Organisation, User) as Item>::PK, &<(Organisation, User) as Item>::SK>, map: Map) -> Result<Member, Self::Error> {
+        let mut member = GetItem::<(Organisation, User), Member>::get_item(self, key.clone()).await?;
+        patch(&mut member, map)?;
+        self.update_item(key, member).await
+    }
+
+    /// Members do not support deleting individual fields
+    async fn delete_fields(&self, _: Key<&<(Organisation, User) as Item>::PK, &<(Organisation, User) as Item>::SK>, _: HashSet<String>) -> Result<Member, Self::Error> {
+        Err(Error::UnsupportedOperation)
+    }
+}
+
+
+impl DeleteItem<Member> for Mongo {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<Member as Item>::PK, &<Member as Item>::SK>) -> Result<(), Self::Error> {
+        match self.collection("members").delete_one(filter(key)?).await?.deleted_count {
+            0 => Err(Error::MemberNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+impl Mongo {
+    /// The memberships matching `filter`, only those of owners when `owners` is set
+    async fn find_members(&self, mut filter: Document, owners: bool) -> Result<Vec<Member>, Error> {
+        if owners {
+            filter.insert("owner", true);
+        }
+        all(self.collection("members").find(filter).await?).await?
+            .iter()
+            .map(from_document)
+            .collect()
+    }
+
+    /// The organisations a user is a member of, with the memberships, only those the user owns when `owners` is set
+    async fn memberships(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, owners: bool) -> Result<Vec<(Member, Organisation)>, Error> {
+        let user_id = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => *pk,
+            Key::Sk(sk) => self.user_id(sk).await?.ok_or(Error::UserNotFound)?
+        };
+        let mut members: HashMap<Id, Member> = self.find_members(doc! {"user_id": user_id.0}, owners).await?
+            .into_iter()
+            .map(|member| (member.org_id, member))
+            .collect();
+        let org_ids: Vec<Id> = members.keys().copied().collect();
+        let documents = all(self.collection("organisations").find(doc! {"_id": {"$in": ids(&org_ids)}}).sort(doc! {"name": 1}).await?).await?;
+        documents.iter()
+            .map(super::organisations::from_document)
+            .filter_map(|organisation| match organisation {
+                Ok(organisation) => members.remove(&organisation.id).map(|member| Ok((member, organisation))),
+                Err(err) => Some(Err(err))
+            })
+            .collect()
+    }
+
+    /// The members of an organisation with their users, only the owners when `owners` is set
+    async fn members(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, owners: bool) -> Result<Vec<(Member, User)>, Error> {
+        let org_id = self.organisation_id(key).await?;
+        let mut members: HashMap<Id, Member> = self.find_members(doc! {"org_id": org_id.0}, owners).await?
+            .into_iter()
+            .map(|member| (member.user_id, member))
+            .collect();
+        let user_ids: Vec<Id> = members.keys().copied().collect();
+        let documents = all(self.collection("users").find(doc! {"_id": {"$in": ids(&user_ids)}}).sort(doc! {"username": 1}).await?).await?;
+        documents.iter()
+            .map(super::users::from_document)
+            .filter_map(|user| match user {
+                Ok(user) => members.remove(&user.id).map(|member| Ok((member, user))),
+                Err(err) => Some(Err(err))
+            })
+            .collect()
+    }
+}
+
+
+/// User-related GetItems Operations
+impl GetItems<User, Organisation> for Mongo {
+    type Error = Error;
+    type Filter = bool;
+
+    async fn get_items(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, filter: Self::Filter) -> Result<Vec<Organisation>, Self::Error> {
+        Ok(self.memberships(key, filter).await?.into_iter().map(|(_, organisation)| organisation).collect())
+    }
+}
+
+
+impl GetItems<User, (Member, Organisation)> for Mongo {
+    type Error = Error;
+    type Filter = bool;
+
+    async fn get_items(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, filter: Self::Filter) -> Result<Vec<(Member, Organisation)>, Self::Error> {
+        self.memberships(key, filter).await
+    }
+}
+
+
+/// Organisation-related GetItems Operations
+impl GetItems<Organisation, User> for Mongo {
+    type Error = Error;
+    type Filter = bool;
+
+    async fn get_items(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, filter: Self::Filter) -> Result<Vec<User>, Self::Error> {
+        Ok(self.members(key, filter).await?.into_iter().map(|(_, user)| user).collect())
+    }
+}
+
+
+impl GetItems<Organisation, (Member, User)> for Mongo {
+    type Error = Error;
+    type Filter = bool;
+
+    async fn get_items(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, filter: Self::Filter) -> Result<Vec<(Member, User)>, Self::Error> {
+        self.members(key, filter).await
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::connect;
+    use crate::domain::types::{Contact, EmailAddress, Value};
+
+    fn user(email: &str) -> User {
+        User {
+            id: Id::default(),
+            username: email.to_string(),
+            first_name: "Test".to_string(),
+            last_name: "User".to_string(),
+            password: String::new(),
+            contact: Contact::Email(EmailAddress::New(email.parse().unwrap()))
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a running mongod"]
+    async fn test_members() {
+        let db = connect().await;
+        let organisation = Organisation {id: Id::default(), name: "Beekeeper".to_string(), domain: None, home: None, contacts: Vec::new()};
+        let organisation = db.create_item(organisation).await.unwrap();
+        let owner = db.create_item(user("owner@example.com")).await.unwrap();
+        let member = db.create_item(user("member@example.com")).await.unwrap();
+
+        let membership = Member {org_id: organisation.id, user_id: owner.id, title: "Founder".to_string(), owner: true, roles: vec![Id::default()]};
+        db.create_item(membership.clone()).await.unwrap();
+        assert!(matches!(db.create_item(membership.clone()).await, Err(Error::MemberAlreadyExists)));
+        db.create_item(Member {org_id: organisation.id, user_id: member.id, ..Default::default()}).await.unwrap();
+        assert_eq!(GetItem::<(Organisation, User), Member>::get_item(&db, Key::Pk(&(organisation.id, owner.id))).await.unwrap(), membership);
+
+        let users = GetItems::<Organisation, User>::get_items(&db, Key::Pk(&organisation.id), false).await.unwrap();
+        assert_eq!(users.len(), 2);
+        let owners = GetItems::<Organisation, (Member, User)>::get_items(&db, Key::Sk(&organisation.name), true).await.unwrap();
+        assert_eq!(owners, vec![(membership, owner.clone())]);
+        let organisations = GetItems::<User, Organisation>::get_items(&db, Key::Pk(&member.id), true).await.unwrap();
+        assert!(organisations.is_empty());
+
+        let mut map = Map::new();
+        map.insert("owner".to_string(), Value::Bool(true));
+        UpdateItem::<(Organisation, User), Member>::patch_item(&db, Key::Pk(&(organisation.id, member.id)), map).await.unwrap();
+        let organisations = GetItems::<User, (Member, Organisation)>::get_items(&db, Key::Pk(&member.id), true).await.unwrap();
+        assert_eq!(organisations[0].1, organisation);
+
+        DeleteItem::<Organisation>::delete_item(&db, Key::Pk(&organisation.id)).await.unwrap();
+        let organisations = GetItems::<User, Organisation>::get_items(&db, Key::Pk(&owner.id), false).await.unwrap();
+        assert!(organisations.is_empty());
+        db.database.drop().await.unwrap();
+    }
+}
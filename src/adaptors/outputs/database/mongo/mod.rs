@@ -0,0 +1,191 @@
+//! MongoDB Database Implementation
+//!
+//! This module stores every item of the memory database in MongoDB, one collection per item type.
+//! An item's key is its `_id`, ids are kept as BSON ObjectIds and times as BSON dates to the millisecond.
+//!
+//! # Indexes
+//! The indexes are created on connect. Uniqueness rules are unique indexes, their violations are
+//! reported with the same errors as the memory database, such as `UserWithEmailExists`.
+//! Verifications are removed by a TTL index once they expire. Failed logins are counted in their
+//! own collection, so every server sharing the database locks out the same accounts.
+
+mod users;
+mod organisations;
+mod members;
+mod services;
+mod verifications;
+mod invitations;
+mod authorization_codes;
+mod refresh_tokens;
+mod revocations;
+mod attempts;
+
+use mongodb::bson::{self, doc, oid::ObjectId, Bson, Document};
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::IndexOptions;
+use crate::domain::types::{Error as DomainError, Id};
+use mongodb::{Client, Collection, Cursor, Database, IndexModel};
+use serde::{de::DeserializeOwned, Serialize};
+use chrono::{DateTime, Utc};
+use super::memory::Error;
+use std::time::Duration;
+
+
+/// The server's code for a write that violates a unique index.
+const DUPLICATE_KEY: i32 = 11000;
+
+
+/// A database implementation on MongoDB.
+///
+/// # Concurrency
+/// Operations run on the driver's connection pool, clones share the pool.
+#[derive(Debug, Clone)]
+pub struct Mongo {
+    database: Database,
+}
+
+
+impl Mongo {
+    /// Connects to a database and creates its indexes.
+    ///
+    /// # Arguments
+    /// * `uri`: Such as `mongodb://localhost:27017`
+    /// * `name`: The name of the database, such as `beekeeper`
+    pub async fn connect(uri: &str, name: &str) -> Result<Self, Error> {
+        let client = Client::with_uri_str(uri).await?;
+        let mongo = Self {database: client.database(name)};
+        mongo.indexes().await?;
+        Ok(mongo)
+    }
+
+    fn collection(&self, name: &str) -> Collection<Document> {
+        self.database.collection(name)
+    }
+
+    /// Creates the indexes, the ones that already exist are left as they are
+    async fn indexes(&self) -> Result<(), Error> {
+        // Sparse, so users without an email or a phone number do not collide
+        self.collection("users").create_indexes([
+            index(doc! {"phone": 1}, "users_phone_key", true, true),
+            index(doc! {"email": 1}, "users_email_key", true, true),
+        ]).await?;
+        self.collection("organisations").create_index(index(doc! {"name": 1}, "organisations_name_key", true, false)).await?;
+        self.collection("members").create_indexes([
+            index(doc! {"org_id": 1, "user_id": 1}, "members_pkey", true, false),
+            index(doc! {"user_id": 1}, "members_user_id_idx", false, false),
+        ]).await?;
+        self.collection("services").create_indexes([
+            index(doc! {"owner_id": 1, "name": 1}, "services_owner_id_name_key", true, false),
+            index(doc! {"name": 1}, "services_name_idx", false, false),
+        ]).await?;
+        self.collection("verifications").create_indexes([
+            index(doc! {"id": 1}, "verifications_id_key", true, false),
+            IndexModel::builder()
+                .keys(doc! {"expires": 1})
+                .options(IndexOptions::builder().name(String::from("verifications_expires_ttl")).expire_after(Duration::ZERO).build())
+                .build(),
+        ]).await?;
+        self.collection("invitations").create_index(index(doc! {"org_id": 1, "contact": 1}, "invitations_org_id_contact_key", true, false)).await?;
+        self.collection("refresh_tokens").create_indexes([
+            index(doc! {"family": 1}, "refresh_tokens_family_idx", false, false),
+            index(doc! {"user_id": 1}, "refresh_tokens_user_id_idx", false, false),
+        ]).await?;
+        self.collection("revocations").create_index(index(doc! {"subject": 1}, "revocations_subject_idx", false, false)).await?;
+        Ok(())
+    }
+}
+
+
+fn index(keys: Document, name: &str, unique: bool, sparse: bool) -> IndexModel {
+    let options = IndexOptions::builder()
+        .name(String::from(name))
+        .unique(unique)
+        .sparse(sparse)
+        .build();
+    IndexModel::builder().keys(keys).options(options).build()
+}
+
+
+impl From<mongodb::error::Error> for Error {
+    fn from(err: mongodb::error::Error) -> Self {
+        Self::DomainError(DomainError::internal(err))
+    }
+}
+
+
+impl From<bson::document::ValueAccessError> for Error {
+    fn from(err: bson::document::ValueAccessError) -> Self {
+        Self::DomainError(DomainError::internal(err))
+    }
+}
+
+
+/// Maps the violation of a unique index with `conflict`, which is given the message naming
+/// the violated index, and any other failure to an internal error.
+fn unique(err: mongodb::error::Error, conflict: impl FnOnce(&str) -> Error) -> Error {
+    match err.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(write)) if write.code == DUPLICATE_KEY => conflict(&write.message),
+        ErrorKind::Command(command) if command.code == DUPLICATE_KEY => conflict(&command.message),
+        _ => err.into()
+    }
+}
+
+
+/// Collects the documents of a cursor.
+async fn all(mut cursor: Cursor<Document>) -> Result<Vec<Document>, Error> {
+    let mut documents = Vec::new();
+    while cursor.advance().await? {
+        documents.push(cursor.deserialize_current()?);
+    }
+    Ok(documents)
+}
+
+
+/// Lists and other nested values are stored as their JSON representation, which the domain types
+/// are written for.
+fn nested<T: Serialize>(value: &T) -> Result<Bson, Error> {
+    let value = serde_json::to_value(value).map_err(DomainError::from)?;
+    Ok(Bson::try_from(value).map_err(DomainError::internal)?)
+}
+
+
+/// Read back from JSON text, the domain types borrow the keys of the maps they are read from.
+fn from_nested<T: DeserializeOwned>(document: &Document, key: &str) -> Result<T, Error> {
+    let value = document.get(key).cloned().unwrap_or(Bson::Null).into_relaxed_extjson();
+    Ok(serde_json::from_str(&value.to_string()).map_err(DomainError::from)?)
+}
+
+
+fn id(document: &Document, key: &str) -> Result<Id, Error> {
+    Ok(Id(document.get_object_id(key)?))
+}
+
+
+fn ids(ids: &[Id]) -> Vec<ObjectId> {
+    ids.iter().map(|id| id.0).collect()
+}
+
+
+fn date(time: &DateTime<Utc>) -> bson::DateTime {
+    bson::DateTime::from_millis(time.timestamp_millis())
+}
+
+
+fn time(document: &Document, key: &str) -> Result<DateTime<Utc>, Error> {
+    let millis = document.get_datetime(key)?.timestamp_millis();
+    DateTime::from_timestamp_millis(millis)
+        .ok_or_else(|| DomainError::Internal {message: format!("a stored {} is out of range", key), source: None}.into())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Mongo;
+
+    /// Connects to a fresh database on the server at `MONGODB_URI`, a local `mongod` by default
+    pub(super) async fn connect() -> Mongo {
+        let uri = std::env::var("MONGODB_URI").unwrap_or_else(|_| String::from("mongodb://localhost:27017"));
+        let name = format!("beekeeper_test_{}", bson::oid::ObjectId::new());
+        Mongo::connect(&uri, &name).await.unwrap()
+    }
+}
@@ -0,0 +1,157 @@
+//! Organisations collection implementation for the MongoDB database
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, UpdateItem, DeleteItem, Map};
+use crate::domain::types::{Organisation, Key};
+use super::super::memory::{organisations::patch, Error};
+use super::{from_nested, id, nested, unique, Mongo};
+use mongodb::bson::{doc, Document};
+use std::collections::HashSet;
+
+
+fn to_document(organisation: &Organisation) -> Result<Document, Error> {
+    Ok(doc! {
+        "_id": organisation.id.0,
+        "name": &organisation.name,
+        "domain": organisation.domain.clone(),
+        "home": organisation.home.clone(),
+        "contacts": nested(&organisation.contacts)?,
+    })
+}
+
+
+pub(super) fn from_document(document: &Document) -> Result<Organisation, Error> {
+    Ok(Organisation {
+        id: id(document, "_id")?,
+        name: document.get_str("name")?.to_string(),
+        domain: from_nested(document, "domain")?,
+        home: from_nested(document, "home")?,
+        contacts: from_nested(document, "contacts")?,
+    })
+}
+
+
+impl Mongo {
+    /// Finds the organisation ID for a name
+    pub(super) async fn organisation_id(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>) -> Result<<Organisation as Item>::PK, Error> {
+        let name = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => return Ok(*pk),
+            Key::Sk(name) => name
+        };
+        let document = self.collection("organisations")
+            .find_one(doc! {"name": name})
+            .projection(doc! {"_id": 1}).await?
+            .ok_or(Error::OrganisationNotFound)?;
+        id(&document, "_id")
+    }
+}
+
+
+impl CreateItem<Organisation> for Mongo {
+    type Error = Error;
+
+    async fn create_item(&self, organisation: Organisation) -> Result<Organisation, Self::Error> {
+        self.collection("organisations").insert_one(to_document(&organisation)?).await
+            .map_err(|err| unique(err, |_| Error::OrganisationWithNameExists))?;
+        Ok(organisation)
+    }
+}
+
+
+impl GetItem<Organisation> for Mongo {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>) -> Result<Organisation, Self::Error> {
+        let filter = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => doc! {"_id": pk.0},
+            Key::Sk(name) => doc! {"name": name}
+        };
+        let document = self.collection("organisations").find_one(filter).await?.ok_or(Error::OrganisationNotFound)?;
+        from_document(&document)
+    }
+}
+
+
+impl UpdateItem<Organisation> for Mongo {
+    type Error = Error;
+    type Update = Map;
+
+    /// Renaming must not take over another organisation's name
+    async fn update_item(&self, _: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, organisation: Organisation) -> Result<Organisation, Self::Error> {
+        let result = self.collection("organisations").replace_one(doc! {"_id": organisation.id.0}, to_document(&organisation)?).await
+            .map_err(|err| unique(err, |_| Error::OrganisationWithNameExists))?;
+        match result.matched_count {
+            0 => Err(Error::OrganisationNotFound),
+            _ => Ok(organisation)
+        }
+    }
+
+    async fn patch_item(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, map: Map) -> Result<Organisation, Self::Error> {
+        let mut organisation = GetItem::<Organisation>::get_item(self, key.clone()).await?;
+        patch(&mut organisation, map)?;
+        self.update_item(key, organisation).await
+    }
+
+    /// Organisations do not support deleting individual fields
+    async fn delete_fields(&self, _: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, _: HashSet<String>) -> Result<Organisation, Self::Error> {
+        Err(Error::UnsupportedOperation)
+    }
+}
+
+
+impl DeleteItem<Organisation> for Mongo {
+    type Error = Error;
+
+    /// Deletes an organisation with every membership of and invitation to it
+    async fn delete_item(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>) -> Result<(), Self::Error> {
+        let org_id = self.organisation_id(key).await?;
+        let result = self.collection("organisations").delete_one(doc! {"_id": org_id.0}).await?;
+        if result.deleted_count == 0 {
+            return Err(Error::OrganisationNotFound)
+        }
+        self.collection("members").delete_many(doc! {"org_id": org_id.0}).await?;
+        self.collection("invitations").delete_many(doc! {"org_id": org_id.0}).await?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::connect;
+    use crate::domain::types::{Contact, EmailAddress, Id, Value};
+
+    fn named(name: &str) -> Organisation {
+        Organisation {
+            id: Id::default(),
+            name: name.to_string(),
+            domain: Some("example.com".to_string()),
+            home: None,
+            contacts: vec![("support".to_string(), Contact::Email(EmailAddress::New("support@example.com".parse().unwrap())))],
+        }
+    }
+
+    #[test]
+    fn test_document() {
+        let organisation = named("Beekeeper");
+        assert_eq!(from_document(&to_document(&organisation).unwrap()).unwrap(), organisation);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a running mongod"]
+    async fn test_organisations() {
+        let db = connect().await;
+        let organisation = db.create_item(named("Beekeeper")).await.unwrap();
+        assert_eq!(GetItem::<Organisation>::get_item(&db, Key::Sk(&organisation.name)).await.unwrap(), organisation);
+        assert!(matches!(db.create_item(named("Beekeeper")).await, Err(Error::OrganisationWithNameExists)));
+
+        let other = db.create_item(named("Hive")).await.unwrap();
+        let mut map = Map::new();
+        map.insert("name".to_string(), Value::String("Beekeeper".to_string()));
+        assert!(matches!(UpdateItem::<Organisation>::patch_item(&db, Key::Pk(&other.id), map).await, Err(Error::OrganisationWithNameExists)));
+
+        DeleteItem::<Organisation>::delete_item(&db, Key::Sk(&organisation.name)).await.unwrap();
+        assert!(matches!(GetItem::<Organisation>::get_item(&db, Key::Pk(&organisation.id)).await, Err(Error::OrganisationNotFound)));
+        db.database.drop().await.unwrap();
+    }
+}
@@ -0,0 +1,206 @@
+//! Refresh tokens collection implementation for the MongoDB database
+//!
+//! Tokens are keyed by their digest and indexed by family and by user.
+
+use crate::ports::outputs::{consume::ConsumeRefreshToken, database::{Item, CreateItem, GetItem, GetItems, UpdateItem, DeleteItem, Map}};
+use crate::domain::types::{RefreshToken, User, Key, Id};
+use super::{all, date, from_nested, id, nested, time, Mongo};
+use super::super::memory::Error;
+use mongodb::bson::{doc, Document};
+use std::collections::HashSet;
+
+
+fn to_document(token: &RefreshToken) -> Result<Document, Error> {
+    Ok(doc! {
+        "_id": &token.id,
+        "family": token.family.0,
+        "user_id": token.user_id.0,
+        "client_id": token.client_id.map(|id| id.0),
+        "scope": nested(&token.scope)?,
+        "used": token.used,
+        "expires": date(&token.expires),
+    })
+}
+
+
+fn from_document(document: &Document) -> Result<RefreshToken, Error> {
+    let client_id = match document.get_object_id("client_id") {
+        Ok(client_id) => Some(Id(client_id)),
+        Err(_) => None
+    };
+    Ok(RefreshToken {
+        id: document.get_str("_id")?.to_string(),
+        family: id(document, "family")?,
+        user_id: id(document, "user_id")?,
+        client_id,
+        scope: from_nested(document, "scope")?,
+        used: document.get_bool("used")?,
+        expires: time(document, "expires")?,
+    })
+}
+
+
+impl Mongo {
+    async fn refresh_tokens(&self, filter: Document) -> Result<Vec<RefreshToken>, Error> {
+        all(self.collection("refresh_tokens").find(filter).await?).await?
+            .iter()
+            .map(from_document)
+            .collect()
+    }
+}
+
+
+impl CreateItem<RefreshToken> for Mongo {
+    type Error = Error;
+
+    async fn create_item(&self, token: RefreshToken) -> Result<RefreshToken, Self::Error> {
+        self.collection("refresh_tokens")
+            .replace_one(doc! {"_id": &token.id}, to_document(&token)?)
+            .upsert(true).await?;
+        Ok(token)
+    }
+}
+
+
+impl GetItem<RefreshToken> for Mongo {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>) -> Result<RefreshToken, Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::RefreshTokenNotFound)
+        };
+        let document = self.collection("refresh_tokens").find_one(doc! {"_id": pk}).await?.ok_or(Error::RefreshTokenNotFound)?;
+        from_document(&document)
+    }
+}
+
+
+impl GetItems<RefreshToken> for Mongo {
+    type Error = Error;
+    type Filter = ();
+
+    /// Retrieves every token of a refresh token family
+    async fn get_items(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>, _: Self::Filter) -> Result<Vec<RefreshToken>, Self::Error> {
+        let family = match key {
+            Key::Sk(sk) | Key::Both((_, sk)) => *sk,
+            Key::Pk(pk) => GetItem::<RefreshToken>::get_item(self, Key::Pk(pk)).await?.family
+        };
+        self.refresh_tokens(doc! {"family": family.0}).await
+    }
+}
+
+
+impl UpdateItem<RefreshToken> for Mongo {
+    type Error = Error;
+    type Update = Map;
+
+    /// Replaces a refresh token, which must keep its digest and family
+    async fn update_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>, token: RefreshToken) -> Result<RefreshToken, Self::Error> {
+        let stored = GetItem::<RefreshToken>::get_item(self, key).await?;
+        if stored.id != token.id || stored.family != token.family {
+            return Err(Error::UnsupportedOperation)
+        }
+        match self.collection("refresh_tokens").replace_one(doc! {"_id": &token.id}, to_document(&token)?).await?.matched_count {
+            0 => Err(Error::RefreshTokenNotFound),
+            _ => Ok(token)
+        }
+    }
+
+    /// Partially updates a refresh token, only `used` can be changed
+    async fn patch_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>, map: Map) -> Result<RefreshToken, Self::Error> {
+        let mut token = GetItem::<RefreshToken>::get_item(self, key.clone()).await?;
+        if let Some(value) = map.get("used") {
+            token.used = value.clone().try_into()?;
+        }
+        self.update_item(key, token).await
+    }
+
+    /// Refresh tokens do not support deleting fields
+    async fn delete_fields(&self, _: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>, _: HashSet<String>) -> Result<RefreshToken, Self::Error> {
+        Err(Error::UnsupportedOperation)
+    }
+}
+
+
+impl DeleteItem<RefreshToken> for Mongo {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>) -> Result<(), Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::RefreshTokenNotFound)
+        };
+        match self.collection("refresh_tokens").delete_one(doc! {"_id": pk}).await?.deleted_count {
+            0 => Err(Error::RefreshTokenNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+impl ConsumeRefreshToken for Mongo {
+    type Error = Error;
+
+    /// Marks a refresh token as used in a single step, the document is returned as it was before
+    async fn consume(&self, digest: &str) -> Result<RefreshToken, Self::Error> {
+        let document = self.collection("refresh_tokens")
+            .find_one_and_update(doc! {"_id": digest}, doc! {"$set": {"used": true}})
+            .await?
+            .ok_or(Error::RefreshTokenNotFound)?;
+        from_document(&document)
+    }
+}
+
+
+impl GetItems<User, RefreshToken> for Mongo {
+    type Error = Error;
+    type Filter = ();
+
+    /// Retrieves every refresh token issued to a user
+    async fn get_items(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, _: Self::Filter) -> Result<Vec<RefreshToken>, Self::Error> {
+        let user_id = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => *pk,
+            Key::Sk(sk) => self.user_id(sk).await?.ok_or(Error::UserNotFound)?
+        };
+        self.refresh_tokens(doc! {"user_id": user_id.0}).await
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::connect;
+    use crate::domain::types::Value;
+    use chrono::{Duration, DurationRound};
+
+    fn truncated((mut token, _): (RefreshToken, String)) -> RefreshToken {
+        token.expires = token.expires.duration_trunc(Duration::milliseconds(1)).unwrap();
+        token
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a running mongod"]
+    async fn test_refresh_tokens() {
+        let db = connect().await;
+        let user_id = Id::default();
+        let first = db.create_item(truncated(RefreshToken::new(user_id, None, vec![], 60))).await.unwrap();
+        let second = db.create_item(truncated(first.rotate(60))).await.unwrap();
+        db.create_item(truncated(RefreshToken::new(Id::default(), Some(Id::default()), vec![], 60))).await.unwrap();
+        assert_eq!(GetItem::<RefreshToken>::get_item(&db, Key::Pk(&second.id)).await.unwrap(), second);
+        assert_eq!(GetItems::<RefreshToken>::get_items(&db, Key::Pk(&second.id), ()).await.unwrap().len(), 2);
+        assert_eq!(GetItems::<User, RefreshToken>::get_items(&db, Key::Pk(&user_id), ()).await.unwrap().len(), 2);
+
+        assert!(!db.consume(&second.id).await.unwrap().used);
+        assert!(db.consume(&second.id).await.unwrap().used);
+        let map = Map::from([("used".to_string(), Value::Bool(true))]);
+        assert!(UpdateItem::<RefreshToken>::patch_item(&db, Key::Pk(&first.id), map).await.unwrap().used);
+        let moved = RefreshToken {family: Id::default(), ..first.clone()};
+        assert!(matches!(UpdateItem::<RefreshToken>::update_item(&db, Key::Pk(&first.id), moved).await, Err(Error::UnsupportedOperation)));
+
+        DeleteItem::<RefreshToken>::delete_item(&db, Key::Pk(&first.id)).await.unwrap();
+        assert!(matches!(GetItem::<RefreshToken>::get_item(&db, Key::Pk(&first.id)).await, Err(Error::RefreshTokenNotFound)));
+        db.database.drop().await.unwrap();
+    }
+}
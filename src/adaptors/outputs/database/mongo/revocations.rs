@@ -0,0 +1,123 @@
+//! Revocations collection implementation for the MongoDB database
+//!
+//! Revocations are keyed by their id and indexed by subject, only live revocations are returned.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, GetItems, DeleteItem};
+use crate::domain::types::{Revocation, Key};
+use super::{all, date, id, time, Mongo};
+use super::super::memory::Error;
+use mongodb::bson::{doc, Document};
+use chrono::Utc;
+
+
+fn to_document(revocation: &Revocation) -> Document {
+    doc! {
+        "_id": revocation.id.0,
+        "subject": revocation.subject.0,
+        "revoked_at": date(&revocation.revoked_at),
+        "expires": date(&revocation.expires),
+    }
+}
+
+
+fn from_document(document: &Document) -> Result<Revocation, Error> {
+    Ok(Revocation {
+        id: id(document, "_id")?,
+        subject: id(document, "subject")?,
+        revoked_at: time(document, "revoked_at")?,
+        expires: time(document, "expires")?,
+    })
+}
+
+
+impl CreateItem<Revocation> for Mongo {
+    type Error = Error;
+
+    /// Records a revocation, pruning the ones that are no longer needed
+    async fn create_item(&self, revocation: Revocation) -> Result<Revocation, Self::Error> {
+        let revocations = self.collection("revocations");
+        revocations.delete_many(doc! {"expires": {"$lte": date(&Utc::now())}}).await?;
+        revocations.replace_one(doc! {"_id": revocation.id.0}, to_document(&revocation)).upsert(true).await?;
+        Ok(revocation)
+    }
+}
+
+
+impl GetItem<Revocation> for Mongo {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<Revocation as Item>::PK, &<Revocation as Item>::SK>) -> Result<Revocation, Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::RevocationNotFound)
+        };
+        let filter = doc! {"_id": pk.0, "expires": {"$gt": date(&Utc::now())}};
+        let document = self.collection("revocations").find_one(filter).await?.ok_or(Error::RevocationNotFound)?;
+        from_document(&document)
+    }
+}
+
+
+impl GetItems<Revocation> for Mongo {
+    type Error = Error;
+    type Filter = ();
+
+    /// Retrieves the live revocations of a subject
+    async fn get_items(&self, key: Key<&<Revocation as Item>::PK, &<Revocation as Item>::SK>, _: Self::Filter) -> Result<Vec<Revocation>, Self::Error> {
+        let subject = match key {
+            Key::Sk(sk) | Key::Both((_, sk)) => sk,
+            Key::Pk(pk) => return Ok(vec![GetItem::<Revocation>::get_item(self, Key::Pk(pk)).await?])
+        };
+        let filter = doc! {"subject": subject.0, "expires": {"$gt": date(&Utc::now())}};
+        all(self.collection("revocations").find(filter).await?).await?
+            .iter()
+            .map(from_document)
+            .collect()
+    }
+}
+
+
+impl DeleteItem<Revocation> for Mongo {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<Revocation as Item>::PK, &<Revocation as Item>::SK>) -> Result<(), Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::RevocationNotFound)
+        };
+        match self.collection("revocations").delete_one(doc! {"_id": pk.0}).await?.deleted_count {
+            0 => Err(Error::RevocationNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::connect;
+    use crate::domain::types::Id;
+    use chrono::{Duration, DurationRound};
+
+    fn revocation(subject: Id, ttl: Duration) -> Revocation {
+        let now = Utc::now().duration_trunc(Duration::milliseconds(1)).unwrap();
+        Revocation {id: Id::default(), subject, revoked_at: now, expires: now + ttl}
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a running mongod"]
+    async fn test_revocations() {
+        let db = connect().await;
+        let subject = Id::default();
+        let live = db.create_item(revocation(subject, Duration::minutes(5))).await.unwrap();
+        let expired = db.create_item(revocation(subject, Duration::minutes(-5))).await.unwrap();
+        assert_eq!(GetItem::<Revocation>::get_item(&db, Key::Pk(&live.id)).await.unwrap(), live);
+        assert!(matches!(GetItem::<Revocation>::get_item(&db, Key::Pk(&expired.id)).await, Err(Error::RevocationNotFound)));
+        assert_eq!(GetItems::<Revocation>::get_items(&db, Key::Sk(&subject), ()).await.unwrap(), vec![live.clone()]);
+
+        DeleteItem::<Revocation>::delete_item(&db, Key::Pk(&live.id)).await.unwrap();
+        assert!(GetItems::<Revocation>::get_items(&db, Key::Sk(&subject), ()).await.unwrap().is_empty());
+        db.database.drop().await.unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+//! Services collection implementation for the MongoDB database
+//!
+//! Service names are unique per owner, a service is found by name across owners.
+
+use crate::ports::outputs::{expiry::TokenExpiry, database::{Item, CreateItem, GetItem, UpdateItem, DeleteItem, Map}};
+use crate::domain::types::{Service, ClientType, Key};
+use super::super::memory::{services::patch, Error};
+use super::{from_nested, id, nested, unique, Mongo};
+use mongodb::bson::{doc, Document};
+use std::collections::HashSet;
+use chrono::Duration;
+
+
+fn to_document(service: &Service) -> Result<Document, Error> {
+    Ok(doc! {
+        "_id": service.id.0,
+        "owner_id": service.owner_id.0,
+        "name": &service.name,
+        "client_secret": &service.client_secret,
+        "redirect_uris": nested(&service.redirect_uris)?,
+        "scopes": nested(&service.scopes)?,
+        "grant_types": nested(&service.grant_types)?,
+        "token_expiry": service.token_expiry.map(|expiry| expiry.num_seconds()),
+        "client_type": service.client_type.as_str(),
+    })
+}
+
+
+fn from_document(document: &Document) -> Result<Service, Error> {
+    Ok(Service {
+        id: id(document, "_id")?,
+        owner_id: id(document, "owner_id")?,
+        name: document.get_str("name")?.to_string(),
+        client_secret: document.get_str("client_secret")?.to_string(),
+        redirect_uris: from_nested(document, "redirect_uris")?,
+        scopes: from_nested(document, "scopes")?,
+        grant_types: from_nested(document, "grant_types")?,
+        token_expiry: from_nested::<Option<i64>>(document, "token_expiry")?.map(Duration::seconds),
+        // Clients stored before client types were are confidential
+        client_type: document.get_str("client_type").map_or(ClientType::Confidential, ClientType::from_stored),
+    })
+}
+
+
+impl CreateItem<Service> for Mongo {
+    type Error = Error;
+
+    async fn create_item(&self, service: Service) -> Result<Service, Self::Error> {
+        self.collection("services").insert_one(to_document(&service)?).await
+            .map_err(|err| unique(err, |_| Error::ServiceAlreadyExists))?;
+        Ok(service)
+    }
+}
+
+
+impl GetItem<Service> for Mongo {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<Service as Item>::PK, &<Service as Item>::SK>) -> Result<Service, Self::Error> {
+        let filter = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => doc! {"_id": pk.0},
+            Key::Sk(name) => doc! {"name": name}
+        };
+        let document = self.collection("services").find_one(filter).await?.ok_or(Error::ServiceNotFound)?;
+        from_document(&document)
+    }
+}
+
+
+impl TokenExpiry for Mongo {
+    type Error = Error;
+
+    async fn longest_token_expiry(&self) -> Result<Option<Duration>, Self::Error> {
+        let document = self.collection("services")
+            .find_one(doc! {"token_expiry": {"$ne": null}})
+            .sort(doc! {"token_expiry": -1})
+            .await?;
+        match document {
+            Some(document) => Ok(from_document(&document)?.token_expiry),
+            None => Ok(None)
+        }
+    }
+}
+
+
+impl UpdateItem<Service> for Mongo {
+    type Error = Error;
+    type Update = Map;
+
+    /// Renaming must not take over the name of another service of the owner
+    async fn update_item(&self, _: Key<&<Service as Item>::PK, &<Service as Item>::SK>, service: Service) -> Result<Service, Self::Error> {
+        let result = self.collection("services").replace_one(doc! {"_id": service.id.0}, to_document(&service)?).await
+            .map_err(|err| unique(err, |_| Error::ServiceAlreadyExists))?;
+        match result.matched_count {
+            0 => Err(Error::ServiceNotFound),
+            _ => Ok(service)
+        }
+    }
+
+    async fn patch_item(&self, key: Key<&<Service as Item>::PK, &<Service as Item>::SK>, map: Map) -> Result<Service, Self::Error> {
+        let mut service = GetItem::<Service>::get_item(self, key.clone()).await?;
+        patch(&mut service, map)?;
+        self.update_item(key, service).await
+    }
+
+    /// Services do not support deleting fields
+    async fn delete_fields(&self, _: Key<&<Service as Item>::PK, &<Service as Item>::SK>, _: HashSet<String>) -> Result<Service, Self::Error> {
+        Err(Error::UnsupportedOperation)
+    }
+}
+
+
+impl DeleteItem<Service> for Mongo {
+    type Error = Error;
+
+    /// Deletes a service by ID, by name, or by owner ID and name
+    async fn delete_item(&self, key: Key<&<Service as Item>::PK, &<Service as Item>::SK>) -> Result<(), Self::Error> {
+        let filter = match key {
+            Key::Pk(pk) => doc! {"_id": pk.0},
+            Key::Both((owner_id, name)) => doc! {"owner_id": owner_id.0, "name": name},
+            Key::Sk(name) => doc! {"name": name},
+        };
+        match self.collection("services").delete_one(filter).await?.deleted_count {
+            0 => Err(Error::ServiceNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::connect;
+    use crate::domain::types::{GrantType, Id, Permission, Scope, Value};
+
+    fn service(owner_id: Id) -> Service {
+        Service {
+            id: Id::default(),
+            owner_id,
+            name: "Test Service".to_string(),
+            client_secret: "secret".to_string(),
+            redirect_uris: vec!["http://localhost".to_string()],
+            scopes: vec![Scope {id: Id::default(), name: "test_scope".to_string(), permission: Permission::Read}],
+            grant_types: vec![GrantType::AuthorizationCode],
+            token_expiry: Some(Duration::hours(1)),
+            client_type: ClientType::Confidential,
+        }
+    }
+
+    #[test]
+    fn test_document() {
+        let service = service(Id::default());
+        assert_eq!(from_document(&to_document(&service).unwrap()).unwrap(), service);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a running mongod"]
+    async fn test_services() {
+        let db = connect().await;
+        let owner = Id::default();
+        let created = db.create_item(service(owner)).await.unwrap();
+        assert_eq!(GetItem::<Service>::get_item(&db, Key::Pk(&created.id)).await.unwrap(), created);
+        assert_eq!(GetItem::<Service>::get_item(&db, Key::Sk(&created.name)).await.unwrap(), created);
+
+        // Names are only unique per owner
+        assert!(matches!(db.create_item(service(owner)).await, Err(Error::ServiceAlreadyExists)));
+        let other = db.create_item(service(Id::default())).await.unwrap();
+        db.create_item(Service {name: "Short lived".to_string(), token_expiry: Some(Duration::minutes(5)), ..service(owner)}).await.unwrap();
+        assert_eq!(db.longest_token_expiry().await.unwrap(), Some(Duration::hours(1)));
+
+        let mut map = Map::new();
+        map.insert("client_secret".to_string(), Value::String("rotated".to_string()));
+        assert_eq!(UpdateItem::<Service>::patch_item(&db, Key::Pk(&created.id), map).await.unwrap().client_secret, "rotated");
+
+        DeleteItem::<Service>::delete_item(&db, Key::Both((&owner, &created.name))).await.unwrap();
+        assert!(matches!(GetItem::<Service>::get_item(&db, Key::Pk(&created.id)).await, Err(Error::ServiceNotFound)));
+        assert_eq!(GetItem::<Service>::get_item(&db, Key::Sk(&created.name)).await.unwrap(), other);
+        db.database.drop().await.unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+//! Users collection implementation for the MongoDB database
+//!
+//! The contact is stored in the `phone` and `email` fields with their verification states,
+//! each in a unique index, so a contact is found by its raw number or address whether verified or not.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, UpdateItem, DeleteItem, Map};
+use crate::domain::types::{User, Contact, Key, EmailAddress, Phone, Error as DomainError};
+use super::super::memory::{users::patch, Error};
+use mongodb::bson::{doc, Document};
+use std::collections::HashSet;
+use super::{id, unique, Mongo};
+
+
+fn to_document(user: &User) -> Document {
+    let mut document = doc! {
+        "_id": user.id.0,
+        "username": &user.username,
+        "first_name": &user.first_name,
+        "last_name": &user.last_name,
+        "password": &user.password,
+    };
+    let (phone, email) = match &user.contact {
+        Contact::Phone(phone) => (Some(phone), None),
+        Contact::Email(email) => (None, Some(email)),
+        Contact::Both(phone, email) => (Some(phone), Some(email))
+    };
+    // Absent rather than null, the unique indexes are sparse
+    if let Some(phone) = phone {
+        document.insert("phone", phone.to_string());
+        document.insert("phone_verified", matches!(phone, Phone::Verified(_)));
+    }
+    if let Some(email) = email {
+        document.insert("email", email.to_string());
+        document.insert("email_verified", matches!(email, EmailAddress::Verified(_)));
+    }
+    document
+}
+
+
+pub(super) fn from_document(document: &Document) -> Result<User, Error> {
+    let phone = match document.get_str("phone") {
+        Ok(phone) if document.get_bool("phone_verified")? => Some(Phone::Verified(phone.to_string())),
+        Ok(phone) => Some(Phone::New(phone.to_string())),
+        Err(_) => None
+    };
+    let email = match document.get_str("email") {
+        Ok(email) => {
+            let address = email.parse().map_err(|_| DomainError::InvalidEmail)?;
+            match document.get_bool("email_verified")? {
+                true => Some(EmailAddress::Verified(address)),
+                false => Some(EmailAddress::New(address))
+            }
+        },
+        Err(_) => None
+    };
+    let contact = match (phone, email) {
+        (Some(phone), Some(email)) => Contact::Both(phone, email),
+        (Some(phone), None) => Contact::Phone(phone),
+        (None, Some(email)) => Contact::Email(email),
+        (None, None) => Err(DomainError::Internal {message: String::from("a stored user has no contact info"), source: None})?
+    };
+    Ok(User {
+        id: id(document, "_id")?,
+        username: document.get_str("username")?.to_string(),
+        first_name: document.get_str("first_name")?.to_string(),
+        last_name: document.get_str("last_name")?.to_string(),
+        password: document.get_str("password")?.to_string(),
+        contact,
+    })
+}
+
+
+impl Mongo {
+    /// Finds the user ID for a contact
+    pub(super) async fn user_id(&self, sk: &<User as Item>::SK) -> Result<Option<<User as Item>::PK>, Error> {
+        let filter = match sk {
+            Contact::Phone(phone) | Contact::Both(phone, _) => doc! {"phone": &phone[..]},
+            Contact::Email(email) => doc! {"email": &email[..]}
+        };
+        match self.collection("users").find_one(filter).projection(doc! {"_id": 1}).await? {
+            Some(document) => Ok(Some(id(&document, "_id")?)),
+            None => Ok(None)
+        }
+    }
+
+    /// Maps a failed write of a user, the phone number is checked first like the memory database does
+    async fn conflict(&self, user: &User, err: mongodb::error::Error) -> Error {
+        let err = unique(err, |message| match message.contains("phone") {
+            true => Error::UserWithPhoneExists,
+            false => Error::UserWithEmailExists
+        });
+        if let (Error::UserWithEmailExists, Contact::Both(phone, _)) = (&err, &user.contact) {
+            if let Ok(Some(id)) = self.user_id(&Contact::Phone(phone.clone())).await {
+                if id != user.id {
+                    return Error::UserWithPhoneExists
+                }
+            }
+        }
+        err
+    }
+}
+
+
+impl CreateItem<User> for Mongo {
+    type Error = Error;
+
+    async fn create_item(&self, user: User) -> Result<User, Self::Error> {
+        match self.collection("users").insert_one(to_document(&user)).await {
+            Ok(_) => Ok(user),
+            Err(err) => Err(self.conflict(&user, err).await)
+        }
+    }
+}
+
+
+impl GetItem<User> for Mongo {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>) -> Result<User, Self::Error> {
+        let filter = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => doc! {"_id": pk.0},
+            Key::Sk(Contact::Phone(phone) | Contact::Both(phone, _)) => doc! {"phone": &phone[..]},
+            Key::Sk(Contact::Email(email)) => doc! {"email": &email[..]}
+        };
+        let document = self.collection("users").find_one(filter).await?.ok_or(Error::UserNotFound)?;
+        from_document(&document)
+    }
+}
+
+
+impl UpdateItem<User> for Mongo {
+    type Error = Error;
+    type Update = Map;
+
+    async fn update_item(&self, _: Key<&<User as Item>::PK, &<User as Item>::SK>, user: User) -> Result<User, Self::Error> {
+        match self.collection("users").replace_one(doc! {"_id": user.id.0}, to_document(&user)).await {
+            Ok(result) if result.matched_count == 0 => Err(Error::UserNotFound),
+            Ok(_) => Ok(user),
+            Err(err) => Err(self.conflict(&user, err).await)
+        }
+    }
+
+    async fn patch_item(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, map: Map) -> Result<User, Self::Error> {
+        let mut user = GetItem::<User>::get_item(self, key.clone()).await?;
+        patch(&mut user, map)?;
+        self.update_item(key, user).await
+    }
+
+    /// Only deletes the email or the phone of a user who has both
+    async fn delete_fields(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, mut fields: HashSet<String>) -> Result<User, Self::Error> {
+        let mut user = GetItem::<User>::get_item(self, key.clone()).await?;
+        let email = fields.remove("email");
+        let phone = fields.remove("phone");
+        if !email && !phone {
+            return Err(Error::CannotDeleteFields(fields))
+        }
+        user.contact = match (user.contact, email, phone) {
+            (Contact::Both(phone, _), true, false) => Contact::Phone(phone),
+            (Contact::Both(_, email), false, true) => Contact::Email(email),
+            _ => return Err(Error::CannotDeleteContact)
+        };
+        self.update_item(key, user).await
+    }
+}
+
+
+impl DeleteItem<User> for Mongo {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>) -> Result<(), Self::Error> {
+        let id = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => *pk,
+            Key::Sk(sk) => self.user_id(sk).await?.ok_or(Error::UserNotFound)?
+        };
+        match self.collection("users").delete_one(doc! {"_id": id.0}).await?.deleted_count {
+            0 => Err(Error::UserNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::connect;
+    use crate::domain::types::{Id, Value};
+
+    fn user() -> User {
+        User {
+            id: Id::default(),
+            username: "testuser".to_string(),
+            first_name: "Test".to_string(),
+            last_name: "User".to_string(),
+            password: String::new(),
+            contact: Contact::Both(
+                Phone::New("1234567890".to_string()),
+                EmailAddress::New("test@example.com".parse().unwrap())
+            )
+        }
+    }
+
+    #[test]
+    fn test_document() {
+        let user = user();
+        assert_eq!(from_document(&to_document(&user)).unwrap(), user);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a running mongod"]
+    async fn test_users() {
+        let db = connect().await;
+        let user = db.create_item(user()).await.unwrap();
+        assert_eq!(GetItem::<User>::get_item(&db, Key::Pk(&user.id)).await.unwrap(), user);
+        let verified = Contact::Email(EmailAddress::Verified("test@example.com".parse().unwrap()));
+        assert_eq!(GetItem::<User>::get_item(&db, Key::Sk(&verified)).await.unwrap(), user);
+
+        let duplicate = User {id: Id::default(), ..user.clone()};
+        assert!(matches!(db.create_item(duplicate.clone()).await, Err(Error::UserWithPhoneExists)));
+        let duplicate = User {contact: Contact::Email(EmailAddress::New("test@example.com".parse().unwrap())), ..duplicate};
+        assert!(matches!(db.create_item(duplicate).await, Err(Error::UserWithEmailExists)));
+
+        let mut map = Map::new();
+        map.insert("first_name".to_string(), Value::String("Updated".to_string()));
+        assert_eq!(UpdateItem::<User>::patch_item(&db, Key::Pk(&user.id), map).await.unwrap().first_name, "Updated");
+
+        let updated = UpdateItem::<User>::delete_fields(&db, Key::Pk(&user.id), ["email".to_string()].into()).await.unwrap();
+        assert_eq!(updated.contact, Contact::Phone(Phone::New("1234567890".to_string())));
+        assert!(matches!(UpdateItem::<User>::delete_fields(&db, Key::Pk(&user.id), ["phone".to_string()].into()).await, Err(Error::CannotDeleteContact)));
+
+        DeleteItem::<User>::delete_item(&db, Key::Pk(&user.id)).await.unwrap();
+        assert!(matches!(GetItem::<User>::get_item(&db, Key::Pk(&user.id)).await, Err(Error::UserNotFound)));
+        db.database.drop().await.unwrap();
+    }
+}
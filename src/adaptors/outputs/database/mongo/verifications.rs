@@ -0,0 +1,154 @@
+//! Verifications collection implementation for the MongoDB database
+//!
+//! A verification is keyed by the raw number or address it was sent to,
+//! creating one replaces any earlier verification for the same contact.
+//! The server removes expired verifications through a TTL index on `expires`.
+
+use crate::ports::outputs::{database::{Item, CreateItem, GetItem, DeleteItem}, wrong_code::CountWrongCode};
+use crate::domain::types::{Verification, Key};
+use super::{date, from_nested, id, nested, time, Mongo};
+use super::super::memory::Error;
+use mongodb::{bson::{doc, Document}, options::ReturnDocument};
+
+
+fn to_document(verification: &Verification) -> Result<Document, Error> {
+    Ok(doc! {
+        "_id": verification.owner_contact.as_str(),
+        "id": verification.id.0,
+        "owner_contact": nested(&verification.owner_contact)?,
+        "code": verification.code.as_str(),
+        "expires": date(&verification.expires),
+        "attempts": verification.attempts as i64,
+        "token": verification.token.as_str(),
+    })
+}
+
+
+fn from_document(document: &Document) -> Result<Verification, Error> {
+    Ok(Verification {
+        owner_contact: from_nested(document, "owner_contact")?,
+        id: id(document, "id")?,
+        // Codes stored before they were kept as text are numbers
+        code: match document.get_str("code") {
+            Ok(code) => code.to_string(),
+            Err(_) => document.get_i64("code")?.to_string(),
+        },
+        expires: time(document, "expires")?,
+        // Verifications stored before attempts were counted have none
+        attempts: document.get_i64("attempts").unwrap_or_default() as u32,
+        // Verifications stored before links had a secret have none
+        token: document.get_str("token").unwrap_or_default().to_string(),
+    })
+}
+
+
+fn filter(key: Key<&<Verification as Item>::PK, &<Verification as Item>::SK>) -> Document {
+    match key {
+        Key::Pk(pk) | Key::Both((pk, _)) => doc! {"_id": pk.as_str()},
+        Key::Sk(sk) => doc! {"id": sk.0}
+    }
+}
+
+
+impl CreateItem<Verification> for Mongo {
+    type Error = Error;
+
+    async fn create_item(&self, verification: Verification) -> Result<Verification, Self::Error> {
+        self.collection("verifications")
+            .replace_one(doc! {"_id": verification.owner_contact.as_str()}, to_document(&verification)?)
+            .upsert(true).await?;
+        Ok(verification)
+    }
+}
+
+
+impl GetItem<Verification> for Mongo {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<Verification as Item>::PK, &<Verification as Item>::SK>) -> Result<Verification, Self::Error> {
+        let document = self.collection("verifications").find_one(filter(key)).await?.ok_or(Error::VerificationNotFound)?;
+        from_document(&document)
+    }
+}
+
+
+impl DeleteItem<Verification> for Mongo {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<Verification as Item>::PK, &<Verification as Item>::SK>) -> Result<(), Self::Error> {
+        match self.collection("verifications").delete_one(filter(key)).await?.deleted_count {
+            0 => Err(Error::VerificationNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+impl CountWrongCode<Verification> for Mongo {
+    type Error = Error;
+
+    /// Counts a wrong code in a single update, then deletes the verification by its id if it ran out of attempts
+    async fn count_wrong_code(&self, id: &<Verification as Item>::SK, max_attempts: u32) -> Result<u32, Self::Error> {
+        let collection = self.collection("verifications");
+        let document = collection
+            .find_one_and_update(doc! {"id": id.0}, doc! {"$inc": {"attempts": 1i64}})
+            .return_document(ReturnDocument::After)
+            .await?
+            .ok_or(Error::VerificationNotFound)?;
+        let attempts = from_document(&document)?.attempts;
+        if attempts >= max_attempts {
+            collection.delete_one(doc! {"id": id.0}).await?;
+        }
+        Ok(attempts)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::connect;
+    use crate::domain::types::{Either, EmailAddress, Id};
+    use chrono::{Duration, DurationRound, Utc};
+
+    fn verification() -> Verification {
+        Verification {
+            owner_contact: Either::Right(EmailAddress::New("test@example.com".parse().unwrap())),
+            id: Id::default(),
+            code: String::from("123456"),
+            expires: (Utc::now() + Duration::minutes(10)).duration_trunc(Duration::milliseconds(1)).unwrap(),
+            attempts: 2,
+            token: String::from("secret"),
+        }
+    }
+
+    #[test]
+    fn test_document() {
+        let verification = verification();
+        assert_eq!(from_document(&to_document(&verification).unwrap()).unwrap(), verification);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a running mongod"]
+    async fn test_verifications() {
+        let db = connect().await;
+        let first = db.create_item(verification()).await.unwrap();
+        assert_eq!(GetItem::<Verification>::get_item(&db, Key::Sk(&first.id)).await.unwrap(), first);
+
+        // A new verification replaces the earlier one for the contact
+        let second = db.create_item(verification()).await.unwrap();
+        assert!(matches!(GetItem::<Verification>::get_item(&db, Key::Sk(&first.id)).await, Err(Error::VerificationNotFound)));
+        assert_eq!(GetItem::<Verification>::get_item(&db, Key::Pk(&second.owner_contact)).await.unwrap(), second);
+
+        // Wrong codes are only counted against the verification still stored
+        assert!(matches!(db.count_wrong_code(&first.id, 5).await, Err(Error::VerificationNotFound)));
+        assert_eq!(db.count_wrong_code(&second.id, 5).await.unwrap(), 3);
+        assert_eq!(db.count_wrong_code(&second.id, 4).await.unwrap(), 4);
+        assert!(matches!(GetItem::<Verification>::get_item(&db, Key::Sk(&second.id)).await, Err(Error::VerificationNotFound)));
+
+        let third = db.create_item(verification()).await.unwrap();
+        DeleteItem::<Verification>::delete_item(&db, Key::Pk(&third.owner_contact)).await.unwrap();
+        assert!(matches!(DeleteItem::<Verification>::delete_item(&db, Key::Sk(&third.id)).await, Err(Error::VerificationNotFound)));
+        db.database.drop().await.unwrap();
+    }
+}
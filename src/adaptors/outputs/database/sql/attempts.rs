@@ -0,0 +1,103 @@
+//! Login attempts table implementation for the SQL database
+//!
+//! Failures are keyed by the account or client address they are held against. Counts whose last
+//! failure is older than the lockout are pruned whenever a failure is counted.
+
+use crate::ports::outputs::attempts::LoginAttempts;
+use crate::domain::types::Failures;
+use super::super::memory::Error;
+use super::{time, timestamp, Sql};
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+
+
+impl LoginAttempts for Sql {
+    type Error = Error;
+
+    async fn failures(&self, key: &str) -> Result<Option<Failures>, Self::Error> {
+        let row = sqlx::query("SELECT count, last FROM login_attempts WHERE id = $1")
+            .bind(key)
+            .fetch_optional(&self.pool).await?;
+        match row {
+            Some(row) => Ok(Some(Failures {count: row.try_get::<i64, _>("count")? as u32, last: time(&row, "last")?})),
+            None => Ok(None)
+        }
+    }
+
+    /// Counts the failure in one upsert, which starts the count over when the last failure is too old
+    /// and keeps the time of the one before, so the earlier count can be returned
+    async fn fail(&self, key: &str, at: DateTime<Utc>, forget_before: DateTime<Utc>) -> Result<Option<Failures>, Self::Error> {
+        let stale = timestamp(&forget_before);
+        sqlx::query("DELETE FROM login_attempts WHERE id <> $1 AND last < $2")
+            .bind(key)
+            .bind(&stale)
+            .execute(&self.pool).await?;
+        let row = sqlx::query("INSERT INTO login_attempts (id, count, last, previous) VALUES ($1, 1, $2, $2) \
+                ON CONFLICT (id) DO UPDATE SET \
+                count = CASE WHEN login_attempts.last >= $3 THEN login_attempts.count + 1 ELSE 1 END, \
+                previous = login_attempts.last, \
+                last = $2 \
+                RETURNING count, previous")
+            .bind(key)
+            .bind(timestamp(&at))
+            .bind(&stale)
+            .fetch_one(&self.pool).await?;
+        match row.try_get::<i64, _>("count")? as u32 {
+            1 => Ok(None),
+            count => Ok(Some(Failures {count: count - 1, last: time(&row, "previous")?}))
+        }
+    }
+
+    async fn forgive(&self, key: &str) -> Result<(), Self::Error> {
+        let forgiven = sqlx::query("UPDATE login_attempts SET count = count - 1 WHERE id = $1 AND count > 1")
+            .bind(key)
+            .execute(&self.pool).await?;
+        if forgiven.rows_affected() == 0 {
+            sqlx::query("DELETE FROM login_attempts WHERE id = $1 AND count <= 1")
+                .bind(key)
+                .execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), Self::Error> {
+        sqlx::query("DELETE FROM login_attempts WHERE id = $1")
+            .bind(key)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn test_fail_and_forget() {
+        let db = Sql::connect("sqlite::memory:").await.unwrap();
+        let now = Utc::now();
+        let forget_before = now - Duration::minutes(15);
+        assert!(db.failures("email:jane@example.com").await.unwrap().is_none());
+
+        db.fail("email:jane@example.com", now - Duration::hours(1), forget_before - Duration::hours(1)).await.unwrap();
+        db.fail("email:jane@example.com", now - Duration::hours(1), forget_before - Duration::hours(1)).await.unwrap();
+        // The earlier failures are older than the lockout, so the count starts over
+        assert!(db.fail("email:jane@example.com", now, forget_before).await.unwrap().is_none());
+        assert_eq!(db.failures("email:jane@example.com").await.unwrap(), Some(Failures {count: 1, last: now}));
+        assert_eq!(db.fail("email:jane@example.com", now, forget_before).await.unwrap(), Some(Failures {count: 1, last: now}));
+        assert_eq!(db.fail("email:jane@example.com", now, forget_before).await.unwrap().unwrap().count, 2);
+
+        db.forgive("email:jane@example.com").await.unwrap();
+        assert_eq!(db.failures("email:jane@example.com").await.unwrap().unwrap().count, 2);
+
+        // Stale counts of other keys are pruned
+        db.fail("ip:127.0.0.1", now - Duration::hours(1), forget_before).await.unwrap();
+        db.fail("email:jane@example.com", now, forget_before).await.unwrap();
+        assert!(db.failures("ip:127.0.0.1").await.unwrap().is_none());
+
+        db.clear("email:jane@example.com").await.unwrap();
+        assert!(db.failures("email:jane@example.com").await.unwrap().is_none());
+    }
+}
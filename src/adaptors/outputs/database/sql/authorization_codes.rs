@@ -0,0 +1,104 @@
+//! Authorization codes table implementation for the SQL database
+//!
+//! Codes are keyed by the code itself, deleting one consumes it so it is only exchanged once.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, DeleteItem};
+use crate::domain::types::{AuthorizationCode, Key};
+use super::{from_json, id, json, time, timestamp, Sql};
+use super::super::memory::Error;
+use sqlx::any::AnyRow;
+use sqlx::Row;
+
+
+const COLUMNS: &str = "code, client_id, user_id, redirect_uri, code_challenge, scope, nonce, expires";
+
+
+fn from_row(row: &AnyRow) -> Result<AuthorizationCode, Error> {
+    Ok(AuthorizationCode {
+        code: row.try_get("code")?,
+        client_id: id(row, "client_id")?,
+        user_id: id(row, "user_id")?,
+        redirect_uri: row.try_get("redirect_uri")?,
+        code_challenge: row.try_get("code_challenge")?,
+        scope: from_json(row, "scope")?,
+        nonce: row.try_get("nonce")?,
+        expires: time(row, "expires")?,
+    })
+}
+
+
+impl CreateItem<AuthorizationCode> for Sql {
+    type Error = Error;
+
+    async fn create_item(&self, code: AuthorizationCode) -> Result<AuthorizationCode, Self::Error> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("DELETE FROM authorization_codes WHERE code = $1")
+            .bind(&code.code)
+            .execute(&mut *transaction).await?;
+        sqlx::query(&format!("INSERT INTO authorization_codes ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)", COLUMNS))
+            .bind(&code.code)
+            .bind(code.client_id.to_hex())
+            .bind(code.user_id.to_hex())
+            .bind(&code.redirect_uri)
+            .bind(&code.code_challenge)
+            .bind(json(&code.scope)?)
+            .bind(code.nonce.clone())
+            .bind(timestamp(&code.expires))
+            .execute(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(code)
+    }
+}
+
+
+impl GetItem<AuthorizationCode> for Sql {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<AuthorizationCode as Item>::PK, &<AuthorizationCode as Item>::SK>) -> Result<AuthorizationCode, Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::AuthorizationCodeNotFound)
+        };
+        let row = sqlx::query(&format!("SELECT {} FROM authorization_codes WHERE code = $1", COLUMNS))
+            .bind(pk)
+            .fetch_optional(&self.pool).await?
+            .ok_or(Error::AuthorizationCodeNotFound)?;
+        from_row(&row)
+    }
+}
+
+
+impl DeleteItem<AuthorizationCode> for Sql {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<AuthorizationCode as Item>::PK, &<AuthorizationCode as Item>::SK>) -> Result<(), Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::AuthorizationCodeNotFound)
+        };
+        match sqlx::query("DELETE FROM authorization_codes WHERE code = $1").bind(pk).execute(&self.pool).await?.rows_affected() {
+            0 => Err(Error::AuthorizationCodeNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::Id;
+
+    #[tokio::test]
+    async fn test_authorization_codes() {
+        let db = Sql::connect("sqlite::memory:").await.unwrap();
+        let mut code = AuthorizationCode::new(Id::default(), Id::default(), "https://example.com/callback".to_string(), "challenge".to_string(), vec!["openid".to_string()]);
+        code.nonce = Some("nonce".to_string());
+        let code = db.create_item(code).await.unwrap();
+        assert_eq!(GetItem::<AuthorizationCode>::get_item(&db, Key::Pk(&code.code)).await.unwrap(), code);
+
+        // A code is consumed once
+        DeleteItem::<AuthorizationCode>::delete_item(&db, Key::Pk(&code.code)).await.unwrap();
+        assert!(matches!(DeleteItem::<AuthorizationCode>::delete_item(&db, Key::Pk(&code.code)).await, Err(Error::AuthorizationCodeNotFound)));
+    }
+}
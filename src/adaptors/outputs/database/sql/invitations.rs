@@ -0,0 +1,146 @@
+//! Invitations table implementation for the SQL database
+//!
+//! The raw number or address an invitation was sent to is kept in `contact`, an organisation
+//! invites a contact once, whether the contact is verified or not.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, GetItems, DeleteItem};
+use crate::domain::types::{Invitation, Organisation, Contact, Key};
+use super::{from_json, id, json, time, timestamp, unique, Sql};
+use super::super::memory::Error;
+use sqlx::any::AnyRow;
+use sqlx::Row;
+
+
+const COLUMNS: &str = "id, org_id, contact, owner_contact, title, roles, invited_by, expires";
+
+
+/// The contact an invitation is unique by, the email address when there are both
+fn contact(contact: &Contact) -> &str {
+    match contact {
+        Contact::Phone(phone) => phone,
+        Contact::Email(email) | Contact::Both(_, email) => email
+    }
+}
+
+
+fn from_row(row: &AnyRow) -> Result<Invitation, Error> {
+    Ok(Invitation {
+        id: id(row, "id")?,
+        org_id: id(row, "org_id")?,
+        contact: from_json(row, "owner_contact")?,
+        title: row.try_get("title")?,
+        roles: from_json(row, "roles")?,
+        invited_by: id(row, "invited_by")?,
+        expires: time(row, "expires")?,
+    })
+}
+
+
+impl CreateItem<Invitation> for Sql {
+    type Error = Error;
+
+    async fn create_item(&self, invitation: Invitation) -> Result<Invitation, Self::Error> {
+        sqlx::query(&format!("INSERT INTO invitations ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)", COLUMNS))
+            .bind(invitation.id.to_hex())
+            .bind(invitation.org_id.to_hex())
+            .bind(contact(&invitation.contact))
+            .bind(json(&invitation.contact)?)
+            .bind(&invitation.title)
+            .bind(json(&invitation.roles)?)
+            .bind(invitation.invited_by.to_hex())
+            .bind(timestamp(&invitation.expires))
+            .execute(&self.pool).await
+            .map_err(|err| unique(err, |_| Error::InvitationAlreadyExists))?;
+        Ok(invitation)
+    }
+}
+
+
+impl GetItem<Invitation> for Sql {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<Invitation as Item>::PK, &<Invitation as Item>::SK>) -> Result<Invitation, Self::Error> {
+        let row = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => sqlx::query(&format!("SELECT {} FROM invitations WHERE id = $1", COLUMNS))
+                .bind(pk.to_hex())
+                .fetch_optional(&self.pool).await?,
+            Key::Sk((org_id, sk)) => sqlx::query(&format!("SELECT {} FROM invitations WHERE org_id = $1 AND contact = $2", COLUMNS))
+                .bind(org_id.to_hex())
+                .bind(contact(sk))
+                .fetch_optional(&self.pool).await?
+        };
+        from_row(&row.ok_or(Error::InvitationNotFound)?)
+    }
+}
+
+
+impl DeleteItem<Invitation> for Sql {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<Invitation as Item>::PK, &<Invitation as Item>::SK>) -> Result<(), Self::Error> {
+        let query = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => sqlx::query("DELETE FROM invitations WHERE id = $1").bind(pk.to_hex()),
+            Key::Sk((org_id, sk)) => sqlx::query("DELETE FROM invitations WHERE org_id = $1 AND contact = $2").bind(org_id.to_hex()).bind(contact(sk)),
+        };
+        match query.execute(&self.pool).await?.rows_affected() {
+            0 => Err(Error::InvitationNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+impl GetItems<Organisation, Invitation> for Sql {
+    type Error = Error;
+    type Filter = ();
+
+    /// Retrieves the pending invitations of an organisation
+    async fn get_items(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, _: Self::Filter) -> Result<Vec<Invitation>, Self::Error> {
+        let org_id = self.organisation_id(key).await?;
+        sqlx::query(&format!("SELECT {} FROM invitations WHERE org_id = $1", COLUMNS))
+            .bind(org_id.to_hex())
+            .fetch_all(&self.pool).await?
+            .iter()
+            .map(from_row)
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::{EmailAddress, Id};
+
+    fn invitation(org_id: Id) -> Invitation {
+        let mut invitation = Invitation {
+            id: Id::default(),
+            org_id,
+            contact: Contact::Email(EmailAddress::New("invitee@example.com".parse().unwrap())),
+            title: "Engineer".to_string(),
+            roles: vec![],
+            invited_by: Id::default(),
+            expires: Default::default(),
+        };
+        invitation.renew();
+        invitation
+    }
+
+    #[tokio::test]
+    async fn test_invitations() {
+        let db = Sql::connect("sqlite::memory:").await.unwrap();
+        let organisation = Organisation {id: Id::default(), name: "Beekeeper".to_string(), domain: None, home: None, contacts: Vec::new()};
+        let organisation = db.create_item(organisation).await.unwrap();
+        let created = db.create_item(invitation(organisation.id)).await.unwrap();
+        assert!(matches!(db.create_item(invitation(organisation.id)).await, Err(Error::InvitationAlreadyExists)));
+
+        let verified = Contact::Email(EmailAddress::Verified("invitee@example.com".parse().unwrap()));
+        assert_eq!(GetItem::<Invitation>::get_item(&db, Key::Sk(&(organisation.id, verified))).await.unwrap(), created);
+        let invitations = GetItems::<Organisation, Invitation>::get_items(&db, Key::Sk(&organisation.name), ()).await.unwrap();
+        assert_eq!(invitations, vec![created.clone()]);
+
+        // Invitations go with their organisation
+        DeleteItem::<Organisation>::delete_item(&db, Key::Pk(&organisation.id)).await.unwrap();
+        assert!(matches!(DeleteItem::<Invitation>::delete_item(&db, Key::Pk(&created.id)).await, Err(Error::InvitationNotFound)));
+    }
+}
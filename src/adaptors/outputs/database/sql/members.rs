@@ -0,0 +1,243 @@
+//! Members table implementation for the SQL database
+//!
+//! Members are keyed by the organisation and user IDs, and join organisations to users
+//! for the `GetItems` operations.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, GetItems, UpdateItem, DeleteItem, Map};
+use crate::domain::types::{Member, Organisation, User, Key};
+use super::super::memory::{members::patch, Error};
+use super::{from_json, id, json, unique, Sql};
+use std::collections::HashSet;
+use sqlx::any::AnyRow;
+use sqlx::Row;
+
+
+const COLUMNS: &str = "org_id, user_id, title, owner, roles";
+
+
+fn from_row(row: &AnyRow) -> Result<Member, Error> {
+    Ok(Member {
+        org_id: id(row, "org_id")?,
+        user_id: id(row, "user_id")?,
+        title: row.try_get("title")?,
+        owner: row.try_get::<i64, _>("owner")? == 1,
+        roles: from_json(row, "roles")?,
+    })
+}
+
+
+impl CreateItem<Member> for Sql {
+    type Error = Error;
+
+    async fn create_item(&self, member: Member) -> Result<Member, Self::Error> {
+        sqlx::query(&format!("INSERT INTO members ({}) VALUES ($1, $2, $3, $4, $5)", COLUMNS))
+            .bind(member.org_id.to_hex())
+            .bind(member.user_id.to_hex())
+            .bind(&member.title)
+            .bind(member.owner as i64)
+            .bind(json(&member.roles)?)
+            .execute(&self.pool).await
+            .map_err(|err| unique(err, |_| Error::MemberAlreadyExists))?;
+        Ok(member)
+    }
+}
+
+
+impl GetItem<(Organisation, User), Member> for Sql {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<(Organisation, User) as Item>::PK, &<(Organisation, User) as Item>::SK>) -> Result<Member, Self::Error> {
+        let (org_id, user_id) = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::MemberNotFound)
+        };
+        let row = sqlx::query(&format!("SELECT {} FROM members WHERE org_id = $1 AND user_id = $2", COLUMNS))
+            .bind(org_id.to_hex())
+            .bind(user_id.to_hex())
+            .fetch_optional(&self.pool).await?
+            .ok_or(Error::MemberNotFound)?;
+        from_row(&row)
+    }
+}
+
+
+impl UpdateItem<(Organisation, User), Member> for Sql {
+    type Error = Error;
+    type Update = Map;
+
+    async fn update_item(&self, key: Key<&<(Organisation, User) as Item>::PK, &<(Organisation, User) as Item>::SK>, member: Member) -> Result<Member, Self::Error> {
+        let (org_id, user_id) = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::MemberNotFound)
+        };
+        let result = sqlx::query("UPDATE members SET title = $3, owner = $4, roles = $5 WHERE org_id = $1 AND user_id = $2")
+            .bind(org_id.to_hex())
+            .bind(user_id.to_hex())
+            .bind(&member.title)
+            .bind(member.owner as i64)
+            .bind(json(&member.roles)?)
+            .execute(&self.pool).await?;
+        match result.rows_affected() {
+            0 => Err(Error::MemberNotFound),
+            _ => Ok(member)
+        }
+    }
+
+    async fn patch_item(&self, key: Key<&<(Organisation, User) as Item>::PK, &<(Organisation, User) as Item>::SK>, map: Map) -> Result<Member, Self::Error> {
+        let mut member = GetItem::<(Organisation, User), Member>::get_item(self, key.clone()).await?;
+        patch(&mut member, map)?;
+        self.update_item(key, member).await
+    }
+
+    /// Members do not support deleting individual fields
+    async fn delete_fields(&self, _: Key<&<(Organisation, User) as Item>::PK, &<(Organisation, User) as Item>::SK>, _: HashSet<String>) -> Result<Member, Self::Error> {
+        Err(Error::UnsupportedOperation)
+    }
+}
+
+
+impl DeleteItem<Member> for Sql {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<Member as Item>::PK, &<Member as Item>::SK>) -> Result<(), Self::Error> {
+        let (org_id, user_id) = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::MemberNotFound)
+        };
+        let result = sqlx::query("DELETE FROM members WHERE org_id = $1 AND user_id = $2")
+            .bind(org_id.to_hex())
+            .bind(user_id.to_hex())
+            .execute(&self.pool).await?;
+        match result.rows_affected() {
+            0 => Err(Error::MemberNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+impl Sql {
+    /// The organisations a user is a member of, with the memberships, only those the user owns when `owners` is set
+    async fn memberships(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, owners: bool) -> Result<Vec<(Member, Organisation)>, Error> {
+        let user_id = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => *pk,
+            Key::Sk(sk) => self.user_id(sk).await?.ok_or(Error::UserNotFound)?
+        };
+        let rows = sqlx::query("SELECT m.org_id, m.user_id, m.title, m.owner, m.roles, o.id, o.name, o.domain, o.home, o.contacts \
+            FROM members m JOIN organisations o ON o.id = m.org_id \
+            WHERE m.user_id = $1 AND (m.owner = 1 OR $2 = 0) ORDER BY o.name")
+            .bind(user_id.to_hex())
+            .bind(owners as i64)
+            .fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| Ok((from_row(row)?, super::organisations::from_row(row)?)))
+            .collect()
+    }
+
+    /// The members of an organisation with their users, only the owners when `owners` is set
+    async fn members(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, owners: bool) -> Result<Vec<(Member, User)>, Error> {
+        let org_id = self.organisation_id(key).await?;
+        let rows = sqlx::query("SELECT m.org_id, m.user_id, m.title, m.owner, m.roles, \
+            u.id, u.username, u.first_name, u.last_name, u.password, u.phone, u.phone_verified, u.email, u.email_verified \
+            FROM members m JOIN users u ON u.id = m.user_id \
+            WHERE m.org_id = $1 AND (m.owner = 1 OR $2 = 0) ORDER BY u.username")
+            .bind(org_id.to_hex())
+            .bind(owners as i64)
+            .fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| Ok((from_row(row)?, super::users::from_row(row)?)))
+            .collect()
+    }
+}
+
+
+/// User-related GetItems Operations
+impl GetItems<User, Organisation> for Sql {
+    type Error = Error;
+    type Filter = bool;
+
+    async fn get_items(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, filter: Self::Filter) -> Result<Vec<Organisation>, Self::Error> {
+        Ok(self.memberships(key, filter).await?.into_iter().map(|(_, organisation)| organisation).collect())
+    }
+}
+
+
+impl GetItems<User, (Member, Organisation)> for Sql {
+    type Error = Error;
+    type Filter = bool;
+
+    async fn get_items(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, filter: Self::Filter) -> Result<Vec<(Member, Organisation)>, Self::Error> {
+        self.memberships(key, filter).await
+    }
+}
+
+
+/// Organisation-related GetItems Operations
+impl GetItems<Organisation, User> for Sql {
+    type Error = Error;
+    type Filter = bool;
+
+    async fn get_items(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, filter: Self::Filter) -> Result<Vec<User>, Self::Error> {
+        Ok(self.members(key, filter).await?.into_iter().map(|(_, user)| user).collect())
+    }
+}
+
+
+impl GetItems<Organisation, (Member, User)> for Sql {
+    type Error = Error;
+    type Filter = bool;
+
+    async fn get_items(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, filter: Self::Filter) -> Result<Vec<(Member, User)>, Self::Error> {
+        self.members(key, filter).await
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::{Contact, EmailAddress, Id, Value};
+
+    fn user(email: &str) -> User {
+        User {
+            id: Id::default(),
+            username: email.to_string(),
+            first_name: "Test".to_string(),
+            last_name: "User".to_string(),
+            password: String::new(),
+            contact: Contact::Email(EmailAddress::New(email.parse().unwrap()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_members() {
+        let db = Sql::connect("sqlite::memory:").await.unwrap();
+        let organisation = Organisation {id: Id::default(), name: "Beekeeper".to_string(), domain: None, home: None, contacts: Vec::new()};
+        let organisation = db.create_item(organisation).await.unwrap();
+        let owner = db.create_item(user("owner@example.com")).await.unwrap();
+        let member = db.create_item(user("member@example.com")).await.unwrap();
+
+        let membership = Member {org_id: organisation.id, user_id: owner.id, title: "Founder".to_string(), owner: true, roles: vec![Id::default()]};
+        db.create_item(membership.clone()).await.unwrap();
+        assert!(matches!(db.create_item(membership.clone()).await, Err(Error::MemberAlreadyExists)));
+        db.create_item(Member {org_id: organisation.id, user_id: member.id, ..Default::default()}).await.unwrap();
+        assert_eq!(GetItem::<(Organisation, User), Member>::get_item(&db, Key::Pk(&(organisation.id, owner.id))).await.unwrap(), membership);
+
+        let users = GetItems::<Organisation, User>::get_items(&db, Key::Pk(&organisation.id), false).await.unwrap();
+        assert_eq!(users.len(), 2);
+        let owners = GetItems::<Organisation, (Member, User)>::get_items(&db, Key::Sk(&organisation.name), true).await.unwrap();
+        assert_eq!(owners, vec![(membership, owner.clone())]);
+        let organisations = GetItems::<User, Organisation>::get_items(&db, Key::Pk(&member.id), true).await.unwrap();
+        assert!(organisations.is_empty());
+
+        let mut map = Map::new();
+        map.insert("owner".to_string(), Value::Bool(true));
+        UpdateItem::<(Organisation, User), Member>::patch_item(&db, Key::Pk(&(organisation.id, member.id)), map).await.unwrap();
+        let organisations = GetItems::<User, (Member, Organisation)>::get_items(&db, Key::Pk(&member.id), true).await.unwrap();
+        assert_eq!(organisations[0].1, organisation);
+
+        DeleteItem::<Organisation>::delete_item(&db, Key::Pk(&organisation.id)).await.unwrap();
+        let organisations = GetItems::<User, Organisation>::get_items(&db, Key::Pk(&owner.id), false).await.unwrap();
+        assert!(organisations.is_empty());
+    }
+}
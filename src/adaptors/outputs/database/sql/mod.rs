@@ -0,0 +1,115 @@
+//! SQL Database Implementation
+//!
+//! This module stores users, organisations, members, services, verifications, invitations, sessions
+//! and failed logins in SQLite or PostgreSQL.
+//! Both go through sqlx's `Any` driver, the URL given to [`Sql::connect`] picks the database.
+//!
+//! # Schema
+//! The schema is embedded from the `migrations` directory and brought up to date on connect.
+//! Uniqueness rules are constraints of the schema, their violations are reported with the
+//! same errors as the memory database, such as `UserWithEmailExists`.
+
+mod users;
+mod organisations;
+mod members;
+mod services;
+mod verifications;
+mod invitations;
+mod authorization_codes;
+mod refresh_tokens;
+mod revocations;
+mod attempts;
+
+use sqlx::any::{AnyPoolOptions, AnyRow};
+use sqlx::migrate::{MigrateError, Migrator};
+use crate::domain::types::{Error as DomainError, Id};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use super::memory::Error;
+use sqlx::{AnyPool, Row};
+
+
+/// The schema, applied in order and recorded in the `_sqlx_migrations` table.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+
+/// A database implementation on SQLite or PostgreSQL.
+///
+/// # Concurrency
+/// Operations run on a connection pool, clones share the pool.
+#[derive(Debug, Clone)]
+pub struct Sql {
+    pool: AnyPool,
+}
+
+
+impl Sql {
+    /// Connects to a database and migrates it to the current schema.
+    ///
+    /// # Arguments
+    /// * `url`: Such as `postgres://localhost/beekeeper`, `sqlite://beekeeper.db?mode=rwc` or `sqlite::memory:`
+    pub async fn connect(url: &str) -> Result<Self, Error> {
+        sqlx::any::install_default_drivers();
+        let mut options = AnyPoolOptions::new();
+        // Every connection to an in-memory SQLite database opens a database of its own
+        if url.contains(":memory:") || url.contains("mode=memory") {
+            options = options.max_connections(1).min_connections(1).idle_timeout(None).max_lifetime(None);
+        }
+        let pool = options.connect(url).await?;
+        MIGRATOR.run(&pool).await?;
+        Ok(Self {pool})
+    }
+}
+
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        Self::DomainError(DomainError::internal(err))
+    }
+}
+
+
+impl From<MigrateError> for Error {
+    fn from(err: MigrateError) -> Self {
+        Self::DomainError(DomainError::internal(err))
+    }
+}
+
+
+/// Maps the violation of a unique constraint with `conflict`, which is given the constraint
+/// or the message naming the violated columns, and any other failure to an internal error.
+fn unique(err: sqlx::Error, conflict: impl FnOnce(&str) -> Error) -> Error {
+    match &err {
+        sqlx::Error::Database(db) if db.is_unique_violation() => conflict(db.constraint().unwrap_or(db.message())),
+        _ => err.into()
+    }
+}
+
+
+/// Lists and other nested values are stored as JSON text.
+fn json<T: Serialize>(value: &T) -> Result<String, Error> {
+    Ok(serde_json::to_string(value).map_err(DomainError::from)?)
+}
+
+
+fn from_json<T: DeserializeOwned>(row: &AnyRow, column: &str) -> Result<T, Error> {
+    Ok(serde_json::from_str(&row.try_get::<String, _>(column)?).map_err(DomainError::from)?)
+}
+
+
+fn id(row: &AnyRow, column: &str) -> Result<Id, Error> {
+    Ok(row.try_get::<String, _>(column)?.parse()?)
+}
+
+
+/// Times are stored with a fixed width, so they compare as text.
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+
+fn time(row: &AnyRow, column: &str) -> Result<DateTime<Utc>, Error> {
+    let time = DateTime::parse_from_rfc3339(&row.try_get::<String, _>(column)?)
+        .map_err(|_| DomainError::Internal {message: format!("a stored {} is not a valid time", column), source: None})?;
+    Ok(time.to_utc())
+}
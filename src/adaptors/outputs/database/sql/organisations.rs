@@ -0,0 +1,164 @@
+//! Organisations table implementation for the SQL database
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, UpdateItem, DeleteItem, Map};
+use crate::domain::types::{Organisation, Key};
+use super::super::memory::{organisations::patch, Error};
+use super::{from_json, id, json, unique, Sql};
+use std::collections::HashSet;
+use sqlx::any::AnyRow;
+use sqlx::Row;
+
+
+const COLUMNS: &str = "id, name, domain, home, contacts";
+
+
+pub(super) fn from_row(row: &AnyRow) -> Result<Organisation, Error> {
+    Ok(Organisation {
+        id: id(row, "id")?,
+        name: row.try_get("name")?,
+        domain: row.try_get("domain")?,
+        home: row.try_get("home")?,
+        contacts: from_json(row, "contacts")?,
+    })
+}
+
+
+impl Sql {
+    /// Finds the organisation ID for a name
+    pub(super) async fn organisation_id(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>) -> Result<<Organisation as Item>::PK, Error> {
+        let name = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => return Ok(*pk),
+            Key::Sk(name) => name
+        };
+        let row = sqlx::query("SELECT id FROM organisations WHERE name = $1")
+            .bind(name)
+            .fetch_optional(&self.pool).await?
+            .ok_or(Error::OrganisationNotFound)?;
+        id(&row, "id")
+    }
+}
+
+
+impl CreateItem<Organisation> for Sql {
+    type Error = Error;
+
+    async fn create_item(&self, organisation: Organisation) -> Result<Organisation, Self::Error> {
+        sqlx::query(&format!("INSERT INTO organisations ({}) VALUES ($1, $2, $3, $4, $5)", COLUMNS))
+            .bind(organisation.id.to_hex())
+            .bind(&organisation.name)
+            .bind(organisation.domain.clone())
+            .bind(organisation.home.clone())
+            .bind(json(&organisation.contacts)?)
+            .execute(&self.pool).await
+            .map_err(|err| unique(err, |_| Error::OrganisationWithNameExists))?;
+        Ok(organisation)
+    }
+}
+
+
+impl GetItem<Organisation> for Sql {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>) -> Result<Organisation, Self::Error> {
+        let row = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => sqlx::query(&format!("SELECT {} FROM organisations WHERE id = $1", COLUMNS))
+                .bind(pk.to_hex())
+                .fetch_optional(&self.pool).await?,
+            Key::Sk(name) => sqlx::query(&format!("SELECT {} FROM organisations WHERE name = $1", COLUMNS))
+                .bind(name)
+                .fetch_optional(&self.pool).await?
+        };
+        from_row(&row.ok_or(Error::OrganisationNotFound)?)
+    }
+}
+
+
+impl UpdateItem<Organisation> for Sql {
+    type Error = Error;
+    type Update = Map;
+
+    /// Renaming must not take over another organisation's name
+    async fn update_item(&self, _: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, organisation: Organisation) -> Result<Organisation, Self::Error> {
+        let result = sqlx::query("UPDATE organisations SET name = $2, domain = $3, home = $4, contacts = $5 WHERE id = $1")
+            .bind(organisation.id.to_hex())
+            .bind(&organisation.name)
+            .bind(organisation.domain.clone())
+            .bind(organisation.home.clone())
+            .bind(json(&organisation.contacts)?)
+            .execute(&self.pool).await
+            .map_err(|err| unique(err, |_| Error::OrganisationWithNameExists))?;
+        match result.rows_affected() {
+            0 => Err(Error::OrganisationNotFound),
+            _ => Ok(organisation)
+        }
+    }
+
+    async fn patch_item(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, map: Map) -> Result<Organisation, Self::Error> {
+        let mut organisation = GetItem::<Organisation>::get_item(self, key.clone()).await?;
+        patch(&mut organisation, map)?;
+        self.update_item(key, organisation).await
+    }
+
+    /// Organisations do not support deleting individual fields
+    async fn delete_fields(&self, _: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>, _: HashSet<String>) -> Result<Organisation, Self::Error> {
+        Err(Error::UnsupportedOperation)
+    }
+}
+
+
+impl DeleteItem<Organisation> for Sql {
+    type Error = Error;
+
+    /// Deletes an organisation with every membership of and invitation to it
+    async fn delete_item(&self, key: Key<&<Organisation as Item>::PK, &<Organisation as Item>::SK>) -> Result<(), Self::Error> {
+        let org_id = self.organisation_id(key).await?.to_hex();
+        let mut transaction = self.pool.begin().await?;
+        let result = sqlx::query("DELETE FROM organisations WHERE id = $1")
+            .bind(&org_id)
+            .execute(&mut *transaction).await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::OrganisationNotFound)
+        }
+        sqlx::query("DELETE FROM members WHERE org_id = $1")
+            .bind(&org_id)
+            .execute(&mut *transaction).await?;
+        sqlx::query("DELETE FROM invitations WHERE org_id = $1")
+            .bind(&org_id)
+            .execute(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::{Contact, EmailAddress, Id, Value};
+
+    fn named(name: &str) -> Organisation {
+        Organisation {
+            id: Id::default(),
+            name: name.to_string(),
+            domain: Some("example.com".to_string()),
+            home: None,
+            contacts: vec![("support".to_string(), Contact::Email(EmailAddress::New("support@example.com".parse().unwrap())))],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_organisations() {
+        let db = Sql::connect("sqlite::memory:").await.unwrap();
+        let organisation = db.create_item(named("Beekeeper")).await.unwrap();
+        assert_eq!(GetItem::<Organisation>::get_item(&db, Key::Sk(&organisation.name)).await.unwrap(), organisation);
+        assert!(matches!(db.create_item(named("Beekeeper")).await, Err(Error::OrganisationWithNameExists)));
+
+        let other = db.create_item(named("Hive")).await.unwrap();
+        let mut map = Map::new();
+        map.insert("name".to_string(), Value::String("Beekeeper".to_string()));
+        assert!(matches!(UpdateItem::<Organisation>::patch_item(&db, Key::Pk(&other.id), map).await, Err(Error::OrganisationWithNameExists)));
+
+        DeleteItem::<Organisation>::delete_item(&db, Key::Sk(&organisation.name)).await.unwrap();
+        assert!(matches!(GetItem::<Organisation>::get_item(&db, Key::Pk(&organisation.id)).await, Err(Error::OrganisationNotFound)));
+    }
+}
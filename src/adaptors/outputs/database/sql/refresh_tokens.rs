@@ -0,0 +1,172 @@
+//! Refresh tokens table implementation for the SQL database
+//!
+//! Tokens are keyed by their digest and indexed by family and by user.
+
+use crate::ports::outputs::{consume::ConsumeRefreshToken, database::{Item, CreateItem, GetItem, GetItems, DeleteItem}};
+use crate::domain::types::{RefreshToken, User, Key};
+use super::{from_json, id, json, time, timestamp, Sql};
+use super::super::memory::Error;
+use sqlx::any::AnyRow;
+use sqlx::Row;
+
+
+const COLUMNS: &str = "id, family, user_id, client_id, scope, used, expires";
+
+
+fn from_row(row: &AnyRow) -> Result<RefreshToken, Error> {
+    let client_id = match row.try_get::<Option<String>, _>("client_id")? {
+        Some(client_id) => Some(client_id.parse()?),
+        None => None
+    };
+    Ok(RefreshToken {
+        id: row.try_get("id")?,
+        family: id(row, "family")?,
+        user_id: id(row, "user_id")?,
+        client_id,
+        scope: from_json(row, "scope")?,
+        used: row.try_get::<i64, _>("used")? == 1,
+        expires: time(row, "expires")?,
+    })
+}
+
+
+impl Sql {
+    async fn refresh_tokens(&self, column: &str, value: String) -> Result<Vec<RefreshToken>, Error> {
+        sqlx::query(&format!("SELECT {} FROM refresh_tokens WHERE {} = $1", COLUMNS, column))
+            .bind(value)
+            .fetch_all(&self.pool).await?
+            .iter()
+            .map(from_row)
+            .collect()
+    }
+}
+
+
+impl CreateItem<RefreshToken> for Sql {
+    type Error = Error;
+
+    async fn create_item(&self, token: RefreshToken) -> Result<RefreshToken, Self::Error> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("DELETE FROM refresh_tokens WHERE id = $1")
+            .bind(&token.id)
+            .execute(&mut *transaction).await?;
+        sqlx::query(&format!("INSERT INTO refresh_tokens ({}) VALUES ($1, $2, $3, $4, $5, $6, $7)", COLUMNS))
+            .bind(&token.id)
+            .bind(token.family.to_hex())
+            .bind(token.user_id.to_hex())
+            .bind(token.client_id.map(|id| id.to_hex()))
+            .bind(json(&token.scope)?)
+            .bind(token.used as i64)
+            .bind(timestamp(&token.expires))
+            .execute(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(token)
+    }
+}
+
+
+impl GetItem<RefreshToken> for Sql {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>) -> Result<RefreshToken, Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::RefreshTokenNotFound)
+        };
+        let row = sqlx::query(&format!("SELECT {} FROM refresh_tokens WHERE id = $1", COLUMNS))
+            .bind(pk)
+            .fetch_optional(&self.pool).await?
+            .ok_or(Error::RefreshTokenNotFound)?;
+        from_row(&row)
+    }
+}
+
+
+impl GetItems<RefreshToken> for Sql {
+    type Error = Error;
+    type Filter = ();
+
+    /// Retrieves every token of a refresh token family
+    async fn get_items(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>, _: Self::Filter) -> Result<Vec<RefreshToken>, Self::Error> {
+        let family = match key {
+            Key::Sk(sk) | Key::Both((_, sk)) => *sk,
+            Key::Pk(pk) => GetItem::<RefreshToken>::get_item(self, Key::Pk(pk)).await?.family
+        };
+        self.refresh_tokens("family", family.to_hex()).await
+    }
+}
+
+
+impl DeleteItem<RefreshToken> for Sql {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<RefreshToken as Item>::PK, &<RefreshToken as Item>::SK>) -> Result<(), Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::RefreshTokenNotFound)
+        };
+        match sqlx::query("DELETE FROM refresh_tokens WHERE id = $1").bind(pk).execute(&self.pool).await?.rows_affected() {
+            0 => Err(Error::RefreshTokenNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+impl ConsumeRefreshToken for Sql {
+    type Error = Error;
+
+    /// Marks a refresh token as used in a single statement, only the first caller finds it unused
+    async fn consume(&self, digest: &str) -> Result<RefreshToken, Self::Error> {
+        let consumed = sqlx::query(&format!("UPDATE refresh_tokens SET used = 1 WHERE id = $1 AND used = 0 RETURNING {}", COLUMNS))
+            .bind(digest)
+            .fetch_optional(&self.pool).await?;
+        match consumed {
+            Some(row) => Ok(RefreshToken {used: false, ..from_row(&row)?}),
+            None => GetItem::<RefreshToken>::get_item(self, Key::Pk(&digest.to_string())).await
+        }
+    }
+}
+
+
+impl GetItems<User, RefreshToken> for Sql {
+    type Error = Error;
+    type Filter = ();
+
+    /// Retrieves every refresh token issued to a user
+    async fn get_items(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, _: Self::Filter) -> Result<Vec<RefreshToken>, Self::Error> {
+        let user_id = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => *pk,
+            Key::Sk(sk) => self.user_id(sk).await?.ok_or(Error::UserNotFound)?
+        };
+        self.refresh_tokens("user_id", user_id.to_hex()).await
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::Id;
+
+    #[tokio::test]
+    async fn test_refresh_tokens() {
+        let db = Sql::connect("sqlite::memory:").await.unwrap();
+        let user_id = Id::default();
+        let (first, _) = RefreshToken::new(user_id, None, vec![], 60);
+        let first = db.create_item(first).await.unwrap();
+        let second = db.create_item(first.rotate(60).0).await.unwrap();
+        db.create_item(RefreshToken::new(Id::default(), Some(Id::default()), vec!["read".to_string()], 60).0).await.unwrap();
+        assert_eq!(GetItem::<RefreshToken>::get_item(&db, Key::Pk(&second.id)).await.unwrap(), second);
+        assert_eq!(GetItems::<RefreshToken>::get_items(&db, Key::Pk(&second.id), ()).await.unwrap().len(), 2);
+        assert_eq!(GetItems::<User, RefreshToken>::get_items(&db, Key::Pk(&user_id), ()).await.unwrap().len(), 2);
+
+        // Only the first exchange finds the token unused
+        assert!(!db.consume(&second.id).await.unwrap().used);
+        assert!(db.consume(&second.id).await.unwrap().used);
+        assert!(matches!(db.consume("unknown").await, Err(Error::RefreshTokenNotFound)));
+
+        DeleteItem::<RefreshToken>::delete_item(&db, Key::Pk(&first.id)).await.unwrap();
+        assert!(matches!(GetItem::<RefreshToken>::get_item(&db, Key::Pk(&first.id)).await, Err(Error::RefreshTokenNotFound)));
+    }
+}
@@ -0,0 +1,127 @@
+//! Revocations table implementation for the SQL database
+//!
+//! Revocations are keyed by their id and indexed by subject, only live revocations are returned.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, GetItems, DeleteItem};
+use crate::domain::types::{Revocation, Key};
+use super::{id, time, timestamp, Sql};
+use super::super::memory::Error;
+use sqlx::any::AnyRow;
+use chrono::Utc;
+
+
+const COLUMNS: &str = "id, subject, revoked_at, expires";
+
+
+fn from_row(row: &AnyRow) -> Result<Revocation, Error> {
+    Ok(Revocation {
+        id: id(row, "id")?,
+        subject: id(row, "subject")?,
+        revoked_at: time(row, "revoked_at")?,
+        expires: time(row, "expires")?,
+    })
+}
+
+
+impl CreateItem<Revocation> for Sql {
+    type Error = Error;
+
+    /// Records a revocation, pruning the ones that are no longer needed
+    async fn create_item(&self, revocation: Revocation) -> Result<Revocation, Self::Error> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("DELETE FROM revocations WHERE expires <= $1 OR id = $2")
+            .bind(timestamp(&Utc::now()))
+            .bind(revocation.id.to_hex())
+            .execute(&mut *transaction).await?;
+        sqlx::query(&format!("INSERT INTO revocations ({}) VALUES ($1, $2, $3, $4)", COLUMNS))
+            .bind(revocation.id.to_hex())
+            .bind(revocation.subject.to_hex())
+            .bind(timestamp(&revocation.revoked_at))
+            .bind(timestamp(&revocation.expires))
+            .execute(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(revocation)
+    }
+}
+
+
+impl GetItem<Revocation> for Sql {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<Revocation as Item>::PK, &<Revocation as Item>::SK>) -> Result<Revocation, Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::RevocationNotFound)
+        };
+        let row = sqlx::query(&format!("SELECT {} FROM revocations WHERE id = $1 AND expires > $2", COLUMNS))
+            .bind(pk.to_hex())
+            .bind(timestamp(&Utc::now()))
+            .fetch_optional(&self.pool).await?
+            .ok_or(Error::RevocationNotFound)?;
+        from_row(&row)
+    }
+}
+
+
+impl GetItems<Revocation> for Sql {
+    type Error = Error;
+    type Filter = ();
+
+    /// Retrieves the live revocations of a subject
+    async fn get_items(&self, key: Key<&<Revocation as Item>::PK, &<Revocation as Item>::SK>, _: Self::Filter) -> Result<Vec<Revocation>, Self::Error> {
+        let subject = match key {
+            Key::Sk(sk) | Key::Both((_, sk)) => sk,
+            Key::Pk(pk) => return Ok(vec![GetItem::<Revocation>::get_item(self, Key::Pk(pk)).await?])
+        };
+        sqlx::query(&format!("SELECT {} FROM revocations WHERE subject = $1 AND expires > $2", COLUMNS))
+            .bind(subject.to_hex())
+            .bind(timestamp(&Utc::now()))
+            .fetch_all(&self.pool).await?
+            .iter()
+            .map(from_row)
+            .collect()
+    }
+}
+
+
+impl DeleteItem<Revocation> for Sql {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<Revocation as Item>::PK, &<Revocation as Item>::SK>) -> Result<(), Self::Error> {
+        let pk = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => pk,
+            Key::Sk(_) => return Err(Error::RevocationNotFound)
+        };
+        match sqlx::query("DELETE FROM revocations WHERE id = $1").bind(pk.to_hex()).execute(&self.pool).await?.rows_affected() {
+            0 => Err(Error::RevocationNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::Id;
+    use chrono::Duration;
+
+    fn revocation(subject: Id, ttl: Duration) -> Revocation {
+        let now = Utc::now();
+        Revocation {id: Id::default(), subject, revoked_at: now, expires: now + ttl}
+    }
+
+    #[tokio::test]
+    async fn test_revocations() {
+        let db = Sql::connect("sqlite::memory:").await.unwrap();
+        let subject = Id::default();
+        let live = db.create_item(revocation(subject, Duration::minutes(5))).await.unwrap();
+        let expired = db.create_item(revocation(subject, Duration::minutes(-5))).await.unwrap();
+        assert_eq!(GetItem::<Revocation>::get_item(&db, Key::Pk(&live.id)).await.unwrap(), live);
+        assert!(matches!(GetItem::<Revocation>::get_item(&db, Key::Pk(&expired.id)).await, Err(Error::RevocationNotFound)));
+        assert_eq!(GetItems::<Revocation>::get_items(&db, Key::Sk(&subject), ()).await.unwrap(), vec![live.clone()]);
+
+        DeleteItem::<Revocation>::delete_item(&db, Key::Pk(&live.id)).await.unwrap();
+        assert!(GetItems::<Revocation>::get_items(&db, Key::Sk(&subject), ()).await.unwrap().is_empty());
+    }
+}
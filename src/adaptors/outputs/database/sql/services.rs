@@ -0,0 +1,179 @@
+//! Services table implementation for the SQL database
+//!
+//! Service names are unique per owner, a service is found by name across owners.
+
+use crate::ports::outputs::{expiry::TokenExpiry, database::{Item, CreateItem, GetItem, UpdateItem, DeleteItem, Map}};
+use crate::domain::types::{Service, ClientType, Key};
+use super::super::memory::{services::patch, Error};
+use super::{from_json, id, json, unique, Sql};
+use std::collections::HashSet;
+use sqlx::any::AnyRow;
+use chrono::Duration;
+use sqlx::Row;
+
+
+const COLUMNS: &str = "id, owner_id, name, client_secret, redirect_uris, scopes, grant_types, token_expiry, client_type";
+
+
+fn from_row(row: &AnyRow) -> Result<Service, Error> {
+    Ok(Service {
+        id: id(row, "id")?,
+        owner_id: id(row, "owner_id")?,
+        name: row.try_get("name")?,
+        client_secret: row.try_get("client_secret")?,
+        redirect_uris: from_json(row, "redirect_uris")?,
+        scopes: from_json(row, "scopes")?,
+        grant_types: from_json(row, "grant_types")?,
+        token_expiry: row.try_get::<Option<i64>, _>("token_expiry")?.map(Duration::seconds),
+        client_type: ClientType::from_stored(row.try_get("client_type")?),
+    })
+}
+
+
+impl CreateItem<Service> for Sql {
+    type Error = Error;
+
+    async fn create_item(&self, service: Service) -> Result<Service, Self::Error> {
+        sqlx::query(&format!("INSERT INTO services ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)", COLUMNS))
+            .bind(service.id.to_hex())
+            .bind(service.owner_id.to_hex())
+            .bind(&service.name)
+            .bind(&service.client_secret)
+            .bind(json(&service.redirect_uris)?)
+            .bind(json(&service.scopes)?)
+            .bind(json(&service.grant_types)?)
+            .bind(service.token_expiry.map(|expiry| expiry.num_seconds()))
+            .bind(service.client_type.as_str())
+            .execute(&self.pool).await
+            .map_err(|err| unique(err, |_| Error::ServiceAlreadyExists))?;
+        Ok(service)
+    }
+}
+
+
+impl TokenExpiry for Sql {
+    type Error = Error;
+
+    async fn longest_token_expiry(&self) -> Result<Option<Duration>, Self::Error> {
+        let row = sqlx::query("SELECT MAX(token_expiry) AS longest FROM services")
+            .fetch_one(&self.pool).await?;
+        Ok(row.try_get::<Option<i64>, _>("longest")?.map(Duration::seconds))
+    }
+}
+
+
+impl GetItem<Service> for Sql {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<Service as Item>::PK, &<Service as Item>::SK>) -> Result<Service, Self::Error> {
+        let row = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => sqlx::query(&format!("SELECT {} FROM services WHERE id = $1", COLUMNS))
+                .bind(pk.to_hex())
+                .fetch_optional(&self.pool).await?,
+            Key::Sk(name) => sqlx::query(&format!("SELECT {} FROM services WHERE name = $1 LIMIT 1", COLUMNS))
+                .bind(name)
+                .fetch_optional(&self.pool).await?
+        };
+        from_row(&row.ok_or(Error::ServiceNotFound)?)
+    }
+}
+
+
+impl UpdateItem<Service> for Sql {
+    type Error = Error;
+    type Update = Map;
+
+    /// Renaming must not take over the name of another service of the owner
+    async fn update_item(&self, _: Key<&<Service as Item>::PK, &<Service as Item>::SK>, service: Service) -> Result<Service, Self::Error> {
+        let result = sqlx::query("UPDATE services SET owner_id = $2, name = $3, client_secret = $4, redirect_uris = $5, scopes = $6, grant_types = $7, token_expiry = $8, client_type = $9 WHERE id = $1")
+            .bind(service.id.to_hex())
+            .bind(service.owner_id.to_hex())
+            .bind(&service.name)
+            .bind(&service.client_secret)
+            .bind(json(&service.redirect_uris)?)
+            .bind(json(&service.scopes)?)
+            .bind(json(&service.grant_types)?)
+            .bind(service.token_expiry.map(|expiry| expiry.num_seconds()))
+            .bind(service.client_type.as_str())
+            .execute(&self.pool).await
+            .map_err(|err| unique(err, |_| Error::ServiceAlreadyExists))?;
+        match result.rows_affected() {
+            0 => Err(Error::ServiceNotFound),
+            _ => Ok(service)
+        }
+    }
+
+    async fn patch_item(&self, key: Key<&<Service as Item>::PK, &<Service as Item>::SK>, map: Map) -> Result<Service, Self::Error> {
+        let mut service = GetItem::<Service>::get_item(self, key.clone()).await?;
+        patch(&mut service, map)?;
+        self.update_item(key, service).await
+    }
+
+    /// Services do not support deleting fields
+    async fn delete_fields(&self, _: Key<&<Service as Item>::PK, &<Service as Item>::SK>, _: HashSet<String>) -> Result<Service, Self::Error> {
+        Err(Error::UnsupportedOperation)
+    }
+}
+
+
+impl DeleteItem<Service> for Sql {
+    type Error = Error;
+
+    /// Deletes a service by ID, by name, or by owner ID and name
+    async fn delete_item(&self, key: Key<&<Service as Item>::PK, &<Service as Item>::SK>) -> Result<(), Self::Error> {
+        let query = match key {
+            Key::Pk(pk) => sqlx::query("DELETE FROM services WHERE id = $1").bind(pk.to_hex()),
+            Key::Both((owner_id, name)) => sqlx::query("DELETE FROM services WHERE owner_id = $1 AND name = $2").bind(owner_id.to_hex()).bind(name),
+            Key::Sk(name) => sqlx::query("DELETE FROM services WHERE id = (SELECT id FROM services WHERE name = $1 LIMIT 1)").bind(name),
+        };
+        match query.execute(&self.pool).await?.rows_affected() {
+            0 => Err(Error::ServiceNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::{GrantType, Id, Permission, Scope, Value};
+
+    fn service(owner_id: Id) -> Service {
+        Service {
+            id: Id::default(),
+            owner_id,
+            name: "Test Service".to_string(),
+            client_secret: "secret".to_string(),
+            redirect_uris: vec!["http://localhost".to_string()],
+            scopes: vec![Scope {id: Id::default(), name: "test_scope".to_string(), permission: Permission::Read}],
+            grant_types: vec![GrantType::AuthorizationCode],
+            token_expiry: Some(Duration::hours(1)),
+            client_type: ClientType::Confidential,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_services() {
+        let db = Sql::connect("sqlite::memory:").await.unwrap();
+        let owner = Id::default();
+        let created = db.create_item(service(owner)).await.unwrap();
+        assert_eq!(GetItem::<Service>::get_item(&db, Key::Pk(&created.id)).await.unwrap(), created);
+        assert_eq!(GetItem::<Service>::get_item(&db, Key::Sk(&created.name)).await.unwrap(), created);
+
+        // Names are only unique per owner
+        assert!(matches!(db.create_item(service(owner)).await, Err(Error::ServiceAlreadyExists)));
+        let other = db.create_item(service(Id::default())).await.unwrap();
+        let public = db.create_item(Service {name: "Short lived".to_string(), token_expiry: Some(Duration::minutes(5)), client_type: ClientType::Public, ..service(owner)}).await.unwrap();
+        assert_eq!(GetItem::<Service>::get_item(&db, Key::Pk(&public.id)).await.unwrap().client_type, ClientType::Public);
+        assert_eq!(db.longest_token_expiry().await.unwrap(), Some(Duration::hours(1)));
+
+        let mut map = Map::new();
+        map.insert("client_secret".to_string(), Value::String("rotated".to_string()));
+        assert_eq!(UpdateItem::<Service>::patch_item(&db, Key::Pk(&created.id), map).await.unwrap().client_secret, "rotated");
+
+        DeleteItem::<Service>::delete_item(&db, Key::Both((&owner, &created.name))).await.unwrap();
+        assert!(matches!(GetItem::<Service>::get_item(&db, Key::Pk(&created.id)).await, Err(Error::ServiceNotFound)));
+        assert_eq!(GetItem::<Service>::get_item(&db, Key::Sk(&created.name)).await.unwrap(), other);
+    }
+}
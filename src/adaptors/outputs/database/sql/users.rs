@@ -0,0 +1,246 @@
+//! Users table implementation for the SQL database
+//!
+//! The contact is stored in the `phone` and `email` columns with their verification states,
+//! each unique, so a contact is found by its raw number or address whether verified or not.
+
+use crate::ports::outputs::database::{Item, CreateItem, GetItem, UpdateItem, DeleteItem, Map};
+use crate::domain::types::{User, Contact, Key, EmailAddress, Phone, Error as DomainError};
+use super::super::memory::{users::patch, Error};
+use super::{id, unique, Sql};
+use sqlx::any::AnyRow;
+use std::collections::HashSet;
+use sqlx::Row;
+
+
+const COLUMNS: &str = "id, username, first_name, last_name, password, phone, phone_verified, email, email_verified";
+
+
+/// Splits a contact into the `phone`, `phone_verified`, `email` and `email_verified` columns.
+fn columns(contact: &Contact) -> (Option<String>, bool, Option<String>, bool) {
+    let (phone, email) = match contact {
+        Contact::Phone(phone) => (Some(phone), None),
+        Contact::Email(email) => (None, Some(email)),
+        Contact::Both(phone, email) => (Some(phone), Some(email))
+    };
+    let phone_verified = matches!(phone, Some(Phone::Verified(_)));
+    let email_verified = matches!(email, Some(EmailAddress::Verified(_)));
+    (phone.map(|phone| phone.to_string()), phone_verified, email.map(|email| email.to_string()), email_verified)
+}
+
+
+pub(super) fn from_row(row: &AnyRow) -> Result<User, Error> {
+    let phone = match row.try_get::<Option<String>, _>("phone")? {
+        Some(phone) if row.try_get::<i64, _>("phone_verified")? == 1 => Some(Phone::Verified(phone)),
+        Some(phone) => Some(Phone::New(phone)),
+        None => None
+    };
+    let email = match row.try_get::<Option<String>, _>("email")? {
+        Some(email) => {
+            let address = email.parse().map_err(|_| DomainError::InvalidEmail)?;
+            match row.try_get::<i64, _>("email_verified")? == 1 {
+                true => Some(EmailAddress::Verified(address)),
+                false => Some(EmailAddress::New(address))
+            }
+        },
+        None => None
+    };
+    let contact = match (phone, email) {
+        (Some(phone), Some(email)) => Contact::Both(phone, email),
+        (Some(phone), None) => Contact::Phone(phone),
+        (None, Some(email)) => Contact::Email(email),
+        (None, None) => Err(DomainError::Internal {message: String::from("a stored user has no contact info"), source: None})?
+    };
+    Ok(User {
+        id: id(row, "id")?,
+        username: row.try_get("username")?,
+        first_name: row.try_get("first_name")?,
+        last_name: row.try_get("last_name")?,
+        password: row.try_get("password")?,
+        contact,
+    })
+}
+
+
+impl Sql {
+    /// Finds the user ID for a contact
+    pub(super) async fn user_id(&self, sk: &<User as Item>::SK) -> Result<Option<<User as Item>::PK>, Error> {
+        let (query, contact) = match sk {
+            Contact::Phone(phone) | Contact::Both(phone, _) => ("SELECT id FROM users WHERE phone = $1", &phone[..]),
+            Contact::Email(email) => ("SELECT id FROM users WHERE email = $1", &email[..])
+        };
+        match sqlx::query(query).bind(contact).fetch_optional(&self.pool).await? {
+            Some(row) => Ok(Some(id(&row, "id")?)),
+            None => Ok(None)
+        }
+    }
+
+    /// Maps a failed write of a user, the phone number is checked first like the memory database does
+    async fn conflict(&self, user: &User, err: sqlx::Error) -> Error {
+        let err = unique(err, |constraint| match constraint.contains("phone") {
+            true => Error::UserWithPhoneExists,
+            false => Error::UserWithEmailExists
+        });
+        if let (Error::UserWithEmailExists, Contact::Both(phone, _)) = (&err, &user.contact) {
+            if let Ok(Some(id)) = self.user_id(&Contact::Phone(phone.clone())).await {
+                if id != user.id {
+                    return Error::UserWithPhoneExists
+                }
+            }
+        }
+        err
+    }
+}
+
+
+impl CreateItem<User> for Sql {
+    type Error = Error;
+
+    async fn create_item(&self, user: User) -> Result<User, Self::Error> {
+        let (phone, phone_verified, email, email_verified) = columns(&user.contact);
+        let result = sqlx::query(&format!("INSERT INTO users ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)", COLUMNS))
+            .bind(user.id.to_hex())
+            .bind(&user.username)
+            .bind(&user.first_name)
+            .bind(&user.last_name)
+            .bind(&user.password)
+            .bind(phone)
+            .bind(phone_verified as i64)
+            .bind(email)
+            .bind(email_verified as i64)
+            .execute(&self.pool).await;
+        match result {
+            Ok(_) => Ok(user),
+            Err(err) => Err(self.conflict(&user, err).await)
+        }
+    }
+}
+
+
+impl GetItem<User> for Sql {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>) -> Result<User, Self::Error> {
+        let id = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => *pk,
+            Key::Sk(sk) => self.user_id(sk).await?.ok_or(Error::UserNotFound)?
+        };
+        let row = sqlx::query(&format!("SELECT {} FROM users WHERE id = $1", COLUMNS))
+            .bind(id.to_hex())
+            .fetch_optional(&self.pool).await?
+            .ok_or(Error::UserNotFound)?;
+        from_row(&row)
+    }
+}
+
+
+impl UpdateItem<User> for Sql {
+    type Error = Error;
+    type Update = Map;
+
+    async fn update_item(&self, _: Key<&<User as Item>::PK, &<User as Item>::SK>, user: User) -> Result<User, Self::Error> {
+        let (phone, phone_verified, email, email_verified) = columns(&user.contact);
+        let result = sqlx::query("UPDATE users SET username = $2, first_name = $3, last_name = $4, password = $5, phone = $6, phone_verified = $7, email = $8, email_verified = $9 WHERE id = $1")
+            .bind(user.id.to_hex())
+            .bind(&user.username)
+            .bind(&user.first_name)
+            .bind(&user.last_name)
+            .bind(&user.password)
+            .bind(phone)
+            .bind(phone_verified as i64)
+            .bind(email)
+            .bind(email_verified as i64)
+            .execute(&self.pool).await;
+        match result {
+            Ok(result) if result.rows_affected() == 0 => Err(Error::UserNotFound),
+            Ok(_) => Ok(user),
+            Err(err) => Err(self.conflict(&user, err).await)
+        }
+    }
+
+    async fn patch_item(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, map: Map) -> Result<User, Self::Error> {
+        let mut user = GetItem::<User>::get_item(self, key.clone()).await?;
+        patch(&mut user, map)?;
+        self.update_item(key, user).await
+    }
+
+    /// Only deletes the email or the phone of a user who has both
+    async fn delete_fields(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>, mut fields: HashSet<String>) -> Result<User, Self::Error> {
+        let mut user = GetItem::<User>::get_item(self, key.clone()).await?;
+        let email = fields.remove("email");
+        let phone = fields.remove("phone");
+        if !email && !phone {
+            return Err(Error::CannotDeleteFields(fields))
+        }
+        user.contact = match (user.contact, email, phone) {
+            (Contact::Both(phone, _), true, false) => Contact::Phone(phone),
+            (Contact::Both(_, email), false, true) => Contact::Email(email),
+            _ => return Err(Error::CannotDeleteContact)
+        };
+        self.update_item(key, user).await
+    }
+}
+
+
+impl DeleteItem<User> for Sql {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<User as Item>::PK, &<User as Item>::SK>) -> Result<(), Self::Error> {
+        let id = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => *pk,
+            Key::Sk(sk) => self.user_id(sk).await?.ok_or(Error::UserNotFound)?
+        };
+        let result = sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(id.to_hex())
+            .execute(&self.pool).await?;
+        match result.rows_affected() {
+            0 => Err(Error::UserNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::{Id, Value};
+
+    fn user() -> User {
+        User {
+            id: Id::default(),
+            username: "testuser".to_string(),
+            first_name: "Test".to_string(),
+            last_name: "User".to_string(),
+            password: String::new(),
+            contact: Contact::Both(
+                Phone::New("1234567890".to_string()),
+                EmailAddress::New("test@example.com".parse().unwrap())
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn test_users() {
+        let db = Sql::connect("sqlite::memory:").await.unwrap();
+        let user = db.create_item(user()).await.unwrap();
+        assert_eq!(GetItem::<User>::get_item(&db, Key::Pk(&user.id)).await.unwrap(), user);
+        let verified = Contact::Email(EmailAddress::Verified("test@example.com".parse().unwrap()));
+        assert_eq!(GetItem::<User>::get_item(&db, Key::Sk(&verified)).await.unwrap(), user);
+
+        let duplicate = User {id: Id::default(), ..user.clone()};
+        assert!(matches!(db.create_item(duplicate.clone()).await, Err(Error::UserWithPhoneExists)));
+        let duplicate = User {contact: Contact::Email(EmailAddress::New("test@example.com".parse().unwrap())), ..duplicate};
+        assert!(matches!(db.create_item(duplicate).await, Err(Error::UserWithEmailExists)));
+
+        let mut map = Map::new();
+        map.insert("first_name".to_string(), Value::String("Updated".to_string()));
+        assert_eq!(UpdateItem::<User>::patch_item(&db, Key::Pk(&user.id), map).await.unwrap().first_name, "Updated");
+
+        let updated = UpdateItem::<User>::delete_fields(&db, Key::Pk(&user.id), ["email".to_string()].into()).await.unwrap();
+        assert_eq!(updated.contact, Contact::Phone(Phone::New("1234567890".to_string())));
+        assert!(matches!(UpdateItem::<User>::delete_fields(&db, Key::Pk(&user.id), ["phone".to_string()].into()).await, Err(Error::CannotDeleteContact)));
+
+        DeleteItem::<User>::delete_item(&db, Key::Pk(&user.id)).await.unwrap();
+        assert!(matches!(GetItem::<User>::get_item(&db, Key::Pk(&user.id)).await, Err(Error::UserNotFound)));
+    }
+}
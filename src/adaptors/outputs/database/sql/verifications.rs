@@ -0,0 +1,151 @@
+//! Verifications table implementation for the SQL database
+//!
+//! A verification is keyed by the raw number or address it was sent to,
+//! creating one replaces any earlier verification for the same contact.
+
+use crate::ports::outputs::{database::{Item, CreateItem, GetItem, DeleteItem}, wrong_code::CountWrongCode};
+use crate::domain::types::{Verification, Key, Error as DomainError};
+use super::{from_json, id, json, Sql};
+use chrono::{DateTime, SecondsFormat};
+use super::super::memory::Error;
+use sqlx::any::AnyRow;
+use sqlx::Row;
+
+
+const COLUMNS: &str = "contact, id, owner_contact, code, expires, attempts, token";
+
+
+fn from_row(row: &AnyRow) -> Result<Verification, Error> {
+    let expires = DateTime::parse_from_rfc3339(&row.try_get::<String, _>("expires")?)
+        .map_err(|_| DomainError::Internal {message: String::from("a stored verification has an invalid expiry"), source: None})?;
+    Ok(Verification {
+        owner_contact: from_json(row, "owner_contact")?,
+        id: id(row, "id")?,
+        code: row.try_get("code")?,
+        expires: expires.to_utc(),
+        attempts: row.try_get::<i64, _>("attempts")? as u32,
+        token: row.try_get("token")?,
+    })
+}
+
+
+impl CreateItem<Verification> for Sql {
+    type Error = Error;
+
+    async fn create_item(&self, verification: Verification) -> Result<Verification, Self::Error> {
+        let contact = verification.owner_contact.as_str();
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("DELETE FROM verifications WHERE contact = $1")
+            .bind(contact)
+            .execute(&mut *transaction).await?;
+        sqlx::query(&format!("INSERT INTO verifications ({}) VALUES ($1, $2, $3, $4, $5, $6, $7)", COLUMNS))
+            .bind(contact)
+            .bind(verification.id.to_hex())
+            .bind(json(&verification.owner_contact)?)
+            .bind(verification.code.as_str())
+            // Fixed width, so expiries compare as text
+            .bind(verification.expires.to_rfc3339_opts(SecondsFormat::Nanos, true))
+            .bind(verification.attempts as i64)
+            .bind(verification.token.as_str())
+            .execute(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(verification)
+    }
+}
+
+
+impl GetItem<Verification> for Sql {
+    type Error = Error;
+
+    async fn get_item(&self, key: Key<&<Verification as Item>::PK, &<Verification as Item>::SK>) -> Result<Verification, Self::Error> {
+        let row = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => sqlx::query(&format!("SELECT {} FROM verifications WHERE contact = $1", COLUMNS))
+                .bind(pk.as_str())
+                .fetch_optional(&self.pool).await?,
+            Key::Sk(sk) => sqlx::query(&format!("SELECT {} FROM verifications WHERE id = $1", COLUMNS))
+                .bind(sk.to_hex())
+                .fetch_optional(&self.pool).await?
+        };
+        from_row(&row.ok_or(Error::VerificationNotFound)?)
+    }
+}
+
+
+impl DeleteItem<Verification> for Sql {
+    type Error = Error;
+
+    async fn delete_item(&self, key: Key<&<Verification as Item>::PK, &<Verification as Item>::SK>) -> Result<(), Self::Error> {
+        let query = match key {
+            Key::Pk(pk) | Key::Both((pk, _)) => sqlx::query("DELETE FROM verifications WHERE contact = $1").bind(pk.as_str()),
+            Key::Sk(sk) => sqlx::query("DELETE FROM verifications WHERE id = $1").bind(sk.to_hex()),
+        };
+        match query.execute(&self.pool).await?.rows_affected() {
+            0 => Err(Error::VerificationNotFound),
+            _ => Ok(())
+        }
+    }
+}
+
+
+impl CountWrongCode<Verification> for Sql {
+    type Error = Error;
+
+    async fn count_wrong_code(&self, id: &<Verification as Item>::SK, max_attempts: u32) -> Result<u32, Self::Error> {
+        let mut transaction = self.pool.begin().await?;
+        let row = sqlx::query("UPDATE verifications SET attempts = attempts + 1 WHERE id = $1 RETURNING attempts")
+            .bind(id.to_hex())
+            .fetch_optional(&mut *transaction).await?
+            .ok_or(Error::VerificationNotFound)?;
+        let attempts = row.try_get::<i64, _>("attempts")? as u32;
+        if attempts >= max_attempts {
+            sqlx::query("DELETE FROM verifications WHERE id = $1")
+                .bind(id.to_hex())
+                .execute(&mut *transaction).await?;
+        }
+        transaction.commit().await?;
+        Ok(attempts)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::{Either, EmailAddress, Id};
+    use chrono::{Duration, Utc};
+
+    fn verification() -> Verification {
+        Verification {
+            owner_contact: Either::Right(EmailAddress::New("test@example.com".parse().unwrap())),
+            id: Id::default(),
+            code: String::from("123456"),
+            expires: Utc::now() + Duration::minutes(10),
+            attempts: 0,
+            token: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verifications() {
+        let db = Sql::connect("sqlite::memory:").await.unwrap();
+        let first = db.create_item(verification()).await.unwrap();
+        assert_eq!(GetItem::<Verification>::get_item(&db, Key::Sk(&first.id)).await.unwrap(), first);
+
+        // A new verification replaces the earlier one for the contact
+        let second = db.create_item(verification()).await.unwrap();
+        assert!(matches!(GetItem::<Verification>::get_item(&db, Key::Sk(&first.id)).await, Err(Error::VerificationNotFound)));
+        assert_eq!(GetItem::<Verification>::get_item(&db, Key::Pk(&second.owner_contact)).await.unwrap(), second);
+
+        // Wrong codes are only counted against the verification still stored
+        assert!(matches!(db.count_wrong_code(&first.id, 2).await, Err(Error::VerificationNotFound)));
+        assert_eq!(db.count_wrong_code(&second.id, 2).await.unwrap(), 1);
+        assert_eq!(GetItem::<Verification>::get_item(&db, Key::Sk(&second.id)).await.unwrap().attempts, 1);
+
+        DeleteItem::<Verification>::delete_item(&db, Key::Pk(&second.owner_contact)).await.unwrap();
+        assert!(matches!(DeleteItem::<Verification>::delete_item(&db, Key::Sk(&second.id)).await, Err(Error::VerificationNotFound)));
+
+        let third = db.create_item(verification()).await.unwrap();
+        assert_eq!(db.count_wrong_code(&third.id, 1).await.unwrap(), 1);
+        assert!(matches!(GetItem::<Verification>::get_item(&db, Key::Sk(&third.id)).await, Err(Error::VerificationNotFound)));
+    }
+}
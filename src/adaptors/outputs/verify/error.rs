@@ -10,6 +10,15 @@ use crate::ports::ErrorTrait;
 #[derive(Debug)]
 pub enum Error {
     InvalidCode,
+    ExpiredCode,
+    /// Too many wrong codes were tried, the verification is no longer valid
+    TooManyAttempts,
+    /// A code was sent to the contact moments ago
+    ResendTooSoon,
+    /// The contact was sent as many codes as it can be sent within an hour
+    QuotaExceeded,
+    /// The service cannot deliver organisation invitations
+    InvitationsUnsupported,
     Internal(Box<dyn StdError + Send + Sync + 'static>),
     Err(Box<dyn ErrorTrait + 'static>)
 }
@@ -47,6 +56,11 @@ impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::InvalidCode => write!(f, "invlid code"),
+            Error::ExpiredCode => write!(f, "the code has expired"),
+            Error::TooManyAttempts => write!(f, "too many wrong codes"),
+            Error::ResendTooSoon => write!(f, "a code was sent moments ago"),
+            Error::QuotaExceeded => write!(f, "too many codes sent within the hour"),
+            Error::InvitationsUnsupported => write!(f, "invitations cannot be delivered"),
             Error::Internal(err) => Display::fmt(err, f),
             Error::Err(err) => Display::fmt(err, f)
         }
@@ -61,6 +75,11 @@ impl ErrorTrait for Error {
     fn log_message(&self) -> String {
         match self {
             Error::InvalidCode => format!("invlid code"),
+            Error::ExpiredCode => String::from("expired code"),
+            Error::TooManyAttempts => String::from("too many wrong codes, the verification was invalidated"),
+            Error::ResendTooSoon => String::from("a code was requested again before the cooldown ended"),
+            Error::QuotaExceeded => String::from("the hourly quota of codes was exceeded"),
+            Error::InvitationsUnsupported => String::from("an invitation was sent through a verifyer that cannot deliver them"),
             Error::Internal(err) => format!("internal error: {}", err),
             Error::Err(err) => err.log_message()
         }
@@ -69,6 +88,11 @@ impl ErrorTrait for Error {
     fn user_message(&self) -> String {
         match self {
             Error::InvalidCode => format!("invlid code"),
+            Error::ExpiredCode => String::from("the code has expired, request a new one"),
+            Error::TooManyAttempts => String::from("too many wrong codes, request a new one"),
+            Error::ResendTooSoon => String::from("a code was sent moments ago, wait a minute before requesting another"),
+            Error::QuotaExceeded => String::from("too many codes were requested, try again later"),
+            Error::InvitationsUnsupported => String::from("invitations are not supported by this server"),
             Error::Internal(_) => format!("internal server error occured"),
             Error::Err(err) => err.user_message()
         }
@@ -77,7 +101,9 @@ impl ErrorTrait for Error {
     #[cfg(feature = "http")]
     fn status(&self) -> StatusCode {
         match self {
-            Error::InvalidCode => StatusCode::BAD_REQUEST,
+            Error::InvalidCode | Error::ExpiredCode => StatusCode::BAD_REQUEST,
+            Error::TooManyAttempts | Error::ResendTooSoon | Error::QuotaExceeded => StatusCode::TOO_MANY_REQUESTS,
+            Error::InvitationsUnsupported => StatusCode::NOT_IMPLEMENTED,
             Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Error::Err(err) => err.status()
         }
@@ -0,0 +1,247 @@
+//! Limits that keep verification codes from being guessed or sent en masse
+//!
+//! Codes sent to a contact are tracked in the running process, wrong guesses on the stored verification.
+//! The wrong guesses are also kept by contact for an hour, so a new code starts from the count of
+//! the one it replaces rather than from zero.
+
+use crate::ports::outputs::wrong_code::CountWrongCode;
+use crate::domain::types::Verification;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Duration, Utc};
+use std::sync::{Arc, Mutex};
+use super::Error;
+
+
+/// Default number of wrong codes before a verification is invalidated
+const MAX_ATTEMPTS: u32 = 5;
+/// Default number of seconds before another code can be sent to a contact
+const COOLDOWN: u64 = 60;
+/// Default number of codes a contact can be sent within an hour
+const HOURLY_QUOTA: usize = 5;
+
+
+/// What is tracked for each contact, shared by the clones of the limits
+type ByContact<T> = Arc<Mutex<HashMap<String, T>>>;
+
+
+/// How often codes can be sent to a contact and guessed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    /// Wrong codes tried before a verification is invalidated
+    pub max_attempts: u32,
+    /// Seconds before another code can be sent to the same contact
+    pub cooldown: u64,
+    /// Codes sent to the same contact within an hour
+    pub hourly_quota: usize,
+    /// When codes were sent to each contact within the last hour
+    #[serde(skip)]
+    sent: ByContact<Vec<DateTime<Utc>>>,
+    /// The wrong codes tried by each contact and when the last one was
+    #[serde(skip)]
+    wrong: ByContact<(u32, DateTime<Utc>)>,
+}
+
+
+/// A code being sent to a contact, which only counts towards the quota once it was sent.
+///
+/// Dropping it before [`Sending::sent`] is called gives the contact its send back.
+pub struct Sending<'a> {
+    limits: &'a Limits,
+    contact: String,
+    at: DateTime<Utc>,
+    attempts: u32,
+    sent: bool,
+}
+
+
+impl Sending<'_> {
+    /// The wrong codes the contact already tried, which the new code starts from
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Counts the code towards the contact's quota
+    pub fn sent(mut self) {
+        self.sent = true;
+    }
+}
+
+
+impl Drop for Sending<'_> {
+    fn drop(&mut self) {
+        if self.sent {
+            return
+        }
+        if let Ok(mut sent) = self.limits.sent.lock() {
+            if let Some(times) = sent.get_mut(&self.contact) {
+                times.retain(|time| *time != self.at);
+            }
+        }
+    }
+}
+
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {max_attempts: MAX_ATTEMPTS, cooldown: COOLDOWN, hourly_quota: HOURLY_QUOTA, sent: Default::default(), wrong: Default::default()}
+    }
+}
+
+
+impl Limits {
+    /// Holds a send to `contact`, unless the last code was sent too recently or the hourly quota is used up
+    ///
+    /// The send is held while the code is sent, so concurrent requests cannot go over the quota,
+    /// and given back if sending fails.
+    pub fn send(&self, contact: &str) -> Result<Sending<'_>, Error> {
+        let now = Utc::now();
+        let hour_ago = now - Duration::hours(1);
+        let mut sent = self.sent.lock().map_err(|_| Error::internal("the verification limits lock is poisoned"))?;
+        // Forget the contacts that have not been sent a code for an hour
+        sent.retain(|_, times| {
+            times.retain(|time| *time > hour_ago);
+            !times.is_empty()
+        });
+        let times = sent.entry(contact.to_string()).or_default();
+        if times.last().is_some_and(|last| now < *last + Duration::seconds(self.cooldown as i64)) {
+            return Err(Error::ResendTooSoon)
+        }
+        if times.len() >= self.hourly_quota {
+            return Err(Error::QuotaExceeded)
+        }
+        times.push(now);
+        drop(sent);
+        let attempts = self.attempts(contact, hour_ago)?;
+        Ok(Sending {limits: self, contact: contact.to_string(), at: now, attempts, sent: false})
+    }
+
+    /// The wrong codes `contact` tried within the last hour
+    fn attempts(&self, contact: &str, hour_ago: DateTime<Utc>) -> Result<u32, Error> {
+        let mut wrong = self.wrong.lock().map_err(|_| Error::internal("the verification limits lock is poisoned"))?;
+        wrong.retain(|_, (_, last)| *last > hour_ago);
+        Ok(wrong.get(contact).map_or(0, |(attempts, _)| *attempts))
+    }
+
+    /// Forgets the wrong codes of a contact that was verified
+    pub fn verified(&self, contact: &str) {
+        if let Ok(mut wrong) = self.wrong.lock() {
+            wrong.remove(contact);
+        }
+    }
+
+    /// Counts a wrong code against a verification in the store, which invalidates it once it ran out of attempts
+    ///
+    /// A verification used or replaced since it was read is not brought back, the count fails instead.
+    pub async fn reject<DB: CountWrongCode<Verification>>(&self, verification: &Verification, db: &DB) -> Error {
+        let attempts = match db.count_wrong_code(&verification.id, self.max_attempts).await {
+            Ok(attempts) => attempts,
+            Err(err) => return Error::err(err)
+        };
+        if let Ok(mut wrong) = self.wrong.lock() {
+            let count = wrong.entry(verification.owner_contact.as_str().to_string()).or_insert((0, Utc::now()));
+            *count = (count.0.max(attempts), Utc::now());
+        }
+        match attempts >= self.max_attempts {
+            true => Error::TooManyAttempts,
+            false => Error::InvalidCode
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::database::memory::Memory;
+    use crate::ports::outputs::database::{CreateItem, GetItem, DeleteItem};
+    use crate::domain::types::{Id, Either, EmailAddress, Key};
+
+    #[test]
+    fn test_send() {
+        let limits = Limits {cooldown: 0, hourly_quota: 2, ..Default::default()};
+        limits.send("test@example.com").unwrap().sent();
+        limits.send("test@example.com").unwrap().sent();
+        assert!(matches!(limits.send("test@example.com"), Err(Error::QuotaExceeded)));
+        limits.send("other@example.com").unwrap().sent();
+    }
+
+    #[test]
+    fn test_failed_send_is_given_back() {
+        let limits = Limits {cooldown: 0, hourly_quota: 1, ..Default::default()};
+        let sending = limits.send("test@example.com").unwrap();
+        // A concurrent send is refused while the first is held
+        assert!(matches!(limits.send("test@example.com"), Err(Error::QuotaExceeded)));
+        drop(sending);
+        limits.send("test@example.com").unwrap().sent();
+        assert!(matches!(limits.send("test@example.com"), Err(Error::QuotaExceeded)));
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let limits = Limits::default();
+        limits.send("test@example.com").unwrap().sent();
+        assert!(matches!(limits.send("test@example.com"), Err(Error::ResendTooSoon)));
+        limits.send("other@example.com").unwrap().sent();
+    }
+
+    #[tokio::test]
+    async fn test_reject() {
+        let db = Memory::default();
+        let limits = Limits {max_attempts: 2, ..Default::default()};
+        let verification = Verification {
+            owner_contact: Either::Right(EmailAddress::New("test@example.com".parse().unwrap())),
+            id: Id::default(),
+            code: String::from("123456"),
+            expires: Utc::now() + Duration::minutes(5),
+            attempts: 0,
+            token: String::new(),
+        };
+        let contact = verification.owner_contact.clone();
+        let key = Key::Pk(&contact);
+        db.create_item(verification.clone()).await.unwrap();
+
+        assert!(matches!(limits.reject(&verification, &db).await, Error::InvalidCode));
+        assert_eq!(GetItem::<Verification>::get_item(&db, key.clone()).await.unwrap().attempts, 1);
+        // The stale copy does not undo the count
+        assert!(matches!(limits.reject(&verification, &db).await, Error::TooManyAttempts));
+        assert!(GetItem::<Verification>::get_item(&db, key).await.is_err());
+
+        // Nor does it bring back a verification that was used in the meantime
+        let verification = Verification {id: Id::default(), ..verification};
+        db.create_item(verification.clone()).await.unwrap();
+        DeleteItem::<Verification>::delete_item(&db, Key::Pk(&contact)).await.unwrap();
+        assert!(!matches!(limits.reject(&verification, &db).await, Error::InvalidCode | Error::TooManyAttempts));
+        assert!(GetItem::<Verification>::get_item(&db, Key::Pk(&contact)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_attempts_carry_over_resends() {
+        let db = Memory::default();
+        let limits = Limits {cooldown: 0, max_attempts: 3, ..Default::default()};
+        let contact = "test@example.com";
+        let verification = Verification {
+            owner_contact: Either::Right(EmailAddress::New(contact.parse().unwrap())),
+            id: Id::default(),
+            code: String::from("123456"),
+            expires: Utc::now() + Duration::minutes(5),
+            attempts: limits.send(contact).unwrap().attempts(),
+            token: String::new(),
+        };
+        db.create_item(verification.clone()).await.unwrap();
+        assert!(matches!(limits.reject(&verification, &db).await, Error::InvalidCode));
+        assert!(matches!(limits.reject(&verification, &db).await, Error::InvalidCode));
+
+        // A new code starts from the wrong codes tried against the one it replaces
+        let sending = limits.send(contact).unwrap();
+        assert_eq!(sending.attempts(), 2);
+        let resent = Verification {id: Id::default(), attempts: sending.attempts(), ..verification};
+        sending.sent();
+        db.create_item(resent.clone()).await.unwrap();
+        assert!(matches!(limits.reject(&resent, &db).await, Error::TooManyAttempts));
+
+        limits.verified(contact);
+        assert_eq!(limits.send(contact).unwrap().attempts(), 0);
+    }
+}
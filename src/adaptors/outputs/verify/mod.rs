@@ -3,6 +3,18 @@ mod twilio;
 #[cfg(all(feature = "smtp", feature = "email"))]
 mod smtp;
 mod error;
+mod limits;
 
+/// SMTP handles email verification whenever it is enabled, phone verification is only supported through Twilio.
+///
+/// There is none without either adaptor, embedders then bring their own verifyer.
+#[cfg(all(feature = "smtp", feature = "email", not(feature = "phone")))]
+pub type Verifyer = smtp::Smtp;
+#[cfg(all(any(feature = "twilio-email", feature = "twilio-phone"), not(all(feature = "smtp", feature = "email", not(feature = "phone")))))]
 pub type Verifyer = twilio::Twilio;
-pub use error::*;
\ No newline at end of file
+#[cfg(any(feature = "twilio-email", feature = "twilio-phone"))]
+pub use twilio::Twilio;
+#[cfg(all(feature = "smtp", feature = "email"))]
+pub use smtp::Smtp;
+pub use error::*;
+pub use limits::{Limits, Sending};
\ No newline at end of file
@@ -1,7 +1,8 @@
 use serde::{Serialize, Deserialize, Deserializer, de::{self, MapAccess, Visitor}};
 use std::fmt;
 use lettre::{message::Mailbox, transport::smtp::authentication::Credentials, Address};
-use super::Smtp;
+use crate::domain::types::{deserialize_secret, CodeFormat};
+use super::{Smtp, super::Limits};
 
 impl<'de> Deserialize<'de> for Smtp {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -12,9 +13,9 @@ impl<'de> Deserialize<'de> for Smtp {
 
         #[derive(Deserialize)]
         struct Cred {
-            #[serde(alias = "user", alias = "user_name")]
+            #[serde(alias = "user", alias = "user_name", deserialize_with = "deserialize_secret")]
             name: String,
-            #[serde(alias = "secret")]
+            #[serde(alias = "secret", deserialize_with = "deserialize_secret")]
             password: String
         }
 
@@ -38,6 +39,8 @@ impl<'de> Deserialize<'de> for Smtp {
                 let mut credentials = None;
                 let mut url = Option::<String>::None;
                 let mut sender = None;
+                let mut limits = None;
+                let mut code = None;
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -59,6 +62,12 @@ impl<'de> Deserialize<'de> for Smtp {
                         "sender" => {
                             sender = Some(map.next_value()?);
                         }
+                        "limits" => {
+                            limits = Some(map.next_value::<Limits>()?);
+                        }
+                        "code" => {
+                            code = Some(map.next_value::<CodeFormat>()?);
+                        }
                         _ => {
                             let _: de::IgnoredAny = map.next_value()?;
                         }
@@ -68,11 +77,14 @@ impl<'de> Deserialize<'de> for Smtp {
                 let url = url.ok_or_else(|| de::Error::missing_field("url"))?;
                 let sender = sender.ok_or_else(|| de::Error::missing_field("sender"))?;
 
-                Smtp::new(url, credentials, sender).map_err(de::Error::custom)
+                let mut smtp = Smtp::new(url, credentials, sender).map_err(de::Error::custom)?;
+                smtp.limits = limits.unwrap_or_default();
+                smtp.code = code.unwrap_or_default();
+                Ok(smtp)
             }
         }
 
-        const FIELDS: &'static [&'static str] = &["credentials", "url", "sender"];
+        const FIELDS: &'static [&'static str] = &["credentials", "url", "sender", "limits", "code"];
         deserializer.deserialize_struct("Smtp", FIELDS, SmtpVisitor)
     }
 }
@@ -0,0 +1,24 @@
+use crate::domain::types::{Contact, Error as DomainError};
+use crate::ports::outputs::verify::Invite;
+use lettre::message::Mailbox;
+use super::{Smtp, Error};
+
+
+impl Invite for Smtp {
+    type Error = Error;
+
+    async fn invite(&self, contact: &Contact, organisation: &str, link: &str) -> Result<(), Self::Error> {
+        let email = match contact {
+            Contact::Email(email) | Contact::Both(_, email) => email,
+            Contact::Phone(_) => return Err(Error::err(DomainError::validation("phone", "invitations can only be sent by email")))
+        };
+
+        // Create email content
+        let to = Mailbox::new(None, email.clone().into());
+        let subject = format!("Invitation to join {}", organisation);
+        let body = self.create_invitation_email(organisation, link);
+
+        // Send the email
+        self.send_email(to, subject, body).await
+    }
+}
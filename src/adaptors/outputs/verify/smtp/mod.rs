@@ -1,10 +1,11 @@
 use lettre::{message::{Mailbox, Message, SinglePart}, transport::smtp::{authentication::Credentials, PoolConfig}, Address, AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
-use crate::domain::types::EmailAddress;
+use crate::domain::types::{EmailAddress, CodeFormat};
 use serde::Serialize;
-use super::Error;
+use super::{Error, Limits};
 use std::fmt;
 
 mod deserialize;
+mod invite;
 mod verify;
 
 type Client = AsyncSmtpTransport<Tokio1Executor>;
@@ -15,6 +16,9 @@ pub struct Smtp {
     url: String,
     credentials: Option<Credentials>,
     sender: Mailbox,
+    limits: Limits,
+    /// How the emailed codes look
+    code: CodeFormat,
     #[serde(skip)]
     client: Client
 }
@@ -22,6 +26,7 @@ pub struct Smtp {
 impl Smtp {
 
     const TEMPLATE: &'static str = include_str!("./template.html");
+    const INVITATION: &'static str = include_str!("./invitation.html");
 
     /// Creates a new SMTP client from the given configuration
     pub fn new(url: String, credentials: Option<Credentials>, sender: Mailbox) -> Result<Self, Error> {
@@ -35,6 +40,8 @@ impl Smtp {
             url,
             credentials,
             sender,
+            limits: Limits::default(),
+            code: CodeFormat::default(),
             client
         })
     }
@@ -57,6 +64,29 @@ impl Smtp {
         let body = Self::TEMPLATE.replace("{{magic_link}}", link);
         body.replace("{{code}}", code)
     }
+
+    /// Creates an invitation email for the given organisation, whose name is chosen by its owners
+    pub fn create_invitation_email(&self, organisation: &str, link: &str) -> String {
+        let body = Self::INVITATION.replace("{{link}}", &escape(link));
+        body.replace("{{organisation}}", &escape(organisation))
+    }
+}
+
+
+/// Escapes text for HTML, so it cannot add markup to an email
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
 }
 
 impl Default for Smtp {
@@ -74,7 +104,47 @@ impl Default for Smtp {
             url,
             credentials,
             sender,
+            limits: Limits::default(),
+            code: CodeFormat::default(),
             client
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::database::memory::Memory;
+    use crate::ports::outputs::{database::{CreateItem, GetItem}, verify::Verify};
+    use crate::domain::types::{Verification, EmailAddress, Either, Key, Id};
+    use chrono::{Duration, Utc};
+
+    #[tokio::test]
+    async fn test_invitation_escapes_organisation() {
+        let body = Smtp::default().create_invitation_email("<a href=\"https://evil.example\">Acme</a> & Co", "https://auth.example.com/invitations/1");
+        assert!(!body.contains("<a href=\"https://evil.example\">"));
+        assert!(body.contains("&lt;a href=&quot;https://evil.example&quot;&gt;Acme&lt;/a&gt; &amp; Co"));
+    }
+
+    #[tokio::test]
+    async fn test_expired_code() {
+        let db = Memory::default();
+        let email = EmailAddress::New("test@example.com".parse().unwrap());
+        let contact = Either::Right(email.clone());
+        let verification = Verification {
+            owner_contact: contact.clone(),
+            id: Id::default(),
+            code: String::from("123456"),
+            expires: Utc::now() - Duration::seconds(1),
+            attempts: 0,
+            token: String::new(),
+        };
+        db.create_item(verification).await.unwrap();
+
+        // Even the right code is turned down once it expired, and the verification is dropped
+        let result = Smtp::default().verify(&email, Either::Left("123456"), &db).await;
+        assert!(matches!(result, Err(Error::ExpiredCode)));
+        assert!(GetItem::<Verification>::get_item(&db, Key::Pk(&contact)).await.is_err());
+    }
+}
@@ -1,14 +1,22 @@
 use crate::domain::types::{EmailAddress, Verification, Either, Key, Id};
-use crate::ports::outputs::database::{CreateItem, GetItem};
+use crate::ports::outputs::{database::{CreateItem, GetItem, DeleteItem}, wrong_code::CountWrongCode};
 use crate::ports::{outputs::verify::{Verify, Code}};
 use lettre::message::Mailbox;
+use chrono::Utc;
 use super::{Smtp, Error};
 
 
-const SUBJECT: &'static str = "Verification";
+const SUBJECT: &str = "Verification";
 
 
 
+/// The link that verifies `contact` with the secret `token`, the contact is encoded so `+`, `&` or `#` stay in it
+fn magic_link(base_url: &str, token: &str, contact: &str) -> Result<String, Error> {
+    let query = serde_urlencoded::to_string([("contact", contact)]).map_err(Error::internal)?;
+    Ok(format!("{}/{}?{}", base_url.trim_end_matches('/'), token, query))
+}
+
+
 impl Verify<EmailAddress> for Smtp {
     type Error = Error;
     type Channel = ();  // No channel selection needed for email
@@ -21,20 +29,22 @@ impl Verify<EmailAddress> for Smtp {
         base_url: &str,
         db: &DB
     ) -> Result<(), Self::Error> {
-        // Create a new verification code
-        let mut verification = Self::Verification::new(email, None, Id::default());
+        let sending = self.limits.send(email.as_ref())?;
+
+        // Create a new verification code, carrying over the wrong codes tried against the last one
+        let mut verification = <Self::Verification as Code<EmailAddress>>::generate(email, None, Id::default(), &self.code);
+        verification.attempts = sending.attempts();
         let code = Code::<EmailAddress>::as_str(&verification);
-        let base_url = base_url.trim_end_matches('/');
-        let contact: &str = email.as_ref();
-        let link = format!("{}/{}?contact={}", base_url, verification.id.to_hex(), contact);
+        let link = magic_link(base_url, &verification.token, email.as_ref())?;
         
         // Create email content
         let to = Mailbox::new(None, email.clone().into());
         let subject = String::from(SUBJECT);
-        let body = self.create_verification_email(&code, &link);
+        let body = self.create_verification_email(code, &link);
         
         // Send the email
         self.send_email(to, subject, body).await?;
+        sending.sent();
         
         // Store the verification in the database
         db.create_item(verification).await.map_err(Error::err)?;
@@ -42,29 +52,47 @@ impl Verify<EmailAddress> for Smtp {
         Ok(())
     }
 
-    async fn verify<DB: GetItem<Self::Verification>>(
+    async fn verify<DB: GetItem<Self::Verification> + CountWrongCode<Self::Verification> + DeleteItem<Self::Verification>>(
         &self,
         email: &EmailAddress,
-        code: Either<&str, &Id>,
+        code: Either<&str, &str>,
         db: &DB
     ) -> Result<(), Self::Error> {
         // Retrieve the verification from the database
         let contact = Either::Right(email.clone());
         let key = Key::Pk(&contact);
-        let verification = db.get_item(key).await.map_err(Self::Error::err)?;
+        let verification = db.get_item(key.clone()).await.map_err(Self::Error::err)?;
+
+        if verification.expires <= Utc::now() {
+            db.delete_item(key).await.map_err(Self::Error::err)?;
+            return Err(Error::ExpiredCode);
+        }
 
         let valid = match code {
-            Either::Left(code) => {
-                let saved_code = Code::<EmailAddress>::as_str(&verification);
-                saved_code.as_str() == code
-            },
-            Either::Right(id) => verification.id == *id,
+            Either::Left(code) => Code::<EmailAddress>::matches(&verification, code),
+            Either::Right(token) => verification.link_matches(token),
         };
 
         if !valid {
-            return Err(Error::InvalidCode);
+            return Err(self.limits.reject(&verification, db).await);
         }
-        // If we get here, the code is valid
+        // If we get here, the code is valid and used up
+        db.delete_item(key).await.map_err(Self::Error::err)?;
+        self.limits.verified(email.as_ref());
         Ok(())
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_magic_link_encodes_the_contact() {
+        let link = magic_link("https://auth.example.com/verify/", "c2VjcmV0", "jane+hive&co#1@example.com").unwrap();
+        assert_eq!(link, "https://auth.example.com/verify/c2VjcmV0?contact=jane%2Bhive%26co%231%40example.com");
+        let query = link.split_once('?').unwrap().1;
+        assert_eq!(serde_urlencoded::from_str::<Vec<(String, String)>>(query).unwrap(), vec![("contact".to_string(), "jane+hive&co#1@example.com".to_string())]);
+    }
+}
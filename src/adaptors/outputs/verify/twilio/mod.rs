@@ -1,10 +1,17 @@
-use crate::ports::outputs::{database::{CreateItem, DeleteItem, GetItem, GetItems, Item}, verify::{self, Verify, Code}};
-use crate::domain::types::{Verification, Phone, EmailAddress, VerificationMedia, Contact, Key, Either};
+use crate::ports::outputs::{database::{CreateItem, DeleteItem, GetItem, GetItems, Item}, verify::{self, Verify, Invite, Code}, wrong_code::CountWrongCode};
+use crate::domain::types::{Verification, Phone, EmailAddress, VerificationMedia, Contact, CodeFormat, Key, Either, Id, deserialize_secret};
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 use reqwest::Client;
+use chrono::Utc;
 use std::ops::Deref;
-use super::Error;
+use super::{Error, Limits};
+#[cfg(all(feature = "smtp", feature = "email"))]
+use super::smtp::Smtp;
+
+
+/// Twilio's Programmable Messaging API, which texts the invitations
+const MESSAGING_URL: &str = "https://api.twilio.com/2010-04-01";
 
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -15,36 +22,54 @@ pub struct Twilio {
     friendly_name: Option<String>,
     base_url: String,
     custom_code: bool,
+    #[serde(default)]
+    limits: Limits,
+    /// How custom codes look on each channel, six digits on any other
+    #[serde(default)]
+    codes: HashMap<VerificationMedia, CodeFormat>,
+    /// Texts invitations to phone numbers, none are texted when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    messaging: Option<Messaging>,
+    /// Emails invitations, which Twilio Verify cannot send
+    #[cfg(all(feature = "smtp", feature = "email"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    smtp: Option<Smtp>,
     #[serde(skip)]
     client: Client
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Messaging {
+    /// The number, or the SID of the messaging service, invitations are sent from
+    from: String,
+    #[serde(default = "messaging_url")]
+    base_url: String,
+}
+
+fn messaging_url() -> String {
+    MESSAGING_URL.to_string()
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(default)]
 struct Credentials {
-    #[serde(alias = "user_name")]
+    #[serde(alias = "user_name", deserialize_with = "deserialize_secret")]
     username: String,
+    #[serde(deserialize_with = "deserialize_secret")]
     password: String
 }
 
 #[derive(Deserialize, Default)]
 #[serde(default)]
 struct Response {
-    #[serde(alias = "sid")]
-    id: String,
     valid: bool
 }
 
 
 impl Twilio {
-    fn code(verification: Verification) -> String {
-        let mut code = String::new();
-        if verification.code < 100000 {
-            code.push('0');
-        }
-        let string = verification.code.to_string();
-        code.push_str(&string);
-        code
+    /// How custom codes sent over `channel` look
+    fn format(&self, channel: VerificationMedia) -> CodeFormat {
+        self.codes.get(&channel).copied().unwrap_or_default()
     }
 
     async fn initiate_request(&self, form: &HashMap<&str, &str>) -> Result<Response, Error> {
@@ -59,6 +84,24 @@ impl Twilio {
         Ok(res.json().await.map_err(Error::internal)?)
     }
 
+    /// Texts `body` to `to` through the Messaging API
+    async fn message(&self, messaging: &Messaging, to: &str, body: &str) -> Result<(), Error> {
+        let base_url = messaging.base_url.as_str().trim_end_matches("/");
+        let url = format!("{base_url}/Accounts/{}/Messages.json", self.account_sid);
+        let mut form = HashMap::from([("To", to), ("Body", body)]);
+        match messaging.from.starts_with("MG") {
+            true => form.insert("MessagingServiceSid", messaging.from.as_str()),
+            false => form.insert("From", messaging.from.as_str())
+        };
+        let (username, password) = (self.credentials.username.as_str(), Some(self.credentials.password.as_str()));
+        let res = self.client.post(url).basic_auth(username, password).form(&form).send().await.map_err(Error::internal)?;
+        if res.status() != 201 {
+            let err = format!("{:?}", res);
+            Err(Error::internal(err))?
+        }
+        Ok(())
+    }
+
     async fn verify_request(&self, form: &HashMap<&str, &str>, id: Option<&str>) -> Result<(), Error> {
         let base_url = self.base_url.as_str().trim_end_matches("/");
         let url = match id {
@@ -80,15 +123,18 @@ impl Twilio {
 impl Verify<Phone> for Twilio {
     type Error = Error;
     type Channel = VerificationMedia;
-    type Verification = Verification<String>;
+    type Verification = Verification<Id>;
 
     async fn initiate<DB: CreateItem<Self::Verification>>(
             &self,
             contact: &Phone, 
             channel: Self::Channel, 
+            _: &str,
             db: &DB
         ) -> Result<(), Self::Error> {
         let receiver = contact.as_ref();
+        let sending = self.limits.send(receiver)?;
+        let format = self.format(channel);
         let channel = channel.to_string();
         let mut form = HashMap::new();
         if let Some(name) = &self.friendly_name {
@@ -98,23 +144,29 @@ impl Verify<Phone> for Twilio {
         form.insert("Channel", channel.as_str());
         if !self.custom_code {
             self.initiate_request(&form).await?;
+            sending.sent();
             return Ok(())
         }
-        let mut verification = Self::Verification::new(contact, None, String::new());
-        let code = Code::<Phone>::as_str(&verification);
+        let mut verification = <Self::Verification as Code<Phone>>::generate(contact, None, Id::default(), &format);
+        verification.attempts = sending.attempts();
+        let code = Code::<Phone>::as_str(&verification).to_string();
         form.insert("CustomCode", code.as_str());
-        let res = self.initiate_request(&form).await?;
-        verification.id = res.id;
+        self.initiate_request(&form).await?;
+        sending.sent();
         db.create_item(verification).await.map_err(Self::Error::err)?;
         Ok(())
     }
 
-    async fn verify<DB: GetItem<Self::Verification>>(
+    async fn verify<DB: GetItem<Self::Verification> + CountWrongCode<Self::Verification> + DeleteItem<Self::Verification>>(
             &self,
             contact: &Phone, 
-            code: &str, 
+            code: Either<&str, &str>,
             db: &DB
         ) -> Result<(), Self::Error> {
+        let code = match code {
+            Either::Left(code) => code,
+            Either::Right(_) => return Err(Error::InvalidCode)
+        };
         if !self.custom_code {
             let contact = contact.as_ref();
             let mut form = HashMap::new();
@@ -124,14 +176,20 @@ impl Verify<Phone> for Twilio {
         }
         let contact = Either::Left(contact.clone());
         let key = Key::Pk(&contact);
-        let verification = db.get_item(key).await.map_err(Self::Error::err)?;
-        let saved_code = Code::<Phone>::as_str(&verification);
-        if saved_code.as_str() != code {
-            return Err(Error::InvalidCode)
+        let verification = db.get_item(key.clone()).await.map_err(Self::Error::err)?;
+        if verification.expires <= Utc::now() {
+            db.delete_item(key).await.map_err(Self::Error::err)?;
+            return Err(Error::ExpiredCode)
         }
+        if !Code::<Phone>::matches(&verification, code) {
+            return Err(self.limits.reject(&verification, db).await)
+        }
+        // Used up before Twilio hears of it, so it cannot be approved twice
+        db.delete_item(key).await.map_err(Self::Error::err)?;
+        self.limits.verified(contact.as_str());
+        // Twilio takes the contact in place of the verification SID
         let form = [("Status", "approved")].into();
-        let id = Some(verification.id.as_str());
-        self.verify_request(&form, id).await?;
+        self.verify_request(&form, Some(contact.as_str())).await?;
         Ok(())
     }
 }
@@ -141,7 +199,7 @@ impl Verify<Phone> for Twilio {
 impl Verify<EmailAddress> for Twilio {
     type Error = Error;
     type Channel = VerificationMedia;
-    type Verification = Verification<String>;
+    type Verification = Verification<Id>;
 
     async fn initiate<DB: CreateItem<Self::Verification>>(
             &self,
@@ -151,6 +209,8 @@ impl Verify<EmailAddress> for Twilio {
             db: &DB
         ) -> Result<(), Self::Error> {
         let receiver = contact.as_ref();
+        let sending = self.limits.send(receiver)?;
+        let format = self.format(channel);
         let channel = channel.to_string();
         let mut form = HashMap::new();
         if let Some(name) = &self.friendly_name {
@@ -160,21 +220,23 @@ impl Verify<EmailAddress> for Twilio {
         form.insert("Channel", channel.as_str());
         if !self.custom_code {
             self.initiate_request(&form).await?;
+            sending.sent();
             return Ok(())
         }
-        let mut verification = Self::Verification::new(contact, None, String::new());
-        let code = Code::<EmailAddress>::as_str(&verification);
+        let mut verification = <Self::Verification as Code<EmailAddress>>::generate(contact, None, Id::default(), &format);
+        verification.attempts = sending.attempts();
+        let code = Code::<EmailAddress>::as_str(&verification).to_string();
         form.insert("CustomCode", code.as_str());
-        let res = self.initiate_request(&form).await?;
-        verification.id = res.id;
+        self.initiate_request(&form).await?;
+        sending.sent();
         db.create_item(verification).await.map_err(Self::Error::err)?;
         Ok(())
     }
 
-    async fn verify<DB: GetItem<Self::Verification>>(
+    async fn verify<DB: GetItem<Self::Verification> + CountWrongCode<Self::Verification> + DeleteItem<Self::Verification>>(
             &self,
             contact: &EmailAddress,
-            code: Either<&str, &<Self::Verification as Code<EmailAddress>>::Id>,
+            code: Either<&str, &str>,
             db: &DB
         ) -> Result<(), Self::Error> {
         let code = match code {
@@ -190,14 +252,94 @@ impl Verify<EmailAddress> for Twilio {
         }
         let contact = Either::Right(contact.clone());
         let key = Key::Pk(&contact);
-        let verification = db.get_item(key).await.map_err(Self::Error::err)?;
-        let saved_code = Code::<EmailAddress>::as_str(&verification);
-        if saved_code.as_str() != code {
-            return Err(Error::InvalidCode)
+        let verification = db.get_item(key.clone()).await.map_err(Self::Error::err)?;
+        if verification.expires <= Utc::now() {
+            db.delete_item(key).await.map_err(Self::Error::err)?;
+            return Err(Error::ExpiredCode)
+        }
+        if !Code::<EmailAddress>::matches(&verification, code) {
+            return Err(self.limits.reject(&verification, db).await)
         }
+        // Used up before Twilio hears of it, so it cannot be approved twice
+        db.delete_item(key).await.map_err(Self::Error::err)?;
+        self.limits.verified(contact.as_str());
+        // Twilio takes the contact in place of the verification SID
         let form = [("Status", "approved")].into();
-        let id = Some(verification.id.as_str());
-        self.verify_request(&form, id).await?;
+        self.verify_request(&form, Some(contact.as_str())).await?;
         Ok(())
     }
 }
+
+
+/// Twilio Verify only sends one-time codes, which cannot carry an invitation. Invitations are
+/// texted through the Messaging API when `messaging` is set, and emailed through `smtp` when set.
+impl Invite for Twilio {
+    type Error = Error;
+
+    async fn invite(&self, contact: &Contact, organisation: &str, link: &str) -> Result<(), Self::Error> {
+        #[cfg(all(feature = "smtp", feature = "email"))]
+        if let (Some(smtp), Contact::Email(_) | Contact::Both(..)) = (&self.smtp, contact) {
+            return smtp.invite(contact, organisation, link).await
+        }
+        match (&self.messaging, contact) {
+            (Some(messaging), Contact::Phone(phone) | Contact::Both(phone, _)) => {
+                let body = format!("You are invited to join {} on {}", organisation, link);
+                self.message(messaging, phone, &body).await
+            },
+            _ => Err(Error::InvitationsUnsupported)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    /// Answers one request with `201 Created` and returns it
+    async fn respond_once(listener: TcpListener) -> String {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = stream.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head.lines()
+                    .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|length| length.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+                if body.len() >= length {
+                    break
+                }
+            }
+        }
+        stream.write_all(b"HTTP/1.1 201 Created\r\ncontent-length: 2\r\n\r\n{}").await.unwrap();
+        String::from_utf8(request).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_invitation_is_texted() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let twilio = Twilio {
+            account_sid: "AC123".to_string(),
+            messaging: Some(Messaging {from: "+15005550006".to_string(), base_url}),
+            ..Default::default()
+        };
+        let contact = Contact::Phone(Phone::New("+15005550001".to_string()));
+        let (request, sent) = tokio::join!(respond_once(listener), twilio.invite(&contact, "Acme", "https://auth.example.com/invitations/1"));
+        sent.unwrap();
+        assert!(request.starts_with("POST /Accounts/AC123/Messages.json "));
+        assert!(request.contains("To=%2B15005550001"));
+        assert!(request.contains("From=%2B15005550006"));
+        assert!(request.contains("Acme"));
+    }
+
+    #[tokio::test]
+    async fn test_invitation_needs_a_channel() {
+        let contact = Contact::Phone(Phone::New("+15005550001".to_string()));
+        assert!(matches!(Twilio::default().invite(&contact, "Acme", "https://auth.example.com/invitations/1").await, Err(Error::InvitationsUnsupported)));
+    }
+}
@@ -1,5 +1,5 @@
-use super::super::types::{Token, User, Paseto, Key, Audience, Error as DomainError};
-use crate::ports::{Error, outputs::database::{Item, CreateItem, GetItem}};
+use super::super::types::{Token, User, Paseto, Key, Audience, Revocation, Error as DomainError};
+use crate::ports::{Error, outputs::database::{Item, CreateItem, GetItem, GetItems}};
 use argon2::{PasswordHasher, PasswordVerifier};
 use super::{Password, Paseto as PasetoTrait};
 
@@ -9,7 +9,10 @@ pub trait Authentication: Sized + Item {
     type QueryKey;
     async fn register<DB: CreateItem<Self>, H: PasswordHasher>(self, db: &DB, hasher: &H, paseto: &Paseto, issuer: String, audience: Audience) -> Result<Token, Self::Error>;
     async fn authenticate<DB: GetItem<Self>, V: PasswordVerifier>(query_key: &Self::QueryKey, password: &str, db: &DB, verifier: &V, paseto: &Paseto, issuer: String, audience: Audience) -> Result<Token, Self::Error>;
-    async fn authorize(token: &str, paseto: &Paseto) -> Result<<Self as Item>::PK, Self::Error>;
+    /// Checks that the token is correctly signed, unexpired and was not revoked.
+    async fn authorize<DB: GetItems<Revocation, Filter = ()>>(token: &str, paseto: &Paseto, db: &DB) -> Result<<Self as Item>::PK, Self::Error>;
+    /// Like `authorize`, returning the whole token for checks on its claims.
+    async fn verify<DB: GetItems<Revocation, Filter = ()>>(token: &str, paseto: &Paseto, db: &DB) -> Result<Token, Self::Error>;
 }
 
 
@@ -21,7 +24,7 @@ impl Authentication for User {
     async fn register<DB: CreateItem<Self>, H: PasswordHasher>(mut self, db: &DB, hasher: &H, paseto: &Paseto, issuer: String, audience: Audience) -> Result<Token, Self::Error> {
         self.password = self.password.hash(hasher)?;
         let mut user = db.create_item(self).await?;
-        let keys = &paseto.keys;
+        let keys = &paseto.keys();
         let ttl = paseto.ttl;
         let token = user.token(issuer, audience, ttl).try_sign(keys)?;
         Ok(token)
@@ -33,18 +36,37 @@ impl Authentication for User {
         let user = db.get_item(key).await?;
         let hash = &user.password;
         password.verify(hash, verifier)?;
-        let keys = &paseto.keys;
+        let keys = &paseto.keys();
         let ttl = paseto.ttl;
         let token = user.token(issuer, audience, ttl).try_sign(keys)?;
         Ok(token)
     }
 
-    async fn authorize(signature: &str, paseto: &Paseto) -> Result<<Self as Item>::PK, Self::Error> {
-        let keys = &paseto.keys;
-        let token = Token::try_verify(signature, keys)?;
-        if token.expired() {
-            Err(DomainError::TokenExpired)?
-        }
+    async fn authorize<DB: GetItems<Revocation, Filter = ()>>(signature: &str, paseto: &Paseto, db: &DB) -> Result<<Self as Item>::PK, Self::Error> {
+        let token = verify(signature, paseto, db).await?;
         Ok(token.subject)
     }
+
+    async fn verify<DB: GetItems<Revocation, Filter = ()>>(signature: &str, paseto: &Paseto, db: &DB) -> Result<Token, Self::Error> {
+        verify(signature, paseto, db).await
+    }
+}
+
+
+/// Verifies a token, making sure it is unexpired, already valid and was not revoked.
+pub(super) async fn verify<DB: GetItems<Revocation, Filter = ()>>(signature: &str, paseto: &Paseto, db: &DB) -> Result<Token, Error> {
+    let keys = &paseto.keys();
+    let mut token = Token::try_verify(signature, keys)?;
+    if token.expired() {
+        Err(DomainError::TokenExpired)?
+    }
+    if token.premature() {
+        Err(DomainError::InvalidToken)?
+    }
+    let revocations = db.get_items(Key::Sk(&token.subject), ()).await?;
+    if revocations.iter().any(|revocation| revocation.covers(&token)) {
+        Err(DomainError::TokenRevoked)?
+    }
+    token.signature = Some(signature.to_string());
+    Ok(token)
 }
\ No newline at end of file
@@ -0,0 +1,169 @@
+use crate::ports::{Error, outputs::{database::{Item, CreateItem, GetItem, DeleteItem}, verify::Invite}};
+use super::super::types::{Invitation, Organisation, Member, User, Contact, EmailAddress, Phone, Id, Key, Error as DomainError};
+
+
+/// A trait for inviting contacts to join an organisation.
+///
+/// Delivering the invitation is left to an `Invite` implementation, the invitee answers it
+/// while signed in to an account holding the invited contact.
+pub trait Invitations: Sized + Item {
+    type Error;
+
+    /// Stores the invitation and delivers it to the invited contact.
+    ///
+    /// # Arguments
+    ///
+    /// * `organisation` - The organisation the contact is invited to.
+    /// * `invited_by` - The id of the owner sending the invitation.
+    /// * `base_url` - The base URL used to build the invitation link.
+    /// * `db` - The database to store the invitation in.
+    /// * `inviter` - The service delivering the invitation.
+    async fn send<DB, I>(self, organisation: &Organisation, invited_by: &Id, base_url: &str, db: &DB, inviter: &I) -> Result<Self, Self::Error>
+    where
+        DB: GetItem<Self> + CreateItem<Self> + DeleteItem<Self>,
+        I: Invite;
+
+    /// Fetches an invitation addressed to the user, for the page the invitation link leads to.
+    async fn open<DB>(id: &Self::PK, user_id: &Id, db: &DB) -> Result<Self, Self::Error>
+    where
+        DB: GetItem<Self> + DeleteItem<Self> + GetItem<User>;
+
+    /// Makes the user a member of the organisation they were invited to.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Member, Self::Error>` - The new membership.
+    async fn accept<DB>(id: &Self::PK, user_id: &Id, db: &DB) -> Result<Member, Self::Error>
+    where
+        DB: GetItem<Self> + DeleteItem<Self> + GetItem<User> + CreateItem<Member>;
+
+    /// Turns down an invitation addressed to the user.
+    async fn decline<DB>(id: &Self::PK, user_id: &Id, db: &DB) -> Result<(), Self::Error>
+    where
+        DB: GetItem<Self> + DeleteItem<Self> + GetItem<User>;
+
+    /// Withdraws a pending invitation of the given organisation.
+    async fn revoke<DB>(id: &Self::PK, org_id: &Id, db: &DB) -> Result<(), Self::Error>
+    where
+        DB: GetItem<Self> + DeleteItem<Self>;
+}
+
+
+impl Invitation {
+    /// Fetches an invitation addressed to the user, dropping it if it has expired.
+    async fn addressed_to<DB>(id: &Id, user_id: &Id, db: &DB) -> Result<Self, Error>
+    where
+        DB: GetItem<Self> + DeleteItem<Self> + GetItem<User>,
+    {
+        let invitation = GetItem::<Invitation>::get_item(db, Key::Pk(id)).await?;
+        let user = GetItem::<User>::get_item(db, Key::Pk(user_id)).await?;
+        holds(&user.contact, &invitation.contact)?;
+        if invitation.expired() {
+            DeleteItem::<Invitation>::delete_item(db, Key::Pk(id)).await?;
+            Err(DomainError::validation("invitation", "the invitation has expired"))?
+        }
+        Ok(invitation)
+    }
+}
+
+
+/// Checks that the user's contact is the verified counterpart of the invited one.
+fn holds(user: &Contact, invited: &Contact) -> Result<(), DomainError> {
+    match invited {
+        Contact::Email(invited) | Contact::Both(_, invited) => match user {
+            Contact::Email(email) | Contact::Both(_, email) if email[..] == invited[..] => match email {
+                EmailAddress::Verified(_) => Ok(()),
+                EmailAddress::New(_) => Err(DomainError::validation("email", "verify your email address before accepting invitations"))
+            },
+            _ => Err(DomainError::Forbidden)
+        },
+        Contact::Phone(invited) => match user {
+            Contact::Phone(phone) | Contact::Both(phone, _) if phone[..] == invited[..] => match phone {
+                Phone::Verified(_) => Ok(()),
+                Phone::New(_) => Err(DomainError::validation("phone", "verify your phone number before accepting invitations"))
+            },
+            _ => Err(DomainError::Forbidden)
+        }
+    }
+}
+
+
+impl Invitations for Invitation {
+    type Error = Error;
+
+    async fn send<DB, I>(mut self, organisation: &Organisation, invited_by: &Id, base_url: &str, db: &DB, inviter: &I) -> Result<Self, Self::Error>
+    where
+        DB: GetItem<Self> + CreateItem<Self> + DeleteItem<Self>,
+        I: Invite,
+    {
+        if let Contact::Both(..) = self.contact {
+            Err(DomainError::validation("contact", "only one contact can be invited at a time"))?
+        }
+        self.id = Id::default();
+        self.org_id = organisation.id;
+        self.invited_by = *invited_by;
+        self.renew();
+
+        // An expired invitation should not stop the contact from being invited again
+        let sk = (self.org_id, self.contact.clone());
+        if let Ok(previous) = GetItem::<Invitation>::get_item(db, Key::Sk(&sk)).await {
+            if previous.expired() {
+                DeleteItem::<Invitation>::delete_item(db, Key::Pk(&previous.id)).await?;
+            }
+        }
+        let invitation = CreateItem::<Invitation>::create_item(db, self).await?;
+
+        let link = format!("{}/{}", base_url.trim_end_matches('/'), invitation.id.to_hex());
+        if let Err(err) = inviter.invite(&invitation.contact, &organisation.name, &link).await {
+            // An invitation nobody received should not block sending it again
+            DeleteItem::<Invitation>::delete_item(db, Key::Pk(&invitation.id)).await?;
+            Err(err)?
+        }
+        Ok(invitation)
+    }
+
+    async fn open<DB>(id: &Self::PK, user_id: &Id, db: &DB) -> Result<Self, Self::Error>
+    where
+        DB: GetItem<Self> + DeleteItem<Self> + GetItem<User>,
+    {
+        Self::addressed_to(id, user_id, db).await
+    }
+
+    async fn accept<DB>(id: &Self::PK, user_id: &Id, db: &DB) -> Result<Member, Self::Error>
+    where
+        DB: GetItem<Self> + DeleteItem<Self> + GetItem<User> + CreateItem<Member>,
+    {
+        let invitation = Self::addressed_to(id, user_id, db).await?;
+        let member = Member {
+            org_id: invitation.org_id,
+            user_id: *user_id,
+            title: invitation.title,
+            owner: false,
+            roles: invitation.roles,
+        };
+        let member = CreateItem::<Member>::create_item(db, member).await?;
+        DeleteItem::<Invitation>::delete_item(db, Key::Pk(id)).await?;
+        Ok(member)
+    }
+
+    async fn decline<DB>(id: &Self::PK, user_id: &Id, db: &DB) -> Result<(), Self::Error>
+    where
+        DB: GetItem<Self> + DeleteItem<Self> + GetItem<User>,
+    {
+        Self::addressed_to(id, user_id, db).await?;
+        DeleteItem::<Invitation>::delete_item(db, Key::Pk(id)).await?;
+        Ok(())
+    }
+
+    async fn revoke<DB>(id: &Self::PK, org_id: &Id, db: &DB) -> Result<(), Self::Error>
+    where
+        DB: GetItem<Self> + DeleteItem<Self>,
+    {
+        let invitation = GetItem::<Invitation>::get_item(db, Key::Pk(id)).await?;
+        if &invitation.org_id != org_id {
+            Err(DomainError::Forbidden)?
+        }
+        DeleteItem::<Invitation>::delete_item(db, Key::Pk(id)).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use super::super::types::{Throttle, Contact, User, Key, Error as DomainError};
+use crate::ports::{Error, outputs::{attempts::LoginAttempts, database::GetItem}};
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::net::IpAddr;
+
+
+/// A trait for slowing down and locking out repeated failed logins.
+///
+/// Failures are counted against the account and against the client address, so neither guessing
+/// one password nor spraying many accounts from one address goes unchecked.
+pub trait Lockout {
+    /// Runs `login` unless the account or client address has to wait.
+    ///
+    /// A locked out attempt fails with `LockedOut` without being counted or running `login`, so
+    /// no password is hashed for it and retrying never pushes the end of a lockout back. An
+    /// allowed attempt is counted as a failure before `login` runs. A successful login forgets the
+    /// failures of the account and takes its own attempt back from the address, leaving the other
+    /// failures of the address counted.
+    async fn guard<DB, F, T>(&self, contact: &Contact, address: Option<IpAddr>, db: &DB, login: F) -> Result<T, Error>
+    where
+        DB: LoginAttempts + GetItem<User>,
+        F: Future<Output = Result<T, Error>>;
+
+    /// Lifts the lockout of an account, a client address or both.
+    ///
+    /// An account is unlocked whichever of its contacts it is given by.
+    async fn unlock<DB>(contact: Option<&Contact>, address: Option<IpAddr>, db: &DB) -> Result<(), Error>
+    where
+        DB: LoginAttempts + GetItem<User>;
+}
+
+
+impl Lockout for Throttle {
+    async fn guard<DB, F, T>(&self, contact: &Contact, address: Option<IpAddr>, db: &DB, login: F) -> Result<T, Error>
+    where
+        DB: LoginAttempts + GetItem<User>,
+        F: Future<Output = Result<T, Error>>,
+    {
+        let now = Utc::now();
+        let forget_before = now - self.lockout();
+        let account = account_key(contact, db).await;
+        let address = address.map(address_key);
+        let mut subjects = vec![(account.as_str(), self.account)];
+        if let Some(address) = &address {
+            subjects.push((address.as_str(), self.address));
+        }
+        // Refused attempts are never counted, or anyone knowing a contact could keep its account locked
+        let mut allowed = now;
+        for (key, limit) in &subjects {
+            if let Some(failures) = db.failures(key).await? {
+                allowed = allowed.max(failures.last + limit.wait(failures.count, self.lockout()));
+            }
+        }
+        if allowed > now {
+            Err(locked_out(now, allowed))?
+        }
+        // The attempt is counted before the password is checked, so concurrent attempts each
+        // wait on the failures counted before them instead of all slipping in under one count
+        for (key, limit) in &subjects {
+            if let Some(failures) = db.fail(key, now, forget_before).await? {
+                allowed = allowed.max(failures.last + limit.wait(failures.count, self.lockout()));
+            }
+        }
+        if allowed > now {
+            // Another attempt got in first, this one is taken back as it is refused
+            for (key, _) in &subjects {
+                db.forgive(key).await?;
+            }
+            Err(locked_out(now, allowed))?
+        }
+        let value = login.await?;
+        db.clear(&account).await?;
+        if let Some(address) = &address {
+            db.forgive(address).await?;
+        }
+        Ok(value)
+    }
+
+    async fn unlock<DB>(contact: Option<&Contact>, address: Option<IpAddr>, db: &DB) -> Result<(), Error>
+    where
+        DB: LoginAttempts + GetItem<User>,
+    {
+        if let Some(contact) = contact {
+            db.clear(&account_key(contact, db).await).await?;
+            // The keys an unknown account may have been counted under
+            for contact in contacts(contact) {
+                db.clear(&contact_key(&contact)).await?;
+            }
+        }
+        if let Some(address) = address {
+            db.clear(&address_key(address)).await?;
+        }
+        Ok(())
+    }
+}
+
+
+/// The key failures are counted under for an account, the same whichever contact it logs in with.
+///
+/// An unknown account is counted under the contact it was tried with.
+async fn account_key<DB: GetItem<User>>(contact: &Contact, db: &DB) -> String {
+    match db.get_item(Key::Sk(contact)).await {
+        Ok(user) => format!("user:{}", user.id.0),
+        Err(_) => contact_key(contact)
+    }
+}
+
+/// The key failures are counted under for an unknown account, an email address wins over a phone number
+fn contact_key(contact: &Contact) -> String {
+    match contact {
+        Contact::Phone(phone) => format!("phone:{}", &**phone),
+        Contact::Email(email) | Contact::Both(_, email) => format!("email:{}", email.to_lowercase()),
+    }
+}
+
+/// Each contact in `contact` on its own
+fn contacts(contact: &Contact) -> Vec<Contact> {
+    match contact {
+        Contact::Both(phone, email) => vec![Contact::Phone(phone.clone()), Contact::Email(email.clone())],
+        contact => vec![contact.clone()]
+    }
+}
+
+/// The key failures are counted under for a client address
+fn address_key(address: IpAddr) -> String {
+    format!("ip:{}", address)
+}
+
+/// The error for an attempt refused until `allowed`, rounded up to whole seconds
+fn locked_out(now: DateTime<Utc>, allowed: DateTime<Utc>) -> DomainError {
+    let millis = (allowed - now).num_milliseconds().max(0) as u64;
+    DomainError::LockedOut { retry_after: millis.div_ceil(1000) }
+}
@@ -0,0 +1,61 @@
+use super::super::types::{Token, User, Paseto, RefreshToken, Revocation, Key};
+use crate::ports::{Error, outputs::{expiry::TokenExpiry, database::{Item, CreateItem, GetItem, GetItems, DeleteItem}}};
+use super::{Paseto as PasetoTrait, Refresh};
+
+
+/// A trait for ending sessions before their tokens expire.
+pub trait Logout: Sized + Item {
+    type Error;
+
+    /// Revokes the given verified access token.
+    ///
+    /// The refresh token that came with it, if any, is revoked along with its whole family.
+    /// An expired token has nothing left to revoke.
+    async fn logout<DB>(token: &Token, refresh_token: Option<&str>, db: &DB) -> Result<(), Self::Error>
+    where
+        DB: CreateItem<Revocation> + GetItem<RefreshToken> + GetItems<RefreshToken, Filter = ()> + DeleteItem<RefreshToken>;
+
+    /// Revokes every access and refresh token issued to the subject of the given verified token.
+    async fn logout_everywhere<DB>(token: &Token, paseto: &Paseto, db: &DB) -> Result<(), Self::Error>
+    where
+        DB: CreateItem<Revocation> + GetItems<Self, RefreshToken, Filter = ()> + DeleteItem<RefreshToken> + TokenExpiry;
+}
+
+
+impl Logout for User {
+    type Error = Error;
+
+    async fn logout<DB>(token: &Token, refresh_token: Option<&str>, db: &DB) -> Result<(), Self::Error>
+    where
+        DB: CreateItem<Revocation> + GetItem<RefreshToken> + GetItems<RefreshToken, Filter = ()> + DeleteItem<RefreshToken>,
+    {
+        if let Some(refresh_token) = refresh_token {
+            let digest = RefreshToken::digest(refresh_token);
+            // Only the owner of a refresh token gets to revoke it
+            if let Ok(refresh) = GetItem::<RefreshToken>::get_item(db, Key::Pk(&digest)).await {
+                if refresh.user_id == token.subject {
+                    RefreshToken::revoke_family(&refresh.family, db).await?;
+                }
+            }
+        }
+        if token.expired() {
+            return Ok(())
+        }
+        db.create_item(Revocation::token(token)).await?;
+        Ok(())
+    }
+
+    async fn logout_everywhere<DB>(token: &Token, paseto: &Paseto, db: &DB) -> Result<(), Self::Error>
+    where
+        DB: CreateItem<Revocation> + GetItems<Self, RefreshToken, Filter = ()> + DeleteItem<RefreshToken> + TokenExpiry,
+    {
+        let subject = token.subject;
+        let refresh_tokens = db.get_items(Key::Pk(&subject), ()).await?;
+        let keys = refresh_tokens.iter().map(|refresh| Key::Pk(&refresh.id)).collect();
+        db.delete_items(keys).await?;
+        // Kept for as long as a token issued just before this one can live, clients may have their own expiry
+        let services = db.longest_token_expiry().await?.map(|expiry| expiry.num_seconds()).unwrap_or_default();
+        db.create_item(Revocation::subject(subject, paseto.ttl.max(services))).await?;
+        Ok(())
+    }
+}
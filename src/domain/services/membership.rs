@@ -0,0 +1,114 @@
+use crate::ports::{Error, outputs::database::{Item, CreateItem, GetItem, GetItems, UpdateItem, DeleteItem, Map}};
+use super::super::types::{Organisation, Member, User, Id, Key, Value, Error as DomainError};
+use std::collections::HashMap;
+
+
+/// A trait for items that users can be members of (organisations).
+///
+/// It decides who gets to do what with the item, the storage of members is left to the database.
+pub trait Membership: Sized + Item {
+    type Error;
+
+    /// Creates a new item and makes the given user its first owner.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The item to create, its id is always freshly generated.
+    /// * `owner` - The id of the user founding the item.
+    /// * `db` - The database to store the item and the membership in, the item is deleted again
+    ///   when the membership cannot be stored.
+    async fn found<DB: CreateItem<Self> + CreateItem<Member> + DeleteItem<Self>>(self, owner: &Id, db: &DB) -> Result<Self, Self::Error>;
+
+    /// Returns the membership of a user, failing with `Forbidden` if the user is not a member.
+    async fn member<DB: GetItem<(Self, User), Member>>(id: &Self::PK, user_id: &Id, db: &DB) -> Result<Member, Self::Error>;
+
+    /// Returns the membership of a user, failing with `Forbidden` if the user is not an owner.
+    async fn owner<DB: GetItem<(Self, User), Member>>(id: &Self::PK, user_id: &Id, db: &DB) -> Result<Member, Self::Error>;
+
+    /// Changes the title or roles of a member.
+    async fn update_member<DB>(id: &Self::PK, user_id: &Id, db: &DB, item: HashMap<String, Value>) -> Result<Member, Self::Error>
+    where
+        DB: GetItem<(Self, User), Member> + UpdateItem<(Self, User), Member, Update = Map>;
+
+    /// Removes a member, an item is never left without an owner.
+    async fn remove_member<DB>(id: &Self::PK, user_id: &Id, db: &DB) -> Result<(), Self::Error>
+    where
+        DB: GetItem<(Self, User), Member> + GetItems<Self, (Member, User), Filter = bool> + DeleteItem<Member>;
+}
+
+
+impl Membership for Organisation {
+    type Error = Error;
+
+    async fn found<DB: CreateItem<Self> + CreateItem<Member> + DeleteItem<Self>>(mut self, owner: &Id, db: &DB) -> Result<Self, Self::Error> {
+        self.id = Id::default();
+        let organisation = CreateItem::<Organisation>::create_item(db, self).await?;
+        let member = Member {
+            org_id: organisation.id,
+            user_id: *owner,
+            title: String::from("Owner"),
+            owner: true,
+            roles: Vec::new(),
+        };
+        if let Err(err) = CreateItem::<Member>::create_item(db, member).await {
+            // Nobody would own it, and its name would stay taken
+            DeleteItem::<Organisation>::delete_item(db, Key::Pk(&organisation.id)).await?;
+            Err(err)?
+        }
+        Ok(organisation)
+    }
+
+    async fn member<DB: GetItem<(Self, User), Member>>(id: &Self::PK, user_id: &Id, db: &DB) -> Result<Member, Self::Error> {
+        let key = (*id, *user_id);
+        // Non members should not learn anything about the organisation
+        match db.get_item(Key::Pk(&key)).await {
+            Ok(member) => Ok(member),
+            Err(_) => Err(DomainError::Forbidden)?
+        }
+    }
+
+    async fn owner<DB: GetItem<(Self, User), Member>>(id: &Self::PK, user_id: &Id, db: &DB) -> Result<Member, Self::Error> {
+        let member = Self::member(id, user_id, db).await?;
+        if !member.owner {
+            Err(DomainError::Forbidden)?
+        }
+        Ok(member)
+    }
+
+    async fn update_member<DB>(id: &Self::PK, user_id: &Id, db: &DB, item: HashMap<String, Value>) -> Result<Member, Self::Error>
+    where
+        DB: GetItem<(Self, User), Member> + UpdateItem<(Self, User), Member, Update = Map>,
+    {
+        let mut update = HashMap::new();
+
+        // Only allow updating specific fields
+        for field in ["title", "roles"] {
+            if let Some(value) = item.get(field) {
+                update.insert(field.to_string(), value.clone());
+            }
+        }
+
+        let key = (*id, *user_id);
+        if update.is_empty() {
+            return Ok(db.get_item(Key::Pk(&key)).await?)
+        }
+
+        Ok(db.patch_item(Key::Pk(&key), update).await?)
+    }
+
+    async fn remove_member<DB>(id: &Self::PK, user_id: &Id, db: &DB) -> Result<(), Self::Error>
+    where
+        DB: GetItem<(Self, User), Member> + GetItems<Self, (Member, User), Filter = bool> + DeleteItem<Member>,
+    {
+        let key = (*id, *user_id);
+        let member = GetItem::<(Self, User), Member>::get_item(db, Key::Pk(&key)).await?;
+        if member.owner {
+            let owners = GetItems::<Self, (Member, User)>::get_items(db, Key::Pk(id), true).await?;
+            if owners.len() < 2 {
+                Err(DomainError::validation("owner", "an organisation needs at least one owner"))?
+            }
+        }
+        DeleteItem::<Member>::delete_item(db, Key::Pk(&key)).await?;
+        Ok(())
+    }
+}
@@ -1,10 +1,26 @@
 mod authentication;
+mod invitation;
+mod lockout;
+mod logout;
+mod membership;
+mod oauth;
+mod openid;
 mod operations;
 mod password;
 mod paseto;
+mod refresh;
+mod verification;
 
 // pub use registration::Registration;
 pub use authentication::Authentication;
+pub use invitation::Invitations;
+pub use lockout::Lockout;
+pub use logout::Logout;
+pub use membership::Membership;
+pub use oauth::OAuth;
+pub use openid::OpenId;
 pub use password::Password;
-pub use paseto::Paseto;
+pub use paseto::{key_id, Paseto};
+pub use refresh::Refresh;
+pub use verification::ContactVerification;
 pub use operations::*;
@@ -0,0 +1,371 @@
+use super::super::types::{Service, ClientType, Session, RefreshToken, Revocation, Introspection, User, OIDC_SCOPES, Token, Paseto, AuthorizationCode, AuthorizationRequest, Consent, GrantType, Audience, Id, Key, Value, Error as DomainError};
+use crate::ports::{Error, outputs::database::{Item, CreateItem, GetItem, GetItems, DeleteItem}};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use argon2::{PasswordHasher, PasswordVerifier};
+use super::{Password, Paseto as PasetoTrait, Refresh, OpenId};
+use rand::{rngs::OsRng, TryRngCore};
+
+
+/// The only PKCE challenge method accepted, `plain` offers no protection.
+const CHALLENGE_METHOD: &str = "S256";
+/// Every access token is a v4.public PASETO, anything else is taken for a refresh token.
+const ACCESS_TOKEN_PREFIX: &str = "v4.public.";
+/// The audience of consent tickets, which keeps them from being taken for access tokens.
+const CONSENT_AUDIENCE: &str = "consent";
+/// The claim of a consent ticket holding the authorization request.
+const CONSENT_CLAIM: &str = "authorization_request";
+/// How long a user has to approve or deny a client, in seconds.
+const CONSENT_TTL: i64 = 600;
+
+
+/// A trait for OAuth2 clients (services): registering them and the grants they can use to obtain tokens.
+pub trait OAuth: Sized + Item {
+    type Error;
+
+    /// Registers a new client owned by the given user.
+    ///
+    /// A secret is generated for a confidential client and only its Argon2 hash is stored,
+    /// a public client gets none and cannot use the client credentials grant.
+    /// Every scope must belong to a service the owner controls.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Self, Self::Error>` - The stored client holding the plaintext secret, this is the only time it is revealed.
+    ///   A public client holds no secret.
+    async fn register_client<DB: CreateItem<Self> + GetItem<Self>, H: PasswordHasher>(self, owner_id: &Id, db: &DB, hasher: &H) -> Result<Self, Self::Error>;
+
+    /// Authenticates a client by its id and secret.
+    async fn authenticate_client<DB: GetItem<Self>, V: PasswordVerifier>(client_id: &Self::PK, client_secret: &str, db: &DB, verifier: &V) -> Result<Self, Self::Error>;
+
+    /// Issues an access token to an authenticated client acting on its own behalf.
+    ///
+    /// Every scope registered on the client is granted when none are asked for.
+    async fn client_credentials<DB, V>(client_id: &Self::PK, client_secret: &str, scope: Option<&str>, db: &DB, verifier: &V, paseto: &Paseto, issuer: String) -> Result<Token, Self::Error>
+    where
+        DB: GetItem<Self>,
+        V: PasswordVerifier;
+
+    /// Identifies a client on the token endpoint, a confidential client has to authenticate with its secret.
+    async fn identify_client<DB: GetItem<Self>, V: PasswordVerifier>(client_id: &Self::PK, client_secret: Option<&str>, db: &DB, verifier: &V) -> Result<Self, Self::Error>;
+
+    /// Looks up the client named by an authorization request and makes sure the redirect URI is registered on it.
+    ///
+    /// Until this succeeds errors must be shown to the user, they cannot be sent to the redirect URI.
+    async fn redirect_client<DB: GetItem<Self>>(client_id: &Self::PK, redirect_uri: &str, db: &DB) -> Result<Self, Self::Error>;
+
+    /// Validates an authorization request and asks the user to approve it.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Consent, Self::Error>` - What the client asks for, along with a signed ticket holding the request.
+    ///   Only the given user can redeem the ticket, and only for a few minutes.
+    fn consent(&self, request: &AuthorizationRequest, user_id: &Id, paseto: &Paseto, issuer: String) -> Result<Consent, Self::Error>;
+
+    /// Opens a consent ticket sent back by the user, returning the request they decided on.
+    fn consented(ticket: &str, user_id: &Id, paseto: &Paseto) -> Result<AuthorizationRequest, Self::Error>;
+
+    /// Issues an authorization code to the client for a request the user approved.
+    async fn issue_code<DB: CreateItem<AuthorizationCode>>(&self, request: &AuthorizationRequest, user_id: &Id, db: &DB) -> Result<AuthorizationCode, Self::Error>;
+
+    /// Exchanges an authorization code for an access token.
+    ///
+    /// The code is consumed whether the exchange succeeds or not.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The authorization code and the PKCE verifier its challenge was derived from.
+    /// * `client` - The identified client exchanging the code.
+    /// * `redirect_uri` - The redirect URI the code was sent to.
+    /// * `issuer` - The issuer of the access token.
+    /// * `domain` - The public URL of this server, the issuer of the ID token.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Session, Self::Error>` - The signed access token with the client as its audience,
+    ///   along with a refresh token when the client may use the refresh token grant
+    ///   and an ID token when the `openid` scope was granted.
+    async fn exchange_code<DB>(code: (&str, &str), client: &Self, redirect_uri: &str, db: &DB, paseto: &Paseto, issuer: String, domain: &str) -> Result<Session, Self::Error>
+    where
+        DB: GetItem<AuthorizationCode> + DeleteItem<AuthorizationCode> + CreateItem<RefreshToken> + GetItem<User>;
+
+    /// Tells an authenticated client whether a token is active (RFC 7662).
+    ///
+    /// Expired, revoked, badly signed and unknown tokens are all reported as inactive.
+    async fn introspect<DB, V>(client_id: &Self::PK, client_secret: &str, token: &str, db: &DB, verifier: &V, paseto: &Paseto) -> Result<Introspection, Self::Error>
+    where
+        DB: GetItem<Self> + GetItems<Revocation, Filter = ()> + GetItem<RefreshToken>,
+        V: PasswordVerifier;
+
+    /// Revokes an access or refresh token issued to the authenticated client (RFC 7009).
+    ///
+    /// Invalid and unknown tokens are ignored, there is nothing left to revoke.
+    /// Refresh tokens are revoked along with their whole family.
+    async fn revoke<DB, V>(client_id: &Self::PK, client_secret: &str, token: &str, db: &DB, verifier: &V, paseto: &Paseto) -> Result<(), Self::Error>
+    where
+        DB: GetItem<Self> + CreateItem<Revocation> + GetItem<RefreshToken> + GetItems<RefreshToken, Filter = ()> + DeleteItem<RefreshToken>,
+        V: PasswordVerifier;
+}
+
+
+impl Service {
+    /// How long the tokens issued to this service live, in seconds.
+    fn ttl(&self, paseto: &Paseto) -> i64 {
+        match self.token_expiry {
+            Some(expiry) => expiry.num_seconds(),
+            None => paseto.ttl
+        }
+    }
+
+    /// A fresh access token for this service, carrying the granted scopes.
+    pub(super) fn access_token(&self, issuer: String, subject: Id, scope: &[String], paseto: &Paseto) -> Token {
+        let audience = Audience::One(self.id.to_hex());
+        let mut token = Token::new(issuer, subject, audience, self.ttl(paseto));
+        token.claims.insert("client_id".to_string(), Value::String(self.id.to_hex()));
+        if !scope.is_empty() {
+            token.claims.insert("scope".to_string(), Value::String(scope.join(" ")));
+        }
+        token
+    }
+
+    /// Makes sure every requested scope was registered on the service, or is an OpenID Connect one.
+    fn scope(&self, scope: Option<&str>) -> Result<Vec<String>, DomainError> {
+        let registered = self.scopes.iter().cloned().map(String::from).collect::<Vec<String>>();
+        let mut granted = Vec::new();
+        for scope in scope.unwrap_or_default().split_whitespace() {
+            if !OIDC_SCOPES.contains(&scope) && !registered.iter().any(|registered| registered == scope) {
+                return Err(DomainError::InvalidScope { scope: scope.to_string() })
+            }
+            granted.push(scope.to_string());
+        }
+        Ok(granted)
+    }
+
+    /// Checks an authorization request against the service, returning the scopes it asks for.
+    fn authorization(&self, request: &AuthorizationRequest) -> Result<Vec<String>, DomainError> {
+        if request.response_type != "code" {
+            Err(DomainError::UnsupportedResponseType)?
+        }
+        if !self.grant_types.contains(&GrantType::AuthorizationCode) {
+            Err(DomainError::UnauthorizedClient)?
+        }
+        if request.code_challenge_method != CHALLENGE_METHOD {
+            Err(DomainError::validation("code_challenge_method", "only S256 code challenges are supported"))?
+        }
+        if request.code_challenge.is_empty() {
+            Err(DomainError::validation("code_challenge", "a code challenge is required"))?
+        }
+        self.scope(request.scope.as_deref())
+    }
+}
+
+
+impl OAuth for Service {
+    type Error = Error;
+
+    async fn register_client<DB: CreateItem<Self> + GetItem<Self>, H: PasswordHasher>(mut self, owner_id: &Id, db: &DB, hasher: &H) -> Result<Self, Self::Error> {
+        if self.grant_types.is_empty() {
+            Err(DomainError::validation("grant_types", "a client needs at least one grant type"))?
+        }
+        if self.grant_types.contains(&GrantType::AuthorizationCode) && self.redirect_uris.is_empty() {
+            Err(DomainError::validation("redirect_uris", "the authorization code grant needs at least one redirect uri"))?
+        }
+        if self.client_type == ClientType::Public && self.grant_types.contains(&GrantType::ClientCredentials) {
+            Err(DomainError::validation("grant_types", "a public client cannot use the client credentials grant"))?
+        }
+        for scope in &self.scopes {
+            match GetItem::<Service>::get_item(db, Key::Pk(&scope.id)).await {
+                Ok(service) if &service.owner_id == owner_id => (),
+                _ => Err(DomainError::InvalidScope { scope: String::from(scope.clone()) })?
+            }
+        }
+        self.id = Id::default();
+        self.owner_id = *owner_id;
+        if self.client_type == ClientType::Public {
+            self.client_secret = String::new();
+            return Ok(db.create_item(self).await?)
+        }
+
+        let mut bytes = [0u8; 32];
+        OsRng.try_fill_bytes(&mut bytes).map_err(DomainError::internal)?;
+        let secret = URL_SAFE_NO_PAD.encode(bytes);
+        self.client_secret = secret.hash(hasher)?;
+        let mut service = db.create_item(self).await?;
+        service.client_secret = secret;
+        Ok(service)
+    }
+
+    async fn authenticate_client<DB: GetItem<Self>, V: PasswordVerifier>(client_id: &Self::PK, client_secret: &str, db: &DB, verifier: &V) -> Result<Self, Self::Error> {
+        let mut service = match GetItem::<Service>::get_item(db, Key::Pk(client_id)).await {
+            Ok(service) => service,
+            Err(_) => Err(DomainError::InvalidClient)?
+        };
+        if service.client_type == ClientType::Public || client_secret.verify(&service.client_secret, verifier).is_err() {
+            Err(DomainError::InvalidClient)?
+        }
+        service.client_secret = Default::default();
+        Ok(service)
+    }
+
+    async fn client_credentials<DB, V>(client_id: &Self::PK, client_secret: &str, scope: Option<&str>, db: &DB, verifier: &V, paseto: &Paseto, issuer: String) -> Result<Token, Self::Error>
+    where
+        DB: GetItem<Self>,
+        V: PasswordVerifier,
+    {
+        let service = Self::authenticate_client(client_id, client_secret, db, verifier).await?;
+        if !service.grant_types.contains(&GrantType::ClientCredentials) {
+            Err(DomainError::UnauthorizedClient)?
+        }
+        let scope = match scope {
+            Some(scope) => service.scope(Some(scope))?,
+            None => service.scopes.iter().cloned().map(String::from).collect()
+        };
+        let token = service.access_token(issuer, service.id, &scope, paseto);
+        Ok(token.try_sign(&paseto.keys())?)
+    }
+
+    async fn identify_client<DB: GetItem<Self>, V: PasswordVerifier>(client_id: &Self::PK, client_secret: Option<&str>, db: &DB, verifier: &V) -> Result<Self, Self::Error> {
+        let mut service = match GetItem::<Service>::get_item(db, Key::Pk(client_id)).await {
+            Ok(service) => service,
+            Err(_) => Err(DomainError::InvalidClient)?
+        };
+        if service.client_type == ClientType::Confidential {
+            match client_secret {
+                Some(secret) if secret.verify(&service.client_secret, verifier).is_ok() => (),
+                _ => Err(DomainError::InvalidClient)?
+            }
+        }
+        service.client_secret = Default::default();
+        Ok(service)
+    }
+
+    async fn redirect_client<DB: GetItem<Self>>(client_id: &Self::PK, redirect_uri: &str, db: &DB) -> Result<Self, Self::Error> {
+        let mut service = match GetItem::<Service>::get_item(db, Key::Pk(client_id)).await {
+            Ok(service) => service,
+            Err(_) => Err(DomainError::InvalidClient)?
+        };
+        if !service.redirect_uris.iter().any(|uri| uri == redirect_uri) {
+            Err(DomainError::validation("redirect_uri", "the redirect uri is not registered on the client"))?
+        }
+        service.client_secret = Default::default();
+        Ok(service)
+    }
+
+    fn consent(&self, request: &AuthorizationRequest, user_id: &Id, paseto: &Paseto, issuer: String) -> Result<Consent, Self::Error> {
+        let scope = self.authorization(request)?;
+        let mut ticket = Token::new(issuer, *user_id, Audience::One(CONSENT_AUDIENCE.to_string()), CONSENT_TTL);
+        let claim = serde_json::to_string(request).map_err(DomainError::internal)?;
+        ticket.claims.insert(CONSENT_CLAIM.to_string(), Value::String(claim));
+        let ticket = match ticket.try_sign(&paseto.keys())?.signature {
+            Some(ticket) => ticket,
+            None => Err(DomainError::validation("ticket", "the consent ticket could not be signed"))?
+        };
+        Ok(Consent { client_id: self.id, client_name: self.name.clone(), scope, ticket })
+    }
+
+    fn consented(ticket: &str, user_id: &Id, paseto: &Paseto) -> Result<AuthorizationRequest, Self::Error> {
+        let invalid = || DomainError::validation("ticket", "the consent ticket is invalid or expired");
+        let ticket = match Token::try_verify(ticket, &paseto.keys()) {
+            Ok(ticket) => ticket,
+            Err(_) => Err(invalid())?
+        };
+        if ticket.expired() || &ticket.subject != user_id || !ticket.audience.contains(CONSENT_AUDIENCE) {
+            Err(invalid())?
+        }
+        match ticket.claims.get(CONSENT_CLAIM) {
+            Some(Value::String(request)) => Ok(serde_json::from_str(request).map_err(|_| invalid())?),
+            _ => Err(invalid())?
+        }
+    }
+
+    async fn issue_code<DB: CreateItem<AuthorizationCode>>(&self, request: &AuthorizationRequest, user_id: &Id, db: &DB) -> Result<AuthorizationCode, Self::Error> {
+        // The client may have changed while the user was deciding
+        let scope = self.authorization(request)?;
+        let mut code = AuthorizationCode::new(self.id, *user_id, request.redirect_uri.clone(), request.code_challenge.clone(), scope);
+        code.nonce = request.nonce.clone();
+        Ok(CreateItem::<AuthorizationCode>::create_item(db, code).await?)
+    }
+
+    async fn exchange_code<DB>(code: (&str, &str), client: &Self, redirect_uri: &str, db: &DB, paseto: &Paseto, issuer: String, domain: &str) -> Result<Session, Self::Error>
+    where
+        DB: GetItem<AuthorizationCode> + DeleteItem<AuthorizationCode> + CreateItem<RefreshToken> + GetItem<User>,
+    {
+        let (code, code_verifier) = code;
+        let key = code.to_string();
+        let code = match GetItem::<AuthorizationCode>::get_item(db, Key::Pk(&key)).await {
+            Ok(code) => code,
+            Err(_) => Err(DomainError::InvalidGrant)?
+        };
+        // Codes are single use, a second attempt must fail even if this one does
+        if DeleteItem::<AuthorizationCode>::delete_item(db, Key::Pk(&key)).await.is_err() {
+            Err(DomainError::InvalidGrant)?
+        }
+        if code.expired() || code.client_id != client.id || code.redirect_uri != redirect_uri || !code.challenged_by(code_verifier) {
+            Err(DomainError::InvalidGrant)?
+        }
+
+        let service = client;
+        let id_token = match code.scope.iter().any(|scope| scope == "openid") {
+            true => Some(User::id_token(&code.user_id, &service.id, &code.scope, code.nonce.clone(), domain.to_string(), db, paseto).await?),
+            false => None
+        };
+        let token = service.access_token(issuer, code.user_id, &code.scope, paseto).try_sign(&paseto.keys())?;
+        let mut session = match service.grant_types.contains(&GrantType::RefreshToken) {
+            true => RefreshToken::issue(token, Some(service.id), code.scope, db, paseto).await?,
+            false => Session::from(token)
+        };
+        session.id_token = id_token;
+        Ok(session)
+    }
+
+    async fn introspect<DB, V>(client_id: &Self::PK, client_secret: &str, token: &str, db: &DB, verifier: &V, paseto: &Paseto) -> Result<Introspection, Self::Error>
+    where
+        DB: GetItem<Self> + GetItems<Revocation, Filter = ()> + GetItem<RefreshToken>,
+        V: PasswordVerifier,
+    {
+        Self::authenticate_client(client_id, client_secret, db, verifier).await?;
+        if !token.starts_with(ACCESS_TOKEN_PREFIX) {
+            let digest = RefreshToken::digest(token);
+            return match GetItem::<RefreshToken>::get_item(db, Key::Pk(&digest)).await {
+                Ok(refresh) if !refresh.used && !refresh.expired() => Ok(Introspection::from(refresh)),
+                _ => Ok(Introspection::inactive())
+            }
+        }
+        let token = match Token::try_verify(token, &paseto.keys()) {
+            Ok(token) if !token.expired() => token,
+            _ => return Ok(Introspection::inactive())
+        };
+        let revocations = db.get_items(Key::Sk(&token.subject), ()).await?;
+        if revocations.iter().any(|revocation| revocation.covers(&token)) {
+            return Ok(Introspection::inactive())
+        }
+        Ok(Introspection::from(token))
+    }
+
+    async fn revoke<DB, V>(client_id: &Self::PK, client_secret: &str, token: &str, db: &DB, verifier: &V, paseto: &Paseto) -> Result<(), Self::Error>
+    where
+        DB: GetItem<Self> + CreateItem<Revocation> + GetItem<RefreshToken> + GetItems<RefreshToken, Filter = ()> + DeleteItem<RefreshToken>,
+        V: PasswordVerifier,
+    {
+        let service = Self::authenticate_client(client_id, client_secret, db, verifier).await?;
+        if !token.starts_with(ACCESS_TOKEN_PREFIX) {
+            let digest = RefreshToken::digest(token);
+            let refresh = match GetItem::<RefreshToken>::get_item(db, Key::Pk(&digest)).await {
+                Ok(refresh) => refresh,
+                Err(_) => return Ok(())
+            };
+            if refresh.client_id != Some(service.id) {
+                Err(DomainError::Forbidden)?
+            }
+            return RefreshToken::revoke_family(&refresh.family, db).await
+        }
+        let token = match Token::try_verify(token, &paseto.keys()) {
+            Ok(token) if !token.expired() => token,
+            _ => return Ok(())
+        };
+        if token.claims.get("client_id") != Some(&Value::String(service.id.to_hex())) {
+            Err(DomainError::Forbidden)?
+        }
+        db.create_item(Revocation::token(&token)).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use super::super::types::{User, UserInfo, IdToken, Paseto, Token, Id, Key, Value, Error as DomainError};
+use crate::ports::{Error, outputs::database::{Item, GetItem}};
+
+
+/// A trait for the OpenID Connect side of users: the claims released about them and their ID tokens.
+pub trait OpenId: Sized + Item {
+    type Error;
+
+    /// Returns the claims about the subject of the verified token, limited to the scopes it was granted.
+    ///
+    /// Tokens issued to clients must carry the `openid` scope, first party tokens get every claim.
+    async fn user_info<DB: GetItem<Self>>(token: &Token, db: &DB) -> Result<UserInfo, Self::Error>;
+
+    /// Issues a signed ID token for the user to the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The id of the user who authorized the client.
+    /// * `client_id` - The id of the client, the audience of the token.
+    /// * `scope` - The scopes granted to the client.
+    /// * `nonce` - The nonce sent in the authorization request, it is echoed back as is.
+    /// * `issuer` - The public URL of this server.
+    async fn id_token<DB: GetItem<Self>>(user_id: &Id, client_id: &Id, scope: &[String], nonce: Option<String>, issuer: String, db: &DB, paseto: &Paseto) -> Result<String, Self::Error>;
+}
+
+
+impl OpenId for User {
+    type Error = Error;
+
+    async fn user_info<DB: GetItem<Self>>(token: &Token, db: &DB) -> Result<UserInfo, Self::Error> {
+        let scope = match (token.claims.get("client_id"), token.claims.get("scope")) {
+            (None, _) => None,
+            (Some(_), Some(Value::String(scope))) => Some(scope.split_whitespace().map(String::from).collect::<Vec<String>>()),
+            (Some(_), _) => Some(Vec::new())
+        };
+        if let Some(scope) = &scope {
+            if !scope.iter().any(|scope| scope == "openid") {
+                Err(DomainError::Forbidden)?
+            }
+        }
+        let user = GetItem::<User>::get_item(db, Key::Pk(&token.subject)).await?;
+        Ok(UserInfo::new(&user, scope.as_deref()))
+    }
+
+    async fn id_token<DB: GetItem<Self>>(user_id: &Id, client_id: &Id, scope: &[String], nonce: Option<String>, issuer: String, db: &DB, paseto: &Paseto) -> Result<String, Self::Error> {
+        let user = db.get_item(Key::Pk(user_id)).await?;
+        let claims = UserInfo::new(&user, Some(scope));
+        let token = IdToken::new(issuer, client_id.to_hex(), claims, nonce, paseto.ttl);
+        Ok(token.try_sign(&paseto.keys())?)
+    }
+}
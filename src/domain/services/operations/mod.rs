@@ -1,8 +1,9 @@
-use crate::ports::outputs::database::{Item, GetItem, UpdateItem, Map};
+use crate::ports::outputs::database::{Item, GetItem, GetItems, UpdateItem, DeleteItem, Map};
 use crate::domain::types::Value;
 use std::collections::HashMap;
 
 
+mod organisation;
 mod user;
 
 pub trait Get: Sized + Item {
@@ -20,3 +21,19 @@ pub trait Update: Sized + Item {
     async fn update<DB: UpdateItem<Self, Update = Map> + GetItem<Self>>(filter: &Self::Filter, db: &DB, item: HashMap<String, Value>) -> Result<Self, Self::Error>;
 }
 
+
+pub trait Delete: Sized + Item {
+    type Error;
+    type Filter;
+
+    async fn delete<DB: DeleteItem<Self>>(filter: &Self::Filter, db: &DB) -> Result<(), Self::Error>;
+}
+
+
+/// Lists the `O` items related to an item, e.g. the organisations a user is a member of.
+pub trait List<O: Item>: Sized + Item {
+    type Error;
+    type Filter;
+
+    async fn list<DB: GetItems<Self, O, Filter = Self::Filter>>(id: &Self::PK, filter: Self::Filter, db: &DB) -> Result<Vec<O>, Self::Error>;
+}
@@ -0,0 +1,61 @@
+use crate::ports::{Error, outputs::database::{GetItem, GetItems, UpdateItem, DeleteItem, Map}};
+use crate::domain::types::{Organisation, Invitation, Id, Key, Value};
+use std::collections::HashMap;
+use super::{Get, Update, Delete, List};
+
+
+impl Get for Organisation {
+    type Error = Error;
+    type Filter = Id;
+
+    async fn get<DB: GetItem<Self>>(id: &Self::Filter, db: &DB) -> Result<Self, Self::Error> {
+        let key = Key::Pk(id);
+        Ok(db.get_item(key).await?)
+    }
+}
+
+
+impl Update for Organisation {
+    type Error = Error;
+    type Filter = Id;
+
+    async fn update<DB: UpdateItem<Self, Update = Map> + GetItem<Self>>(id: &Self::Filter, db: &DB, item: HashMap<String, Value>) -> Result<Self, Self::Error> {
+        let mut update = HashMap::new();
+
+        // Only allow updating specific fields
+        for field in ["name", "domain", "home", "contacts"] {
+            if let Some(value) = item.get(field) {
+                update.insert(field.to_string(), value.clone());
+            }
+        }
+
+        let key = Key::Pk(id);
+        if update.is_empty() {
+            return Ok(db.get_item(key).await?)
+        }
+
+        Ok(db.patch_item(key, update).await?)
+    }
+}
+
+
+impl Delete for Organisation {
+    type Error = Error;
+    type Filter = Id;
+
+    async fn delete<DB: DeleteItem<Self>>(id: &Self::Filter, db: &DB) -> Result<(), Self::Error> {
+        let key = Key::Pk(id);
+        Ok(db.delete_item(key).await?)
+    }
+}
+
+
+impl List<Invitation> for Organisation {
+    type Error = Error;
+    type Filter = ();
+
+    async fn list<DB: GetItems<Self, Invitation, Filter = Self::Filter>>(id: &Self::PK, filter: Self::Filter, db: &DB) -> Result<Vec<Invitation>, Self::Error> {
+        let key = Key::Pk(id);
+        Ok(db.get_items(key, filter).await?)
+    }
+}
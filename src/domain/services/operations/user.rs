@@ -1,7 +1,7 @@
-use crate::ports::{Error, outputs::database::{GetItem, UpdateItem, Map}};
-use crate::domain::types::{User, Id, Key, Value};
+use crate::ports::{Error, outputs::database::{GetItem, GetItems, UpdateItem, Map}};
+use crate::domain::types::{User, Organisation, Id, Key, Value};
 use std::collections::HashMap;
-use super::{Get, Update};
+use super::{Get, Update, List};
 
 
 impl Get for User {
@@ -46,3 +46,15 @@ impl Update for User {
         Ok(user)
     }
 }
+
+
+impl List<Organisation> for User {
+    type Error = Error;
+    /// Only list the organisations the user owns
+    type Filter = bool;
+
+    async fn list<DB: GetItems<Self, Organisation, Filter = Self::Filter>>(id: &Self::PK, owner: Self::Filter, db: &DB) -> Result<Vec<Organisation>, Self::Error> {
+        let key = Key::Pk(id);
+        Ok(db.get_items(key, owner).await?)
+    }
+}
@@ -4,10 +4,43 @@ use rusty_paseto::core::Paseto as PasetoBuilder;
 use serde::{Serialize, de::DeserializeOwned};
 use rusty_paseto::core::Footer;
 use rusty_paseto::core::Key;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Deserialize;
 use chrono::Utc;
 
 
 type Result<T> = std::result::Result<T, Error>;
+
+
+/// The footer of every token, naming the key it was signed with.
+#[derive(Serialize, Deserialize)]
+struct KeyFooter {
+    kid: String
+}
+
+impl KeyFooter {
+    /// Reads the footer of a token, if it has one.
+    fn of(signature: &str) -> Result<Option<String>> {
+        match signature.split('.').nth(3) {
+            Some(footer) => {
+                let footer = URL_SAFE_NO_PAD.decode(footer).map_err(|_| Error::InvalidToken)?;
+                Ok(Some(String::from_utf8(footer).map_err(|_| Error::InvalidToken)?))
+            },
+            None => Ok(None)
+        }
+    }
+}
+
+/// The id of the key a token was signed with, from its footer.
+///
+/// Tokens signed before key ids were added to the footer have none.
+pub fn key_id(signature: &str) -> Result<Option<String>> {
+    match KeyFooter::of(signature)? {
+        Some(footer) => Ok(Some(serde_json::from_str::<KeyFooter>(&footer).map_err(|_| Error::InvalidToken)?.kid)),
+        None => Ok(None)
+    }
+}
+
 /// A trait for handling PASETO (Platform-Agnostic Security Tokens) operations.
 ///
 /// This trait provides methods for signing and verifying PASETO tokens using
@@ -30,9 +63,9 @@ pub trait Paseto: Serialize + DeserializeOwned + 'static {
 
     /// Verifies a PASETO token signature and returns the deserialized token if valid.
     ///
-    /// This method attempts to verify the provided PASETO token signature using the
-    /// current public key. If the signature is invalid, it attempts verification
-    /// with a previous public key if available.
+    /// The key id in the token's footer picks the public key to verify it with.
+    /// Tokens signed before key ids were added to the footer are verified with the
+    /// current public key, falling back to the previous public key if available.
     ///
     /// # Arguments
     ///
@@ -52,7 +85,15 @@ pub trait Paseto: Serialize + DeserializeOwned + 'static {
         let footer = Option::<Footer>::None;
         
         let implicit_assertion = Option::<ImplicitAssertion>::None;
-        
+
+        // Verify with the key named in the footer.
+        if let Some(kid) = key_id(signature)? {
+            let public_key = keys.public_keys()
+                .into_iter()
+                .find(|key| PasetoKeys::key_id(key) == kid)
+                .ok_or(Error::InvalidToken)?;
+            return Self::try_verify_with(signature, &public_key)
+        }
         
         // Attempt to verify the signature using the current public key.
         let json = match PasetoBuilder::try_verify(signature, &public_key, footer, implicit_assertion) {
@@ -97,6 +138,17 @@ pub trait Paseto: Serialize + DeserializeOwned + 'static {
     }
 
 
+    /// Verifies a PASETO token signature against a single public key, such as one fetched by a resource server.
+    fn try_verify_with(signature: &str, public_key: &[u8; 32]) -> Result<Self> {
+        let key = Key::from(public_key);
+        let public_key = From::from(&key);
+        let footer = KeyFooter::of(signature)?;
+        let footer = footer.as_deref().map(Footer::from);
+        let json = PasetoBuilder::<V4, Public>::try_verify(signature, &public_key, footer, None)?;
+        serde_json::from_str(&json).map_err(|_| Error::InvalidToken)
+    }
+
+
     /// Signs a token and returns the PASETO token string.
     ///
     /// This method serializes the token and signs it using the provided private key.
@@ -123,10 +175,14 @@ pub trait Paseto: Serialize + DeserializeOwned + 'static {
         
         // Create a payload from the JSON string
         let payload = Payload::from(json.as_str());
+
+        // Name the signing key in the footer so verifiers know which key to use
+        let footer = serde_json::to_string(&KeyFooter{kid: keys.kid()}).map_err(Error::internal)?;
         
         // Sign the payload to generate the PASETO token
         let token = PasetoBuilder::<V4, Public>::builder()
             .set_payload(payload)
+            .set_footer(Footer::from(footer.as_str()))
             .try_sign(&private_key)?;
         
         // Create a new token with the signature
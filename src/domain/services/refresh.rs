@@ -0,0 +1,97 @@
+use super::super::types::{RefreshToken, Service, Session, Token, Paseto, Audience, Id, Key, Error as DomainError};
+use crate::ports::{Error, outputs::{consume::ConsumeRefreshToken, database::{Item, CreateItem, GetItem, GetItems, DeleteItem}}};
+use super::Paseto as PasetoTrait;
+
+
+/// A trait for refresh tokens: starting sessions and rotating them.
+pub trait Refresh: Sized + Item {
+    type Error;
+
+    /// Pairs a freshly signed access token with the first refresh token of a new family.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The signed access token, its subject is the user the refresh token is issued to.
+    /// * `client_id` - The client the token was issued to, none for first party sessions.
+    /// * `scope` - The scopes granted to the client.
+    async fn issue<DB: CreateItem<Self>>(token: Token, client_id: Option<Id>, scope: Vec<String>, db: &DB, paseto: &Paseto) -> Result<Session, Self::Error>;
+
+    /// Exchanges a refresh token for a new access token and a new refresh token.
+    ///
+    /// The presented token is consumed in a single step, so only one of two parties racing
+    /// with it gets through. Presenting a consumed token again revokes every token of its
+    /// family, as one of the parties holding it is not the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `refresh_token` - The opaque refresh token.
+    /// * `client_id` - The authenticated client presenting the token, none for first party sessions.
+    async fn refresh<DB>(refresh_token: &str, client_id: Option<&Id>, db: &DB, paseto: &Paseto, issuer: String) -> Result<Session, Self::Error>
+    where
+        DB: ConsumeRefreshToken + CreateItem<Self> + GetItems<Self, Filter = ()> + DeleteItem<Self> + GetItem<Service>;
+
+    /// Revokes every token of a family.
+    async fn revoke_family<DB>(family: &Id, db: &DB) -> Result<(), Self::Error>
+    where
+        DB: GetItems<Self, Filter = ()> + DeleteItem<Self>;
+}
+
+
+impl Refresh for RefreshToken {
+    type Error = Error;
+
+    async fn issue<DB: CreateItem<Self>>(token: Token, client_id: Option<Id>, scope: Vec<String>, db: &DB, paseto: &Paseto) -> Result<Session, Self::Error> {
+        let (refresh, refresh_token) = RefreshToken::new(token.subject, client_id, scope, paseto.refresh_ttl);
+        db.create_item(refresh).await?;
+        let refresh_token = Some(refresh_token);
+        let id_token = None;
+        Ok(Session{token, refresh_token, id_token})
+    }
+
+    async fn refresh<DB>(refresh_token: &str, client_id: Option<&Id>, db: &DB, paseto: &Paseto, issuer: String) -> Result<Session, Self::Error>
+    where
+        DB: ConsumeRefreshToken + CreateItem<Self> + GetItems<Self, Filter = ()> + DeleteItem<Self> + GetItem<Service>,
+    {
+        let digest = Self::digest(refresh_token);
+        let refresh = match db.consume(&digest).await {
+            Ok(refresh) => refresh,
+            Err(_) => Err(DomainError::InvalidGrant)?
+        };
+        if refresh.used {
+            // The token was replayed, nobody in this family can be trusted anymore
+            Self::revoke_family(&refresh.family, db).await?;
+            Err(DomainError::InvalidGrant)?
+        }
+        if refresh.expired() || refresh.client_id.as_ref() != client_id {
+            Err(DomainError::InvalidGrant)?
+        }
+
+        let token = match &refresh.client_id {
+            Some(client_id) => {
+                let service = match GetItem::<Service>::get_item(db, Key::Pk(client_id)).await {
+                    Ok(service) => service,
+                    Err(_) => Err(DomainError::InvalidClient)?
+                };
+                service.access_token(issuer, refresh.user_id, &refresh.scope, paseto)
+            },
+            None => Token::new(issuer, refresh.user_id, Audience::None, paseto.ttl)
+        };
+        let token = token.try_sign(&paseto.keys())?;
+
+        let (next, refresh_token) = refresh.rotate(paseto.refresh_ttl);
+        CreateItem::<Self>::create_item(db, next).await?;
+        let refresh_token = Some(refresh_token);
+        let id_token = None;
+        Ok(Session{token, refresh_token, id_token})
+    }
+
+    async fn revoke_family<DB>(family: &Id, db: &DB) -> Result<(), Self::Error>
+    where
+        DB: GetItems<Self, Filter = ()> + DeleteItem<Self>,
+    {
+        let tokens = db.get_items(Key::Sk(family), ()).await?;
+        let keys = tokens.iter().map(|token| Key::Pk(&token.id)).collect();
+        db.delete_items(keys).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use crate::ports::{Error, outputs::{database::{Item, CreateItem, GetItem, UpdateItem, DeleteItem}, verify::{Verify, Code}, wrong_code::CountWrongCode}};
+use super::super::types::{User, Contact, EmailAddress, Phone, Either, Key, Error as DomainError};
+
+
+/// A trait for proving ownership of a contact of type `T` (email address, phone number).
+///
+/// The actual delivery and checking of codes is left to a `Verify` implementation,
+/// this trait only decides which contact gets verified and records the outcome on the item.
+pub trait ContactVerification<T: Clone>: Sized + Item {
+    type Error;
+
+    /// Sends a verification code to the unverified contact of the item with the given id.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The primary key of the item that owns the contact.
+    /// * `channel` - The channel the code should be delivered through.
+    /// * `base_url` - The base URL used to build magic links.
+    /// * `db` - The database holding the item and the verification codes.
+    /// * `verifyer` - The verification service delivering the code.
+    async fn initiate<DB, V>(id: &Self::PK, channel: V::Channel, base_url: &str, db: &DB, verifyer: &V) -> Result<(), Self::Error>
+    where
+        DB: GetItem<Self> + CreateItem<V::Verification>,
+        V: Verify<T>;
+
+    /// Checks a code (or magic link id) for the given contact and marks the contact as verified.
+    ///
+    /// The verifyer consumes the code, so it only confirms the contact once.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Self, Self::Error>` - The updated item if the code was valid.
+    async fn confirm<DB, V>(contact: &T, code: Either<&str, &str>, db: &DB, verifyer: &V) -> Result<Self, Self::Error>
+    where
+        DB: GetItem<Self> + UpdateItem<Self> + GetItem<V::Verification> + CreateItem<V::Verification> + CountWrongCode<V::Verification> + DeleteItem<V::Verification>,
+        V: Verify<T>,
+        V::Verification: Item<PK = Either<Phone, EmailAddress>>;
+}
+
+
+#[cfg(feature = "email")]
+impl ContactVerification<EmailAddress> for User {
+    type Error = Error;
+
+    async fn initiate<DB, V>(id: &Self::PK, channel: V::Channel, base_url: &str, db: &DB, verifyer: &V) -> Result<(), Self::Error>
+    where
+        DB: GetItem<Self> + CreateItem<V::Verification>,
+        V: Verify<EmailAddress>,
+    {
+        let user = GetItem::<User>::get_item(db, Key::Pk(id)).await?;
+        let email = match &user.contact {
+            Contact::Email(email) | Contact::Both(_, email) => email,
+            Contact::Phone(_) => Err(DomainError::validation("email", "there is no email address on this account"))?
+        };
+        if let EmailAddress::Verified(_) = email {
+            Err(DomainError::validation("email", "the email address is already verified"))?
+        }
+        verifyer.initiate(email, channel, base_url, db).await?;
+        Ok(())
+    }
+
+    async fn confirm<DB, V>(email: &EmailAddress, code: Either<&str, &str>, db: &DB, verifyer: &V) -> Result<Self, Self::Error>
+    where
+        DB: GetItem<Self> + UpdateItem<Self> + GetItem<V::Verification> + CreateItem<V::Verification> + CountWrongCode<V::Verification> + DeleteItem<V::Verification>,
+        V: Verify<EmailAddress>,
+        V::Verification: Item<PK = Either<Phone, EmailAddress>>,
+    {
+        let contact = Contact::Email(email.clone());
+        let mut user = GetItem::<User>::get_item(db, Key::Sk(&contact)).await?;
+        verifyer.verify(email, code, db).await?;
+
+        // Flip the stored address to its verified state
+        if let Contact::Email(email) | Contact::Both(_, email) = &mut user.contact {
+            *email = EmailAddress::Verified(email.clone().into());
+        }
+        let id = user.id;
+        let mut user = UpdateItem::<User>::update_item(db, Key::Pk(&id), user).await?;
+        user.password = Default::default();
+        Ok(user)
+    }
+}
+
+
+#[cfg(feature = "phone")]
+impl ContactVerification<Phone> for User {
+    type Error = Error;
+
+    async fn initiate<DB, V>(id: &Self::PK, channel: V::Channel, base_url: &str, db: &DB, verifyer: &V) -> Result<(), Self::Error>
+    where
+        DB: GetItem<Self> + CreateItem<V::Verification>,
+        V: Verify<Phone>,
+    {
+        let user = GetItem::<User>::get_item(db, Key::Pk(id)).await?;
+        let phone = match &user.contact {
+            Contact::Phone(phone) | Contact::Both(phone, _) => phone,
+            Contact::Email(_) => Err(DomainError::validation("phone", "there is no phone number on this account"))?
+        };
+        if let Phone::Verified(_) = phone {
+            Err(DomainError::validation("phone", "the phone number is already verified"))?
+        }
+        verifyer.initiate(phone, channel, base_url, db).await?;
+        Ok(())
+    }
+
+    async fn confirm<DB, V>(phone: &Phone, code: Either<&str, &str>, db: &DB, verifyer: &V) -> Result<Self, Self::Error>
+    where
+        DB: GetItem<Self> + UpdateItem<Self> + GetItem<V::Verification> + CreateItem<V::Verification> + CountWrongCode<V::Verification> + DeleteItem<V::Verification>,
+        V: Verify<Phone>,
+        V::Verification: Item<PK = Either<Phone, EmailAddress>>,
+    {
+        let contact = Contact::Phone(phone.clone());
+        let mut user = GetItem::<User>::get_item(db, Key::Sk(&contact)).await?;
+        verifyer.verify(phone, code, db).await?;
+
+        // Flip the stored number to its verified state
+        if let Contact::Phone(phone) | Contact::Both(phone, _) = &mut user.contact {
+            *phone = Phone::Verified(phone.to_string());
+        }
+        let id = user.id;
+        let mut user = UpdateItem::<User>::update_item(db, Key::Pk(&id), user).await?;
+        user.password = Default::default();
+        Ok(user)
+    }
+}
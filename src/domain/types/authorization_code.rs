@@ -0,0 +1,125 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use crate::ports::outputs::database::Item;
+use rand::{rngs::OsRng, TryRngCore};
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use super::Id;
+
+/// A struct representing an OAuth2 authorization code waiting to be exchanged for a token.
+///
+/// Codes are single use and bound to the client, the redirect URI and the PKCE challenge
+/// they were issued for.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuthorizationCode {
+    /// The code handed to the client.
+    pub code: String,
+    /// The id of the service (client) the code was issued to.
+    pub client_id: Id,
+    /// The id of the user who authorized the client.
+    pub user_id: Id,
+    /// The redirect URI the code was sent to.
+    pub redirect_uri: String,
+    /// The S256 PKCE challenge sent by the client.
+    pub code_challenge: String,
+    /// The scopes granted to the client.
+    pub scope: Vec<String>,
+    /// The OpenID Connect nonce to echo back in the ID token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// The time when the code can no longer be exchanged.
+    pub expires: DateTime<Utc>,
+}
+
+/// The parameters of an authorization request (RFC 6749 section 4.1.1).
+///
+/// The request is carried in the consent ticket while the user decides whether to approve the client.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuthorizationRequest {
+    #[serde(default)]
+    pub response_type: String,
+    pub client_id: Id,
+    pub redirect_uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub code_challenge: String,
+    #[serde(default = "plain")]
+    pub code_challenge_method: String,
+}
+
+/// What a user is asked to approve before a client is issued an authorization code.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Consent {
+    /// The id of the client asking for access.
+    pub client_id: Id,
+    /// The name of the client, as registered by its owner.
+    pub client_name: String,
+    /// The scopes the client asks for.
+    pub scope: Vec<String>,
+    /// The signed ticket to send back along with the user's decision.
+    pub ticket: String,
+}
+
+/// The challenge method assumed by RFC 7636 when the client does not send one.
+fn plain() -> String {
+    String::from("plain")
+}
+
+impl AuthorizationCode {
+    /// How long a code can be exchanged for, in seconds.
+    pub const TTL: i64 = 60;
+
+    pub fn new(client_id: Id, user_id: Id, redirect_uri: String, code_challenge: String, scope: Vec<String>) -> Self {
+        let mut bytes = [0u8; 32];
+        OsRng.try_fill_bytes(&mut bytes).expect("Failed to generate random bytes");
+        let code = URL_SAFE_NO_PAD.encode(bytes);
+        let nonce = None;
+        let expires = Utc::now() + Duration::seconds(Self::TTL);
+        Self {code, client_id, user_id, redirect_uri, code_challenge, scope, nonce, expires}
+    }
+
+    pub fn expired(&self) -> bool {
+        self.expires <= Utc::now()
+    }
+
+    /// Checks a PKCE code verifier against the stored S256 challenge.
+    pub fn challenged_by(&self, code_verifier: &str) -> bool {
+        let digest = Sha256::digest(code_verifier.as_bytes());
+        URL_SAFE_NO_PAD.encode(digest) == self.code_challenge
+    }
+}
+
+impl Item for AuthorizationCode {
+    /// This is the code.
+    type PK = String;
+    /// This is the client id.
+    type SK = Id;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_challenged_by() {
+        // The example from RFC 7636 appendix B
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM".to_string();
+        let code = AuthorizationCode::new(Id::default(), Id::default(), "https://example.com".to_string(), challenge, vec![]);
+        assert!(code.challenged_by(verifier));
+        assert!(!code.challenged_by("not-the-verifier"));
+    }
+
+    #[test]
+    fn test_codes_are_unique() {
+        let first = AuthorizationCode::new(Id::default(), Id::default(), String::new(), String::new(), vec![]);
+        let second = AuthorizationCode::new(Id::default(), Id::default(), String::new(), String::new(), vec![]);
+        assert_ne!(first.code, second.code);
+        assert!(!first.expired());
+    }
+}
@@ -1,8 +1,8 @@
 use serde::{de::{self, DeserializeOwned, Visitor}, ser::SerializeStruct, Deserialize, Serialize};
 use crate::ports::inputs::config::Config as ConfigTrait;
 use crate::ports::outputs::verify::Verifyer;
-use super::{argon::Argon, Paseto};
-use std::io::{Read, Write};
+use super::{argon::Argon, Paseto, Http, Args, Format, Layers, Sources};
+use std::io::ErrorKind;
 
 
 
@@ -13,10 +13,80 @@ pub struct Config<DB, V> {
     argon: Argon,
     paseto: Paseto,
     verifyer: V,
+    http: Http,
+    /// Where each value came from, empty unless loaded through [`ConfigTrait::load`]
+    sources: Sources,
+}
+
+
+/// Assembles a [`Config`] in code, from the database and verifyer adaptors the embedder picked.
+///
+/// Sections left unset get their defaults, the PASETO keys are then read from or generated at `paseto_keys.json`.
+pub struct ConfigBuilder<DB, V> {
+    name: String,
+    domain: String,
+    database: DB,
+    argon: Argon,
+    paseto: Option<Paseto>,
+    verifyer: V,
+    http: Http,
+}
+
+
+impl<DB, V> ConfigBuilder<DB, V> {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// The public URL of the server, used as the token issuer.
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = domain.into();
+        self
+    }
+
+    pub fn argon(mut self, argon: Argon) -> Self {
+        self.argon = argon;
+        self
+    }
+
+    pub fn paseto(mut self, paseto: Paseto) -> Self {
+        self.paseto = Some(paseto);
+        self
+    }
+
+    pub fn http(mut self, http: Http) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub fn build(self) -> Result<Config<DB, V>, std::io::Error> {
+        let paseto = match self.paseto {
+            Some(paseto) => paseto,
+            None => Paseto::try_default()?
+        };
+        let Self {name, domain, database, argon, verifyer, http, ..} = self;
+        let sources = Sources::default();
+        Ok(Config {name, domain, database, argon, paseto, verifyer, http, sources})
+    }
 }
 
 
 impl<DB, V> Config<DB, V> {
+    /// Starts a configuration around the given adaptors.
+    pub fn builder(database: DB, verifyer: V) -> ConfigBuilder<DB, V> {
+        let name = String::from("Beekeeper");
+        let domain = String::new();
+        let argon = Argon::default();
+        let paseto = None;
+        let http = Http::default();
+        ConfigBuilder {name, domain, database, argon, paseto, verifyer, http}
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
     pub fn db(&self) -> &DB {
         &self.database
     }
@@ -32,6 +102,21 @@ impl<DB, V> Config<DB, V> {
     pub fn verifyer(&self) -> &V {
         &self.verifyer
     }
+
+    pub fn http(&self) -> &Http {
+        &self.http
+    }
+
+    /// The layer each configured value came from.
+    pub fn sources(&self) -> &Sources {
+        &self.sources
+    }
+
+    /// Swaps the database, such as for the one the loaded `database` section points at.
+    pub fn map_db<D>(self, f: impl FnOnce(DB) -> D) -> Config<D, V> {
+        let Self {name, domain, database, argon, paseto, verifyer, http, sources} = self;
+        Config {name, domain, database: f(database), argon, paseto, verifyer, http, sources}
+    }
 }
 
 
@@ -43,29 +128,39 @@ where
 {
 
 
-    fn load_sync(path: Option<&str>, input: <Self as ConfigTrait>::Input) -> Result<Self, <Self as ConfigTrait>::Error> {
-        let path = match path {Some(path) => path, None => <Self as ConfigTrait>::PATH};
-        match std::fs::File::open(path) {
-            Ok(mut file) => {
-                let mut buf = String::new();
-                file.read_to_string(&mut buf)?;
-                Ok(serde_json::from_str::<Self>(&buf)?)
+    /// Loads the configuration file, overridden by `BEEKEEPER__SECTION__KEY` variables, overridden by flags.
+    ///
+    /// A missing file is replaced by a default one, except in production where it is an error.
+    fn load_sync(path: Option<&str>, args: <Self as ConfigTrait>::Input) -> Result<Self, <Self as ConfigTrait>::Error> {
+        let path = path.or(args.path.as_deref()).unwrap_or(<Self as ConfigTrait>::PATH);
+        let format = Format::of(path)?;
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound && args.production => {
+                Err(format!("configuration file {} not found, a default one is not written in production", path))?
             },
-            _ => {
-                let config = <Self as Default>::default();
-                let path = Some(path);
-                config.save_sync(path, input)?;
-                Ok(config)
-            }
-        }
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                log::warn!("configuration file {} not found, writing a default one", path);
+                <Self as Default>::default().save_sync(Some(path), args.clone())?;
+                std::fs::read_to_string(path)?
+            },
+            Err(err) => Err(err)?
+        };
+        let mut layers = Layers::default();
+        layers.file(format.parse(&text)?, path);
+        layers.env(std::env::vars_os().filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?))));
+        layers.args(&args);
+        let (value, sources) = layers.into_parts();
+        // Through a string, the visitors borrow their keys
+        let mut config = serde_json::from_str::<Self>(&value.to_string())?;
+        config.sources = sources;
+        Ok(config)
     }
 
     fn save_sync(&self, path: Option<&str>, _input: <Self as ConfigTrait>::Input) -> Result<(), <Self as ConfigTrait>::Error> {
         let path = match path {Some(path) => path, None => Self::PATH};
-        let json = serde_json::to_string(&self)?;
-        let mut file = std::fs::OpenOptions::new().write(true).create(true).open(path)?;
-        let buf = json.as_bytes();
-        file.write_all(buf)?;
+        let text = Format::of(path)?.render(self)?;
+        std::fs::write(path, text)?;
         Ok(())
     }
 }
@@ -75,11 +170,14 @@ impl<DB: Serialize, V: Verifyer + Serialize> Serialize for Config<DB, V> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where
             S: serde::Serializer {
-        let mut state = serializer.serialize_struct("Config", 4)?;
+        let mut state = serializer.serialize_struct("Config", 7)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("domain", &self.domain)?;
         state.serialize_field("database", &self.database)?;
         state.serialize_field("argon", &self.argon)?;
         state.serialize_field("paseto", &self.paseto)?;
         state.serialize_field("verifyer", &self.verifyer)?;
+        state.serialize_field("http", &self.http)?;
         state.end()
     }
 }
@@ -91,7 +189,7 @@ where
     V: Verifyer + Default + Serialize + DeserializeOwned,
 {
     type Error = Box<dyn std::error::Error + 'static>;
-    type Input = ();
+    type Input = Args;
     
 
     async  fn load(path: Option<&str>, input: Self::Input) -> Result<Self, Self::Error> {
@@ -112,8 +210,10 @@ impl<DB: Default , V: Verifyer + Default> Default for Config<DB, V> {
         let argon = Default::default();
         let paseto = Default::default();
         let verifyer = Default::default();
+        let http = Default::default();
+        let sources = Default::default();
 
-        Self{name, domain, database, argon, paseto, verifyer}
+        Self{name, domain, database, argon, paseto, verifyer, http, sources}
     }
 }
 
@@ -150,6 +250,7 @@ where
                 let mut argon = None;
                 let mut paseto = None;
                 let mut verifyer = None;
+                let mut http = None;
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -189,6 +290,12 @@ where
                             }
                             verifyer = map.next_value()?;
                         },
+                        "http" => {
+                            if http.is_some() {
+                                return Err(de::Error::duplicate_field("http"));
+                            }
+                            http = map.next_value()?;
+                        },
                         _ => {
                             let _: de::IgnoredAny = map.next_value()?;
                         }
@@ -201,11 +308,41 @@ where
                 let argon = argon.unwrap_or_default();
                 let paseto = paseto.unwrap_or_default();
                 let verifyer = verifyer.unwrap_or_default();
+                let http = http.unwrap_or_default();
+                let sources = Sources::default();
 
-                Ok(Config{name, domain, database, argon, paseto, verifyer})
+                Ok(Config{name, domain, database, argon, paseto, verifyer, http, sources})
             }
         }
         let visitor = ConfigVisitor::<DB, V>{_t: std::marker::PhantomData::default()};
         deserializer.deserialize_map(visitor)
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let path = std::env::temp_dir().join(format!("beekeeper_builder_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let paseto = Paseto::load_or_generate(path, 60, 600, None).unwrap();
+        let config = Config::builder(vec![1, 2, 3], ())
+            .name("Hive")
+            .domain("https://auth.example.com")
+            .paseto(paseto)
+            .build()
+            .unwrap();
+        assert_eq!(config.name, "Hive");
+        assert_eq!(config.domain(), "https://auth.example.com");
+        assert_eq!(config.db(), &vec![1, 2, 3]);
+        assert_eq!(config.paseto().ttl, 60);
+        assert_eq!(config.http(), &Http::default());
+        let config = config.map_db(|database| database.len());
+        assert_eq!(config.db(), &3);
+        assert_eq!(config.domain(), "https://auth.example.com");
+        std::fs::remove_file(path).unwrap();
+    }
+}
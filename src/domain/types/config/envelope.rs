@@ -0,0 +1,176 @@
+use chacha20poly1305::{aead::{Aead, KeyInit, Payload}, XChaCha20Poly1305, XNonce}; // AEAD used to seal files at rest
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}, Engine}; // For encoding binary fields
+use serde::{Serialize, Deserialize}; // For serializing and deserializing data
+use rand::{rngs::OsRng, TryRngCore}; // For generating nonces securely
+use std::io::{Write, Error, ErrorKind}; // Errors are reported like any other key file error
+use std::os::unix::fs::OpenOptionsExt; // For setting file permissions on Unix systems
+use super::super::PasetoKeys; // The keys being sealed
+use super::Secret; // The key encryption key is a secret
+
+
+/// The current version of the envelope format.
+const VERSION: u8 = 1;
+/// The AEAD the current version seals with.
+const ALGORITHM: &str = "XChaCha20-Poly1305";
+/// Binds the ciphertext to the format and version it was sealed with.
+const AAD: &[u8] = b"beekeeper.paseto_keys.v1";
+
+
+/// Writes `contents` to a temporary file next to `path`, readable by the owner only,
+/// and renames it over `path`, so a crash never leaves a truncated file behind.
+pub(super) fn write_private(path: &str, contents: &[u8]) -> Result<(), Error> {
+    let tmp = format!("{}.tmp", path);
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)
+}
+
+
+/// The key encryption key (KEK) the PASETO key file and the secrets vault are sealed with.
+///
+/// It is never written to the configuration, only the secret reference it was resolved from,
+/// such as `$BEEKEEPER_PASETO_KEK`.
+#[derive(Clone)]
+pub struct Kek {
+    /// The secret reference the key was resolved from
+    pub reference: String,
+    key: [u8; 32],
+}
+
+
+impl Kek {
+    /// Resolves the key from a secret reference.
+    ///
+    /// The secret must be 32 random bytes encoded as base64, e.g. the output of `openssl rand -base64 32`.
+    pub fn new(reference: String) -> Result<Self, Error> {
+        let secret = match <String as Secret>::process(&reference) {
+            Ok(Some(secret)) => secret,
+            Ok(None) => Err(Error::new(ErrorKind::InvalidInput, "the key encryption key must be a secret reference such as $BEEKEEPER_PASETO_KEK, not the key itself"))?,
+            Err(err) => Err(Error::new(ErrorKind::NotFound, format!("could not resolve the key encryption key {}: {}", reference, err)))?,
+        };
+        let secret = secret.trim();
+        let bytes = STANDARD.decode(secret).or_else(|_| URL_SAFE_NO_PAD.decode(secret))
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, format!("the key encryption key {} is not valid base64", reference)))?;
+        let key = bytes.try_into()
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, format!("the key encryption key {} must be 32 bytes long", reference)))?;
+        Ok(Self {reference, key})
+    }
+}
+
+
+impl std::fmt::Debug for Kek {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Kek").field("reference", &self.reference).finish_non_exhaustive()
+    }
+}
+
+
+/// The encrypted form of the PASETO key file and the secrets vault.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    /// The version of the format, it decides how the rest is read
+    pub version: u8,
+    pub alg: String,
+    /// The base64 encoded nonce
+    pub nonce: String,
+    /// The base64 encoded contents, sealed with the KEK
+    pub ciphertext: String,
+}
+
+
+impl Envelope {
+    /// Encrypts the keys with a fresh nonce.
+    pub fn seal(keys: &PasetoKeys, kek: &Kek) -> Result<Self, Error> {
+        Self::encrypt(&serde_json::to_vec(keys)?, kek, AAD)
+    }
+
+    /// Decrypts the keys, failing if the KEK is wrong or the file was tampered with.
+    pub fn open(&self, kek: &Kek) -> Result<PasetoKeys, Error> {
+        Ok(serde_json::from_slice(&self.decrypt(kek, AAD)?)?)
+    }
+
+    /// Encrypts `msg` with a fresh nonce, `aad` names what is being sealed.
+    pub(super) fn encrypt(msg: &[u8], kek: &Kek, aad: &[u8]) -> Result<Self, Error> {
+        let mut nonce = [0u8; 24];
+        OsRng.try_fill_bytes(&mut nonce).map_err(Error::other)?;
+        let cipher = XChaCha20Poly1305::new((&kek.key).into());
+        let ciphertext = cipher.encrypt(XNonce::from_slice(&nonce), Payload {msg, aad})
+            .map_err(|_| Error::other("encryption failed"))?;
+        let version = VERSION;
+        let alg = ALGORITHM.to_string();
+        let nonce = STANDARD.encode(nonce);
+        let ciphertext = STANDARD.encode(ciphertext);
+        Ok(Self {version, alg, nonce, ciphertext})
+    }
+
+    /// Decrypts the contents, failing if the KEK is wrong or the file was tampered with.
+    pub(super) fn decrypt(&self, kek: &Kek, aad: &[u8]) -> Result<Vec<u8>, Error> {
+        if self.version != VERSION || self.alg != ALGORITHM {
+            Err(Error::new(ErrorKind::InvalidData, format!("unsupported envelope version {} ({})", self.version, self.alg)))?
+        }
+        let invalid = |_| Error::new(ErrorKind::InvalidData, "the encrypted file is corrupted");
+        let nonce = STANDARD.decode(&self.nonce).map_err(invalid)?;
+        let ciphertext = STANDARD.decode(&self.ciphertext).map_err(invalid)?;
+        if nonce.len() != 24 {
+            Err(Error::new(ErrorKind::InvalidData, "the encrypted file is corrupted"))?
+        }
+        let cipher = XChaCha20Poly1305::new((&kek.key).into());
+        cipher.decrypt(XNonce::from_slice(&nonce), Payload {msg: &ciphertext, aad})
+            .map_err(|_| Error::new(ErrorKind::InvalidData, format!("could not decrypt with {}, the key is wrong or the file was tampered with", kek.reference)))
+    }
+}
+
+
+/// The contents of the key file, sealed or, before the KEK was configured, in plain text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeyFile {
+    Sealed(Envelope),
+    Plain(PasetoKeys),
+}
+
+
+#[cfg(test)]
+pub(super) fn kek(key: [u8; 32]) -> Kek {
+    Kek {reference: String::from("$TEST_KEK"), key}
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let keys = PasetoKeys::default();
+        let envelope = Envelope::seal(&keys, &kek([7u8; 32])).unwrap();
+        assert_eq!(envelope.version, VERSION);
+        assert!(!envelope.ciphertext.contains(&STANDARD.encode(keys.private_key)));
+        assert_eq!(envelope.open(&kek([7u8; 32])).unwrap(), keys);
+        assert!(envelope.open(&kek([8u8; 32])).is_err());
+
+        let future = Envelope {version: VERSION + 1, ..envelope};
+        assert!(future.open(&kek([7u8; 32])).is_err());
+    }
+
+    #[test]
+    fn test_key_file_formats() {
+        let keys = PasetoKeys::default();
+        let plain = serde_json::to_string(&keys).unwrap();
+        assert!(matches!(serde_json::from_str::<KeyFile>(&plain).unwrap(), KeyFile::Plain(plain) if plain == keys));
+        let sealed = serde_json::to_string(&KeyFile::Sealed(Envelope::seal(&keys, &kek([7u8; 32])).unwrap())).unwrap();
+        assert!(matches!(serde_json::from_str::<KeyFile>(&sealed).unwrap(), KeyFile::Sealed(_)));
+    }
+
+    #[test]
+    fn test_kek_must_be_a_reference() {
+        assert!(Kek::new(String::from("not a reference")).is_err());
+        assert!(Kek::new(String::from("$BEEKEEPER_UNSET_TEST_KEK")).is_err());
+    }
+}
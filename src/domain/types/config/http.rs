@@ -0,0 +1,139 @@
+#[cfg(feature = "http")]
+use actix_web::cookie::{Cookie, SameSite as CookieSameSite};
+use serde::{Serialize, Deserialize}; // For serializing and deserializing data
+use super::super::Id; // Admins are named by their user id
+use super::Throttle; // Limits failed logins
+
+
+/// Default address the server listens on
+const DEFAULT_BIND: &str = "127.0.0.1:8080";
+/// Default log level, used unless `RUST_LOG` is set
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+
+/// The HTTP server: where it listens, how it is secured and who may call it from a browser.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Http {
+    /// Addresses to listen on, such as `0.0.0.0:8080`
+    pub bind: Vec<String>,
+    /// Number of worker threads, one per core when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workers: Option<usize>,
+    /// Serves HTTPS with these when set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<Tls>,
+    /// Origins allowed to call the API with credentials, `*` allows any
+    pub cors_origins: Vec<String>,
+    pub cookie: CookieConfig,
+    /// One of `off`, `error`, `warn`, `info`, `debug` or `trace`
+    pub log_level: String,
+    /// How failed logins are slowed down and locked out
+    pub throttle: Throttle,
+    /// The users allowed to administer the server, such as lifting lockouts
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub admins: Vec<Id>,
+}
+
+
+/// The PEM files of the TLS certificate chain and its private key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tls {
+    pub cert: String,
+    pub key: String,
+}
+
+
+/// The attributes of the session cookies set by the token responders.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CookieConfig {
+    /// The `Domain` attribute, host only when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// The `Secure` attribute, set when TLS is configured unless given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secure: Option<bool>,
+    pub same_site: SameSite,
+}
+
+
+/// The `SameSite` attribute, `none` lets SPAs on other sites send the cookies and implies `Secure`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+    Strict,
+    #[default]
+    Lax,
+    None,
+}
+
+
+impl Http {
+    /// The cookie attributes with `secure` resolved.
+    pub fn cookie(&self) -> CookieConfig {
+        let secure = self.cookie.same_site == SameSite::None || self.cookie.secure.unwrap_or(self.tls.is_some());
+        CookieConfig {secure: Some(secure), ..self.cookie.clone()}
+    }
+}
+
+
+impl Default for Http {
+    fn default() -> Self {
+        let bind = vec![DEFAULT_BIND.to_string()];
+        let workers = None;
+        let tls = None;
+        let cors_origins = Vec::new();
+        let cookie = CookieConfig::default();
+        let log_level = DEFAULT_LOG_LEVEL.to_string();
+        let throttle = Throttle::default();
+        let admins = Vec::new();
+        Self {bind, workers, tls, cors_origins, cookie, log_level, throttle, admins}
+    }
+}
+
+
+#[cfg(feature = "http")]
+impl CookieConfig {
+    /// Builds a cookie with the configured attributes.
+    pub fn build(&self, name: &'static str, value: String) -> Cookie<'static> {
+        let mut cookie = Cookie::new(name, value);
+        if let Some(domain) = &self.domain {
+            cookie.set_domain(domain.clone());
+        }
+        cookie.set_secure(self.secure.unwrap_or(false));
+        cookie.set_same_site(match self.same_site {
+            SameSite::Strict => CookieSameSite::Strict,
+            SameSite::Lax => CookieSameSite::Lax,
+            SameSite::None => CookieSameSite::None,
+        });
+        cookie
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let http = serde_json::from_str::<Http>("{}").unwrap();
+        assert_eq!(http, Http::default());
+        assert_eq!(http.bind, vec![DEFAULT_BIND.to_string()]);
+        assert_eq!(http.cookie().secure, Some(false));
+    }
+
+    #[test]
+    fn test_cookie_security() {
+        let json = r#"{"tls": {"cert": "cert.pem", "key": "key.pem"}, "cookie": {"domain": "example.com"}}"#;
+        let http = serde_json::from_str::<Http>(json).unwrap();
+        assert_eq!(http.cookie().secure, Some(true));
+
+        let json = r#"{"cookie": {"same_site": "none", "secure": false}}"#;
+        let http = serde_json::from_str::<Http>(json).unwrap();
+        let cookie = http.cookie().build("token", String::from("value"));
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(cookie.same_site(), Some(CookieSameSite::None));
+    }
+}
@@ -0,0 +1,306 @@
+use std::error::Error as StdError;
+use std::collections::BTreeMap;
+use serde_json::{Map, Value};
+use serde::Serialize;
+use std::fmt;
+
+
+/// The prefix of environment variables overriding the configuration, `BEEKEEPER__PASETO__TTL` sets `paseto.ttl`.
+pub const ENV_PREFIX: &str = "BEEKEEPER__";
+/// The environment variable holding the path of the configuration file.
+pub const CONFIG_PATH_VAR: &str = "BEEKEEPER_CONFIG";
+/// The environment variable selecting the mode, `production` refuses to write a default configuration.
+pub const MODE_VAR: &str = "BEEKEEPER_ENV";
+
+
+/// The formats a configuration file can be written in, picked by its extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+
+impl Format {
+    pub fn of(path: &str) -> Result<Self, Box<dyn StdError + 'static>> {
+        let extension = std::path::Path::new(path).extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            _ => Err(format!("unsupported configuration file {}, expected a .json, .toml, .yaml or .yml file", path))?
+        }
+    }
+
+    pub fn parse(&self, text: &str) -> Result<Value, Box<dyn StdError + 'static>> {
+        Ok(match self {
+            Self::Json => serde_json::from_str(text)?,
+            Self::Toml => toml::from_str(text)?,
+            Self::Yaml => serde_yaml::from_str(text)?,
+        })
+    }
+
+    pub fn render<T: Serialize>(&self, value: &T) -> Result<String, Box<dyn StdError + 'static>> {
+        Ok(match self {
+            Self::Json => serde_json::to_string_pretty(value)?,
+            Self::Toml => toml::to_string_pretty(value)?,
+            Self::Yaml => serde_yaml::to_string(value)?,
+        })
+    }
+}
+
+
+/// Where a configuration value came from, later layers override earlier ones.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Layer {
+    Default,
+    /// The configuration file at the path.
+    File(String),
+    /// The environment variable.
+    Env(String),
+    /// The command-line flag.
+    Cli(String),
+}
+
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::File(path) => write!(f, "file {}", path),
+            Self::Env(name) => write!(f, "environment variable {}", name),
+            Self::Cli(flag) => write!(f, "flag {}", flag),
+        }
+    }
+}
+
+
+/// The layer every configured value came from, by dotted key such as `paseto.ttl`.
+///
+/// Values are left out, they may be secrets.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sources(BTreeMap<String, Layer>);
+
+
+impl Sources {
+    /// The layer that set `key`, values no layer set are defaults.
+    pub fn get(&self, key: &str) -> &Layer {
+        self.0.get(key).unwrap_or(&Layer::Default)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Layer)> {
+        self.0.iter().map(|(key, layer)| (key.as_str(), layer))
+    }
+}
+
+
+impl fmt::Display for Sources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, layer) in self.iter() {
+            writeln!(f, "{}: {}", key, layer)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// The command-line side of the configuration.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Args {
+    /// The configuration file, from `--config` or `BEEKEEPER_CONFIG`.
+    pub path: Option<String>,
+    /// Set by `--production` or `BEEKEEPER_ENV=production`.
+    pub production: bool,
+    /// The `--section.key=value` flags, in order.
+    pub overrides: Vec<(String, String)>,
+}
+
+
+impl Args {
+    /// Parses `--config <path>`, `--production` and `--section.key <value>` flags,
+    /// falling back to the environment for the path and the mode.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, Box<dyn StdError + 'static>> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let flag = match arg.strip_prefix("--") {
+                Some(flag) if !flag.is_empty() => flag,
+                _ => Err(format!("unexpected argument {}", arg))?
+            };
+            if flag == "production" {
+                parsed.production = true;
+                continue
+            }
+            let (key, value) = match flag.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => match args.next() {
+                    Some(value) => (flag.to_string(), value),
+                    None => Err(format!("flag --{} is missing a value", flag))?
+                }
+            };
+            match key.as_str() {
+                "config" => parsed.path = Some(value),
+                _ => parsed.overrides.push((key, value)),
+            }
+        }
+        if parsed.path.is_none() {
+            parsed.path = std::env::var(CONFIG_PATH_VAR).ok();
+        }
+        parsed.production |= std::env::var(MODE_VAR).is_ok_and(|mode| mode.eq_ignore_ascii_case("production"));
+        Ok(parsed)
+    }
+}
+
+
+/// The configuration being assembled from its layers, along with where each value came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layers {
+    value: Value,
+    sources: Sources,
+}
+
+
+impl Default for Layers {
+    fn default() -> Self {
+        let value = Value::Object(Map::new());
+        let sources = Sources::default();
+        Self {value, sources}
+    }
+}
+
+
+impl Layers {
+    /// Lays the contents of a configuration file over the current values.
+    pub fn file(&mut self, value: Value, path: &str) {
+        let mut leaves = Vec::new();
+        Self::leaves(String::new(), value, &mut leaves);
+        for (key, value) in leaves {
+            self.set(&key, value, Layer::File(path.to_string()));
+        }
+    }
+
+    /// Lays the `BEEKEEPER__SECTION__KEY` variables over the current values.
+    pub fn env<I: IntoIterator<Item = (String, String)>>(&mut self, vars: I) {
+        let mut vars = vars.into_iter()
+            .filter_map(|(name, value)| {
+                let key = name.strip_prefix(ENV_PREFIX)?.split("__").collect::<Vec<&str>>().join(".").to_ascii_lowercase();
+                Some((key, value, name))
+            })
+            .collect::<Vec<(String, String, String)>>();
+        // Parents first, so `BEEKEEPER__PASETO__TTL` is not undone by `BEEKEEPER__PASETO`
+        vars.sort();
+        for (key, value, name) in vars {
+            self.set(&key, scalar(&value), Layer::Env(name));
+        }
+    }
+
+    /// Lays the command-line flags over the current values.
+    pub fn args(&mut self, args: &Args) {
+        for (key, value) in &args.overrides {
+            self.set(key, scalar(value), Layer::Cli(format!("--{}", key)));
+        }
+    }
+
+    /// Sets the value at a dotted key, creating the sections on the way.
+    pub fn set(&mut self, key: &str, value: Value, layer: Layer) {
+        let mut current = &mut self.value;
+        for section in key.split('.') {
+            if !current.is_object() {
+                *current = Value::Object(Map::new());
+            }
+            current = match current {
+                Value::Object(map) => map.entry(section).or_insert(Value::Null),
+                _ => unreachable!(),
+            };
+        }
+        *current = value;
+        let prefix = format!("{}.", key);
+        self.sources.0.retain(|source, _| !source.starts_with(&prefix));
+        self.sources.0.insert(key.to_string(), layer);
+    }
+
+    pub fn into_parts(self) -> (Value, Sources) {
+        (self.value, self.sources)
+    }
+
+    fn leaves(key: String, value: Value, leaves: &mut Vec<(String, Value)>) {
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (section, value) in map {
+                    let key = if key.is_empty() {section} else {format!("{}.{}", key, section)};
+                    Self::leaves(key, value, leaves);
+                }
+            },
+            value if !key.is_empty() => leaves.push((key, value)),
+            _ => (),
+        }
+    }
+}
+
+
+/// Reads an override as JSON when it is valid JSON, `600` is a number and `true` a bool,
+/// and as a string otherwise.
+fn scalar(raw: &str) -> Value {
+    serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(Format::of("config.json").unwrap(), Format::Json);
+        assert_eq!(Format::of("/etc/beekeeper/config.TOML").unwrap(), Format::Toml);
+        assert_eq!(Format::of("config.yml").unwrap(), Format::Yaml);
+        assert!(Format::of("config.ini").is_err());
+        let expected = json!({"paseto": {"ttl": 900}, "domain": "example.com"});
+        assert_eq!(Format::Toml.parse("domain = \"example.com\"\n[paseto]\nttl = 900\n").unwrap(), expected);
+        assert_eq!(Format::Yaml.parse("domain: example.com\npaseto:\n  ttl: 900\n").unwrap(), expected);
+        for format in [Format::Json, Format::Toml, Format::Yaml] {
+            assert_eq!(format.parse(&format.render(&expected).unwrap()).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let mut layers = Layers::default();
+        layers.file(json!({"name": "Beekeeper", "paseto": {"path": "keys.json", "ttl": 900}}), "config.toml");
+        layers.env([
+            ("BEEKEEPER__PASETO__TTL".to_string(), "600".to_string()),
+            ("BEEKEEPER__DOMAIN".to_string(), "example.com".to_string()),
+            ("BEEKEEPER_VAULT".to_string(), "ignored".to_string()),
+        ]);
+        let args = Args::parse(["--paseto.ttl=300".to_string(), "--name".to_string(), "\"123\"".to_string()]).unwrap();
+        layers.args(&args);
+        let (value, sources) = layers.into_parts();
+
+        assert_eq!(value, json!({"name": "123", "domain": "example.com", "paseto": {"path": "keys.json", "ttl": 300}}));
+        assert_eq!(sources.get("paseto.path"), &Layer::File("config.toml".to_string()));
+        assert_eq!(sources.get("domain"), &Layer::Env("BEEKEEPER__DOMAIN".to_string()));
+        assert_eq!(sources.get("paseto.ttl"), &Layer::Cli("--paseto.ttl".to_string()));
+        assert_eq!(sources.get("argon"), &Layer::Default);
+    }
+
+    #[test]
+    fn test_replacing_a_section_drops_its_sources() {
+        let mut layers = Layers::default();
+        layers.file(json!({"paseto": {"ttl": 900}}), "config.json");
+        layers.set("paseto", json!({"path": "keys.json"}), Layer::Cli("--paseto".to_string()));
+        let (_, sources) = layers.into_parts();
+        assert_eq!(sources.iter().count(), 1);
+        assert_eq!(sources.get("paseto.ttl"), &Layer::Default);
+    }
+
+    #[test]
+    fn test_args() {
+        let args = Args::parse(["--config".to_string(), "beekeeper.yaml".to_string(), "--production".to_string()]).unwrap();
+        assert_eq!(args.path.as_deref(), Some("beekeeper.yaml"));
+        assert!(args.production);
+        assert!(Args::parse(["serve".to_string()]).is_err());
+        assert!(Args::parse(["--paseto.ttl".to_string()]).is_err());
+    }
+}
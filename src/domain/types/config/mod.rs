@@ -1,8 +1,19 @@
 mod secret;
+mod envelope;
+mod vault;
+mod layers;
 mod config;
 mod paseto;
 mod argon;
+mod http;
+mod throttle;
 
 pub use secret::*;
+pub use vault::*;
+pub use layers::*;
+pub use envelope::Kek;
 pub use paseto::*;
 pub use config::*;
+pub use http::*;
+pub use throttle::*;
+pub use argon::Argon;
@@ -1,63 +1,158 @@
-use std::os::unix::fs::OpenOptionsExt; // For setting file permissions on Unix systems
 use serde::{Serialize, Deserialize}; // For serializing and deserializing data
-use std::io::{Read, Write, Error}; // For file I/O operations
-use std::fs::{File, OpenOptions}; // For file handling
+use std::io::{Read, Error, ErrorKind}; // For file I/O operations
+use std::fs::File; // For file handling
+use std::sync::{Arc, RwLock, PoisonError}; // For swapping the keys of a running server
+use chrono::{Duration, Utc}; // For deciding when the keys are due for rotation
 use super::super::PasetoKeys; // Importing PasetoKeys for key management
+use super::envelope::{write_private, Envelope, KeyFile, Kek}; // For encrypting the key file at rest
 
 
 /// Default file path for storing Paseto keys
-const DEFAULT_PATH: &'static str = "paseto_keys.json";
+const DEFAULT_PATH: &str = "paseto_keys.json";
 ///Default ttl time for a Paseto token
-const DEFAULT_TTL: i64 = 60*60*24;
+const DEFAULT_TTL: i64 = 60*15;
+///Default ttl time for a refresh token
+const DEFAULT_REFRESH_TTL: i64 = 60*60*24*30;
+/// How long before the keys expire they are rotated
+const ROTATION_MARGIN: Duration = Duration::days(7);
 
 
-#[derive(Debug, Clone, PartialEq)] // Paseto struct with serialization capabilities
+/// Clones share the keys, a rotation through one of them is seen by all.
+#[derive(Debug, Clone)] // Paseto struct with serialization capabilities
 pub struct Paseto {
     /// File path for storing keys
     path: String,
-    /// Paseto keys
-    pub keys: PasetoKeys,
+    /// Paseto keys, swapped in place when rotated
+    keys: Arc<RwLock<PasetoKeys>>,
+    /// The key the key file is encrypted with, the file is plain text without one
+    kek: Option<Kek>,
     /// Token's Time To Live in seconds
-    pub ttl: i64
+    pub ttl: i64,
+    /// Refresh token's Time To Live in seconds
+    pub refresh_ttl: i64
 }
 
 
 impl Paseto {
+    /// The keys tokens are currently signed and verified with.
+    pub fn keys(&self) -> PasetoKeys {
+        *self.keys.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
     /// Saves the Paseto keys to the specified file path.
     ///
     /// # Returns
     ///
     /// * `Result<(), Error>` - Result indicating success or failure of the save operation.
-    fn save(&self) -> Result<(), Error> {
-        let path = &self.path;
-        let mut file = OpenOptions::new() // Open file with write permissions
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .mode(0o600) // Set file permissions: owner can read and write
-            .open(path)?;
-        let keys = &self.keys; // Reference to Paseto keys
-        let json = serde_json::to_string(keys)?; // Serialize keys to JSON
-        let buf = json.as_bytes(); // Convert JSON to bytes
-        file.write_all(buf)?;
-        Ok(())
+    pub fn save(&self) -> Result<(), Error> {
+        Self::write(&self.path, &self.keys(), self.kek.as_ref())
+    }
+
+    /// Writes the keys to a temporary file next to `path` and renames it over `path`,
+    /// so a crash never leaves a truncated key file behind.
+    ///
+    /// The keys are sealed in an [`Envelope`] when a KEK is given.
+    fn write(path: &str, keys: &PasetoKeys, kek: Option<&Kek>) -> Result<(), Error> {
+        let contents = match kek {
+            Some(kek) => KeyFile::Sealed(Envelope::seal(keys, kek)?),
+            None => KeyFile::Plain(*keys),
+        };
+        let json = serde_json::to_string(&contents)?; // Serialize keys to JSON
+        write_private(path, json.as_bytes())
+    }
+
+    /// Reads the keys stored at `path`, decrypting them if they were sealed.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(PasetoKeys, bool), Error>` - The keys and whether they were stored in plain text.
+    fn read(path: &str, kek: Option<&Kek>) -> Result<(PasetoKeys, bool), Error> {
+        let mut json = String::new(); // Buffer for file content
+        File::open(path)?.read_to_string(&mut json)?;
+        match (serde_json::from_str::<KeyFile>(&json)?, kek) {
+            (KeyFile::Plain(keys), _) => Ok((keys, true)),
+            (KeyFile::Sealed(envelope), Some(kek)) => Ok((envelope.open(kek)?, false)),
+            (KeyFile::Sealed(_), None) => Err(Error::new(ErrorKind::InvalidInput, format!("{} is encrypted but no key encryption key is configured", path))),
+        }
+    }
+
+    /// Whether the keys expire within the rotation margin.
+    pub fn rotation_due(&self) -> bool {
+        Utc::now() + ROTATION_MARGIN >= self.keys().expires
+    }
+
+    /// Replaces the keys with a new pair, keeping the current public key for verification.
+    ///
+    /// The new keys are persisted before they are swapped in, if saving fails the
+    /// running keys are left untouched.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PasetoKeys, Error>` - The keys tokens are signed with from now on.
+    pub fn rotate(&self) -> Result<PasetoKeys, Error> {
+        let mut current = self.keys.write().unwrap_or_else(PoisonError::into_inner);
+        let keys = current.rotate();
+        Self::write(&self.path, &keys, self.kek.as_ref())?;
+        *current = keys;
+        Ok(keys)
+    }
+
+    /// Picks up keys rotated by another process, such as the `rotate-keys` command.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<bool, Error>` - Whether the keys on disk differed from the running ones.
+    pub fn reload(&self) -> Result<bool, Error> {
+        let (keys, _) = Self::read(&self.path, self.kek.as_ref())?;
+        let mut current = self.keys.write().unwrap_or_else(PoisonError::into_inner);
+        if *current == keys {
+            return Ok(false)
+        }
+        *current = keys;
+        Ok(true)
     }
 
     /// Loads the Paseto keys from the specified file path.
     ///
+    /// A plain text key file is encrypted in place once a KEK is configured.
+    ///
     /// # Arguments
     ///
     /// * `path` - A string slice representing the file path to load the keys from.
+    /// * `kek` - The key the file is encrypted with, if any.
     ///
     /// # Returns
     ///
     /// * `Result<Self, Error>` - Result containing the loaded Paseto struct or an error.
-    fn load(path: &str, ttl: i64) -> Result<Self, Error> {
-        let mut json = String::new(); // Buffer for file content
-        File::open(path)?.read_to_string(&mut json)?;
+    fn load(path: &str, ttl: i64, refresh_ttl: i64, kek: Option<Kek>) -> Result<Self, Error> {
+        let (keys, plain) = Self::read(path, kek.as_ref())?;
+        if plain && kek.is_some() {
+            Self::write(path, &keys, kek.as_ref())?;
+        }
+        let keys = Arc::new(RwLock::new(keys));
         let path = path.to_string();
-        let keys = serde_json::from_str::<PasetoKeys>(&json)?; // Deserialize JSON to PasetoKeys
-        Ok(Self {path, keys, ttl}) // Return Paseto instance
+        Ok(Self {path, keys, kek, ttl, refresh_ttl}) // Return Paseto instance
+    }
+
+    /// Loads the keys at `path`, generating and saving new ones if there is no key file yet.
+    ///
+    /// Any other error is returned, a key file that cannot be read is never overwritten.
+    pub fn load_or_generate(path: &str, ttl: i64, refresh_ttl: i64, kek: Option<Kek>) -> Result<Self, Error> {
+        match Self::load(path, ttl, refresh_ttl, kek.clone()) {
+            Err(err) if err.kind() == ErrorKind::NotFound && !std::path::Path::new(path).exists() => {
+                let path = path.to_string();
+                let keys = Arc::new(RwLock::new(PasetoKeys::default()));
+                let paseto = Paseto {path, keys, kek, ttl, refresh_ttl}; // Create new Paseto instance
+                paseto.save()?;
+                Ok(paseto)
+            },
+            result => result
+        }
+    }
+
+    /// The keys at the default path with the default lifetimes, generated if there are none yet.
+    pub fn try_default() -> Result<Self, Error> {
+        Self::load_or_generate(DEFAULT_PATH, DEFAULT_TTL, DEFAULT_REFRESH_TTL, None)
     }
 }
 
@@ -70,8 +165,12 @@ impl Serialize for Paseto {
         struct PrePaseto {
             path: String,
             ttl: u64,
+            refresh_ttl: u64,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            kek: Option<String>,
         }
-        let pre_paseto = PrePaseto{path: self.path.clone(), ttl: self.ttl as u64};
+        let kek = self.kek.as_ref().map(|kek| kek.reference.clone());
+        let pre_paseto = PrePaseto{path: self.path.clone(), ttl: self.ttl as u64, refresh_ttl: self.refresh_ttl as u64, kek};
         pre_paseto.serialize(serializer)
     }
 }
@@ -80,26 +179,15 @@ impl Serialize for Paseto {
 impl Default for Paseto {
     /// Provides a default instance of Paseto.
     ///
-    /// This function attempts to load keys from the default path. If loading fails,
+    /// This function attempts to load keys from the default path. If there are none,
     /// it generates new keys, saves them, and returns the new instance.
     ///
     /// # Returns
     ///
     /// * `Self` - A default instance of Paseto.
     fn default() -> Self {
-        let path = DEFAULT_PATH;
-        let ttl = DEFAULT_TTL;
-        match Self::load(path, ttl) {
-            Ok(paseto) => paseto,
-            _ => {
-                let path = path.to_string();
-                let keys = PasetoKeys::default();
-                let paseto = Paseto {path, keys, ttl}; // Create new Paseto instance
-                // Save the new keys, panicking if it fails
-                paseto.save().unwrap();
-                paseto
-            }
-        }
+        // Panics if the keys could neither be loaded nor saved
+        Self::try_default().unwrap()
     }
 }
 
@@ -113,10 +201,61 @@ impl<'de> Deserialize<'de> for Paseto {
         struct PrePaseto {
             path: String,
             ttl: u64,
+            #[serde(default)]
+            refresh_ttl: u64,
+            #[serde(default)]
+            kek: Option<String>,
         }
         let prepaseto = PrePaseto::deserialize(deserializer)?;
+        let kek = prepaseto.kek.map(Kek::new).transpose().map_err(serde::de::Error::custom)?;
         let path = if prepaseto.path.is_empty(){DEFAULT_PATH}else{prepaseto.path.as_str()};
         let ttl = if prepaseto.ttl != 0{prepaseto.ttl as i64}else{DEFAULT_TTL};
-        Ok(Paseto::load(&path, ttl).unwrap_or_default())
+        let refresh_ttl = if prepaseto.refresh_ttl != 0{prepaseto.refresh_ttl as i64}else{DEFAULT_REFRESH_TTL};
+        Paseto::load_or_generate(path, ttl, refresh_ttl, kek).map_err(serde::de::Error::custom)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate_persists_and_reloads() {
+        let path = std::env::temp_dir().join(format!("beekeeper_paseto_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let keys = Arc::new(RwLock::new(PasetoKeys::default()));
+        let paseto = Paseto {path: path.to_string(), keys, kek: None, ttl: DEFAULT_TTL, refresh_ttl: DEFAULT_REFRESH_TTL};
+        paseto.save().unwrap();
+        let other = Paseto::load(path, DEFAULT_TTL, DEFAULT_REFRESH_TTL, None).unwrap();
+        let old = paseto.keys();
+        assert!(!paseto.rotation_due());
+
+        let rotated = paseto.rotate().unwrap();
+        assert_eq!(rotated.prev_public_key, Some(old.public_key));
+        assert_eq!(paseto.keys(), rotated);
+        assert_eq!(Paseto::read(path, None).unwrap(), (rotated, true));
+
+        assert_eq!(other.keys(), old);
+        assert!(other.reload().unwrap());
+        assert_eq!(other.keys(), rotated);
+        assert!(!other.reload().unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_plain_key_file_is_migrated() {
+        let path = std::env::temp_dir().join(format!("beekeeper_paseto_migrate_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let keys = PasetoKeys::default();
+        Paseto::write(path, &keys, None).unwrap();
+
+        let kek = super::super::envelope::kek([7u8; 32]);
+        let paseto = Paseto::load(path, DEFAULT_TTL, DEFAULT_REFRESH_TTL, Some(kek.clone())).unwrap();
+        assert_eq!(paseto.keys(), keys);
+        assert_eq!(Paseto::read(path, Some(&kek)).unwrap(), (keys, false));
+        assert!(Paseto::read(path, None).is_err());
+        assert!(Paseto::load_or_generate(path, DEFAULT_TTL, DEFAULT_REFRESH_TTL, None).is_err());
+        std::fs::remove_file(path).unwrap();
     }
 }
@@ -1,7 +1,51 @@
 use std::error::Error as StdError;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Deserializer};
+use super::vault::Vault;
 use std::env::var;
 
 
+/// The environment variable holding the directory `$$name` secrets are read from.
+pub const SECRETS_DIR_VAR: &str = "BEEKEEPER_SECRETS_DIR";
+/// The directory Docker and Kubernetes mount secrets under.
+const DEFAULT_SECRETS_DIR: &str = "/run/secrets";
+
+
+/// The reasons a secret reference could not be resolved.
+#[derive(Debug)]
+pub enum SecretError {
+    /// The environment variable of a `$name` reference is not set.
+    MissingVariable(String),
+    /// A `$$name` reference is not a plain file name.
+    InvalidName(String),
+    /// The file of a `$$name` reference could not be read.
+    Unreadable(PathBuf, std::io::Error),
+    /// The vault could not be opened for a `$$$name` reference.
+    Vault(String, std::io::Error),
+    /// The vault has no secret named by a `$$$name` reference.
+    NotInVault(String),
+    /// The secret resolved to nothing.
+    Empty(String),
+}
+
+
+impl std::fmt::Display for SecretError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingVariable(name) => write!(f, "environment variable {} is not set", name),
+            Self::InvalidName(name) => write!(f, "secret name {:?} must be a plain file name", name),
+            Self::Unreadable(path, err) => write!(f, "could not read secret file {}: {}", path.display(), err),
+            Self::Vault(path, err) => write!(f, "could not open the secrets vault {}: {}", path, err),
+            Self::NotInVault(name) => write!(f, "secret {} is not in the vault", name),
+            Self::Empty(name) => write!(f, "secret {} is empty", name),
+        }
+    }
+}
+
+
+impl StdError for SecretError {}
+
+
 /// A trait for handling secrets with varying levels of security.
 /// 
 /// This trait provides a mechanism to retrieve secrets from different sources
@@ -20,17 +64,17 @@ use std::env::var;
 /// # Methods
 ///
 /// * `process` - Determines the level of security and retrieves the secret accordingly.
-/// * `single` - Retrieves a secret from an environment variable.
-/// * `double` - Retrieves a secret from a more secure location than environment variables.
-/// * `tripple` - Retrieves a secret from a maximum security location.
+/// * `single` - Retrieves a secret from an environment variable (`$NAME`).
+/// * `double` - Retrieves a secret from a file under `BEEKEEPER_SECRETS_DIR` (`$$name`).
+/// * `tripple` - Retrieves a secret from the encrypted vault (`$$$name`).
 pub trait Secret: Sized {
     type Error;
     const DELIMETER: u8 = b'$';
     /// Determines the level of security and retrieves the secret accordingly.
     ///
-    /// This method inspects the prefix of the key to determine the security level
-    /// and calls the appropriate method (`single`, `double`, or `tripple`) to
-    /// retrieve the secret.
+    /// This method counts the leading delimiters of the key to determine the security level
+    /// and calls the appropriate method (`single`, `double`, or `tripple`) with the
+    /// rest of the key to retrieve the secret.
     ///
     /// # Arguments
     ///
@@ -40,28 +84,17 @@ pub trait Secret: Sized {
     ///
     /// * `Result<Option<Self>, <Self as Secret>::Error>` - Returns an optional secret if successful, or an error if not.
     fn process(key: &str) -> Result<Option<Self>, <Self as Secret>::Error> {
-        // Extract the first three bytes of the key to determine the security level.
-        let mut bytes = match key.as_bytes().get(0..3) {
-            Some(elements) => elements,
-            None => key.as_bytes()
-        }.into_iter().peekable();
-        
-        // Initialize a counter to track the number of delimiters found.
-        let mut round = 0u8;
-        // Count the number of delimiters to determine the security level.
-        while let Some(byte) = bytes.peek() { 
-            if *byte == &Self::DELIMETER {
-                round += 1;
-            }
-            bytes.next();
-        }
+        // Count the leading delimiters to determine the security level.
+        let round = key.bytes().take_while(|byte| *byte == Self::DELIMETER).count();
+        // The name of the secret follows the delimiters.
+        let name = &key[round..];
         
         // Call the appropriate method based on the number of delimiters found.
         match round {
             0 => Ok(None),
-            1 => Ok(Some(Self::single(key)?)),
-            2 => Ok(Some(Self::double(key)?)),
-            3 => Ok(Some(Self::tripple(key)?)),
+            1 => Ok(Some(Self::single(name)?)),
+            2 => Ok(Some(Self::double(name)?)),
+            3 => Ok(Some(Self::tripple(name)?)),
             _ => Ok(None),
         }
     }
@@ -79,7 +112,7 @@ pub trait Secret: Sized {
     ///
     /// # Arguments
     ///
-    /// * `key` - A string slice that holds the key for the secure location.
+    /// * `key` - A string slice that holds the name of a file under the secrets directory.
     ///
     /// # Returns
     ///
@@ -89,7 +122,7 @@ pub trait Secret: Sized {
     ///
     /// # Arguments
     ///
-    /// * `key` - A string slice that holds the key for the maximum security location.
+    /// * `key` - A string slice that holds the name of the secret in the vault.
     ///
     /// # Returns
     ///
@@ -105,22 +138,86 @@ where
 {
     type Error = Box<dyn StdError + 'static>;
     fn single(key: &str) -> Result<Self, <Self as Secret>::Error> {
-        Ok(var(key)?.try_into()?)
+        let secret = var(key).map_err(|_| SecretError::MissingVariable(key.to_string()))?;
+        parse(key, secret)
     }
 
-    fn double(_key: &str) -> Result<Self, <Self as Secret>::Error> {
-        Err("")?
+    fn double(key: &str) -> Result<Self, <Self as Secret>::Error> {
+        let dir = var(SECRETS_DIR_VAR).unwrap_or_else(|_| DEFAULT_SECRETS_DIR.to_string());
+        parse(key, read_file(Path::new(&dir), key)?)
     }
 
-    fn tripple(_key: &str) -> Result<Self, <Self as Secret>::Error> {
-        Err("")?
+    fn tripple(key: &str) -> Result<Self, <Self as Secret>::Error> {
+        let path = Vault::path();
+        let vault = Vault::master_key()
+            .and_then(|kek| Vault::open(&path, &kek))
+            .map_err(|err| SecretError::Vault(path, err))?;
+        let secret = vault.get(key).ok_or_else(|| SecretError::NotInVault(key.to_string()))?;
+        parse(key, secret.to_string())
+    }
+}
+
+
+/// Reads the secret file `name` under `dir`, without the trailing newline editors and `echo` leave.
+fn read_file(dir: &Path, name: &str) -> Result<String, SecretError> {
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        Err(SecretError::InvalidName(name.to_string()))?
+    }
+    let path = dir.join(name);
+    let secret = std::fs::read_to_string(&path).map_err(|err| SecretError::Unreadable(path, err))?;
+    Ok(secret.trim_end_matches(['\r', '\n']).to_string())
+}
+
+
+fn parse<T>(name: &str, secret: String) -> Result<T, Box<dyn StdError + 'static>>
+where
+    T: TryFrom<String>,
+    T::Error: StdError + 'static,
+{
+    if secret.is_empty() {
+        Err(SecretError::Empty(name.to_string()))?
+    }
+    Ok(secret.try_into()?)
+}
+
+
+/// Deserializes a string that may be a secret reference, resolving it.
+///
+/// Values without a leading `$` are taken as they are.
+pub fn deserialize_secret<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    match <String as Secret>::process(&value).map_err(serde::de::Error::custom)? {
+        Some(secret) => Ok(secret),
+        None => Ok(value)
     }
 }
 
 
 #[cfg(test)]
-#[test]
-fn test_secret() {
-    let result = <String as Secret>::process("hello").unwrap();
-    assert_eq!(result, None)
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret() {
+        let result = <String as Secret>::process("hello").unwrap();
+        assert_eq!(result, None)
+    }
+
+    #[test]
+    fn test_missing_secrets_are_explained() {
+        let err = <String as Secret>::process("$BEEKEEPER_UNSET_TEST_SECRET").unwrap_err();
+        assert_eq!(err.to_string(), "environment variable BEEKEEPER_UNSET_TEST_SECRET is not set");
+        assert!(<String as Secret>::process("$$../etc/passwd").unwrap_err().to_string().contains("plain file name"));
+    }
+
+    #[test]
+    fn test_read_file() {
+        let dir = std::env::temp_dir().join(format!("beekeeper_secrets_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("pepper"), "s3cret\n").unwrap();
+        assert_eq!(read_file(&dir, "pepper").unwrap(), "s3cret");
+        assert!(matches!(read_file(&dir, "missing"), Err(SecretError::Unreadable(..))));
+        assert!(matches!(read_file(&dir, ".."), Err(SecretError::InvalidName(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,83 @@
+use serde::{Serialize, Deserialize};
+use chrono::Duration;
+
+
+/// Default seconds an account or client address stays locked out
+const DEFAULT_LOCKOUT: u64 = 15 * 60;
+/// Longest delay between attempts before a lockout, in seconds
+const MAX_DELAY: i64 = 5 * 60;
+
+
+/// How failed logins slow down and then lock out further attempts.
+///
+/// Failures are counted per account and per client address, and forgotten once there was none
+/// for `lockout` seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Throttle {
+    /// Limits the failures on one account
+    pub account: Limit,
+    /// Limits the failures from one client address, across accounts
+    pub address: Limit,
+    /// Seconds a lockout lasts
+    pub lockout: u64,
+}
+
+
+/// The failures allowed before attempts are delayed and before they are locked out.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Limit {
+    /// Failures before each further attempt has to wait, twice as long as the one before
+    pub free: u32,
+    /// Failures before attempts are locked out
+    pub max: u32,
+}
+
+
+impl Default for Throttle {
+    fn default() -> Self {
+        let account = Limit {free: 3, max: 10};
+        let address = Limit {free: 20, max: 100};
+        Self {account, address, lockout: DEFAULT_LOCKOUT}
+    }
+}
+
+
+impl Throttle {
+    /// How long a lockout lasts
+    pub fn lockout(&self) -> Duration {
+        Duration::seconds(self.lockout as i64)
+    }
+}
+
+
+impl Limit {
+    /// How long after the last of `count` failures another attempt is allowed
+    pub fn wait(&self, count: u32, lockout: Duration) -> Duration {
+        if count >= self.max {
+            return lockout
+        }
+        match count.checked_sub(self.free) {
+            Some(delayed) => Duration::seconds(1i64 << delayed.min(30)).min(Duration::seconds(MAX_DELAY)).min(lockout),
+            None => Duration::zero()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait() {
+        let limit = Limit {free: 3, max: 10};
+        let lockout = Duration::minutes(15);
+        assert_eq!(limit.wait(2, lockout), Duration::zero());
+        assert_eq!(limit.wait(3, lockout), Duration::seconds(1));
+        assert_eq!(limit.wait(5, lockout), Duration::seconds(4));
+        assert_eq!(limit.wait(9, lockout), Duration::seconds(64));
+        assert_eq!(limit.wait(10, lockout), lockout);
+        assert_eq!(Limit {free: 0, max: 100}.wait(40, lockout), Duration::seconds(MAX_DELAY));
+    }
+}
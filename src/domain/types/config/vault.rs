@@ -0,0 +1,92 @@
+use std::io::{Error, ErrorKind}; // For file I/O operations
+use std::collections::BTreeMap; // Secrets are kept sorted by name
+use super::envelope::{write_private, Envelope, Kek}; // The vault is sealed like the key file
+
+
+/// The environment variable holding the path of the vault.
+pub const VAULT_PATH_VAR: &str = "BEEKEEPER_VAULT";
+/// The environment variable holding the master key of the vault, 32 bytes in base64.
+pub const VAULT_KEY_VAR: &str = "BEEKEEPER_VAULT_KEY";
+/// Default file path for the vault
+const DEFAULT_PATH: &str = "secrets.vault";
+/// Binds the ciphertext to the vault format.
+const AAD: &[u8] = b"beekeeper.vault.v1";
+
+
+/// An encrypted local file of named secrets, read by `$$$name` references.
+///
+/// The whole file is a single envelope sealed with the master key, like the PASETO key file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Vault {
+    secrets: BTreeMap<String, String>,
+}
+
+
+impl Vault {
+    /// The path of the vault, from `BEEKEEPER_VAULT` or the default.
+    pub fn path() -> String {
+        std::env::var(VAULT_PATH_VAR).unwrap_or_else(|_| DEFAULT_PATH.to_string())
+    }
+
+    /// The master key, from `BEEKEEPER_VAULT_KEY`.
+    pub fn master_key() -> Result<Kek, Error> {
+        Kek::new(format!("${}", VAULT_KEY_VAR))
+    }
+
+    /// Decrypts the vault at `path`.
+    pub fn open(path: &str, kek: &Kek) -> Result<Self, Error> {
+        let json = std::fs::read_to_string(path)?;
+        let envelope = serde_json::from_str::<Envelope>(&json)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, format!("{} is not a secrets vault", path)))?;
+        let secrets = serde_json::from_slice(&envelope.decrypt(kek, AAD)?)?;
+        Ok(Self {secrets})
+    }
+
+    /// Encrypts the vault and writes it to `path`.
+    pub fn save(&self, path: &str, kek: &Kek) -> Result<(), Error> {
+        let envelope = Envelope::encrypt(&serde_json::to_vec(&self.secrets)?, kek, AAD)?;
+        write_private(path, serde_json::to_string(&envelope)?.as_bytes())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.secrets.get(name).map(String::as_str)
+    }
+
+    pub fn set(&mut self, name: String, secret: String) {
+        self.secrets.insert(name, secret);
+    }
+
+    /// Removes a secret, returning whether it was there.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.secrets.remove(name).is_some()
+    }
+
+    /// The names of the secrets in the vault.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.secrets.keys().map(String::as_str)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::envelope::kek;
+
+    #[test]
+    fn test_vault_roundtrip() {
+        let path = std::env::temp_dir().join(format!("beekeeper_vault_{}.vault", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut vault = Vault::default();
+        vault.set("smtp_password".to_string(), "hunter2".to_string());
+        vault.save(path, &kek([7u8; 32])).unwrap();
+        assert!(!std::fs::read_to_string(path).unwrap().contains("hunter2"));
+
+        let mut opened = Vault::open(path, &kek([7u8; 32])).unwrap();
+        assert_eq!(opened.get("smtp_password"), Some("hunter2"));
+        assert!(Vault::open(path, &kek([8u8; 32])).is_err());
+        assert!(opened.remove("smtp_password"));
+        assert_eq!(opened.names().count(), 0);
+        std::fs::remove_file(path).unwrap();
+    }
+}
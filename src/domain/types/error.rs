@@ -15,7 +15,20 @@ pub enum Error {
     InvalidEmail,
     InvalidPhone,
     TokenExpired,
+    TokenRevoked,
     InvalidToken,
+    Forbidden,
+    InsufficientScope { scope: String },
+    InvalidClient,
+    InvalidGrant,
+    /// The client may not use the grant or response type it asked for
+    UnauthorizedClient,
+    UnsupportedResponseType,
+    InvalidScope { scope: String },
+    /// The user did not approve the client
+    AccessDenied,
+    /// Too many failed logins, attempts are refused for `retry_after` more seconds
+    LockedOut { retry_after: u64 },
     
     // Resource errors
     ResourceNotFound { resource: String },
@@ -65,8 +78,17 @@ impl From<HashError> for Error {
 impl From<PasetoError> for Error {
     fn from(err: PasetoError) -> Self {
         match err {
-            PasetoError::InvalidSignature => Error::InvalidToken,
-            // PasetoError::FooterInvalid | PasetoError::WrongHeader,
+            // The token is untrusted input, a malformed one is as invalid as a forged one
+            PasetoError::InvalidSignature |
+            PasetoError::IncorrectSize |
+            PasetoError::WrongHeader |
+            PasetoError::FooterInvalid |
+            PasetoError::PayloadBase64Decode { .. } |
+            PasetoError::Utf8Error { .. } |
+            PasetoError::FromUtf8Error { .. } |
+            PasetoError::TryFromSlice { .. } |
+            PasetoError::Signature |
+            PasetoError::RsaCipher { .. } => Error::InvalidToken,
             _ => Error::internal(err),
         }
     }
@@ -119,7 +141,17 @@ impl Display for Error {
             Self::InvalidEmail => write!(f, "Invalid email format"),
             Self::InvalidPhone => write!(f, "Invalid phone number format"),
             Self::TokenExpired => write!(f, "Token has expired"),
+            Self::TokenRevoked => write!(f, "Token has been revoked"),
             Self::InvalidToken => write!(f, "Invalid token"),
+            Self::Forbidden => write!(f, "You are not allowed to perform this action"),
+            Self::InsufficientScope { scope } => write!(f, "The token does not grant the {} scope", scope),
+            Self::InvalidClient => write!(f, "Client authentication failed"),
+            Self::InvalidGrant => write!(f, "The authorization grant is invalid, expired or was already used"),
+            Self::UnauthorizedClient => write!(f, "The client is not allowed to use this grant"),
+            Self::UnsupportedResponseType => write!(f, "Only the code response type is supported"),
+            Self::InvalidScope { scope } => write!(f, "The {} scope cannot be granted to this client", scope),
+            Self::AccessDenied => write!(f, "The user denied the request"),
+            Self::LockedOut { retry_after } => write!(f, "Too many failed logins, try again in {} seconds", retry_after),
             Self::ResourceNotFound { resource } => write!(f, "{} not found", resource),
             Self::DuplicateResource { resource } => write!(f, "{} already exists", resource),
             Self::ValidationError { field, message } => write!(f, "{}: {}", field, message),
@@ -170,16 +202,49 @@ impl ErrorTrait for Error {
         match self {
             Self::WrongPassword |
             Self::TokenExpired | 
-            Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::TokenRevoked |
+            Self::InvalidToken |
+            Self::InvalidClient => StatusCode::UNAUTHORIZED,
+            Self::Forbidden |
+            Self::AccessDenied |
+            Self::InsufficientScope { .. } => StatusCode::FORBIDDEN,
             Self::InvalidEmail |
             Self::InvalidPhone |
+            Self::InvalidGrant |
+            Self::UnauthorizedClient |
+            Self::UnsupportedResponseType |
+            Self::InvalidScope { .. } |
             Self::ValidationError { .. } |
             Self::InvalidFormat { .. } => StatusCode::BAD_REQUEST,
             Self::ResourceNotFound { .. } => StatusCode::NOT_FOUND,
             Self::DuplicateResource { .. } => StatusCode::CONFLICT,
+            Self::LockedOut { .. } => StatusCode::TOO_MANY_REQUESTS,
             Self::Internal { .. } => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    #[cfg(feature = "http")]
+    fn retry_after(&self) -> Option<u64> {
+        match self {
+            Self::LockedOut { retry_after } => Some(*retry_after),
+            _ => None
+        }
+    }
+
+    #[cfg(feature = "http")]
+    fn challenge(&self) -> Option<String> {
+        match self {
+            Self::TokenExpired |
+            Self::TokenRevoked |
+            Self::InvalidToken => Some(format!("Bearer error=\"invalid_token\", error_description=\"{}\"", self)),
+            Self::InsufficientScope { scope } => Some(format!("Bearer error=\"insufficient_scope\", scope=\"{}\"", scope)),
+            Self::InvalidClient => Some(String::from("Basic")),
+            _ => match self.status() {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Some(String::from("Bearer")),
+                _ => None
+            }
+        }
+    }
 }
 
 
@@ -0,0 +1,12 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+
+/// The failed logins counted against an account or a client address.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Failures {
+    /// The number of failures since they were last forgotten
+    pub count: u32,
+    /// When the last one happened
+    pub last: DateTime<Utc>,
+}
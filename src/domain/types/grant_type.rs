@@ -9,6 +9,7 @@ pub enum GrantType {
     Implicit,
     Password,
     ClientCredentials,
+    RefreshToken,
 }
 
 impl FromStr for GrantType {
@@ -20,6 +21,7 @@ impl FromStr for GrantType {
             "implicit" => Ok(GrantType::Implicit),
             "password" => Ok(GrantType::Password),
             "client_credentials" => Ok(GrantType::ClientCredentials),
+            "refresh_token" => Ok(GrantType::RefreshToken),
             _ => Err(Error::invalid_format("GrantType", s, None))?,
         }
     }
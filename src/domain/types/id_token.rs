@@ -0,0 +1,75 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+use chrono::{Duration, Utc};
+use super::{PasetoKeys, UserInfo, Error};
+
+/// A struct representing the claims of an OpenID Connect ID token (OIDC core section 2).
+///
+/// Unlike access tokens, ID tokens are JWTs, as that is what relying parties expect.
+/// They are signed with the same Ed25519 key as the PASETO tokens.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IdToken {
+    pub iss: String,
+    /// The id of the client the token was issued to.
+    pub aud: String,
+    /// The time the token expires, in seconds since the epoch.
+    pub exp: i64,
+    /// The time the token was issued, in seconds since the epoch.
+    pub iat: i64,
+    /// The nonce sent by the client in the authorization request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// The claims about the user, `sub` included.
+    #[serde(flatten)]
+    pub user: UserInfo,
+}
+
+impl IdToken {
+    pub fn new(issuer: String, audience: String, user: UserInfo, nonce: Option<String>, ttl: i64) -> Self {
+        let issued_at = Utc::now();
+        let exp = (issued_at + Duration::seconds(ttl)).timestamp();
+        let iat = issued_at.timestamp();
+        Self {iss: issuer, aud: audience, exp, iat, nonce, user}
+    }
+
+    /// Signs the claims into a compact JWS using EdDSA.
+    ///
+    /// The header carries the id of the signing key, as published in the JWKS.
+    pub fn try_sign(&self, keys: &PasetoKeys) -> Result<String, Error> {
+        let header = serde_json::json!({"alg": "EdDSA", "typ": "JWT", "kid": keys.kid()});
+        let header = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?);
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(self)?);
+        let input = format!("{}.{}", header, payload);
+        let signature = SigningKey::from_bytes(&keys.private_key).sign(input.as_bytes());
+        Ok(format!("{}.{}", input, URL_SAFE_NO_PAD.encode(signature.to_bytes())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    #[test]
+    fn test_signed_jwt_verifies() {
+        let keys = PasetoKeys::default();
+        let user = UserInfo {sub: "subject".to_string(), ..Default::default()};
+        let token = IdToken::new("https://example.com".to_string(), "client".to_string(), user, Some("n-0S6_WzA2Mj".to_string()), 60);
+        let jwt = token.try_sign(&keys).unwrap();
+
+        let parts = jwt.split('.').collect::<Vec<&str>>();
+        assert_eq!(parts.len(), 3);
+        let header: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[0]).unwrap()).unwrap();
+        assert_eq!(header["alg"], "EdDSA");
+        assert_eq!(header["kid"], keys.kid());
+
+        let claims: IdToken = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+        assert_eq!(claims, token);
+
+        let signature = Signature::from_slice(&URL_SAFE_NO_PAD.decode(parts[2]).unwrap()).unwrap();
+        let key = VerifyingKey::from_bytes(&keys.public_key).unwrap();
+        let input = format!("{}.{}", parts[0], parts[1]);
+        assert!(key.verify(input.as_bytes(), &signature).is_ok());
+    }
+}
@@ -0,0 +1,121 @@
+#[cfg(feature = "http")]
+use actix_web::{Responder, HttpResponse, body::BoxBody, http::header::{CACHE_CONTROL, PRAGMA}};
+use serde::{Deserialize, Serialize};
+use super::{Audience, RefreshToken, Token, Value};
+
+/// A struct representing the response of the OAuth2 token introspection endpoint (RFC 7662 section 2.2).
+///
+/// Only `active` is set for tokens that are expired, revoked, badly signed or unknown.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Introspection {
+    /// Whether the token can currently be used.
+    pub active: bool,
+    /// The space separated scopes of the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// The id of the service (client) the token was issued to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// The kind of token, `access_token` or `refresh_token`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    /// The time the token expires, in seconds since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    /// The time the token was issued, in seconds since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    /// The time before which the token must not be accepted, in seconds since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<i64>,
+    /// The subject of the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    /// The audience of the token.
+    #[serde(default, skip_serializing_if = "Audience::is_empty")]
+    pub aud: Audience,
+    /// The issuer of the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    /// The unique id of the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+impl Introspection {
+    /// The response for any token that cannot be used.
+    pub fn inactive() -> Self {
+        Self::default()
+    }
+}
+
+impl From<Token> for Introspection {
+    fn from(token: Token) -> Self {
+        let claim = |name: &str| match token.claims.get(name) {
+            Some(Value::String(value)) if !value.is_empty() => Some(value.clone()),
+            _ => None
+        };
+        Self {
+            active: true,
+            scope: claim("scope"),
+            client_id: claim("client_id"),
+            token_type: Some(String::from("access_token")),
+            exp: Some(token.expiration.timestamp()),
+            iat: Some(token.issued_at.timestamp()),
+            nbf: token.not_before.map(|nbf| nbf.timestamp()),
+            sub: Some(token.subject.to_hex()),
+            aud: token.audience.clone(),
+            iss: Some(token.issuer.clone()),
+            jti: Some(token.id.to_hex()),
+        }
+    }
+}
+
+impl From<RefreshToken> for Introspection {
+    fn from(refresh: RefreshToken) -> Self {
+        Self {
+            active: true,
+            scope: Some(refresh.scope.join(" ")).filter(|scope| !scope.is_empty()),
+            client_id: refresh.client_id.map(|id| id.to_hex()),
+            token_type: Some(String::from("refresh_token")),
+            exp: Some(refresh.expires.timestamp()),
+            sub: Some(refresh.user_id.to_hex()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+impl Responder for Introspection {
+    type Body = BoxBody;
+    fn respond_to(self, _: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
+        HttpResponse::Ok()
+            .insert_header((CACHE_CONTROL, "no-store"))
+            .insert_header((PRAGMA, "no-cache"))
+            .json(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::Id;
+
+    #[test]
+    fn test_inactive_only_has_active() {
+        let json = serde_json::to_value(Introspection::inactive()).unwrap();
+        assert_eq!(json, serde_json::json!({"active": false}));
+    }
+
+    #[test]
+    fn test_from_token() {
+        let mut token = Token::new(String::from("Beekeeper"), Id::default(), Audience::One(String::from("client")), 60);
+        token.claims.insert(String::from("scope"), Value::String(String::from("read write")));
+        let introspection = Introspection::from(token.clone());
+        assert!(introspection.active);
+        assert_eq!(introspection.scope.as_deref(), Some("read write"));
+        assert_eq!(introspection.sub, Some(token.subject.to_hex()));
+        assert_eq!(introspection.exp, Some(token.expiration.timestamp()));
+        assert_eq!(introspection.client_id, None);
+    }
+}
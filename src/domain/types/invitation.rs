@@ -0,0 +1,68 @@
+#[cfg(feature = "http")]
+use actix_web::{Responder, web::Json, http::{Method, StatusCode}};
+use crate::ports::outputs::database::Item;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+use super::{Contact, Id};
+
+/// A struct representing a pending invitation for a contact to join an organisation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Invitation {
+    /// The unique identifier for the invitation.
+    #[serde(default)]
+    pub id: Id,
+    /// The unique identifier for the organisation.
+    #[serde(default)]
+    pub org_id: Id,
+    /// The email address or phone number the invitation is sent to.
+    #[serde(flatten)]
+    pub contact: Contact,
+    /// The title the invitee will have once they join.
+    #[serde(default)]
+    pub title: String,
+    /// The list of role IDs the invitee will have once they join.
+    #[serde(default)]
+    pub roles: Vec<Id>,
+    /// The unique identifier for the user who sent the invitation.
+    #[serde(default)]
+    pub invited_by: Id,
+    /// The time when the invitation can no longer be accepted.
+    #[serde(default = "Utc::now")]
+    pub expires: DateTime<Utc>,
+}
+
+impl Invitation {
+    /// How long an invitation stays valid, in seconds (one week).
+    pub const TTL: i64 = 60 * 60 * 24 * 7;
+
+    /// Starts the validity window of the invitation from now.
+    pub fn renew(&mut self) {
+        self.expires = Utc::now() + Duration::seconds(Self::TTL);
+    }
+
+    pub fn expired(&self) -> bool {
+        self.expires <= Utc::now()
+    }
+}
+
+#[cfg(feature = "http")]
+impl Responder for Invitation {
+    type Body = <Json<Self> as Responder>::Body;
+    fn respond_to(self, req: &actix_web::HttpRequest) -> actix_web::HttpResponse<Self::Body> {
+        match req.method() {
+            &Method::POST => {
+                let mut res = Json(self).respond_to(req);
+                *res.status_mut() = StatusCode::CREATED;
+                res
+            },
+            _ => Json(self).respond_to(req)
+        }
+    }
+}
+
+impl Item for Invitation {
+    /// This is the invitation id.
+    type PK = Id;
+    /// This is the organisation id and the invited contact.
+    type SK = (Id, Contact);
+}
@@ -0,0 +1,115 @@
+#[cfg(feature = "http")]
+use actix_web::{Responder, HttpResponse, body::BoxBody, http::header::CACHE_CONTROL};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use super::PasetoKeys;
+
+/// How long verifiers may cache the published keys, in seconds.
+const MAX_AGE: u32 = 60 * 60;
+
+/// A struct representing an Ed25519 public key as a JWK (RFC 8037).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    /// The base64url encoded public key.
+    pub x: String,
+    pub kid: String,
+    #[serde(rename = "use")]
+    pub usage: String,
+    pub alg: String,
+}
+
+/// A struct representing the public keys tokens may be signed with, as a JWK set (RFC 7517 section 5).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+/// A struct representing a public key as a PASERK `k4.public` string.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Paserk {
+    /// The PASERK `k4.pid` of the key, the same id found in token footers.
+    pub kid: String,
+    pub paserk: String,
+}
+
+/// A struct representing the public keys tokens may be signed with, as PASERK strings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PaserkSet {
+    pub keys: Vec<Paserk>,
+}
+
+impl Paserk {
+    /// The public key, if it is a `k4.public` key matching its id.
+    pub fn public_key(&self) -> Option<[u8; 32]> {
+        let key = self.paserk.strip_prefix("k4.public.")?;
+        let key = URL_SAFE_NO_PAD.decode(key).ok()?.try_into().ok()?;
+        (PasetoKeys::key_id(&key) == self.kid).then_some(key)
+    }
+}
+
+impl From<&PasetoKeys> for Jwks {
+    fn from(keys: &PasetoKeys) -> Self {
+        let keys = keys.public_keys().iter().map(|key| Jwk {
+            kty: String::from("OKP"),
+            crv: String::from("Ed25519"),
+            x: URL_SAFE_NO_PAD.encode(key),
+            kid: PasetoKeys::key_id(key),
+            usage: String::from("sig"),
+            alg: String::from("EdDSA"),
+        }).collect();
+        Self {keys}
+    }
+}
+
+impl From<&PasetoKeys> for PaserkSet {
+    fn from(keys: &PasetoKeys) -> Self {
+        let keys = keys.public_keys().iter().map(|key| Paserk {
+            kid: PasetoKeys::key_id(key),
+            paserk: PasetoKeys::paserk(key),
+        }).collect();
+        Self {keys}
+    }
+}
+
+#[cfg(feature = "http")]
+impl Responder for Jwks {
+    type Body = BoxBody;
+    fn respond_to(self, _: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
+        HttpResponse::Ok()
+            .insert_header((CACHE_CONTROL, format!("public, max-age={}", MAX_AGE)))
+            .json(self)
+    }
+}
+
+#[cfg(feature = "http")]
+impl Responder for PaserkSet {
+    type Body = BoxBody;
+    fn respond_to(self, _: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
+        HttpResponse::Ok()
+            .insert_header((CACHE_CONTROL, format!("public, max-age={}", MAX_AGE)))
+            .json(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_both_sets_share_key_ids() {
+        let prev_public_key = Some(PasetoKeys::default().public_key);
+        let keys = PasetoKeys {prev_public_key, ..PasetoKeys::default()};
+        let jwks = Jwks::from(&keys);
+        let paserks = PaserkSet::from(&keys);
+        assert_eq!(jwks.keys.len(), 2);
+        assert_eq!(jwks.keys[0].kid, keys.kid());
+        let kids = paserks.keys.iter().map(|key| key.kid.clone()).collect::<Vec<String>>();
+        assert_eq!(kids, jwks.keys.iter().map(|key| key.kid.clone()).collect::<Vec<String>>());
+        assert_eq!(paserks.keys[0].paserk, PasetoKeys::paserk(&keys.public_key));
+        assert_eq!(paserks.keys[1].public_key(), keys.prev_public_key);
+        let forged = Paserk {kid: paserks.keys[1].kid.clone(), ..paserks.keys[0].clone()};
+        assert_eq!(forged.public_key(), None);
+    }
+}
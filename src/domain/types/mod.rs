@@ -1,10 +1,19 @@
 mod organisation;
+mod authorization_code;
+mod token_response;
+mod refresh_token;
+mod revocation;
+mod invitation;
+mod id_token;
+mod introspection;
 mod verification;
 mod paseto_keys;
+mod key_set;
 mod permission;
 mod grant_type;
 mod resource;
 mod service;
+mod session;
 mod contact;
 mod number;
 mod either;
@@ -18,18 +27,29 @@ mod grant;
 mod email;
 mod token;
 mod user;
+mod user_info;
 mod role;
 mod key;
 mod id;
+mod failures;
 
 /// Re-exporting types for external access.
 pub use organisation::*;
+pub use authorization_code::*;
+pub use token_response::*;
+pub use refresh_token::*;
+pub use revocation::*;
+pub use invitation::*;
+pub use id_token::*;
+pub use introspection::*;
 pub use verification::*;
 pub use paseto_keys::*;
+pub use key_set::*;
 pub use permission::*;
 pub use grant_type::*;
 pub use resource::*;
 pub use service::*;
+pub use session::*;
 pub use contact::*;
 pub use either::*;
 pub use member::*;
@@ -42,6 +62,8 @@ pub use grant::*;
 pub use email::*;
 pub use token::*;
 pub use user::*;
+pub use user_info::*;
 pub use role::*;
 pub use key::*;
-pub use id::*;
\ No newline at end of file
+pub use id::*;
+pub use failures::*;
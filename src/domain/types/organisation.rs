@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Organisation {
     /// The unique identifier for the organisation.
+    #[serde(default)]
     pub id: Id,
     /// The name of the organisation.
     pub name: String,
@@ -16,6 +17,7 @@ pub struct Organisation {
     /// The home URL of the organisation, if available.
     pub home: Option<String>,
     /// A list of named contact information associated with the organisation.
+    #[serde(default)]
     pub contacts: Vec<(String, Contact)>,
 }
 
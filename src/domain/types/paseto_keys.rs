@@ -3,6 +3,8 @@ use ed25519_dalek::{SigningKey, SecretKey}; // Importing the SigningKey for gene
 use serde::{Serialize, Deserialize}; // Importing necessary traits for serialization and deserialization.
 use chrono::{DateTime, Utc}; // Importing DateTime and Utc for handling time-related data.
 use chrono::Duration; // Importing Duration for handling time intervals.
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine}; // Importing base64url for PASERK encoding.
+use blake2::{Blake2b, Digest, digest::consts::U33}; // Importing BLAKE2b-264 for PASERK key ids.
 use std::ops::DerefMut;
 
 
@@ -30,6 +32,39 @@ impl PasetoKeys {
         csprng.try_fill_bytes(&mut secret).expect("Failed to generate random bytes");
         SigningKey::from_bytes(&secret)
     }
+
+    /// Serializes a public key as a PASERK `k4.public` string.
+    pub fn paserk(public_key: &[u8; 32]) -> String {
+        format!("k4.public.{}", URL_SAFE_NO_PAD.encode(public_key))
+    }
+
+    /// The PASERK `k4.pid` of a public key, a stable id derived from the key itself.
+    pub fn key_id(public_key: &[u8; 32]) -> String {
+        let header = "k4.pid.";
+        let digest = Blake2b::<U33>::new()
+            .chain_update(header)
+            .chain_update(Self::paserk(public_key))
+            .finalize();
+        format!("{}{}", header, URL_SAFE_NO_PAD.encode(digest))
+    }
+
+    /// The id of the key tokens are currently signed with.
+    pub fn kid(&self) -> String {
+        Self::key_id(&self.public_key)
+    }
+
+    /// A new key pair, demoting the current public key to the previous one.
+    ///
+    /// Tokens signed before the rotation keep verifying until the next one.
+    pub fn rotate(&self) -> Self {
+        let prev_public_key = Some(self.public_key);
+        Self {prev_public_key, ..Self::default()}
+    }
+
+    /// Every public key tokens may still be signed with, the current one first.
+    pub fn public_keys(&self) -> Vec<[u8; 32]> {
+        std::iter::once(self.public_key).chain(self.prev_public_key).collect()
+    }
 }
 
 
@@ -60,3 +95,33 @@ impl Default for PasetoKeys {
         Self {private_key, public_key, prev_public_key, created_time, expires}
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paserk() {
+        let key: [u8; 32] = std::array::from_fn(|i| i as u8);
+        assert_eq!(PasetoKeys::paserk(&key), "k4.public.AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8");
+        assert_eq!(PasetoKeys::key_id(&key), "k4.pid.7t-vnCSipGvqwSmW6xCWuLwd97Xu_SRXA9EwEgYTkhgV");
+    }
+
+    #[test]
+    fn test_public_keys() {
+        let mut keys = PasetoKeys::default();
+        assert_eq!(keys.public_keys(), vec![keys.public_key]);
+        keys.prev_public_key = Some([0u8; 32]);
+        assert_eq!(keys.public_keys(), vec![keys.public_key, [0u8; 32]]);
+    }
+
+    #[test]
+    fn test_rotate() {
+        let keys = PasetoKeys::default();
+        let rotated = keys.rotate();
+        assert_ne!(rotated.private_key, keys.private_key);
+        assert_eq!(rotated.prev_public_key, Some(keys.public_key));
+        assert!(rotated.expires >= keys.expires);
+    }
+}
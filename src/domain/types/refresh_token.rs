@@ -0,0 +1,100 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use crate::ports::outputs::database::Item;
+use rand::{rngs::OsRng, TryRngCore};
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use super::Id;
+
+/// A struct representing an opaque refresh token stored on the server.
+///
+/// Only the SHA-256 digest of the token is kept, the token itself is handed out once.
+/// Every refresh consumes the token and issues a new one in the same family, so a token
+/// showing up a second time means it was stolen and the whole family is revoked.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RefreshToken {
+    /// The base64url encoded SHA-256 digest of the token.
+    pub id: String,
+    /// The family every rotation of the first token belongs to.
+    pub family: Id,
+    /// The id of the user the token was issued to.
+    pub user_id: Id,
+    /// The id of the service (client) the token was issued to, none for first party sessions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<Id>,
+    /// The scopes granted to the client.
+    #[serde(default)]
+    pub scope: Vec<String>,
+    /// Whether the token was already exchanged for a new one.
+    #[serde(default)]
+    pub used: bool,
+    /// The time when the token can no longer be used.
+    pub expires: DateTime<Utc>,
+}
+
+impl RefreshToken {
+    /// Starts a new family of refresh tokens.
+    ///
+    /// # Returns
+    ///
+    /// * `(Self, String)` - The token to store and the opaque token to hand out.
+    pub fn new(user_id: Id, client_id: Option<Id>, scope: Vec<String>, ttl: i64) -> (Self, String) {
+        Self::generate(Id::default(), user_id, client_id, scope, ttl)
+    }
+
+    /// The next token of this token's family.
+    pub fn rotate(&self, ttl: i64) -> (Self, String) {
+        Self::generate(self.family, self.user_id, self.client_id, self.scope.clone(), ttl)
+    }
+
+    /// The digest a refresh token is stored under.
+    pub fn digest(token: &str) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+    }
+
+    pub fn expired(&self) -> bool {
+        self.expires <= Utc::now()
+    }
+
+    fn generate(family: Id, user_id: Id, client_id: Option<Id>, scope: Vec<String>, ttl: i64) -> (Self, String) {
+        let mut bytes = [0u8; 32];
+        OsRng.try_fill_bytes(&mut bytes).expect("Failed to generate random bytes");
+        let token = URL_SAFE_NO_PAD.encode(bytes);
+        let id = Self::digest(&token);
+        let used = false;
+        let expires = Utc::now() + Duration::seconds(ttl);
+        (Self {id, family, user_id, client_id, scope, used, expires}, token)
+    }
+}
+
+impl Item for RefreshToken {
+    /// This is the digest of the token.
+    type PK = String;
+    /// This is the family.
+    type SK = Id;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_digest_is_stored() {
+        let (refresh, token) = RefreshToken::new(Id::default(), None, vec![], 60);
+        assert_ne!(refresh.id, token);
+        assert_eq!(refresh.id, RefreshToken::digest(&token));
+        assert!(!refresh.used);
+        assert!(!refresh.expired());
+    }
+
+    #[test]
+    fn test_rotate_keeps_the_family() {
+        let (refresh, token) = RefreshToken::new(Id::default(), Some(Id::default()), vec!["read".to_string()], 60);
+        let (next, next_token) = refresh.rotate(60);
+        assert_ne!(token, next_token);
+        assert_ne!(refresh.id, next.id);
+        assert_eq!(refresh.family, next.family);
+        assert_eq!(refresh.client_id, next.client_id);
+        assert_eq!(refresh.scope, next.scope);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::ports::outputs::database::Item;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+use super::{Id, Token};
+
+/// A struct representing a revoked access token, or every token of a subject issued up to a point in time.
+///
+/// Entries are only needed until the tokens they cover expire on their own.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Revocation {
+    /// The jti of the revoked token, or the subject when all of its tokens were revoked.
+    pub id: Id,
+    /// The subject of the revoked tokens.
+    pub subject: Id,
+    /// The time of the revocation.
+    pub revoked_at: DateTime<Utc>,
+    /// The time after which every covered token has expired anyway.
+    pub expires: DateTime<Utc>,
+}
+
+impl Revocation {
+    /// Revokes a single token.
+    pub fn token(token: &Token) -> Self {
+        let revoked_at = Utc::now();
+        Self {id: token.id, subject: token.subject, revoked_at, expires: token.expiration}
+    }
+
+    /// Revokes every token issued to the subject so far.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - The lifetime of the longest lived token the subject may hold, in seconds.
+    pub fn subject(subject: Id, ttl: i64) -> Self {
+        let revoked_at = Utc::now();
+        let expires = revoked_at + Duration::seconds(ttl);
+        Self {id: subject, subject, revoked_at, expires}
+    }
+
+    pub fn expired(&self) -> bool {
+        self.expires <= Utc::now()
+    }
+
+    /// Whether the token is revoked by this entry.
+    pub fn covers(&self, token: &Token) -> bool {
+        if self.subject != token.subject {
+            return false
+        }
+        self.id == token.id || (self.id == self.subject && token.issued_at <= self.revoked_at)
+    }
+}
+
+impl Item for Revocation {
+    /// This is the jti, or the subject.
+    type PK = Id;
+    /// This is the subject.
+    type SK = Id;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_revocation_covers_only_the_token() {
+        let subject = Id::default();
+        let token = Token::new(String::new(), subject, Default::default(), 60);
+        let other = Token::new(String::new(), subject, Default::default(), 60);
+        let revocation = Revocation::token(&token);
+        assert!(revocation.covers(&token));
+        assert!(!revocation.covers(&other));
+        assert_eq!(revocation.expires, token.expiration);
+    }
+
+    #[test]
+    fn test_subject_revocation_covers_earlier_tokens() {
+        let subject = Id::default();
+        let before = Token::new(String::new(), subject, Default::default(), 60);
+        let revocation = Revocation::subject(subject, 60);
+        let after = Token::new(String::new(), subject, Default::default(), 60);
+        let stranger = Token::new(String::new(), Id::default(), Default::default(), 60);
+        assert!(revocation.covers(&before));
+        assert!(!revocation.covers(&after));
+        assert!(!revocation.covers(&stranger));
+    }
+}
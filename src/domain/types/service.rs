@@ -8,14 +8,56 @@ use chrono::Duration;
 
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Service {
+    #[serde(default)]
     pub id: Id,
+    #[serde(default)]
     pub owner_id: Id,
     pub name: String,
+    /// The Argon2 hash of the secret, it only holds the plaintext secret in the response to a registration.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub client_secret: String,
+    #[serde(default)]
     pub redirect_uris: Vec<String>,
+    #[serde(default)]
     pub scopes: Vec<Scope>,
+    #[serde(default)]
     pub grant_types: Vec<GrantType>,
+    #[serde(default)]
     pub token_expiry: Option<Duration>,
+    /// Whether the client can keep a secret, clients registered before client types were are confidential.
+    #[serde(default)]
+    pub client_type: ClientType,
+}
+
+
+/// Whether an OAuth2 client can keep a secret (RFC 6749 section 2.1).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientType {
+    /// Runs on a server and authenticates with its secret
+    #[default]
+    Confidential,
+    /// Runs on the user's device, such as a single page or mobile app, and has no secret
+    Public,
+}
+
+
+impl ClientType {
+    /// The name a client type is stored under
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Confidential => "confidential",
+            Self::Public => "public",
+        }
+    }
+
+    /// Reads a stored client type, anything but a public client is confidential
+    pub fn from_stored(name: &str) -> Self {
+        match name {
+            "public" => Self::Public,
+            _ => Self::Confidential,
+        }
+    }
 }
 
 
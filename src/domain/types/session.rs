@@ -0,0 +1,72 @@
+#[cfg(feature = "http")]
+use actix_web::{Responder, HttpResponse, body::BoxBody, http::header::{AUTHORIZATION, CACHE_CONTROL, PRAGMA}};
+#[cfg(feature = "http")]
+use super::CookieConfig;
+use super::{Token, TokenResponse, Error};
+
+/// A struct representing a signed access token together with the tokens issued alongside it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    /// The signed access token.
+    pub token: Token,
+    /// The opaque refresh token, none when the grant does not allow refreshing.
+    pub refresh_token: Option<String>,
+    /// The OpenID Connect ID token, only issued when the `openid` scope was granted.
+    pub id_token: Option<String>,
+}
+
+impl From<Token> for Session {
+    fn from(token: Token) -> Self {
+        Self {token, refresh_token: None, id_token: None}
+    }
+}
+
+impl TryFrom<Session> for TokenResponse {
+    type Error = Error;
+    fn try_from(session: Session) -> Result<Self, Self::Error> {
+        let mut response = TokenResponse::try_from(session.token)?;
+        response.refresh_token = session.refresh_token;
+        response.id_token = session.id_token;
+        Ok(response)
+    }
+}
+
+#[cfg(feature = "http")]
+impl Responder for Session {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
+        let is_json = req.headers().get("Content-Type")
+            .map(|h| h.to_str().unwrap_or(""))
+            .map(|h| h.contains("json"))
+            .unwrap_or(false);
+
+        let response = match TokenResponse::try_from(self) {
+            Ok(response) => response,
+            Err(err) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": err.to_string()}))
+        };
+
+        let mut builder = HttpResponse::Ok();
+        builder
+            .insert_header((CACHE_CONTROL, "no-store"))
+            .insert_header((PRAGMA, "no-cache"));
+
+        if is_json {
+            // Same as the token responder, plus the refresh token in the body
+            builder
+                .insert_header((AUTHORIZATION, format!("Bearer {}", response.access_token)))
+                .json(response)
+        } else {
+            let cookies = req.app_data::<CookieConfig>().cloned().unwrap_or_default();
+            builder.cookie(cookies.build("token", response.access_token));
+            if let Some(refresh_token) = response.refresh_token {
+                // The refresh token is only ever sent back to the refresh endpoint
+                let mut cookie = cookies.build("refresh_token", refresh_token);
+                cookie.set_path("/token/refresh");
+                cookie.set_http_only(true);
+                builder.cookie(cookie);
+            }
+            builder.body(String::new())
+        }
+    }
+}
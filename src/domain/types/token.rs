@@ -1,7 +1,7 @@
 use actix_web::http::StatusCode;
 use serde::{Serialize, Deserialize};
 use std::{collections::HashMap, slice::Windows};
-use super::{Id, Value};
+use super::{Id, Value, Scope, Permission};
 use chrono::{Utc, DateTime};
 
 #[cfg(feature = "http")]
@@ -11,9 +11,10 @@ use actix_web::{
     HttpResponseBuilder,
     web::{Json, Data},
     body::BoxBody,
-    cookie::Cookie,
     ResponseError
 };
+#[cfg(feature = "http")]
+use super::CookieConfig;
 // use crate::adaptors::outputs::{database::memory::Memory, mailer::smtp::SmtpMailer};
 use std::sync::Arc;
 use crate::{
@@ -58,6 +59,19 @@ pub struct Token<T = HashMap<String, Value>> {
 }
 
 
+impl Token {
+    pub fn new(issuer: String, subject: Id, audience: Audience, ttl: i64) -> Self {
+        let id = Default::default();
+        let issued_at = Utc::now();
+        let not_before = None;
+        let expiration = issued_at + chrono::Duration::seconds(ttl);
+        let claims = Default::default();
+        let signature = None;
+        Token{id, issuer, subject, audience, expiration, not_before, issued_at, claims, signature}
+    }
+}
+
+
 impl Audience {
     pub fn is_empty(&self) -> bool {
         match self {
@@ -66,6 +80,66 @@ impl Audience {
             Audience::Many(aud) => aud.is_empty()
         }
     }
+
+    pub fn contains(&self, audience: &str) -> bool {
+        match self {
+            Audience::None => false,
+            Audience::One(aud) => aud == audience,
+            Audience::Many(aud) => aud.iter().any(|aud| aud == audience)
+        }
+    }
+}
+
+
+impl Token {
+    /// Whether the token's `nbf` is still in the future.
+    pub fn premature(&self) -> bool {
+        self.not_before.is_some_and(|not_before| Utc::now() < not_before)
+    }
+
+    /// The id of the client the token was issued to, none for first party tokens.
+    pub fn client_id(&self) -> Option<&str> {
+        match self.claims.get("client_id") {
+            Some(Value::String(client_id)) => Some(client_id),
+            _ => None
+        }
+    }
+
+    /// The scopes granted to the client.
+    pub fn scopes(&self) -> Vec<&str> {
+        match self.claims.get("scope") {
+            Some(Value::String(scope)) => scope.split_whitespace().collect(),
+            _ => Vec::new()
+        }
+    }
+
+    /// Whether the token was issued to the user themselves: it names no client,
+    /// and carries no audience or one of the given audiences of this server.
+    pub fn first_party(&self, audiences: &[&str]) -> bool {
+        self.client_id().is_none() && (self.audience.is_empty() || audiences.iter().any(|audience| self.audience.contains(audience)))
+    }
+
+    /// Whether the token can be used on this server: first party tokens, and tokens issued
+    /// to clients, which name the client or one of the given audiences.
+    pub fn intended_for(&self, audiences: &[&str]) -> bool {
+        if self.audience.is_empty() {
+            return true
+        }
+        self.client_id().is_some_and(|client_id| self.audience.contains(client_id))
+            || audiences.iter().any(|audience| self.audience.contains(audience))
+    }
+
+    /// Whether the token grants the scope, first party tokens act with the user's full authority.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.client_id().is_none() || self.scopes().contains(&scope)
+    }
+
+    /// Whether the token grants the permission on the resource of the service through a `service:resource:permission` scope.
+    pub fn has_permission(&self, service: &Id, resource: &str, permission: Permission) -> bool {
+        self.client_id().is_none() || self.scopes().iter()
+            .filter_map(|scope| scope.parse::<Scope>().ok())
+            .any(|scope| &scope.id == service && scope.name == resource && scope.permission == permission)
+    }
 }
 
 #[cfg(feature = "http")]
@@ -86,8 +160,9 @@ impl Responder for Token {
             Some(token) => {
                 if !is_json {
                     // If HTML is requested, set as cookie
+                    let cookies = req.app_data::<CookieConfig>().cloned().unwrap_or_default();
                     HttpResponseBuilder::new(status)
-                        .cookie(Cookie::new("token", token.clone()))
+                        .cookie(cookies.build("token", token.clone()))
                         .body(String::new())
                 } else {
                     // Set token in Authorization header for JSON
@@ -99,3 +174,67 @@ impl Responder for Token {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::PasetoKeys;
+
+    #[test]
+    fn test_footer_names_the_signing_key() {
+        let keys = PasetoKeys::default();
+        let token = Token::new(String::from("Beekeeper"), Id::default(), Audience::None, 60).try_sign(&keys).unwrap();
+        let signature = token.signature.clone().unwrap();
+        assert_eq!(signature.split('.').count(), 4);
+        assert_eq!(Token::try_verify(&signature, &keys).unwrap().id, token.id);
+    }
+
+    #[test]
+    fn test_client_tokens_are_limited_to_their_scopes() {
+        let user = Token::new(String::from("Beekeeper"), Id::default(), Audience::None, 60);
+        assert!(user.has_scope("openid") && user.has_permission(&Id::default(), "files", Permission::Delete));
+        assert!(user.intended_for(&[]) && user.first_party(&[]));
+        let mut elsewhere = Token::new(String::from("Beekeeper"), Id::default(), Audience::One(String::from("consent")), 60);
+        assert!(!elsewhere.first_party(&["https://auth.example.com"]));
+        elsewhere.audience = Audience::One(String::from("https://auth.example.com"));
+        assert!(elsewhere.first_party(&["https://auth.example.com"]));
+
+        let service = Id::default();
+        let mut client = Token::new(String::from("Beekeeper"), Id::default(), Audience::One(service.to_hex()), 60);
+        client.claims.insert("client_id".to_string(), Value::String(service.to_hex()));
+        client.claims.insert("scope".to_string(), Value::String(format!("openid {}:files:{}", service.to_hex(), Permission::Read)));
+        assert!(client.has_scope("openid"));
+        assert!(!client.has_scope("email"));
+        assert!(client.has_permission(&service, "files", Permission::Read));
+        assert!(!client.has_permission(&service, "files", Permission::Write));
+        assert!(!client.has_permission(&Id::default(), "files", Permission::Read));
+        assert!(client.intended_for(&[]));
+        assert!(!client.first_party(&[]));
+
+        client.audience = Audience::One(String::from("https://api.example.com"));
+        assert!(!client.intended_for(&["https://auth.example.com"]));
+        assert!(client.intended_for(&["https://api.example.com"]));
+        assert!(!client.first_party(&["https://api.example.com"]));
+    }
+
+    #[test]
+    fn test_premature() {
+        let mut token = Token::new(String::from("Beekeeper"), Id::default(), Audience::None, 60);
+        assert!(!token.premature());
+        token.not_before = Some(Utc::now() + chrono::Duration::seconds(30));
+        assert!(token.premature());
+    }
+
+    #[test]
+    fn test_verify_with_previous_key() {
+        let old = PasetoKeys::default();
+        let token = Token::new(String::from("Beekeeper"), Id::default(), Audience::None, 60).try_sign(&old).unwrap();
+        let signature = token.signature.unwrap();
+
+        let rotated = PasetoKeys {prev_public_key: Some(old.public_key), ..PasetoKeys::default()};
+        assert!(Token::try_verify(&signature, &rotated).is_ok());
+
+        let unrelated = PasetoKeys::default();
+        assert!(Token::try_verify(&signature, &unrelated).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+#[cfg(feature = "http")]
+use actix_web::{Responder, HttpResponse, body::BoxBody, http::header::{CACHE_CONTROL, PRAGMA}};
+use serde::{Deserialize, Serialize};
+use super::{Token, Value, Error};
+use chrono::Utc;
+
+/// A struct representing a successful response of the OAuth2 token endpoint (RFC 6749 section 5.1).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenResponse {
+    /// The signed access token.
+    pub access_token: String,
+    /// The type of the token, always `Bearer`.
+    pub token_type: String,
+    /// The number of seconds the access token stays valid.
+    pub expires_in: i64,
+    /// The space separated scopes granted to the client.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// The refresh token the client can exchange for a new access token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    /// The OpenID Connect ID token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+impl TryFrom<Token> for TokenResponse {
+    type Error = Error;
+    fn try_from(token: Token) -> Result<Self, Self::Error> {
+        let access_token = token.signature.ok_or(Error::InvalidToken)?;
+        let token_type = String::from("Bearer");
+        let expires_in = (token.expiration - Utc::now()).num_seconds().max(0);
+        let scope = match token.claims.get("scope") {
+            Some(Value::String(scope)) if !scope.is_empty() => Some(scope.clone()),
+            _ => None
+        };
+        let refresh_token = None;
+        let id_token = None;
+        Ok(Self {access_token, token_type, expires_in, scope, refresh_token, id_token})
+    }
+}
+
+#[cfg(feature = "http")]
+impl Responder for TokenResponse {
+    type Body = BoxBody;
+    fn respond_to(self, _: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
+        // Tokens must never end up in a cache
+        HttpResponse::Ok()
+            .insert_header((CACHE_CONTROL, "no-store"))
+            .insert_header((PRAGMA, "no-cache"))
+            .json(self)
+    }
+}
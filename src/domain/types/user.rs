@@ -7,7 +7,6 @@ use actix_web::{
     Responder,
 };
 use serde::{Deserialize, Serialize};
-use chrono::{Utc, Duration};
 
 /// A struct representing a user.
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
@@ -32,14 +31,7 @@ pub struct User {
 
 impl User {
     pub fn token(&self, issuer: String, audience: Audience, ttl: i64) -> Token {
-        let id = Default::default();
-        let subject = self.id;
-        let issued_at = Utc::now();
-        let not_before = None;
-        let expiration = issued_at + Duration::seconds(ttl);
-        let claims = Default::default();
-        let signature = None;
-        Token{id, issuer, subject, audience, expiration, not_before, issued_at, claims, signature}
+        Token::new(issuer, self.id, audience, ttl)
     }
 }
 
@@ -0,0 +1,112 @@
+#[cfg(feature = "http")]
+use actix_web::{Responder, HttpResponse, body::BoxBody, http::header::CACHE_CONTROL};
+use serde::{Deserialize, Serialize};
+use super::{User, Contact, EmailAddress, Phone};
+
+/// The scopes defined by OpenID Connect core, clients never have to register them.
+pub const OIDC_SCOPES: [&str; 4] = ["openid", "profile", "email", "phone"];
+
+/// A struct representing the standard OpenID Connect claims about a user (OIDC core section 5.1).
+///
+/// Claims are only filled in for the scopes that were granted, `sub` is always present.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UserInfo {
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub given_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub family_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phone_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phone_number_verified: Option<bool>,
+}
+
+impl UserInfo {
+    /// Maps a user to the claims released for the given scopes.
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - The granted scopes, `None` releases every claim, which is what first party tokens get.
+    pub fn new(user: &User, scope: Option<&[String]>) -> Self {
+        let granted = |name: &str| scope.map(|scope| scope.iter().any(|scope| scope == name)).unwrap_or(true);
+        let mut info = Self {sub: user.id.to_hex(), ..Default::default()};
+
+        if granted("profile") {
+            info.preferred_username = Some(user.username.clone()).filter(|name| !name.is_empty());
+            info.given_name = Some(user.first_name.clone()).filter(|name| !name.is_empty());
+            info.family_name = Some(user.last_name.clone()).filter(|name| !name.is_empty());
+        }
+
+        let (phone, email) = match &user.contact {
+            Contact::Phone(phone) => (Some(phone), None),
+            Contact::Email(email) => (None, Some(email)),
+            Contact::Both(phone, email) => (Some(phone), Some(email)),
+        };
+
+        if let (true, Some(email)) = (granted("email"), email) {
+            info.email = Some(email.to_string());
+            info.email_verified = Some(matches!(email, EmailAddress::Verified(_)));
+        }
+
+        if let (true, Some(phone)) = (granted("phone"), phone) {
+            info.phone_number = Some(phone.to_string());
+            info.phone_number_verified = Some(matches!(phone, Phone::Verified(_)));
+        }
+
+        info
+    }
+}
+
+#[cfg(feature = "http")]
+impl Responder for UserInfo {
+    type Body = BoxBody;
+    fn respond_to(self, _: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
+        HttpResponse::Ok()
+            .insert_header((CACHE_CONTROL, "no-store"))
+            .json(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::Id;
+
+    fn user() -> User {
+        User {
+            id: Id::default(),
+            username: "jdoe".to_string(),
+            first_name: "John".to_string(),
+            last_name: "Doe".to_string(),
+            contact: Contact::Email(EmailAddress::Verified("john@example.com".parse().unwrap())),
+            password: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_every_claim_without_scope() {
+        let user = user();
+        let info = UserInfo::new(&user, None);
+        assert_eq!(info.sub, user.id.to_hex());
+        assert_eq!(info.preferred_username.as_deref(), Some("jdoe"));
+        assert_eq!(info.email.as_deref(), Some("john@example.com"));
+        assert_eq!(info.email_verified, Some(true));
+        assert_eq!(info.phone_number, None);
+    }
+
+    #[test]
+    fn test_claims_follow_scope() {
+        let user = user();
+        let scope = ["openid".to_string(), "email".to_string()];
+        let info = UserInfo::new(&user, Some(&scope));
+        assert_eq!(info.given_name, None);
+        assert_eq!(info.email.as_deref(), Some("john@example.com"));
+    }
+}
@@ -1,14 +1,25 @@
 #[cfg(feature = "http")]
 use actix_web::{Responder, web::Json, http::{Method, StatusCode}};
 use crate::ports::outputs::{verify::Code, database::Item};
-use serde::{Serialize, Deserialize, de::DeserializeOwned};
-use super::{Id, Either, Phone, EmailAddress};
+use serde::{Serialize, Deserialize, Deserializer, de::DeserializeOwned};
+use super::{Id, Either, Phone, EmailAddress, Error};
 use chrono::{DateTime, Utc, Duration};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::{rngs::OsRng, Rng, TryRngCore};
 use std::fmt::{Display, Formatter};
-use std::rc::Rc;
 
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Default)]
+/// The characters of numeric codes
+const DIGITS: &[u8] = b"0123456789";
+/// The characters of alphanumeric codes, leaving out `0`, `O`, `1` and `I` which are easily mixed up
+const ALPHANUMERIC: &[u8] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+/// The shortest code generated, shorter ones are too easily guessed
+const MIN_LENGTH: usize = 4;
+/// The longest code generated
+const MAX_LENGTH: usize = 16;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
 pub enum VerificationMedia {
     #[default]
     Email,
@@ -24,10 +35,110 @@ pub struct Verification<ID = Id> {
     pub owner_contact: Either<Phone, EmailAddress>,
     /// This is the Id of the verification code
     pub id: ID,
-    /// This is the actual verification
-    pub code: u32,
+    /// This is the actual verification code, exactly as it is sent
+    #[serde(deserialize_with = "deserialize_code")]
+    pub code: String,
     /// This the time when the verification code become invalid.
     pub expires: DateTime<Utc>,
+    /// The number of wrong codes tried against this verification.
+    #[serde(default)]
+    pub attempts: u32,
+    /// The secret carried by the magic link, none for verifications stored before links had one.
+    #[serde(default)]
+    pub token: String,
+}
+
+
+/// How the verification codes sent over a channel look.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CodeFormat {
+    /// The number of characters, between 4 and 16
+    #[serde(deserialize_with = "deserialize_length")]
+    length: usize,
+    /// Whether letters are used besides digits
+    alphanumeric: bool,
+}
+
+
+impl Default for CodeFormat {
+    fn default() -> Self {
+        Self {length: 6, alphanumeric: false}
+    }
+}
+
+
+/// Refuses the lengths codes cannot be generated with
+fn check_length(length: usize) -> Result<usize, Error> {
+    match length {
+        MIN_LENGTH..=MAX_LENGTH => Ok(length),
+        _ => Err(Error::validation("length", format!("codes must be between {} and {} characters long, not {}", MIN_LENGTH, MAX_LENGTH, length)))
+    }
+}
+
+
+/// Reads the length of a code format, so a misconfigured one is refused when the config is loaded
+fn deserialize_length<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    check_length(usize::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+
+impl CodeFormat {
+    /// Codes of `length` characters, letters are used besides digits when `alphanumeric`
+    pub fn new(length: usize, alphanumeric: bool) -> Result<Self, Error> {
+        Ok(Self {length: check_length(length)?, alphanumeric})
+    }
+
+    /// Numeric codes of `length` digits
+    pub fn digits(length: usize) -> Result<Self, Error> {
+        Self::new(length, false)
+    }
+
+    /// The number of characters of the codes
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Draws a new code from the operating system's generator.
+    ///
+    /// Every character is drawn uniformly, so numeric codes are uniform over the whole
+    /// `0..10^length` range with their leading zeros.
+    pub fn generate(&self) -> String {
+        let alphabet = if self.alphanumeric {ALPHANUMERIC} else {DIGITS};
+        let mut rng = OsRng.unwrap_err();
+        (0..self.length).map(|_| alphabet[rng.random_range(0..alphabet.len())] as char).collect()
+    }
+}
+
+
+/// Draws the 256 bit secret of a magic link, unlike the id it cannot be predicted from other links
+fn link_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.try_fill_bytes(&mut bytes).expect("Failed to generate random bytes");
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+
+impl<ID> Verification<ID> {
+    /// Whether `token` is the secret of this verification's magic link
+    pub fn link_matches(&self, token: &str) -> bool {
+        !self.token.is_empty() && self.token == token
+    }
+}
+
+
+/// Reads a code, also the numbers stored before codes were kept as they are sent
+fn deserialize_code<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Text(String),
+        Number(u64),
+    }
+    Ok(match Stored::deserialize(deserializer)? {
+        Stored::Text(code) => code,
+        Stored::Number(code) => code.to_string(),
+    })
 }
 
 
@@ -35,13 +146,13 @@ pub struct Verification<ID = Id> {
 impl Responder for Verification {
     type Body = <Json<Self> as Responder>::Body;
     fn respond_to(self, req: &actix_web::HttpRequest) -> actix_web::HttpResponse<Self::Body> {
-        match req.method() {
-            &Method::POST => {
+        match *req.method() {
+            Method::POST => {
                 let mut res = Json(self).respond_to(req);
                 *res.status_mut() = StatusCode::CREATED;
                 res
             },
-            &Method::GET => Json(self).respond_to(req),
+            Method::GET => Json(self).respond_to(req),
             _ => Json(self).respond_to(req)
         }
     }
@@ -56,35 +167,35 @@ impl<ID: PartialEq + Clone + std::hash::Hash> Item for Verification<ID> {
 }
 
 #[cfg(feature = "email")]
-impl<ID: Serialize + DeserializeOwned> Code<EmailAddress, 6> for Verification<ID> {
+impl<ID: Serialize + DeserializeOwned> Code<EmailAddress> for Verification<ID> {
     type Id = ID;
-    fn new(email: &EmailAddress, ttl: Option<i64>, id: Self::Id) -> Self {
+    fn generate(email: &EmailAddress, ttl: Option<i64>, id: Self::Id, format: &CodeFormat) -> Self {
         let seconds = match ttl {Some(secs) => secs, None => 60*5};
-        let code = rand::random_range(10000..999999);
+        let code = format.generate();
         let owner_contact = Either::Right(email.clone());
         let expires = Utc::now() + Duration::seconds(seconds);
-        Self{owner_contact, id, code, expires}
+        Self{owner_contact, id, code, expires, attempts: 0, token: link_token()}
     }
 
-    fn code(&self) -> u32 {
-        self.code
+    fn as_str(&self) -> &str {
+        &self.code
     }
 }
 
 
 #[cfg(feature = "phone")]
-impl<ID: Serialize + DeserializeOwned> Code<Phone, 6> for Verification<ID> {
+impl<ID: Serialize + DeserializeOwned> Code<Phone> for Verification<ID> {
     type Id = ID;
-    fn new(phone: &Phone, ttl: Option<i64>, id: Self::Id) -> Self {
+    fn generate(phone: &Phone, ttl: Option<i64>, id: Self::Id, format: &CodeFormat) -> Self {
         let seconds = match ttl {Some(secs) => secs, None => 60*5};
-        let code = rand::random_range(10000..999999);
+        let code = format.generate();
         let owner_contact = Either::Left(phone.clone());
         let expires = Utc::now() + Duration::seconds(seconds);
-        Self{owner_contact, id, code, expires}
+        Self{owner_contact, id, code, expires, attempts: 0, token: link_token()}
     }
 
-    fn code(&self) -> u32 {
-        self.code
+    fn as_str(&self) -> &str {
+        &self.code
     }
 }
 
@@ -97,4 +208,72 @@ impl Display for VerificationMedia {
             Self::Whatsapp => write!(f, "whatsapp")
         }
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numeric_codes() {
+        for length in [4, 6, 8] {
+            let code = CodeFormat::digits(length).unwrap().generate();
+            assert_eq!(code.len(), length);
+            assert!(code.bytes().all(|c| c.is_ascii_digit()));
+        }
+        // Leading zeros are kept, a thousand codes without one would be a one in 10^45 chance
+        assert!((0..1000).any(|_| CodeFormat::digits(4).unwrap().generate().starts_with('0')));
+    }
+
+    #[test]
+    fn test_length_out_of_range() {
+        assert!(CodeFormat::digits(MIN_LENGTH - 1).is_err());
+        assert!(CodeFormat::new(MAX_LENGTH + 1, true).is_err());
+        // A misconfigured length is refused, naming the field, rather than changed
+        let err = serde_json::from_str::<CodeFormat>(r#"{"length": 2}"#).unwrap_err();
+        assert!(err.to_string().contains("length"));
+        assert_eq!(serde_json::from_str::<CodeFormat>(r#"{"alphanumeric": true}"#).unwrap().length(), 6);
+        assert_eq!(serde_json::from_str::<CodeFormat>(r#"{"length": 8}"#).unwrap(), CodeFormat::digits(8).unwrap());
+    }
+
+    #[test]
+    fn test_alphanumeric_codes() {
+        let format = CodeFormat::new(8, true).unwrap();
+        let code = format.generate();
+        assert_eq!(code.len(), 8);
+        assert!(code.bytes().all(|c| ALPHANUMERIC.contains(&c)));
+    }
+
+    #[cfg(feature = "email")]
+    #[test]
+    fn test_code_length() {
+        let email = EmailAddress::New("jane@example.com".parse().unwrap());
+        let verification: Verification = Code::<EmailAddress>::generate(&email, None, Id::default(), &CodeFormat::digits(8).unwrap());
+        assert_eq!(Code::<EmailAddress>::as_str(&verification).len(), 8);
+        assert!(Code::<EmailAddress>::matches(&verification, &verification.code.to_lowercase()));
+    }
+
+    #[cfg(feature = "email")]
+    #[test]
+    fn test_link_token() {
+        let email = EmailAddress::New("jane@example.com".parse().unwrap());
+        let verification: Verification = Code::<EmailAddress>::new(&email, None, Id::default());
+        let other: Verification = Code::<EmailAddress>::new(&email, None, Id::default());
+        assert_eq!(URL_SAFE_NO_PAD.decode(&verification.token).unwrap().len(), 32);
+        assert_ne!(verification.token, other.token);
+        assert!(verification.link_matches(&verification.token));
+        assert!(!verification.link_matches(&other.token));
+        // Verifications stored before links had a secret match none
+        assert!(!Verification {token: String::new(), ..verification}.link_matches(""));
+    }
+
+    #[test]
+    fn test_stored_numeric_code() {
+        let email = EmailAddress::New("jane@example.com".parse().unwrap());
+        let verification = Verification {owner_contact: Either::Right(email), id: Id::default(), code: String::from("123456"), expires: Utc::now(), attempts: 0, token: String::new()};
+        let json = serde_json::to_string(&verification).unwrap().replace(r#""code":"123456""#, r#""code":123456"#);
+        assert!(json.contains(r#""code":123456"#));
+        assert_eq!(serde_json::from_str::<Verification>(&json).unwrap(), verification);
+    }
+}
@@ -0,0 +1,32 @@
+#![allow(unused)]
+// The services and ports are awaited on actix's single threaded workers, their futures need not be `Send`
+#![allow(async_fn_in_trait)]
+//! Beekeeper, an authentication, authorization and user management server.
+//!
+//! The crate is laid out as ports and adaptors around the domain:
+//!
+//! * [`domain`] holds the types and the services acting on them, such as
+//!   [`Authentication`](domain::services::Authentication) and [`Get`](domain::services::Get).
+//!   The services are generic over the database, so any adaptor implementing the ports can back them.
+//! * [`ports`] holds the traits the adaptors implement, such as
+//!   [`GetItem`](ports::outputs::database::GetItem) and [`Verify`](ports::outputs::verify::Verify).
+//! * [`adaptors`] holds the implementations shipped with Beekeeper, each behind its cargo feature:
+//!   the HTTP API (`http`), the in-memory database (`memory`), SQLite and PostgreSQL (`sql`), MongoDB (`mongodb`),
+//!   SMTP (`smtp`) and Twilio (`twilio-email`, `twilio-phone`).
+//! * [`verifier`] lets other services verify the tokens Beekeeper issues.
+//!
+//! [`Config::builder`](domain::types::Config::builder) assembles a configuration from the adaptors of your choice.
+
+/// Implementations of the ports.
+pub mod adaptors;
+/// Module for domain logic.
+pub mod domain;
+/// Module for input and output ports.
+pub mod ports;
+/// Verifying Beekeeper tokens in other services.
+pub mod verifier;
+
+
+#[cfg(all(feature = "http", feature = "memory"))]
+pub use adaptors::inputs::api::actix::{Actix, Messenger, Result, Store};
+pub use domain::types::{Config, ConfigBuilder};
@@ -1,16 +1,19 @@
-#![allow(unused)]
-/// Main module for the application.
-mod adaptors;
-/// Module for domain logic.
-mod domain;
-/// Module for input and output ports.
-mod ports;
+use beekeeper::{adaptors::outputs::verify::Verifyer, Actix, Result};
 
-
-use adaptors::inputs::api::actix::{Actix, Result};
+#[cfg(not(any(all(feature = "smtp", feature = "email", not(feature = "phone")), feature = "twilio-email", feature = "twilio-phone")))]
+compile_error!("the server needs a verifyer, enable `smtp` and `email` or a `twilio-*` feature, phone numbers are only verified through Twilio");
 
 /// Entry point of the application.
+///
+/// Starts the server, or with `rotate-keys` rotates the PASETO keys and exits.
+/// `vault` manages the secrets vault and `config` prints where each configuration value came from.
 #[tokio::main]
 async fn main() -> Result<()> {
-    Actix::start().await
+    let args = std::env::args().collect::<Vec<String>>();
+    match args.get(1).map(String::as_str) {
+        Some("rotate-keys") => Actix::rotate_keys::<Verifyer>(&args[2..]).await,
+        Some("vault") => Actix::vault(&args[2..]).await,
+        Some("config") => Actix::sources::<Verifyer>(&args[2..]).await,
+        _ => Actix::start::<Verifyer>(&args[1..]).await
+    }
 }
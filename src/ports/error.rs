@@ -20,6 +20,21 @@ pub trait ErrorTrait: StdError + Debug + Send + Sync + 'static {
     
     #[cfg(feature = "http")]
     fn status(&self) -> StatusCode;
+
+    /// The seconds sent in the `Retry-After` header of the response, none by default.
+    #[cfg(feature = "http")]
+    fn retry_after(&self) -> Option<u64> {
+        None
+    }
+
+    /// The `WWW-Authenticate` challenge sent with the response, every 401 and 403 gets one.
+    #[cfg(feature = "http")]
+    fn challenge(&self) -> Option<String> {
+        match self.status() {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Some(String::from("Bearer")),
+            _ => None
+        }
+    }
 }
 
 /// Concrete error type that can wrap any ErrorTrait implementation
@@ -45,7 +60,14 @@ impl Error {
             let msg = self.source.log_message();
             error!("{msg}")
         }
-        HttpResponse::build(status).json(body)
+        let mut response = HttpResponse::build(status);
+        if let Some(challenge) = self.source.challenge() {
+            response.insert_header((actix_web::http::header::WWW_AUTHENTICATE, challenge));
+        }
+        if let Some(seconds) = self.source.retry_after() {
+            response.insert_header((actix_web::http::header::RETRY_AFTER, seconds));
+        }
+        response.json(body)
     }
 }
 
@@ -62,6 +84,12 @@ impl Error {
     pub fn get_source(&self) -> &dyn ErrorTrait {
         self.source.as_ref()
     }
+
+    /// The underlying error as a concrete type, when it is one.
+    pub fn downcast_ref<E: ErrorTrait>(&self) -> Option<&E> {
+        let source: &dyn StdError = self.source.as_ref();
+        source.downcast_ref::<E>()
+    }
 }
 
 impl<T: ErrorTrait> From<T> for Error {
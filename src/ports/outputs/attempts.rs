@@ -0,0 +1,29 @@
+use crate::domain::types::Failures;
+use crate::ports::ErrorTrait;
+use chrono::{DateTime, Utc};
+
+
+/// A trait for stores that count failed logins, so they can be slowed down and locked out.
+///
+/// The counts are keyed by the account or client address they are held against. They are
+/// short-lived, a store may keep them in memory only.
+pub trait LoginAttempts: Sized {
+    /// The error type for failed store operations
+    type Error: ErrorTrait;
+
+    /// Retrieves the failures counted against `key`, if any
+    async fn failures(&self, key: &str) -> Result<Option<Failures>, Self::Error>;
+
+    /// Counts a failure at `at` against `key` and returns the failures counted before it
+    ///
+    /// Failures whose last one happened before `forget_before` are forgotten first, so the
+    /// count starts over. Counting and returning the earlier count must happen at once, so
+    /// concurrent attempts never see the same count.
+    async fn fail(&self, key: &str, at: DateTime<Utc>, forget_before: DateTime<Utc>) -> Result<Option<Failures>, Self::Error>;
+
+    /// Takes back one failure counted against `key`, for an attempt that turned out fine
+    async fn forgive(&self, key: &str) -> Result<(), Self::Error>;
+
+    /// Forgets the failures counted against `key`
+    async fn clear(&self, key: &str) -> Result<(), Self::Error>;
+}
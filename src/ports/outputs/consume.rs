@@ -0,0 +1,18 @@
+use crate::domain::types::RefreshToken;
+use crate::ports::ErrorTrait;
+
+
+/// A trait for stores that can consume a refresh token in a single step.
+///
+/// Reading the token and then marking it used lets two parties racing with the same
+/// token both exchange it, so the check and the mark must happen together.
+pub trait ConsumeRefreshToken: Sized {
+    /// The error type for failed store operations
+    type Error: ErrorTrait;
+
+    /// Marks the token stored under `digest` as used
+    ///
+    /// Returns the token as it was before, its `used` flag tells whether it had already
+    /// been consumed.
+    async fn consume(&self, digest: &str) -> Result<RefreshToken, Self::Error>;
+}
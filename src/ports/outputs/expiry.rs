@@ -0,0 +1,14 @@
+use crate::ports::ErrorTrait;
+use chrono::Duration;
+
+
+/// A trait for stores of services that can tell how long the longest lived access token issued to one of them lives.
+///
+/// Revoking every token of a subject has to last until the tokens it covers have expired.
+pub trait TokenExpiry: Sized {
+    /// The error type for failed store operations
+    type Error: ErrorTrait;
+
+    /// The longest `token_expiry` set on any service, none when every service keeps the default
+    async fn longest_token_expiry(&self) -> Result<Option<Duration>, Self::Error>;
+}
@@ -1,3 +1,7 @@
 /// Module for output ports related to database operations.
 pub mod database;
 pub mod verify;
+pub mod attempts;
+pub mod consume;
+pub mod expiry;
+pub mod wrong_code;
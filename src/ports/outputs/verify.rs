@@ -1,8 +1,7 @@
-use crate::ports::outputs::database::{Item, GetItem, CreateItem, DeleteItem, GetItems};
-use crate::domain::types::{EmailAddress, Phone, Either};
+use crate::ports::outputs::{database::{Item, GetItem, CreateItem, DeleteItem, GetItems}, wrong_code::CountWrongCode};
+use crate::domain::types::{EmailAddress, Phone, Either, Contact, CodeFormat};
 use serde::{de::DeserializeOwned, Serialize};
 use crate::ports::ErrorTrait;
-use std::rc::Rc;
 
 /// A trait for verification services that support different contact types and verification methods.
 ///
@@ -11,11 +10,11 @@ use std::rc::Rc;
 ///
 /// # Type Parameters
 /// * `T` - The type of contact being verified (e.g., email, phone number)
-pub trait Verify<T: Clone, const DIGITS: usize = 6>: DeserializeOwned + Sized {
+pub trait Verify<T: Clone>: DeserializeOwned + Sized {
     /// The type of verification code used for this verification process
     /// 
     /// Must implement both the `Code` trait and be storable as an `Item` 
-    type Verification: Code<T, DIGITS> + Item;
+    type Verification: Code<T> + Item;
 
     /// The error type for verification operations
     type Error: ErrorTrait;
@@ -25,6 +24,9 @@ pub trait Verify<T: Clone, const DIGITS: usize = 6>: DeserializeOwned + Sized {
 
     /// Initiates the verification process for a given contact
     ///
+    /// Refuses to send another code while the contact is within its resend cooldown or
+    /// has used up its hourly quota.
+    ///
     /// # Arguments
     /// * `contact` - The contact to be verified
     /// * `channel` - The communication channel for verification
@@ -42,44 +44,64 @@ pub trait Verify<T: Clone, const DIGITS: usize = 6>: DeserializeOwned + Sized {
 
     /// Verifies a contact using a provided verification code
     ///
+    /// A stored code is only accepted before it expires, and is consumed by a successful check
+    /// so it cannot be used again. Wrong codes are counted against it until it is invalidated.
+    ///
     /// # Arguments
     /// * `contact` - The contact being verified
-    /// * `code` - The verification code to check
-    /// * `db` - A database that can retrieve and delete verification codes
+    /// * `code` - The verification code to check, or the secret of the magic link
+    /// * `db` - A database that can retrieve, store and delete verification codes
     ///
     /// # Returns
     /// A result indicating successful verification or an error
-    async fn verify<DB: GetItem<Self::Verification>>(
+    async fn verify<DB: GetItem<Self::Verification> + CountWrongCode<Self::Verification> + DeleteItem<Self::Verification>>(
         &self,
         contact: &T, 
-        code: Either<&str, &<Self::Verification as Code<T, DIGITS>>::Id>,
+        code: Either<&str, &str>,
         db: &DB
     ) -> Result<(), Self::Error>;
 }
 
+/// A trait for services that can deliver organisation invitations.
+///
+/// Unlike `Verify` nothing needs to be stored, the invitee follows the link and accepts
+/// the invitation while signed in.
+pub trait Invite: Sized {
+    /// The error type for delivery failures
+    type Error: ErrorTrait;
+
+    /// Sends an invitation to join `organisation` to the given contact
+    ///
+    /// # Arguments
+    /// * `contact` - The email address or phone number of the invitee
+    /// * `organisation` - The name of the organisation the invitee is invited to
+    /// * `link` - The link where the invitation can be accepted
+    ///
+    /// # Returns
+    /// A result indicating successful delivery or an error
+    async fn invite(&self, contact: &Contact, organisation: &str, link: &str) -> Result<(), Self::Error>;
+}
+
 /// A trait representing a verification code
 ///
-/// Provides methods for creating, accessing, and validating verification codes
-pub trait Code<T: Clone, const DIGITS: usize = 6>: Sized {
+/// Provides methods for creating, accessing, and validating verification codes.
+/// Codes are six digits long unless created in another format.
+pub trait Code<T: Clone>: Sized {
     type Id: Serialize + DeserializeOwned;
-    /// Creates a new verification code
-    fn new(contact: &T, ttl: Option<i64>, id: Self::Id) -> Self;
-
-    /// Retrieves the verification code as a reference-counted string
-    fn code(&self) -> u32;
-
-    /// Converts the code into a String for universal familiarity
-    fn as_str(&self) -> String {
-        let mut code = String::new();
-        let string = self.code().to_string();
-        if code.len() < DIGITS {
-            let missing = DIGITS - code.len();
-            for _ in 0..missing {
-                code.push('0');
-            }
-        }
-        code.push_str(&string);
-        code
+    /// Creates a new verification code of six digits
+    fn new(contact: &T, ttl: Option<i64>, id: Self::Id) -> Self {
+        Self::generate(contact, ttl, id, &CodeFormat::default())
+    }
+
+    /// Creates a new verification code in the given format
+    fn generate(contact: &T, ttl: Option<i64>, id: Self::Id, format: &CodeFormat) -> Self;
+
+    /// The code exactly as it is sent to the contact, leading zeros included
+    fn as_str(&self) -> &str;
+
+    /// Whether `code` is this code, letters match in either case
+    fn matches(&self, code: &str) -> bool {
+        self.as_str().eq_ignore_ascii_case(code.trim())
     }
 }
 
@@ -0,0 +1,18 @@
+use crate::ports::{ErrorTrait, outputs::database::Item};
+
+
+/// A trait for stores that can count a wrong code against a verification in a single step.
+///
+/// Reading the verification and storing it again with one more attempt lets concurrent guesses
+/// overwrite each other's count, and brings back a verification that was used or replaced in
+/// between, so the count must happen in the store.
+pub trait CountWrongCode<T: Item>: Sized {
+    /// The error type for failed store operations
+    type Error: ErrorTrait;
+
+    /// Counts a wrong code against the verification stored under `id` and returns the new count
+    ///
+    /// Fails if no verification is stored under `id` any more, because it was used or replaced
+    /// by a newer one. A verification reaching `max_attempts` is deleted in the same step.
+    async fn count_wrong_code(&self, id: &T::SK, max_attempts: u32) -> Result<u32, Self::Error>;
+}
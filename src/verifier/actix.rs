@@ -0,0 +1,123 @@
+//! Actix-web middleware verifying Beekeeper tokens.
+//!
+//! The verified [`Token`] is put in the request extensions, handlers take it as `web::ReqData<Token>`.
+
+use actix_web::{dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, body::EitherBody, http::header, HttpMessage, Error};
+use crate::domain::types::Error as DomainError;
+use crate::ports::Error as PortError;
+use super::{Token, Verifier};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+
+/// Middleware rejecting requests without a valid token with a 401 and a `WWW-Authenticate` challenge.
+///
+/// `App::new().wrap(Authenticate::new(verifier)).route("/", web::get().to(|token: web::ReqData<Token>| ...))`
+#[derive(Debug, Clone)]
+pub struct Authenticate {
+    verifier: Verifier,
+}
+
+
+impl Authenticate {
+    pub fn new(verifier: Verifier) -> Self {
+        Self {verifier}
+    }
+}
+
+
+/// The token in the `Authorization` header, or in the `token` cookie Beekeeper sets.
+fn credentials(req: &ServiceRequest) -> Option<String> {
+    match req.headers().get(header::AUTHORIZATION) {
+        Some(value) => value.to_str().ok()?.strip_prefix("Bearer ").map(str::to_string),
+        None => req.cookie("token").map(|cookie| cookie.value().to_string())
+    }
+}
+
+
+impl<S, B> Transform<S, ServiceRequest> for Authenticate
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AuthenticateMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthenticateMiddleware {service: Rc::new(service), verifier: self.verifier.clone()}))
+    }
+}
+
+
+pub struct AuthenticateMiddleware<S> {
+    service: Rc<S>,
+    verifier: Verifier,
+}
+
+
+impl<S, B> Service<ServiceRequest> for AuthenticateMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let verifier = self.verifier.clone();
+        Box::pin(async move {
+            let verified = match credentials(&req) {
+                Some(signature) => verifier.verify(&signature).await,
+                None => Err(DomainError::InvalidToken),
+            };
+            match verified {
+                Ok(token) => {
+                    req.extensions_mut().insert::<Token>(token);
+                    Ok(service.call(req).await?.map_into_left_body())
+                },
+                Err(err) => {
+                    let response = PortError::from(err).response();
+                    Ok(req.into_response(response).map_into_right_body())
+                }
+            }
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{key_file, sign, Audience, Id, KeySource};
+    use crate::domain::types::PasetoKeys;
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_authenticate() {
+        let keys = PasetoKeys::default();
+        let path = key_file(&keys, "verifier_actix");
+        let verifier = Verifier::new(KeySource::File(path.clone()));
+        let app = test::init_service(App::new()
+            .wrap(Authenticate::new(verifier))
+            .route("/", web::get().to(|token: web::ReqData<Token>| async move { HttpResponse::Ok().body(token.subject.to_hex()) }))
+        ).await;
+
+        let res = test::call_service(&app, test::TestRequest::get().to_request()).await;
+        assert_eq!(res.status(), 401);
+        assert!(res.headers().contains_key(header::WWW_AUTHENTICATE));
+
+        let token = Token::new(String::from("Beekeeper"), Id::default(), Audience::None, 60);
+        let req = test::TestRequest::get().insert_header((header::AUTHORIZATION, format!("Bearer {}", sign(token.clone(), &keys)))).to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(body, token.subject.to_hex());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+//! Verifying Beekeeper tokens in other services.
+//!
+//! Resource servers verify `v4.public` tokens offline, with the public keys Beekeeper publishes
+//! at `/.well-known/paserk.json` or a copy of them in a local file.
+//! The keys are cached, and fetched again when a token names a key the cache does not know
+//! or the cache is older than an hour.
+//! Revocations are not seen offline, keep access tokens short-lived or introspect them.
+
+use crate::domain::types::{Error, PaserkSet};
+use crate::domain::services::{key_id, Paseto};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+use std::path::PathBuf;
+
+#[cfg(feature = "http")]
+pub mod actix;
+#[cfg(feature = "tower")]
+pub mod tower;
+
+
+pub use crate::domain::types::{Audience, Id, Permission, Token, Value};
+
+
+/// How long the keys are trusted before they are fetched again, as long as Beekeeper lets them be cached.
+const MAX_AGE: Duration = Duration::from_secs(60 * 60);
+/// The least time between two fetches, so tokens naming unknown keys cannot flood Beekeeper.
+const MIN_REFRESH: Duration = Duration::from_secs(30);
+
+
+/// Where the public keys come from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeySource {
+    /// Beekeeper's key endpoint, such as `https://auth.example.com/.well-known/paserk.json`.
+    Url(String),
+    /// A local copy of the key endpoint's response.
+    File(PathBuf),
+}
+
+
+#[derive(Debug, Default)]
+struct Cache {
+    keys: Vec<(String, [u8; 32])>,
+    fetched: Option<Instant>,
+    attempted: Option<Instant>,
+}
+
+
+impl Cache {
+    /// The key named `kid`, or every key for tokens that name none.
+    fn matching(&self, kid: Option<&str>) -> Vec<[u8; 32]> {
+        self.keys.iter()
+            .filter(|(id, _)| kid.is_none_or(|kid| id == kid))
+            .map(|(_, key)| *key)
+            .collect()
+    }
+
+    fn stale(&self) -> bool {
+        self.fetched.is_none_or(|fetched| fetched.elapsed() >= MAX_AGE)
+    }
+
+    fn recently_attempted(&self) -> bool {
+        self.attempted.is_some_and(|attempted| attempted.elapsed() < MIN_REFRESH)
+    }
+}
+
+
+/// Verifies tokens issued by Beekeeper, offline.
+///
+/// Clones share the key cache.
+#[derive(Debug, Clone)]
+pub struct Verifier {
+    source: KeySource,
+    issuer: Option<String>,
+    audiences: Vec<String>,
+    client: reqwest::Client,
+    cache: Arc<RwLock<Cache>>,
+}
+
+
+impl Verifier {
+    pub fn new(source: KeySource) -> Self {
+        let issuer = None;
+        let audiences = Vec::new();
+        let client = reqwest::Client::new();
+        let cache = Default::default();
+        Self {source, issuer, audiences, client, cache}
+    }
+
+    /// Only accepts tokens issued by `issuer`, the `domain` Beekeeper is configured with.
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// Only accepts tokens meant for one of the audiences, tokens are accepted for any audience when none is given.
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audiences.push(audience.into());
+        self
+    }
+
+    /// Verifies the signature, expiry, `nbf`, issuer and audience of a token.
+    pub async fn verify(&self, signature: &str) -> Result<Token, Error> {
+        let kid = key_id(signature)?;
+        let keys = self.keys(kid.as_deref()).await?;
+        let mut token = keys.iter()
+            .find_map(|key| Token::try_verify_with(signature, key).ok())
+            .ok_or(Error::InvalidToken)?;
+        if token.expired() {
+            Err(Error::TokenExpired)?
+        }
+        if token.premature() {
+            Err(Error::InvalidToken)?
+        }
+        if self.issuer.as_ref().is_some_and(|issuer| issuer.trim_end_matches('/') != token.issuer.trim_end_matches('/')) {
+            Err(Error::InvalidToken)?
+        }
+        if !self.audiences.is_empty() && !self.audiences.iter().any(|audience| token.audience.contains(audience)) {
+            Err(Error::InvalidToken)?
+        }
+        token.signature = Some(signature.to_string());
+        Ok(token)
+    }
+
+    /// Fetches the keys again, keeping the cached ones if that fails.
+    pub async fn refresh(&self) -> Result<(), Error> {
+        self.cache.write().unwrap_or_else(PoisonError::into_inner).attempted = Some(Instant::now());
+        let keys = self.fetch().await?;
+        let mut cache = self.cache.write().unwrap_or_else(PoisonError::into_inner);
+        cache.keys = keys;
+        cache.fetched = Some(Instant::now());
+        Ok(())
+    }
+
+    /// The keys a token naming `kid` may be signed with, refreshing the cache when it has none or is stale.
+    async fn keys(&self, kid: Option<&str>) -> Result<Vec<[u8; 32]>, Error> {
+        let (keys, due) = {
+            let cache = self.cache.read().unwrap_or_else(PoisonError::into_inner);
+            let keys = cache.matching(kid);
+            let due = (keys.is_empty() || cache.stale()) && !cache.recently_attempted();
+            (keys, due)
+        };
+        if !due {
+            return Ok(keys)
+        }
+        match self.refresh().await {
+            Ok(()) => Ok(self.cache.read().unwrap_or_else(PoisonError::into_inner).matching(kid)),
+            Err(err) if keys.is_empty() => Err(err),
+            Err(err) => {
+                log::warn!("could not refresh the token verification keys: {}", err);
+                Ok(keys)
+            }
+        }
+    }
+
+    async fn fetch(&self) -> Result<Vec<(String, [u8; 32])>, Error> {
+        let set = match &self.source {
+            KeySource::Url(url) => self.client.get(url).send().await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(Error::internal)?
+                .json::<PaserkSet>().await
+                .map_err(Error::internal)?,
+            KeySource::File(path) => serde_json::from_str(&tokio::fs::read_to_string(path).await?)?,
+        };
+        set.keys.iter()
+            .map(|key| key.public_key().map(|public_key| (key.kid.clone(), public_key)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::Internal {message: String::from("the key set holds an invalid key"), source: None})
+    }
+}
+
+
+/// Writes the public half of `keys` where a verifier can read it.
+#[cfg(test)]
+fn key_file(keys: &crate::domain::types::PasetoKeys, name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("beekeeper_{}_{}.json", name, std::process::id()));
+    std::fs::write(&path, serde_json::to_string(&PaserkSet::from(keys)).unwrap()).unwrap();
+    path
+}
+
+
+#[cfg(test)]
+fn sign(token: Token, keys: &crate::domain::types::PasetoKeys) -> String {
+    token.try_sign(keys).unwrap().signature.unwrap()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::PasetoKeys;
+
+    #[tokio::test]
+    async fn test_verify_offline() {
+        let keys = PasetoKeys::default();
+        let path = key_file(&keys, "verifier");
+        let verifier = Verifier::new(KeySource::File(path.clone())).issuer("https://auth.example.com/").audience("api");
+
+        let token = Token::new(String::from("https://auth.example.com"), Id::default(), Audience::One(String::from("api")), 60);
+        let verified = verifier.verify(&sign(token.clone(), &keys)).await.unwrap();
+        assert_eq!(verified.subject, token.subject);
+
+        let other = Token {audience: Audience::One(String::from("billing")), ..token.clone()};
+        assert!(matches!(verifier.verify(&sign(other, &keys)).await, Err(Error::InvalidToken)));
+        let expired = Token {expiration: chrono::Utc::now() - chrono::Duration::seconds(1), ..token.clone()};
+        assert!(matches!(verifier.verify(&sign(expired, &keys)).await, Err(Error::TokenExpired)));
+        let forged = sign(token.clone(), &PasetoKeys::default());
+        assert!(matches!(verifier.verify(&forged).await, Err(Error::InvalidToken)));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unknown_keys_are_fetched() {
+        let keys = PasetoKeys::default();
+        let path = key_file(&keys, "verifier_rotation");
+        let verifier = Verifier::new(KeySource::File(path.clone()));
+        let token = Token::new(String::from("Beekeeper"), Id::default(), Audience::None, 60);
+        verifier.verify(&sign(token.clone(), &keys)).await.unwrap();
+
+        let rotated = keys.rotate();
+        key_file(&rotated, "verifier_rotation");
+        // Within the refresh interval the new key is not fetched yet
+        assert!(verifier.verify(&sign(token.clone(), &rotated)).await.is_err());
+        verifier.cache.write().unwrap().attempted = None;
+        verifier.verify(&sign(token.clone(), &rotated)).await.unwrap();
+        verifier.verify(&sign(token, &keys)).await.unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+}
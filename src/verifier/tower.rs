@@ -0,0 +1,153 @@
+//! A tower layer verifying Beekeeper tokens, for axum, tonic and other tower based servers.
+//!
+//! The verified [`Token`] is put in the request extensions, axum handlers take it as `Extension<Token>`.
+
+use http::{header, HeaderMap, HeaderValue, Request, Response, StatusCode};
+use crate::domain::types::Error as DomainError;
+use crate::ports::ErrorTrait;
+use tower_service::Service;
+use tower_layer::Layer;
+use super::{Token, Verifier};
+use std::future::Future;
+use std::task::{Context, Poll};
+use std::pin::Pin;
+
+
+/// Layer rejecting requests without a valid token with a 401 and a `WWW-Authenticate` challenge.
+#[derive(Debug, Clone)]
+pub struct AuthenticateLayer {
+    verifier: Verifier,
+}
+
+
+impl AuthenticateLayer {
+    pub fn new(verifier: Verifier) -> Self {
+        Self {verifier}
+    }
+}
+
+
+impl<S> Layer<S> for AuthenticateLayer {
+    type Service = Authenticate<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Authenticate {inner, verifier: self.verifier.clone()}
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Authenticate<S> {
+    inner: S,
+    verifier: Verifier,
+}
+
+
+/// The token in the `Authorization` header, or in the `token` cookie Beekeeper sets.
+fn credentials(headers: &HeaderMap) -> Option<String> {
+    if let Some(value) = headers.get(header::AUTHORIZATION) {
+        return value.to_str().ok()?.strip_prefix("Bearer ").map(str::to_string)
+    }
+    headers.get_all(header::COOKIE).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .find_map(|cookie| cookie.trim().strip_prefix("token="))
+        .map(str::to_string)
+}
+
+
+fn rejection<B: Default>(err: DomainError) -> Response<B> {
+    let mut response = Response::new(B::default());
+    *response.status_mut() = StatusCode::from_u16(err.status().as_u16()).unwrap_or(StatusCode::UNAUTHORIZED);
+    if let Some(challenge) = err.challenge().and_then(|challenge| HeaderValue::from_str(&challenge).ok()) {
+        response.headers_mut().insert(header::WWW_AUTHENTICATE, challenge);
+    }
+    response
+}
+
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for Authenticate<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        // Only the polled service is known to be ready, it goes with the request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let verifier = self.verifier.clone();
+        Box::pin(async move {
+            let verified = match credentials(req.headers()) {
+                Some(signature) => verifier.verify(&signature).await,
+                None => Err(DomainError::InvalidToken),
+            };
+            match verified {
+                Ok(token) => {
+                    req.extensions_mut().insert::<Token>(token);
+                    inner.call(req).await
+                },
+                Err(err) => Ok(rejection(err))
+            }
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{key_file, sign, Audience, Id, KeySource};
+    use crate::domain::types::PasetoKeys;
+    use std::convert::Infallible;
+    use std::future::{ready, Ready};
+
+    /// Answers with the subject of the verified token.
+    #[derive(Clone)]
+    struct Subject;
+
+    impl Service<Request<String>> for Subject {
+        type Response = Response<String>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: Request<String>) -> Self::Future {
+            let token = req.extensions().get::<Token>().unwrap();
+            ready(Ok(Response::new(token.subject.to_hex())))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_layer() {
+        let keys = PasetoKeys::default();
+        let path = key_file(&keys, "verifier_tower");
+        let mut service = AuthenticateLayer::new(Verifier::new(KeySource::File(path.clone()))).layer(Subject);
+
+        let res = service.call(Request::new(String::new())).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert!(res.headers().contains_key(header::WWW_AUTHENTICATE));
+
+        let token = Token::new(String::from("Beekeeper"), Id::default(), Audience::None, 60);
+        let req = Request::builder()
+            .header(header::COOKIE, format!("theme=dark; token={}", sign(token.clone(), &keys)))
+            .body(String::new())
+            .unwrap();
+        let res = service.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.into_body(), token.subject.to_hex());
+        std::fs::remove_file(path).unwrap();
+    }
+}